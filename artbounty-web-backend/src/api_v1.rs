@@ -0,0 +1,1044 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use artbounty_web_frontend::{
+    api::RATING_SFW,
+    db::{
+        DbError,
+        acc::{self, DbAcc},
+        api_token::DbApiToken,
+        artwork::{self, DbArtwork},
+        bounty::{self, DbBounty, STATUS_CLOSED, STATUS_OPEN},
+    },
+    server::{
+        ServerError, ServerState,
+        api_token::{self, ErrorApiToken},
+        artwork::ErrorArtwork,
+        bounty::ErrorBounty,
+        rating,
+    },
+};
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{
+        HeaderValue, StatusCode,
+        header::{AUTHORIZATION, RETRY_AFTER},
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, error, trace};
+use utoipa::{
+    IntoParams, Modify, OpenApi, ToSchema,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::throttle::{
+    Threshold, ThresholdTracker, delta_minutes, threshold_allow, threshold_retry_after,
+};
+
+pub const PREFIX: &str = "/api/v1";
+pub const DEFAULT_PAGE_SIZE: u32 = 30;
+pub const MAXIMUM_PAGE_SIZE: u32 = 100;
+
+pub const RATE_LIMIT_HEADER: &str = "x-ratelimit-limit";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
+/// Tracked tokens past which the ones outside their window are dropped.
+const PRUNE_AT: usize = 10_000;
+
+/// Versioned JSON API authenticated with personal access tokens.
+#[derive(Clone)]
+pub struct ApiV1 {
+    state: ServerState,
+    trackers: Arc<Mutex<HashMap<String, ThresholdTracker>>>,
+}
+
+/// Token and account of the current request, set by the auth middleware.
+#[derive(Clone)]
+pub struct ApiAuth {
+    pub token: DbApiToken,
+    pub acc: DbAcc,
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorApiV1 {
+    #[error("missing or invalid access token")]
+    Unauthorized,
+
+    #[error("token is missing the {0} scope")]
+    Scope(&'static str),
+
+    #[error("rate limit exceeded")]
+    RateLimited(TimeDelta),
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("invalid status {0}")]
+    Status(String),
+
+    #[error(transparent)]
+    Artwork(#[from] ErrorArtwork),
+
+    #[error(transparent)]
+    Bounty(#[from] ErrorBounty),
+
+    #[error("token error: {0}")]
+    ApiToken(#[from] ErrorApiToken),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiUser {
+    pub username: String,
+    pub role: String,
+    pub created_at: i64,
+}
+
+/// The token owner's own account.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiAccount {
+    pub username: String,
+    pub email: String,
+    pub verified_email: bool,
+    pub role: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiArtwork {
+    pub id: String,
+    pub author: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub width: u32,
+    pub height: u32,
+    /// `sfw`, `mature` or `explicit`.
+    pub rating: String,
+    /// Hex colors, the dominant one first.
+    pub palette: Vec<String>,
+    /// BlurHash placeholder, empty until the preview is rendered.
+    pub blurhash: String,
+    pub modified_at: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiBounty {
+    pub id: String,
+    pub author: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// In cents.
+    pub reward: u64,
+    /// `open` or `closed`.
+    pub status: String,
+    /// `sfw`, `mature` or `explicit`.
+    pub rating: String,
+    pub modified_at: i64,
+    pub created_at: i64,
+}
+
+/// Results the token can see, sections without a matching scope stay empty.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiSearch {
+    pub users: Vec<ApiUser>,
+    pub artworks: Vec<ApiArtwork>,
+    pub bounties: Vec<ApiBounty>,
+}
+
+/// Fields left out keep their current value.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiArtworkPatch {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiBountyCreate {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// In cents.
+    pub reward: u64,
+    /// `sfw`, `mature` or `explicit`, defaults to `sfw`.
+    #[serde(default = "default_rating")]
+    pub rating: String,
+}
+
+fn default_rating() -> String {
+    String::from(RATING_SFW)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PageQuery {
+    /// 1-100, defaults to 30.
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BountyQuery {
+    /// `open` or `closed`, every bounty when left out.
+    pub status: Option<String>,
+    /// 1-100, defaults to 30.
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SearchQuery {
+    pub q: String,
+    /// Per section, 1-100, defaults to 30.
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ArtBounty API", version = "1"),
+    paths(
+        get_me,
+        get_user,
+        get_user_artworks,
+        get_artworks,
+        get_artwork,
+        patch_artwork,
+        get_bounties,
+        post_bounty,
+        get_bounty,
+        close_bounty,
+        search
+    ),
+    components(schemas(
+        ApiError,
+        ApiUser,
+        ApiAccount,
+        ApiArtwork,
+        ApiBounty,
+        ApiSearch,
+        ApiArtworkPatch,
+        ApiBountyCreate
+    )),
+    modifiers(&SecurityAddon)
+)]
+pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Personal access token from account settings."))
+                    .build(),
+            ),
+        );
+    }
+}
+
+impl ApiV1 {
+    pub fn new(state: ServerState) -> Self {
+        Self {
+            state,
+            trackers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        let authed = Router::new()
+            .route("/me", get(get_me))
+            .route("/users/:username", get(get_user))
+            .route("/users/:username/artworks", get(get_user_artworks))
+            .route("/artworks", get(get_artworks))
+            .route(
+                "/artworks/:artwork_id",
+                get(get_artwork).patch(patch_artwork),
+            )
+            .route("/bounties", get(get_bounties).post(post_bounty))
+            .route("/bounties/:bounty_id", get(get_bounty))
+            .route("/bounties/:bounty_id/close", post(close_bounty))
+            .route("/search", get(search))
+            .route_layer(middleware::from_fn_with_state(self.clone(), authenticate));
+
+        let api = Router::new()
+            .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+            .merge(authed)
+            .fallback(|| async { ErrorApiV1::NotFound.into_response() });
+
+        Router::new().nest(PREFIX, api).with_state(self)
+    }
+
+    /// Counts a request against the token's per minute limit, returns the requests left.
+    pub fn rate_limit(&self, token: &DbApiToken, time: DateTime<Utc>) -> Result<u64, ErrorApiV1> {
+        let threshold = Threshold {
+            amount: u64::from(token.rate_limit),
+            delta: delta_minutes(1),
+        };
+        let mut trackers = self.trackers.lock().unwrap();
+        if trackers.len() > PRUNE_AT {
+            // revoked tokens stop showing up, so this is what drops their trackers too
+            let before = trackers.len();
+            trackers.retain(|_, tracker| time - tracker.started_at < threshold.delta);
+            debug!("pruned api trackers: {} -> {}", before, trackers.len());
+        }
+        let tracker = trackers
+            .entry(token.token_hash.clone())
+            .or_insert_with(|| ThresholdTracker::new(time));
+        if !threshold_allow(tracker, &threshold, &time) {
+            return Err(ErrorApiV1::RateLimited(threshold_retry_after(
+                tracker, &threshold, &time,
+            )));
+        }
+        tracker.amount += 1;
+        Ok(threshold.amount.saturating_sub(tracker.amount))
+    }
+}
+
+impl ApiAuth {
+    pub fn require(&self, scope: &'static str) -> Result<(), ErrorApiV1> {
+        if !api_token::has_scope(&self.token, scope) {
+            return Err(ErrorApiV1::Scope(scope));
+        }
+        Ok(())
+    }
+
+    /// Ratings listings return for the token owner, their own work is always included.
+    pub fn visible_ratings(&self, author: Option<&str>) -> Vec<String> {
+        if author == Some(self.acc.username.as_str()) {
+            return rating::all_ratings();
+        }
+        rating::visible_ratings(&rating::prefs_for(Some(&self.acc)))
+    }
+
+    /// Single records follow the same rule as listings, hidden ones are reported as not found.
+    pub fn can_see(&self, author: &str, rating: &str) -> bool {
+        self.visible_ratings(Some(author))
+            .iter()
+            .any(|visible| visible == rating)
+    }
+}
+
+impl IntoResponse for ErrorApiV1 {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ErrorApiV1::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorApiV1::Scope(_) => StatusCode::FORBIDDEN,
+            ErrorApiV1::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ErrorApiV1::NotFound => StatusCode::NOT_FOUND,
+            ErrorApiV1::Status(_) => StatusCode::BAD_REQUEST,
+            ErrorApiV1::Artwork(ErrorArtwork::NotFound)
+            | ErrorApiV1::Bounty(ErrorBounty::NotFound) => StatusCode::NOT_FOUND,
+            ErrorApiV1::Artwork(ErrorArtwork::Forbidden)
+            | ErrorApiV1::Bounty(ErrorBounty::Forbidden) => StatusCode::FORBIDDEN,
+            ErrorApiV1::Artwork(err) if !err.is_internal() => StatusCode::BAD_REQUEST,
+            ErrorApiV1::Bounty(err) if !err.is_internal() => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let message = if status == StatusCode::INTERNAL_SERVER_ERROR {
+            error!("api error: {}", self);
+            String::from("internal server error")
+        } else {
+            self.to_string()
+        };
+        let mut response = (status, Json(ApiError { error: message })).into_response();
+        if let ErrorApiV1::RateLimited(retry_after) = self
+            && let Ok(value) = HeaderValue::from_str(&retry_after.num_seconds().max(1).to_string())
+        {
+            response.headers_mut().insert(RETRY_AFTER, value);
+        }
+        response
+    }
+}
+
+impl From<DbAcc> for ApiUser {
+    fn from(acc: DbAcc) -> Self {
+        Self {
+            username: acc.username,
+            role: acc.role,
+            created_at: acc.created_at,
+        }
+    }
+}
+
+impl From<DbAcc> for ApiAccount {
+    fn from(acc: DbAcc) -> Self {
+        Self {
+            username: acc.username,
+            email: acc.email,
+            verified_email: acc.verified_email,
+            role: acc.role,
+            created_at: acc.created_at,
+        }
+    }
+}
+
+impl From<DbArtwork> for ApiArtwork {
+    fn from(artwork: DbArtwork) -> Self {
+        Self {
+            id: artwork.artwork_id,
+            author: artwork.acc,
+            title: artwork.title,
+            description: artwork.description,
+            tags: artwork.tags,
+            width: artwork.width,
+            height: artwork.height,
+            rating: artwork.rating,
+            palette: artwork.palette,
+            blurhash: artwork.blurhash,
+            modified_at: artwork.modified_at,
+            created_at: artwork.created_at,
+        }
+    }
+}
+
+impl From<DbBounty> for ApiBounty {
+    fn from(bounty: DbBounty) -> Self {
+        Self {
+            id: bounty.bounty_id,
+            author: bounty.acc,
+            title: bounty.title,
+            description: bounty.description,
+            tags: bounty.tags,
+            reward: bounty.reward,
+            status: bounty.status,
+            rating: bounty.rating,
+            modified_at: bounty.modified_at,
+            created_at: bounty.created_at,
+        }
+    }
+}
+
+fn page(limit: Option<u32>, offset: Option<u32>) -> (u32, u32) {
+    (
+        limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAXIMUM_PAGE_SIZE),
+        offset.unwrap_or(0),
+    )
+}
+
+async fn authenticate(
+    State(api): State<ApiV1>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ErrorApiV1> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(ErrorApiV1::Unauthorized)?
+        .trim()
+        .to_string();
+
+    let time = Utc::now();
+    let (token, acc) = api_token::authenticate(&api.state.db, &token, time.timestamp_millis())
+        .await
+        .map_err(|err| match err {
+            ErrorApiToken::Db(err) => ErrorApiV1::Db(err),
+            _ => ErrorApiV1::Unauthorized,
+        })?;
+    let remaining = api.rate_limit(&token, time)?;
+    trace!(
+        "api request by {} with token {}",
+        acc.username, token.token_id
+    );
+
+    let limit = token.rate_limit;
+    req.extensions_mut().insert(ApiAuth { token, acc });
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert(RATE_LIMIT_HEADER, HeaderValue::from(limit));
+    headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from(remaining));
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/me",
+    tag = "users",
+    security(("token" = ["account:read"])),
+    responses(
+        (status = 200, body = ApiAccount),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError)
+    )
+)]
+async fn get_me(Extension(auth): Extension<ApiAuth>) -> Result<Json<ApiAccount>, ErrorApiV1> {
+    auth.require("account:read")?;
+    Ok(Json(auth.acc.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}",
+    tag = "users",
+    security(("token" = ["users:read"])),
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, body = ApiUser),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError)
+    )
+)]
+async fn get_user(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Path(username): Path<String>,
+) -> Result<Json<ApiUser>, ErrorApiV1> {
+    auth.require("users:read")?;
+    let acc = acc::get_by_username(&api.state.db, &username)
+        .await?
+        .ok_or(ErrorApiV1::NotFound)?;
+    Ok(Json(acc.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}/artworks",
+    tag = "artworks",
+    security(("token" = ["artworks:read"])),
+    params(("username" = String, Path), PageQuery),
+    responses((status = 200, body = [ApiArtwork]), (status = 403, body = ApiError))
+)]
+async fn get_user_artworks(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Path(username): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Vec<ApiArtwork>>, ErrorApiV1> {
+    auth.require("artworks:read")?;
+    let (limit, offset) = page(query.limit, query.offset);
+    let ratings = auth.visible_ratings(Some(&username));
+    let artworks =
+        artwork::get_page_by_acc(&api.state.db, &username, &ratings, limit, offset).await?;
+    Ok(Json(artworks.into_iter().map(ApiArtwork::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/artworks",
+    tag = "artworks",
+    security(("token" = ["artworks:read"])),
+    params(PageQuery),
+    responses((status = 200, body = [ApiArtwork]), (status = 403, body = ApiError))
+)]
+async fn get_artworks(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Vec<ApiArtwork>>, ErrorApiV1> {
+    auth.require("artworks:read")?;
+    let (limit, offset) = page(query.limit, query.offset);
+    let ratings = auth.visible_ratings(None);
+    let artworks = artwork::get_page(&api.state.db, &ratings, limit, offset).await?;
+    Ok(Json(artworks.into_iter().map(ApiArtwork::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/artworks/{artwork_id}",
+    tag = "artworks",
+    security(("token" = ["artworks:read"])),
+    params(("artwork_id" = String, Path)),
+    responses((status = 200, body = ApiArtwork), (status = 404, body = ApiError))
+)]
+async fn get_artwork(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Path(artwork_id): Path<String>,
+) -> Result<Json<ApiArtwork>, ErrorApiV1> {
+    auth.require("artworks:read")?;
+    let artwork = artwork::get(&api.state.db, &artwork_id)
+        .await?
+        .filter(|artwork| auth.can_see(&artwork.acc, &artwork.rating))
+        .ok_or(ErrorApiV1::NotFound)?;
+    Ok(Json(artwork.into()))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/artworks/{artwork_id}",
+    tag = "artworks",
+    security(("token" = ["artworks:write"])),
+    params(("artwork_id" = String, Path)),
+    request_body = ApiArtworkPatch,
+    responses(
+        (status = 200, body = ApiArtwork),
+        (status = 400, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError)
+    )
+)]
+async fn patch_artwork(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Path(artwork_id): Path<String>,
+    Json(patch): Json<ApiArtworkPatch>,
+) -> Result<Json<ApiArtwork>, ErrorApiV1> {
+    use artbounty_web_frontend::server::artwork::set_info;
+
+    auth.require("artworks:write")?;
+    let current = artwork::get(&api.state.db, &artwork_id)
+        .await?
+        .ok_or(ErrorApiV1::NotFound)?;
+    let artwork = set_info(
+        &api.state.db,
+        &auth.acc.username,
+        &artwork_id,
+        patch.title.as_deref().unwrap_or(&current.title),
+        patch.description.as_deref().unwrap_or(&current.description),
+        patch.tags.as_deref().unwrap_or(&current.tags),
+        Utc::now().timestamp_millis(),
+    )
+    .await?;
+    Ok(Json(artwork.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/bounties",
+    tag = "bounties",
+    security(("token" = ["bounties:read"])),
+    params(BountyQuery),
+    responses((status = 200, body = [ApiBounty]), (status = 400, body = ApiError))
+)]
+async fn get_bounties(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Query(query): Query<BountyQuery>,
+) -> Result<Json<Vec<ApiBounty>>, ErrorApiV1> {
+    auth.require("bounties:read")?;
+    if let Some(status) = query.status.as_deref()
+        && status != STATUS_OPEN
+        && status != STATUS_CLOSED
+    {
+        return Err(ErrorApiV1::Status(status.to_string()));
+    }
+    let (limit, offset) = page(query.limit, query.offset);
+    let ratings = auth.visible_ratings(None);
+    let bounties = bounty::get_page(
+        &api.state.db,
+        query.status.as_deref(),
+        &ratings,
+        limit,
+        offset,
+    )
+    .await?;
+    Ok(Json(bounties.into_iter().map(ApiBounty::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/bounties",
+    tag = "bounties",
+    security(("token" = ["bounties:write"])),
+    request_body = ApiBountyCreate,
+    responses(
+        (status = 201, body = ApiBounty),
+        (status = 400, body = ApiError),
+        (status = 403, body = ApiError)
+    )
+)]
+async fn post_bounty(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Json(body): Json<ApiBountyCreate>,
+) -> Result<(StatusCode, Json<ApiBounty>), ErrorApiV1> {
+    use artbounty_web_frontend::server::bounty::create;
+
+    auth.require("bounties:write")?;
+    let bounty = create(
+        &api.state.db,
+        &auth.acc.username,
+        &body.title,
+        &body.description,
+        &body.tags,
+        body.reward,
+        &body.rating,
+        Utc::now().timestamp_millis(),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(bounty.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/bounties/{bounty_id}",
+    tag = "bounties",
+    security(("token" = ["bounties:read"])),
+    params(("bounty_id" = String, Path)),
+    responses((status = 200, body = ApiBounty), (status = 404, body = ApiError))
+)]
+async fn get_bounty(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Path(bounty_id): Path<String>,
+) -> Result<Json<ApiBounty>, ErrorApiV1> {
+    auth.require("bounties:read")?;
+    let bounty = bounty::get(&api.state.db, &bounty_id)
+        .await?
+        .filter(|bounty| auth.can_see(&bounty.acc, &bounty.rating))
+        .ok_or(ErrorApiV1::NotFound)?;
+    Ok(Json(bounty.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/bounties/{bounty_id}/close",
+    tag = "bounties",
+    security(("token" = ["bounties:write"])),
+    params(("bounty_id" = String, Path)),
+    responses(
+        (status = 200, body = ApiBounty),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError)
+    )
+)]
+async fn close_bounty(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Path(bounty_id): Path<String>,
+) -> Result<Json<ApiBounty>, ErrorApiV1> {
+    use artbounty_web_frontend::server::bounty::close;
+
+    auth.require("bounties:write")?;
+    let bounty = close(
+        &api.state.db,
+        &auth.acc.username,
+        &bounty_id,
+        Utc::now().timestamp_millis(),
+    )
+    .await?;
+    Ok(Json(bounty.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/search",
+    tag = "search",
+    security(("token" = [])),
+    params(SearchQuery),
+    responses((status = 200, body = ApiSearch))
+)]
+async fn search(
+    State(api): State<ApiV1>,
+    Extension(auth): Extension<ApiAuth>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<ApiSearch>, ErrorApiV1> {
+    let (limit, offset) = page(query.limit, query.offset);
+    let q = query.q.trim();
+
+    let users = if api_token::has_scope(&auth.token, "users:read") {
+        acc::search(&api.state.db, q, limit, offset).await?
+    } else {
+        Vec::new()
+    };
+    let ratings = auth.visible_ratings(None);
+    let artworks = if api_token::has_scope(&auth.token, "artworks:read") {
+        artwork::search(&api.state.db, q, &ratings, limit, offset).await?
+    } else {
+        Vec::new()
+    };
+    let bounties = if api_token::has_scope(&auth.token, "bounties:read") {
+        bounty::search(&api.state.db, q, &ratings, limit, offset).await?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Json(ApiSearch {
+        users: users.into_iter().map(ApiUser::from).collect(),
+        artworks: artworks.into_iter().map(ApiArtwork::from).collect(),
+        bounties: bounties.into_iter().map(ApiBounty::from).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod api_v1_tests {
+    use artbounty_web_frontend::{
+        db::{
+            api_token::DbApiToken,
+            artwork::{self, DbArtwork, PREVIEW_READY},
+        },
+        server::{api_token, auth::register, bounty, test_support::test_state},
+    };
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use chrono::{TimeDelta, Utc};
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use super::{ApiV1, PRUNE_AT};
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        token: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json");
+        let req = req
+            .body(
+                body.map(|body| Body::from(body.to_string()))
+                    .unwrap_or_default(),
+            )
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn scoped_tokens_and_rate_limit() {
+        let (state, _) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        artwork::insert(
+            &state.db,
+            DbArtwork {
+                artwork_id: String::from("fox1"),
+                acc: String::from("hey"),
+                title: String::from("Red Fox"),
+                description: String::new(),
+                tags: vec![String::from("fox")],
+                file: String::from("fox1"),
+                width: 100,
+                height: 200,
+                protected: false,
+                preview_status: String::from(PREVIEW_READY),
+                rating: String::from("sfw"),
+                palette: Vec::new(),
+                blurhash: String::new(),
+                sha256: String::new(),
+                modified_at: 0,
+                created_at: 0,
+            },
+        )
+        .await
+        .unwrap();
+        // hidden by the default preferences of the token owner
+        artwork::insert(
+            &state.db,
+            DbArtwork {
+                artwork_id: String::from("fox2"),
+                title: String::from("Red Fox at night"),
+                rating: String::from("explicit"),
+                created_at: 1,
+                ..artwork::get(&state.db, "fox1").await.unwrap().unwrap()
+            },
+        )
+        .await
+        .unwrap();
+        let (reader, _) = api_token::create(&state.db, &acc, "reader", "artworks:read", 3, 0)
+            .await
+            .unwrap();
+        let (writer, _) = api_token::create(
+            &state.db,
+            &acc,
+            "writer",
+            "bounties:read bounties:write",
+            60,
+            0,
+        )
+        .await
+        .unwrap();
+        let app = ApiV1::new(state).routes::<()>();
+
+        let (status, doc) = send(&app, "GET", "/api/v1/openapi.json", "", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(doc["paths"]["/api/v1/artworks/{artwork_id}"]["patch"].is_object());
+
+        let (status, _) = send(&app, "GET", "/api/v1/artworks", "abt_nope", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, artworks) = send(&app, "GET", "/api/v1/artworks", &reader, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(artworks[0]["id"], "fox1");
+        assert_eq!(artworks.as_array().unwrap().len(), 1);
+
+        let (status, found) = send(&app, "GET", "/api/v1/search?q=FOX", &reader, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found["artworks"][0]["title"], "Red Fox");
+        assert_eq!(found["artworks"].as_array().unwrap().len(), 1);
+
+        // third request in the same minute is the last one allowed
+        let (status, _) = send(&app, "GET", "/api/v1/bounties", &reader, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, "GET", "/api/v1/artworks/fox1", &reader, None).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        let (status, bounty) = send(
+            &app,
+            "POST",
+            "/api/v1/bounties",
+            &writer,
+            Some(json!({ "title": "Draw my cat", "tags": ["Cat"], "reward": 5000 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(bounty["status"], "open");
+        assert_eq!(bounty["tags"][0], "cat");
+
+        let uri = format!("/api/v1/bounties/{}/close", bounty["id"].as_str().unwrap());
+        let (status, bounty) = send(&app, "POST", &uri, &writer, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(bounty["status"], "closed");
+
+        let (status, bounties) =
+            send(&app, "GET", "/api/v1/bounties?status=open", &writer, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(bounties.as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn users_need_their_scope() {
+        let (state, _) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let (reader, _) = api_token::create(&state.db, &acc, "reader", "artworks:read", 60, 0)
+            .await
+            .unwrap();
+        let (people, _) = api_token::create(&state.db, &acc, "people", "users:read", 60, 0)
+            .await
+            .unwrap();
+        let app = ApiV1::new(state).routes::<()>();
+
+        let (status, _) = send(&app, "GET", "/api/v1/users/hey", &reader, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, found) = send(&app, "GET", "/api/v1/search?q=hey", &reader, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found["users"].as_array().unwrap().len(), 0);
+
+        let (status, user) = send(&app, "GET", "/api/v1/users/hey", &people, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(user["username"], "hey");
+        let (_, found) = send(&app, "GET", "/api/v1/search?q=hey", &people, None).await;
+        assert_eq!(found["users"][0]["username"], "hey");
+    }
+
+    #[tokio::test]
+    async fn trackers_outside_their_window_are_pruned() {
+        let (state, _) = test_state().await;
+        let api = ApiV1::new(state);
+        let token = |token_hash: String| DbApiToken {
+            token_hash,
+            token_id: String::new(),
+            acc: String::from("hey"),
+            name: String::from("reader"),
+            scopes: Vec::new(),
+            rate_limit: 60,
+            last_used: None,
+            modified_at: 0,
+            created_at: 0,
+        };
+
+        let time = Utc::now();
+        for i in 0..=PRUNE_AT {
+            api.rate_limit(&token(i.to_string()), time).unwrap();
+        }
+        api.rate_limit(&token(String::from("late")), time + TimeDelta::minutes(1))
+            .unwrap();
+        assert_eq!(api.trackers.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn hidden_ratings_are_not_found_by_id() {
+        let (state, _) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        register(&state, "other", "other@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let fox = DbArtwork {
+            artwork_id: String::from("fox1"),
+            acc: String::from("hey"),
+            title: String::from("Red Fox"),
+            description: String::new(),
+            tags: Vec::new(),
+            file: String::from("fox1"),
+            width: 100,
+            height: 200,
+            protected: false,
+            preview_status: String::from(PREVIEW_READY),
+            rating: String::from("explicit"),
+            palette: Vec::new(),
+            blurhash: String::new(),
+            sha256: String::new(),
+            modified_at: 0,
+            created_at: 0,
+        };
+        artwork::insert(&state.db, fox.clone()).await.unwrap();
+        artwork::insert(
+            &state.db,
+            DbArtwork {
+                artwork_id: String::from("cat1"),
+                acc: String::from("other"),
+                ..fox
+            },
+        )
+        .await
+        .unwrap();
+        let hidden = bounty::create(&state.db, "other", "Draw me", "", &[], 100, "explicit", 0)
+            .await
+            .unwrap();
+        let (token, _) = api_token::create(
+            &state.db,
+            &acc,
+            "reader",
+            "artworks:read bounties:read",
+            60,
+            0,
+        )
+        .await
+        .unwrap();
+        let app = ApiV1::new(state).routes::<()>();
+
+        let (status, _) = send(&app, "GET", "/api/v1/artworks/fox1", &token, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, "GET", "/api/v1/artworks/cat1", &token, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let uri = format!("/api/v1/bounties/{}", hidden.bounty_id);
+        let (status, _) = send(&app, "GET", &uri, &token, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
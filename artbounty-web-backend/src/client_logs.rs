@@ -0,0 +1,164 @@
+use artbounty_web_frontend::{
+    api::{
+        CLIENT_LOG_BATCH_SIZE, CLIENT_LOG_PATH, ClientLogEvent, MAXIMUM_CLIENT_LOG_FIELD_LENGTH,
+        clip_client_log_field,
+    },
+    server::{ServerState, auth},
+};
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, State},
+    http::{HeaderMap, StatusCode, header::COOKIE},
+    routing::post,
+};
+use chrono::Utc;
+use tracing::{error, warn};
+
+/// Room for a full batch of fields at their maximum length plus the json around them.
+pub const MAXIMUM_CLIENT_LOG_BODY: usize =
+    CLIENT_LOG_BATCH_SIZE * MAXIMUM_CLIENT_LOG_FIELD_LENGTH * 2;
+
+/// Re-emits browser events into the server logs under the `client` target.
+#[derive(Clone)]
+pub struct ClientLogRoutes {
+    state: ServerState,
+}
+
+impl ClientLogRoutes {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(CLIENT_LOG_PATH, post(ingest))
+            .layer(DefaultBodyLimit::max(MAXIMUM_CLIENT_LOG_BODY))
+            .with_state(self)
+    }
+
+    async fn session_acc(&self, headers: &HeaderMap) -> Option<String> {
+        let token = headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(auth::get_session_token)?;
+        match auth::get_session_acc(&self.state, token, Utc::now().timestamp_millis()).await {
+            Ok(acc) => acc.map(|acc| acc.username),
+            Err(err) => {
+                error!("failed to read session: {}", err);
+                None
+            }
+        }
+    }
+}
+
+/// Beacons are sent as text/plain, so the body is parsed regardless of its content type.
+async fn ingest(
+    State(routes): State<ClientLogRoutes>,
+    headers: HeaderMap,
+    body: String,
+) -> StatusCode {
+    let Ok(events) = serde_json::from_str::<Vec<ClientLogEvent>>(&body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let acc = routes.session_acc(&headers).await.unwrap_or_default();
+    for event in events.iter().take(CLIENT_LOG_BATCH_SIZE) {
+        emit(&acc, event);
+    }
+    StatusCode::NO_CONTENT
+}
+
+fn emit(acc: &str, event: &ClientLogEvent) {
+    let message = clip_client_log_field(&event.message);
+    let client_target = clip_client_log_field(&event.target);
+    let spans = clip_client_log_field(&event.spans);
+    let origin = clip_client_log_field(&event.origin);
+    let url = clip_client_log_field(&event.url);
+    let user_agent = clip_client_log_field(&event.user_agent);
+    let build = clip_client_log_field(&event.build);
+    match event.level.as_str() {
+        "ERROR" | "PANIC" => error!(
+            target: "client",
+            acc,
+            level = %clip_client_log_field(&event.level),
+            client_target,
+            spans,
+            origin,
+            url,
+            user_agent,
+            build,
+            "{}",
+            message
+        ),
+        _ => warn!(
+            target: "client",
+            acc,
+            level = %clip_client_log_field(&event.level),
+            client_target,
+            spans,
+            origin,
+            url,
+            user_agent,
+            build,
+            "{}",
+            message
+        ),
+    }
+}
+
+#[cfg(test)]
+mod client_logs_tests {
+    use artbounty_web_frontend::{
+        api::{ClientLogEvent, MAXIMUM_CLIENT_LOG_FIELD_LENGTH, clip_client_log_field},
+        server::test_support::test_state,
+    };
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use super::{ClientLogRoutes, MAXIMUM_CLIENT_LOG_BODY};
+
+    async fn post(app: &axum::Router, body: String) -> StatusCode {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/client_logs")
+            .header("content-type", "text/plain;charset=UTF-8")
+            .body(Body::from(body))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn batches_are_accepted_and_garbage_is_not() {
+        let app = ClientLogRoutes::new(test_state().await.0).routes::<()>();
+        let event = ClientLogEvent {
+            level: String::from("ERROR"),
+            message: String::from("failed to load artworks"),
+            target: String::from("artbounty_web_frontend::app"),
+            spans: String::from("gallery"),
+            origin: String::from("src/app.rs:10"),
+            url: String::from("http://localhost:3000/"),
+            user_agent: String::from("test"),
+            build: String::from("0.1.0"),
+        };
+        let batch = serde_json::to_string(&vec![event]).unwrap();
+        assert_eq!(post(&app, batch).await, StatusCode::NO_CONTENT);
+        assert_eq!(
+            post(&app, String::from("not json")).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            post(&app, "x".repeat(MAXIMUM_CLIENT_LOG_BODY + 1)).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let long = "é".repeat(MAXIMUM_CLIENT_LOG_FIELD_LENGTH + 1);
+        assert_eq!(
+            clip_client_log_field(&long).chars().count(),
+            MAXIMUM_CLIENT_LOG_FIELD_LENGTH
+        );
+        assert_eq!(clip_client_log_field("short"), "short");
+    }
+}
@@ -0,0 +1,527 @@
+use artbounty_web_frontend::{
+    db::acc::DbAccDiscord,
+    server::{
+        ServerState,
+        auth::{self, ErrorAuth},
+        discord::{self, ErrorDiscord},
+        encode_query_value,
+        token::{ErrorToken, Token, TokenKind},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{AUTHORIZATION, COOKIE},
+    },
+    response::Redirect,
+    routing::{get, post},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use tracing::{error, trace};
+
+pub const START_PATH: &str = "/auth/discord/start";
+pub const CALLBACK_PATH: &str = "/auth/discord/callback";
+pub const REDEEM_PATH: &str = "/auth/discord/redeem";
+pub const STATE_DURATION_MS: i64 = 1000 * 60 * 10;
+
+pub const CLIENT_ID_ENV: &str = "DISCORD_CLIENT_ID";
+pub const CLIENT_SECRET_ENV: &str = "DISCORD_CLIENT_SECRET";
+pub const AUTHORIZE_URL_ENV: &str = "DISCORD_AUTHORIZE_URL";
+pub const TOKEN_URL_ENV: &str = "DISCORD_TOKEN_URL";
+pub const USER_URL_ENV: &str = "DISCORD_USER_URL";
+pub const BOT_SECRET_ENV: &str = "DISCORD_BOT_SECRET";
+
+/// Provider endpoints are configurable so a local mock provider can stand in for discord.
+#[derive(Debug, Clone)]
+pub struct DiscordOAuthConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub user_url: String,
+    pub redirect_url: String,
+    pub scope: String,
+}
+
+#[derive(Clone)]
+pub struct DiscordOAuth {
+    config: DiscordOAuthConfig,
+    http: reqwest::Client,
+    state: ServerState,
+}
+
+/// Lets the discord bot redeem `/link` codes, authenticated with a shared secret.
+#[derive(Clone)]
+pub struct DiscordBotLink {
+    secret: String,
+    state: ServerState,
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorDiscordOAuth {
+    #[error("login required")]
+    Unauthorized,
+
+    #[error("invalid state: {0}")]
+    State(#[from] ErrorToken),
+
+    #[error("provider denied access: {0}")]
+    Denied(String),
+
+    #[error("provider request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("auth error: {0}")]
+    Auth(#[from] ErrorAuth),
+
+    #[error("link error: {0}")]
+    Link(#[from] ErrorDiscord),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedeemBody {
+    pub code: String,
+    pub user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserResponse {
+    id: String,
+}
+
+impl DiscordOAuthConfig {
+    /// Returns `None` when `DISCORD_CLIENT_ID` is not set, oauth linking is then disabled.
+    pub fn from_env(site_url: &str) -> Option<Self> {
+        let client_id = std::env::var(CLIENT_ID_ENV)
+            .ok()
+            .filter(|id| !id.is_empty())?;
+        let env_or = |name: &str, default: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| default.to_string())
+        };
+
+        Some(Self {
+            client_id,
+            client_secret: env_or(CLIENT_SECRET_ENV, ""),
+            authorize_url: env_or(AUTHORIZE_URL_ENV, "https://discord.com/oauth2/authorize"),
+            token_url: env_or(TOKEN_URL_ENV, "https://discord.com/api/oauth2/token"),
+            user_url: env_or(USER_URL_ENV, "https://discord.com/api/users/@me"),
+            redirect_url: format!("{}{}", site_url, CALLBACK_PATH),
+            scope: String::from("identify"),
+        })
+    }
+}
+
+impl DiscordOAuth {
+    pub fn new(config: DiscordOAuthConfig, state: ServerState) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            state,
+        }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(START_PATH, get(start))
+            .route(CALLBACK_PATH, get(callback))
+            .with_state(self)
+    }
+
+    /// Provider url the user is sent to, `state` binds the flow to their account.
+    pub fn authorize_url(&self, username: &str, time: i64) -> String {
+        let state = Token::new(
+            TokenKind::DiscordLink,
+            username,
+            time + STATE_DURATION_MS,
+            auth::new_session_token(),
+        )
+        .sign(&self.state.settings.token_secret);
+
+        let query = [
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", self.config.scope.as_str()),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("state", state.as_str()),
+            ("prompt", "consent"),
+        ]
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, encode_query_value(value)))
+        .collect::<Vec<String>>()
+        .join("&");
+
+        format!("{}?{}", self.config.authorize_url, query)
+    }
+
+    /// Exchanges the authorization code and links the discord user to the account in `state`.
+    ///
+    /// The account must also own the current session, so a callback url can't be replayed in someone else's browser.
+    pub async fn finish(
+        &self,
+        session_acc: Option<&str>,
+        query: CallbackQuery,
+        time: i64,
+    ) -> Result<String, ErrorDiscordOAuth> {
+        if let Some(error) = query.error {
+            return Err(ErrorDiscordOAuth::Denied(error));
+        }
+        let (Some(code), Some(state)) = (query.code, query.state) else {
+            return Err(ErrorDiscordOAuth::Denied(String::from("missing code")));
+        };
+        let state = Token::verify(
+            &self.state.settings.token_secret,
+            TokenKind::DiscordLink,
+            &state,
+            time,
+        )?;
+        if session_acc != Some(state.subject.as_str()) {
+            return Err(ErrorDiscordOAuth::Unauthorized);
+        }
+
+        let token = self
+            .http
+            .post(&self.config.token_url)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TokenResponse>()
+            .await?;
+
+        let user = self
+            .http
+            .get(&self.config.user_url)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<UserResponse>()
+            .await?;
+
+        discord::link(
+            &self.state,
+            &state.subject,
+            DbAccDiscord {
+                user_id: user.id.clone(),
+                token: token.access_token,
+            },
+            time,
+        )
+        .await?;
+        trace!("discord user {} linked to {}", user.id, state.subject);
+
+        Ok(user.id)
+    }
+
+    async fn session_acc(&self, headers: &HeaderMap, time: i64) -> Option<String> {
+        let token = headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(auth::get_session_token)?;
+        match auth::get_session_acc(&self.state, token, time).await {
+            Ok(acc) => acc.map(|acc| acc.username),
+            Err(err) => {
+                error!("failed to read session: {}", err);
+                None
+            }
+        }
+    }
+}
+
+impl DiscordBotLink {
+    pub fn new(secret: impl Into<String>, state: ServerState) -> Self {
+        Self {
+            secret: secret.into(),
+            state,
+        }
+    }
+
+    /// Returns `None` when `DISCORD_BOT_SECRET` is not set.
+    pub fn from_env(state: ServerState) -> Option<Self> {
+        let secret = std::env::var(BOT_SECRET_ENV)
+            .ok()
+            .filter(|secret| !secret.is_empty())?;
+        Some(Self::new(secret, state))
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(REDEEM_PATH, post(redeem))
+            .with_state(self)
+    }
+}
+
+impl ErrorDiscordOAuth {
+    /// Short reason passed back to the settings page.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ErrorDiscordOAuth::Unauthorized => "login_required",
+            ErrorDiscordOAuth::State(_) => "expired",
+            ErrorDiscordOAuth::Denied(_) => "denied",
+            ErrorDiscordOAuth::Link(ErrorDiscord::Taken) => "already_linked",
+            _ => "error",
+        }
+    }
+}
+
+async fn start(State(oauth): State<DiscordOAuth>, headers: HeaderMap) -> Redirect {
+    let time = Utc::now().timestamp_millis();
+    let Some(username) = oauth.session_acc(&headers, time).await else {
+        return Redirect::to("/login");
+    };
+    Redirect::to(&oauth.authorize_url(&username, time))
+}
+
+async fn callback(
+    State(oauth): State<DiscordOAuth>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> Redirect {
+    let time = Utc::now().timestamp_millis();
+    let session_acc = oauth.session_acc(&headers, time).await;
+    match oauth.finish(session_acc.as_deref(), query, time).await {
+        Ok(_) => Redirect::to("/settings?discord=linked"),
+        Err(err) => {
+            error!("discord oauth failed: {}", err);
+            Redirect::to(&format!("/settings?discord={}", err.reason()))
+        }
+    }
+}
+
+/// Responds with the linked username, the bot shows it back to the user.
+async fn redeem(
+    State(bot): State<DiscordBotLink>,
+    headers: HeaderMap,
+    Json(body): Json<RedeemBody>,
+) -> (StatusCode, String) {
+    let authorized = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|secret| bool::from(secret.as_bytes().ct_eq(bot.secret.as_bytes())));
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, String::from("unauthorized"));
+    }
+
+    let time = Utc::now().timestamp_millis();
+    match discord::redeem_link_code(&bot.state, &body.code, &body.user_id, time).await {
+        Ok(username) => (StatusCode::OK, username),
+        Err(err @ ErrorDiscord::Code) => (StatusCode::NOT_FOUND, err.to_string()),
+        Err(err @ ErrorDiscord::Taken) => (StatusCode::CONFLICT, err.to_string()),
+        Err(err) => {
+            error!("failed to redeem discord link code: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                String::from("internal error"),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod discord_tests {
+    use artbounty_web_frontend::{
+        db::acc,
+        server::{
+            auth::{LoginStep, login, register},
+            discord::new_link_code,
+            test_support::test_state,
+        },
+    };
+    use axum::{
+        Form, Json, Router,
+        body::Body,
+        http::{HeaderMap, Request, StatusCode, header},
+        routing::{get, post},
+    };
+    use chrono::Utc;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    use super::{DiscordBotLink, DiscordOAuth, DiscordOAuthConfig};
+
+    /// Minimal stand-in for discord's token and user endpoints.
+    async fn mock_provider() -> String {
+        let app = Router::new()
+            .route(
+                "/token",
+                post(|Form(form): Form<HashMap<String, String>>| async move {
+                    if form.get("code").map(String::as_str) != Some("good-code") {
+                        return Err(StatusCode::BAD_REQUEST);
+                    }
+                    Ok(Json(
+                        json!({ "access_token": "mock-token", "token_type": "Bearer" }),
+                    ))
+                }),
+            )
+            .route(
+                "/users/@me",
+                get(|headers: HeaderMap| async move {
+                    let auth = headers.get(header::AUTHORIZATION).unwrap();
+                    assert_eq!(auth, "Bearer mock-token");
+                    Json(json!({ "id": "1234", "username": "hey" }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn oauth_flow_links_discord_account() {
+        let provider = mock_provider().await;
+        let (state, _) = test_state().await;
+        // the routes read the wall clock, so the session has to be fresh
+        let time = Utc::now().timestamp_millis();
+        register(&state, "hey", "hey@example.com", "password123", time)
+            .await
+            .unwrap();
+        let Ok(LoginStep::Session(session, _)) =
+            login(&state, "hey@example.com", "password123", "", "", time).await
+        else {
+            panic!("expected a session");
+        };
+
+        let oauth = DiscordOAuth::new(
+            DiscordOAuthConfig {
+                client_id: String::from("client"),
+                client_secret: String::from("secret"),
+                authorize_url: format!("{}/authorize", provider),
+                token_url: format!("{}/token", provider),
+                user_url: format!("{}/users/@me", provider),
+                redirect_url: String::from("http://localhost:3000/auth/discord/callback"),
+                scope: String::from("identify"),
+            },
+            state.clone(),
+        );
+        let app = oauth.routes::<()>();
+        let cookie = format!("session={}", session);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::get("/auth/discord/start")
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let location = res.headers()[header::LOCATION].to_str().unwrap();
+        assert!(location.starts_with(&format!("{}/authorize?response_type=code", provider)));
+        let (_, oauth_state) = location.split_once("&state=").unwrap();
+        let (oauth_state, _) = oauth_state.split_once('&').unwrap();
+
+        // callback without the matching session is refused
+        let res = app
+            .clone()
+            .oneshot(
+                Request::get(format!(
+                    "/auth/discord/callback?code=good-code&state={}",
+                    oauth_state
+                ))
+                .body(Body::empty())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            res.headers()[header::LOCATION],
+            "/settings?discord=login_required"
+        );
+
+        let res = app
+            .oneshot(
+                Request::get(format!(
+                    "/auth/discord/callback?code=good-code&state={}",
+                    oauth_state
+                ))
+                .header(header::COOKIE, &cookie)
+                .body(Body::empty())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[header::LOCATION], "/settings?discord=linked");
+
+        let acc = acc::get_by_username(&state.db, "hey")
+            .await
+            .unwrap()
+            .unwrap();
+        let discord = acc.discord.unwrap();
+        assert_eq!(discord.user_id, "1234");
+        assert_eq!(discord.token, "mock-token");
+    }
+
+    #[tokio::test]
+    async fn bot_redeems_link_code() {
+        let (state, _) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let code = new_link_code(&state.db, &acc, Utc::now().timestamp_millis())
+            .await
+            .unwrap();
+        let app = DiscordBotLink::new("bot-secret", state.clone()).routes::<()>();
+        let redeem = |secret: &str, code: &str| {
+            Request::post("/auth/discord/redeem")
+                .header(header::AUTHORIZATION, format!("Bearer {}", secret))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "code": code, "user_id": "1234" }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(redeem("wrong", &code)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = app
+            .clone()
+            .oneshot(redeem("bot-secret", &code))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hey");
+
+        let res = app.oneshot(redeem("bot-secret", &code)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let acc = acc::get_by_discord_id(&state.db, "1234")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(acc.username, "hey");
+    }
+}
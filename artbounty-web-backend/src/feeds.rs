@@ -0,0 +1,211 @@
+use artbounty_web_frontend::{
+    api::{
+        BOUNTY_FEED_PATH, FEED_FILE, PROFILE_PAGE_PATH, SITEMAP_CHUNK_PATH, SITEMAP_PATH,
+        TAG_FEED_PATH,
+    },
+    server::{
+        ServerState,
+        feed::{self, Document, ErrorFeed},
+    },
+};
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use tracing::error;
+
+const SITEMAP_CONTENT_TYPE: &str = "application/xml";
+const FEED_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+const CACHE_CONTROL: &str = "public, max-age=600";
+
+/// Sitemaps for crawlers and Atom feeds of artists, tags and open bounties.
+#[derive(Clone)]
+pub struct FeedRoutes {
+    state: ServerState,
+}
+
+impl FeedRoutes {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(SITEMAP_PATH, get(sitemap_index))
+            .route(
+                &format!("{}/:kind/:chunk", SITEMAP_CHUNK_PATH),
+                get(sitemap_chunk),
+            )
+            .route(
+                &format!("{}/:handle/{}", PROFILE_PAGE_PATH, FEED_FILE),
+                get(artist_feed),
+            )
+            .route(
+                &format!("{}/:tag/{}", TAG_FEED_PATH, FEED_FILE),
+                get(tag_feed),
+            )
+            .route(BOUNTY_FEED_PATH, get(bounty_feed))
+            .with_state(self)
+    }
+}
+
+async fn sitemap_index(State(routes): State<FeedRoutes>, headers: HeaderMap) -> Response {
+    let state = &routes.state;
+    let document = feed::sitemap_index(&state.db, &state.settings).await;
+    respond(&headers, document, SITEMAP_CONTENT_TYPE)
+}
+
+async fn sitemap_chunk(
+    State(routes): State<FeedRoutes>,
+    Path((kind, chunk)): Path<(String, u32)>,
+    headers: HeaderMap,
+) -> Response {
+    let state = &routes.state;
+    let document = feed::sitemap_chunk(&state.db, &state.settings, &kind, chunk).await;
+    respond(&headers, document, SITEMAP_CONTENT_TYPE)
+}
+
+async fn artist_feed(
+    State(routes): State<FeedRoutes>,
+    Path(handle): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let state = &routes.state;
+    let document = feed::artist_feed(&state.db, &state.settings, &handle).await;
+    respond(&headers, document, FEED_CONTENT_TYPE)
+}
+
+async fn tag_feed(
+    State(routes): State<FeedRoutes>,
+    Path(tag): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let state = &routes.state;
+    let document = feed::tag_feed(&state.db, &state.settings, &tag).await;
+    respond(&headers, document, FEED_CONTENT_TYPE)
+}
+
+async fn bounty_feed(State(routes): State<FeedRoutes>, headers: HeaderMap) -> Response {
+    let state = &routes.state;
+    let document = feed::bounty_feed(&state.db, &state.settings).await;
+    respond(&headers, document, FEED_CONTENT_TYPE)
+}
+
+/// Answers 304 when the client's copy is still current, If-None-Match wins over If-Modified-Since.
+fn respond(
+    headers: &HeaderMap,
+    document: Result<Document, ErrorFeed>,
+    content_type: &'static str,
+) -> Response {
+    let document = match document {
+        Ok(document) => document,
+        Err(ErrorFeed::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!("failed to render feed: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let modified_at = Utc
+        .timestamp_millis_opt(document.modified_at)
+        .single()
+        .unwrap_or_default();
+    let not_modified = match headers.get(header::IF_NONE_MATCH) {
+        Some(value) => value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == document.etag)
+        }),
+        None => headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .is_some_and(|since| modified_at.timestamp() <= since.timestamp()),
+    };
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, content_type)], document.body).into_response()
+    };
+    let response_headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&document.etag) {
+        response_headers.insert(header::ETAG, etag);
+    }
+    if let Ok(last_modified) =
+        HeaderValue::from_str(&modified_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    {
+        response_headers.insert(header::LAST_MODIFIED, last_modified);
+    }
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+    response
+}
+
+#[cfg(test)]
+mod feeds_tests {
+    use artbounty_web_frontend::server::test_support::test_state;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use tower::ServiceExt;
+
+    use super::FeedRoutes;
+
+    #[tokio::test]
+    async fn unchanged_documents_are_not_sent_again() {
+        let app = FeedRoutes::new(test_state().await.0).routes::<()>();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/sitemap.xml")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+
+        let request = Request::builder()
+            .uri("/sitemap.xml")
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let request = Request::builder()
+            .uri("/bounties/feed.atom")
+            .header(header::IF_MODIFIED_SINCE, last_modified)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let request = Request::builder()
+            .uri("/u/nobody/feed.atom")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::builder()
+            .uri("/sitemap/comments/0")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,589 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageReader, Rgba, RgbaImage, imageops::FilterType};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ErrorImg {
+    #[error("failed to read image: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to decode image: {0}")]
+    Decode(#[from] image::ImageError),
+
+    #[error("failed to encode webp: {0}")]
+    Encode(String),
+}
+
+pub fn decode(bytes: &[u8]) -> Result<DynamicImage, ErrorImg> {
+    let img = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .decode()?;
+    Ok(img)
+}
+
+/// Raw pixels of a resized variant, ready to be encoded.
+pub struct ImgData {
+    pub bytes: Vec<u8>,
+    pub color: webp::PixelLayout,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImgData {
+    /// Scales `img` down to at most `max_height` keeping its ratio, smaller images keep their size,
+    /// then stamps the watermark over the scaled variant.
+    pub fn new(img: &DynamicImage, max_height: u32, watermark: Option<&Watermark>) -> ImgData {
+        let mut img = if img.height() > max_height {
+            let ratio = img.width() as f32 / img.height() as f32;
+            let new_width = ((max_height as f32 * ratio) as u32).max(1);
+            img.resize(new_width, max_height, FilterType::Triangle)
+        } else {
+            img.clone()
+        };
+        let color = ImgData::webp_color_type(img.color());
+
+        if let Some(watermark) = watermark {
+            let mut rgba = img.to_rgba8();
+            watermark.apply(&mut rgba);
+            img = DynamicImage::ImageRgba8(rgba);
+        }
+
+        let bytes = if color == webp::PixelLayout::Rgba {
+            img.to_rgba8().into_raw()
+        } else {
+            img.to_rgb8().into_raw()
+        };
+
+        ImgData {
+            bytes,
+            color,
+            width: img.width(),
+            height: img.height(),
+        }
+    }
+
+    pub fn encode_webp(&self) -> Result<Vec<u8>, ErrorImg> {
+        let encoder = webp::Encoder::new(&self.bytes, self.color, self.width, self.height);
+        let webp = encoder
+            .encode_simple(false, 75f32)
+            .map_err(|err| ErrorImg::Encode(format!("{:?}", err)))?;
+        Ok(webp.to_vec())
+    }
+
+    fn webp_color_type(t: image::ColorType) -> webp::PixelLayout {
+        match t {
+            image::ColorType::Rgba8 => webp::PixelLayout::Rgba,
+            image::ColorType::Rgba16 => webp::PixelLayout::Rgba,
+            image::ColorType::Rgba32F => webp::PixelLayout::Rgba,
+            image::ColorType::La8 => webp::PixelLayout::Rgba,
+            image::ColorType::La16 => webp::PixelLayout::Rgba,
+            _ => webp::PixelLayout::Rgb,
+        }
+    }
+}
+
+/// What gets stamped over a preview.
+pub enum Mark {
+    Text(String),
+    Image(RgbaImage),
+}
+
+pub enum Placement {
+    Tiled,
+    Corner,
+}
+
+pub struct Watermark {
+    pub mark: Mark,
+    pub placement: Placement,
+    /// 0.0-1.0, multiplied with the alpha of the mark.
+    pub opacity: f32,
+}
+
+impl Watermark {
+    /// Stamps the mark over `img`, sized relative to it so previews of any size look alike.
+    pub fn apply(&self, img: &mut RgbaImage) {
+        let (width, height) = img.dimensions();
+        let share = match self.placement {
+            Placement::Tiled => 4,
+            Placement::Corner => 5,
+        };
+        let mark = match &self.mark {
+            Mark::Text(text) => {
+                let cell = (height / (GLYPH_HEIGHT * 12)).max(1);
+                render_text(text, cell)
+            }
+            Mark::Image(mark) => {
+                let max_width = (width / share).max(1);
+                if mark.width() > max_width {
+                    let new_height = ((mark.height() as f32 * max_width as f32
+                        / mark.width() as f32) as u32)
+                        .max(1);
+                    image::imageops::resize(mark, max_width, new_height, FilterType::Triangle)
+                } else {
+                    mark.clone()
+                }
+            }
+        };
+        if mark.width() == 0 || mark.height() == 0 {
+            return;
+        }
+
+        match self.placement {
+            Placement::Tiled => {
+                let step_x = mark.width() + mark.width() / 2;
+                let step_y = mark.height() * 3;
+                let mut row = 0;
+                let mut y = 0;
+                while y < height {
+                    // every other row is shifted so the marks don't line up in columns
+                    let mut x = if row % 2 == 0 {
+                        0
+                    } else {
+                        -(step_x as i64 / 2)
+                    };
+                    while x < width as i64 {
+                        overlay(img, &mark, x, y as i64, self.opacity);
+                        x += step_x as i64;
+                    }
+                    y += step_y;
+                    row += 1;
+                }
+            }
+            Placement::Corner => {
+                let margin = (width.min(height) / 40) as i64;
+                let x = width as i64 - mark.width() as i64 - margin;
+                let y = height as i64 - mark.height() as i64 - margin;
+                overlay(img, &mark, x.max(0), y.max(0), self.opacity);
+            }
+        }
+    }
+}
+
+/// Alpha blends `mark` onto `img` with its top left corner at `x`, `y`, clipping what's outside.
+fn overlay(img: &mut RgbaImage, mark: &RgbaImage, x: i64, y: i64, opacity: f32) {
+    for (mx, my, over) in mark.enumerate_pixels() {
+        let (px, py) = (x + mx as i64, y + my as i64);
+        if px < 0 || py < 0 || px >= img.width() as i64 || py >= img.height() as i64 {
+            continue;
+        }
+        let alpha = over[3] as f32 / 255.0 * opacity;
+        if alpha <= 0.0 {
+            continue;
+        }
+        let pixel = img.get_pixel_mut(px as u32, py as u32);
+        blend(pixel, *over, alpha);
+    }
+}
+
+fn blend(pixel: &mut Rgba<u8>, over: Rgba<u8>, opacity: f32) {
+    for channel in 0..3 {
+        let under = pixel[channel] as f32;
+        let over = over[channel] as f32;
+        pixel[channel] = (under + (over - under) * opacity).round() as u8;
+    }
+}
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// 5x7 bitmap font, one row per byte with the leftmost pixel in the 5th bit. Lowercase is drawn
+/// as uppercase and anything missing as `?`, though watermark text is limited to what it covers.
+const FONT: [(char, [u8; 7]); 46] = [
+    (' ', [0, 0, 0, 0, 0, 0, 0]),
+    (
+        'A',
+        [
+            0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+    ),
+    (
+        'B',
+        [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110,
+        ],
+    ),
+    (
+        'C',
+        [
+            0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        'D',
+        [
+            0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110,
+        ],
+    ),
+    (
+        'E',
+        [
+            0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111,
+        ],
+    ),
+    (
+        'F',
+        [
+            0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000,
+        ],
+    ),
+    (
+        'G',
+        [
+            0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111,
+        ],
+    ),
+    (
+        'H',
+        [
+            0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+    ),
+    (
+        'I',
+        [
+            0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+    ),
+    (
+        'J',
+        [
+            0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100,
+        ],
+    ),
+    (
+        'K',
+        [
+            0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001,
+        ],
+    ),
+    (
+        'L',
+        [
+            0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
+        ],
+    ),
+    (
+        'M',
+        [
+            0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001,
+        ],
+    ),
+    (
+        'N',
+        [
+            0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001,
+        ],
+    ),
+    (
+        'O',
+        [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        'P',
+        [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000,
+        ],
+    ),
+    (
+        'Q',
+        [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101,
+        ],
+    ),
+    (
+        'R',
+        [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001,
+        ],
+    ),
+    (
+        'S',
+        [
+            0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
+        ],
+    ),
+    (
+        'T',
+        [
+            0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
+        ],
+    ),
+    (
+        'U',
+        [
+            0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        'V',
+        [
+            0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100,
+        ],
+    ),
+    (
+        'W',
+        [
+            0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010,
+        ],
+    ),
+    (
+        'X',
+        [
+            0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001,
+        ],
+    ),
+    (
+        'Y',
+        [
+            0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100,
+        ],
+    ),
+    (
+        'Z',
+        [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111,
+        ],
+    ),
+    (
+        '0',
+        [
+            0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        '1',
+        [
+            0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+    ),
+    (
+        '2',
+        [
+            0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111,
+        ],
+    ),
+    (
+        '3',
+        [
+            0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        '4',
+        [
+            0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010,
+        ],
+    ),
+    (
+        '5',
+        [
+            0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        '6',
+        [
+            0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        '7',
+        [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000,
+        ],
+    ),
+    (
+        '8',
+        [
+            0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110,
+        ],
+    ),
+    (
+        '9',
+        [
+            0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100,
+        ],
+    ),
+    (
+        '@',
+        [
+            0b01110, 0b10001, 0b10111, 0b10101, 0b10111, 0b10000, 0b01111,
+        ],
+    ),
+    (
+        '.',
+        [
+            0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100,
+        ],
+    ),
+    (
+        '-',
+        [
+            0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000,
+        ],
+    ),
+    (
+        '_',
+        [
+            0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111,
+        ],
+    ),
+    (
+        ':',
+        [
+            0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000,
+        ],
+    ),
+    (
+        '/',
+        [
+            0b00001, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b10000,
+        ],
+    ),
+    (
+        '!',
+        [
+            0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100,
+        ],
+    ),
+    (
+        '?',
+        [
+            0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100,
+        ],
+    ),
+    (
+        '#',
+        [
+            0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010,
+        ],
+    ),
+];
+
+fn glyph(c: char) -> [u8; 7] {
+    let c = c.to_ascii_uppercase();
+    FONT.iter()
+        .find(|(glyph, _)| *glyph == c)
+        .or_else(|| FONT.iter().find(|(glyph, _)| *glyph == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
+
+/// White text with a dark shadow so it shows on light and dark images alike, `cell` pixels per font pixel.
+pub fn render_text(text: &str, cell: u32) -> RgbaImage {
+    let chars = text.chars().count() as u32;
+    if chars == 0 {
+        return RgbaImage::new(0, 0);
+    }
+    let shadow = (cell / 3).max(1);
+    let width = (chars * (GLYPH_WIDTH + 1) - 1) * cell + shadow;
+    let height = GLYPH_HEIGHT * cell + shadow;
+    let mut img = RgbaImage::new(width, height);
+    // shadow first so the text drawn after it stays on top
+    for (offset, color) in [
+        (shadow, Rgba([0, 0, 0, 255])),
+        (0, Rgba([255, 255, 255, 255])),
+    ] {
+        for (i, c) in text.chars().enumerate() {
+            let left = i as u32 * (GLYPH_WIDTH + 1) * cell;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 0 {
+                        continue;
+                    }
+                    for dy in 0..cell {
+                        for dx in 0..cell {
+                            let x = left + column * cell + dx + offset;
+                            let y = row as u32 * cell + dy + offset;
+                            img.put_pixel(x, y, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    img
+}
+
+#[cfg(test)]
+mod img_tests {
+    use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+
+    use artbounty_web_frontend::server::watermark::is_watermark_char;
+
+    use super::{FONT, GLYPH_HEIGHT, ImgData, Mark, Placement, Watermark, decode, render_text};
+
+    fn black(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([0, 0, 0])))
+    }
+
+    #[test]
+    fn variants_are_resized_and_encoded() {
+        let plain = ImgData::new(&black(200, 100), 50, None);
+        assert_eq!((plain.width, plain.height), (100, 50));
+        assert!(plain.bytes.iter().all(|b| *b == 0));
+
+        let webp = plain.encode_webp().unwrap();
+        let decoded = decode(&webp).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (100, 50));
+    }
+
+    #[test]
+    fn text_is_tiled_and_images_go_in_the_corner() {
+        let text = render_text("@hey", 2);
+        assert_eq!(text.height(), GLYPH_HEIGHT * 2 + 1);
+        assert!(
+            text.pixels()
+                .any(|pixel| *pixel == Rgba([255, 255, 255, 255]))
+        );
+
+        let tiled = Watermark {
+            mark: Mark::Text(String::from("@hey")),
+            placement: Placement::Tiled,
+            opacity: 0.5,
+        };
+        let marked = ImgData::new(&black(400, 400), 400, Some(&tiled));
+        assert_eq!(marked.bytes.len(), 400 * 400 * 3);
+        let lit = |data: &ImgData, x: u32, y: u32| {
+            let i = ((y * data.width + x) * 3) as usize;
+            data.bytes[i] > 0
+        };
+        let lit_rows = (0..400)
+            .filter(|y| (0..400).any(|x| lit(&marked, x, *y)))
+            .count();
+        // more than one row of marks, but plenty of the image left clean
+        assert!(lit_rows > 60 && lit_rows < 300);
+        assert!(marked.bytes.iter().all(|b| *b <= 128));
+
+        let corner = Watermark {
+            mark: Mark::Image(RgbaImage::from_pixel(200, 100, Rgba([255, 255, 255, 255]))),
+            placement: Placement::Corner,
+            opacity: 1.0,
+        };
+        let marked = ImgData::new(&black(400, 200), 200, Some(&corner));
+        // scaled down to a fifth of the width and kept off the edge
+        assert!(lit(&marked, 385, 185));
+        assert!(!lit(&marked, 399, 199));
+        assert!(!lit(&marked, 10, 10));
+        assert!(!lit(&marked, 300, 185));
+    }
+
+    #[test]
+    fn font_covers_the_watermark_charset() {
+        for c in (0..=127_u8)
+            .map(char::from)
+            .filter(|c| is_watermark_char(*c))
+        {
+            let c = c.to_ascii_uppercase();
+            assert!(
+                FONT.iter().any(|(glyph, _)| *glyph == c),
+                "no glyph for {:?}",
+                c
+            );
+        }
+    }
+}
@@ -0,0 +1,662 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use artbounty_web_frontend::{
+    api::{MAXIMUM_USERNAME_LENGTH, MINIMUM_USERNAME_LENGTH, RATING_SFW},
+    db::{
+        DbError,
+        acc::{self, DbAcc, DbAccDiscord},
+        artwork::{self, DbArtwork, PREVIEW_PENDING},
+    },
+    server::{
+        ServerState,
+        auth::{self, DEFAULT_ROLE, ErrorAuth},
+        new_id,
+        upload::ORIGINAL_DIR,
+    },
+};
+use futures::TryStreamExt;
+use mongodb::{
+    Client,
+    bson::{Document, doc},
+    options::FindOptions,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{trace, warn};
+
+pub const SUBCOMMAND: &str = "import";
+pub const MONGO_URL_ENV: &str = "LEGACY_MONGO_URL";
+pub const MONGO_DATABASE_ENV: &str = "LEGACY_MONGO_DATABASE";
+pub const GALLERY_DIR_ENV: &str = "GALLERY_DIR";
+pub const CHECKPOINT_ENV: &str = "IMPORT_CHECKPOINT";
+/// The checkpoint is written after this many records, resuming from it revisits at most a batch
+/// and those are found to be imported already.
+pub const CHECKPOINT_INTERVAL: u64 = 100;
+/// Legacy images had no title, the new ones require one.
+pub const LEGACY_TITLE: &str = "Untitled";
+/// Legacy resized copies, largest first, tried when the original is gone.
+pub const LEGACY_VARIANTS: [&str; 3] = ["high", "medium", "low"];
+/// Domain of the addresses given to discord users that never made a legacy account.
+pub const PLACEHOLDER_EMAIL_DOMAIN: &str = "users.invalid";
+
+const COLLECTION_ACC: &str = "acc";
+const COLLECTION_USER: &str = "user";
+const COLLECTION_IMG: &str = "img";
+
+#[derive(Error, Debug)]
+pub enum ErrorImport {
+    #[error("missing env variable {0}")]
+    Missing(&'static str),
+
+    #[error("mongo error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+
+    #[error("auth error: {0}")]
+    Auth(#[from] ErrorAuth),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("checkpoint error: {0}")]
+    Checkpoint(#[from] serde_json::Error),
+}
+
+/// Legacy `img` document, only the fields the import reads.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct LegacyImg {
+    pub id: String,
+    pub show: bool,
+    pub user_id: String,
+    /// Hex md5 of the original, names its files under the gallery dir.
+    pub org_hash: String,
+    /// Extension of the original.
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub has_high: bool,
+    pub has_medium: bool,
+    pub has_low: bool,
+    pub modified_at: i64,
+    pub created_at: i64,
+}
+
+/// Legacy `user` document, a discord member whose images were saved.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct LegacyUser {
+    pub id: String,
+    pub author_id: String,
+    pub name: String,
+    pub modified_at: i64,
+    pub created_at: i64,
+}
+
+/// Legacy `acc` document, a site login that may have linked its discord user.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct LegacyAcc {
+    pub email: String,
+    pub verified_email: bool,
+    pub discord: Option<LegacyAccDiscord>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct LegacyAccDiscord {
+    pub user_id: String,
+}
+
+/// Legacy id of the last user and image handled, records are read in id order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    pub user: Option<String>,
+    pub img: Option<String>,
+}
+
+impl Checkpoint {
+    /// Starts over when there's no checkpoint yet.
+    pub async fn load(path: &Path) -> Result<Self, ErrorImport> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Written aside and renamed over, an interrupted save keeps the previous checkpoint.
+    pub async fn save(&self, path: &Path) -> Result<(), ErrorImport> {
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(self)?).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Skip {
+    /// Hidden by a moderator on the legacy gallery.
+    Hidden,
+    /// Author of an image that isn't imported.
+    UnknownUser(String),
+    /// Neither the original nor a resized copy is in the gallery dir.
+    MissingFile,
+    /// Only a resized copy is left and it can't be decoded.
+    Unreadable(String),
+    /// The author already has the same file, e.g. posted in two servers.
+    Duplicate(String),
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Skip::Hidden => write!(f, "hidden"),
+            Skip::UnknownUser(user_id) => write!(f, "unknown user {}", user_id),
+            Skip::MissingFile => write!(f, "missing file"),
+            Skip::Unreadable(err) => write!(f, "unreadable file: {}", err),
+            Skip::Duplicate(artwork_id) => write!(f, "duplicate of artwork {}", artwork_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Imported,
+    /// Imported by an earlier run.
+    Existing,
+    Skipped(Skip),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counts {
+    pub imported: u64,
+    pub existing: u64,
+    /// Legacy id and why it was left out.
+    pub skipped: Vec<(String, Skip)>,
+}
+
+impl Counts {
+    pub fn add(&mut self, id: &str, outcome: Outcome) {
+        match outcome {
+            Outcome::Imported => self.imported += 1,
+            Outcome::Existing => self.existing += 1,
+            Outcome::Skipped(skip) => self.skipped.push((id.to_string(), skip)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub users: Counts,
+    pub artworks: Counts,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, counts) in [("users", &self.users), ("artworks", &self.artworks)] {
+            writeln!(
+                f,
+                "{}: {} imported, {} already imported, {} skipped",
+                name,
+                counts.imported,
+                counts.existing,
+                counts.skipped.len()
+            )?;
+        }
+        for (name, counts) in [("user", &self.users), ("img", &self.artworks)] {
+            for (id, skip) in &counts.skipped {
+                writeln!(f, "skipped {} {}: {}", name, id, skip)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the legacy artcord data is read from.
+#[derive(Debug, Clone)]
+pub struct ImportConfig {
+    pub mongo_url: String,
+    pub mongo_database: String,
+    pub gallery_dir: PathBuf,
+    pub checkpoint: PathBuf,
+}
+
+impl ImportConfig {
+    pub fn from_env() -> Result<Self, ErrorImport> {
+        let mongo_url = std::env::var(MONGO_URL_ENV)
+            .ok()
+            .filter(|url| !url.is_empty())
+            .ok_or(ErrorImport::Missing(MONGO_URL_ENV))?;
+        let env_or = |name: &str, default: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| default.to_string())
+        };
+
+        Ok(Self {
+            mongo_url,
+            mongo_database: env_or(MONGO_DATABASE_ENV, "artcord"),
+            gallery_dir: PathBuf::from(env_or(GALLERY_DIR_ENV, "./gallery/")),
+            checkpoint: PathBuf::from(env_or(CHECKPOINT_ENV, "import_checkpoint.json")),
+        })
+    }
+}
+
+/// Copies discord members and their images from the legacy artcord gallery.
+///
+/// Members become accounts linked to their discord user, images become artworks keyed by their
+/// legacy id, so running it again only picks up what's new.
+#[derive(Clone)]
+pub struct Importer {
+    config: ImportConfig,
+    state: ServerState,
+}
+
+impl Importer {
+    pub fn new(config: ImportConfig, state: ServerState) -> Self {
+        Self { config, state }
+    }
+
+    pub async fn run(&self) -> Result<ImportReport, ErrorImport> {
+        let client = Client::with_uri_str(&self.config.mongo_url).await?;
+        let legacy = client.database(&self.config.mongo_database);
+        let mut checkpoint = Checkpoint::load(&self.config.checkpoint).await?;
+        let mut report = ImportReport::default();
+
+        let mut accs = HashMap::<String, LegacyAcc>::new();
+        let mut cursor = legacy
+            .collection::<LegacyAcc>(COLLECTION_ACC)
+            .find(doc! { "discord": { "$ne": null } }, None)
+            .await?;
+        while let Some(legacy_acc) = cursor.try_next().await? {
+            if let Some(discord) = &legacy_acc.discord {
+                accs.insert(discord.user_id.clone(), legacy_acc);
+            }
+        }
+
+        let mut cursor = legacy
+            .collection::<LegacyUser>(COLLECTION_USER)
+            .find(after(checkpoint.user.as_deref()), by_id())
+            .await?;
+        let mut handled = 0;
+        while let Some(user) = cursor.try_next().await? {
+            let outcome = self.import_user(&user, accs.get(&user.author_id)).await?;
+            report.users.add(&user.id, outcome);
+            checkpoint.user = Some(user.id);
+            handled += 1;
+            if handled % CHECKPOINT_INTERVAL == 0 {
+                checkpoint.save(&self.config.checkpoint).await?;
+            }
+        }
+        checkpoint.save(&self.config.checkpoint).await?;
+
+        let mut cursor = legacy
+            .collection::<LegacyImg>(COLLECTION_IMG)
+            .find(after(checkpoint.img.as_deref()), by_id())
+            .await?;
+        let mut handled = 0;
+        while let Some(img) = cursor.try_next().await? {
+            let outcome = self.import_img(&img).await?;
+            report.artworks.add(&img.id, outcome);
+            checkpoint.img = Some(img.id);
+            handled += 1;
+            if handled % CHECKPOINT_INTERVAL == 0 {
+                checkpoint.save(&self.config.checkpoint).await?;
+            }
+        }
+        checkpoint.save(&self.config.checkpoint).await?;
+
+        Ok(report)
+    }
+
+    /// Creates an account for a discord member, with the login details of their legacy account
+    /// when they had one. Passwords were hashed without the pepper, so imported accounts get a
+    /// random one and sign in by resetting it.
+    pub async fn import_user(
+        &self,
+        user: &LegacyUser,
+        legacy_acc: Option<&LegacyAcc>,
+    ) -> Result<Outcome, ErrorImport> {
+        let db = &self.state.db;
+        if acc::get_by_discord_id(db, &user.author_id).await?.is_some() {
+            return Ok(Outcome::Existing);
+        }
+
+        let mut email = None;
+        if let Some(legacy_acc) = legacy_acc {
+            let legacy_email = auth::normalize_email(&legacy_acc.email);
+            if auth::validate_email(&legacy_email).is_ok()
+                && acc::get_by_email(db, &legacy_email).await?.is_none()
+            {
+                email = Some((legacy_email, legacy_acc.verified_email));
+            } else {
+                warn!("email of legacy user {} is invalid or taken", user.id);
+            }
+        }
+        let (email, verified_email) = email.unwrap_or_else(|| {
+            (
+                format!("discord-{}@{}", user.author_id, PLACEHOLDER_EMAIL_DOMAIN),
+                false,
+            )
+        });
+
+        let base = legacy_username(&user.name, &user.author_id);
+        let mut username = base.clone();
+        let mut suffix = 1;
+        while acc::get_by_username(db, &username).await?.is_some() {
+            suffix += 1;
+            let suffix = format!("-{}", suffix);
+            username = base
+                .chars()
+                .take(MAXIMUM_USERNAME_LENGTH - suffix.len())
+                .chain(suffix.chars())
+                .collect();
+        }
+
+        let created_at = legacy_acc
+            .map(|legacy_acc| legacy_acc.created_at.min(user.created_at))
+            .unwrap_or(user.created_at);
+        let acc = DbAcc {
+            username,
+            email,
+            password: auth::hash_password(&self.state, &new_id())?,
+            verified_email,
+            role: DEFAULT_ROLE.to_string(),
+            totp: None,
+            discord: Some(DbAccDiscord {
+                user_id: user.author_id.clone(),
+                token: String::new(),
+            }),
+            content_prefs: None,
+            theme: None,
+            modified_at: user.modified_at,
+            created_at,
+        };
+        trace!("importing legacy user {} as {}", user.id, acc.username);
+        acc::insert(db, acc).await?;
+        Ok(Outcome::Imported)
+    }
+
+    /// Copies the original into the media dir and leaves the previews to the media worker, the
+    /// legacy resized copies are only used when the original is gone.
+    pub async fn import_img(&self, img: &LegacyImg) -> Result<Outcome, ErrorImport> {
+        let db = &self.state.db;
+        if !img.show {
+            return Ok(Outcome::Skipped(Skip::Hidden));
+        }
+        let artwork_id = legacy_artwork_id(&img.id);
+        if artwork::get(db, &artwork_id).await?.is_some() {
+            return Ok(Outcome::Existing);
+        }
+        let Some(acc) = acc::get_by_discord_id(db, &img.user_id).await? else {
+            return Ok(Outcome::Skipped(Skip::UnknownUser(img.user_id.clone())));
+        };
+
+        let Some((path, ext, original)) = self.find_file(img).await else {
+            return Ok(Outcome::Skipped(Skip::MissingFile));
+        };
+        let (width, height) = if original {
+            (img.width, img.height)
+        } else {
+            match image::image_dimensions(&path) {
+                Ok(size) => size,
+                Err(err) => return Ok(Outcome::Skipped(Skip::Unreadable(err.to_string()))),
+            }
+        };
+        let bytes = tokio::fs::read(&path).await?;
+        let sha256 = Sha256::digest(&bytes)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        if let Some(duplicate) = artwork::get_by_sha256(db, &acc.username, &sha256).await? {
+            return Ok(Outcome::Skipped(Skip::Duplicate(duplicate.artwork_id)));
+        }
+
+        let file = format!("{}/legacy_{}.{}", ORIGINAL_DIR, img.org_hash, ext);
+        let original = self.state.settings.media_dir.join(&file);
+        if let Some(dir) = original.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&original, &bytes).await?;
+
+        let artwork = DbArtwork {
+            artwork_id,
+            acc: acc.username,
+            title: LEGACY_TITLE.to_string(),
+            description: String::new(),
+            tags: Vec::new(),
+            file,
+            width,
+            height,
+            protected: false,
+            preview_status: PREVIEW_PENDING.to_string(),
+            rating: RATING_SFW.to_string(),
+            palette: Vec::new(),
+            blurhash: String::new(),
+            sha256,
+            modified_at: img.modified_at,
+            created_at: img.created_at,
+        };
+        trace!("importing legacy img {} as {}", img.id, artwork.artwork_id);
+        artwork::insert(db, artwork).await?;
+        Ok(Outcome::Imported)
+    }
+
+    /// Original first, then the largest resized copy the legacy gallery recorded.
+    async fn find_file(&self, img: &LegacyImg) -> Option<(PathBuf, String, bool)> {
+        let gallery = &self.config.gallery_dir;
+        let original = gallery.join(format!("org_{}.{}", img.org_hash, img.format));
+        if tokio::fs::try_exists(&original).await.unwrap_or_default() {
+            return Some((original, img.format.clone(), true));
+        }
+        for (variant, has) in
+            LEGACY_VARIANTS
+                .into_iter()
+                .zip([img.has_high, img.has_medium, img.has_low])
+        {
+            let path = gallery.join(format!("{}_{}.webp", variant, img.org_hash));
+            if has && tokio::fs::try_exists(&path).await.unwrap_or_default() {
+                return Some((path, String::from("webp"), false));
+            }
+        }
+        None
+    }
+}
+
+/// Legacy ids are uuids, without the dashes they fit the alphabet of new ids.
+pub fn legacy_artwork_id(id: &str) -> String {
+    id.replace('-', "")
+}
+
+/// Discord names with whatever usernames can't hold replaced, or the discord id when little is
+/// left of them.
+pub fn legacy_username(name: &str, author_id: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim_matches('_');
+    if name.chars().count() < MINIMUM_USERNAME_LENGTH {
+        return format!("discord-{}", author_id)
+            .chars()
+            .take(MAXIMUM_USERNAME_LENGTH)
+            .collect();
+    }
+    name.chars().take(MAXIMUM_USERNAME_LENGTH).collect()
+}
+
+fn after(id: Option<&str>) -> Document {
+    match id {
+        Some(id) => doc! { "id": { "$gt": id } },
+        None => doc! {},
+    }
+}
+
+fn by_id() -> FindOptions {
+    FindOptions::builder().sort(doc! { "id": 1 }).build()
+}
+
+#[cfg(test)]
+mod import_tests {
+    use artbounty_web_frontend::{
+        db::{acc, artwork},
+        server::{new_id, test_support::test_state},
+    };
+
+    use super::{
+        Checkpoint, ImportConfig, Importer, LEGACY_TITLE, LegacyAcc, LegacyAccDiscord, LegacyImg,
+        LegacyUser, Outcome, Skip, legacy_username,
+    };
+
+    async fn test_importer() -> Importer {
+        let (state, _) = test_state().await;
+        let dir = std::env::temp_dir().join(new_id());
+        let config = ImportConfig {
+            mongo_url: String::from("mongodb://localhost:27017"),
+            mongo_database: String::from("artcord"),
+            gallery_dir: dir.join("gallery"),
+            checkpoint: dir.join("checkpoint.json"),
+        };
+        std::fs::create_dir_all(&config.gallery_dir).unwrap();
+        Importer::new(config, state)
+    }
+
+    fn user(author_id: &str, name: &str) -> LegacyUser {
+        LegacyUser {
+            id: format!("user-{}", author_id),
+            author_id: author_id.to_string(),
+            name: name.to_string(),
+            modified_at: 1_500,
+            created_at: 1_000,
+        }
+    }
+
+    fn img(id: &str, user_id: &str, org_hash: &str) -> LegacyImg {
+        LegacyImg {
+            id: id.to_string(),
+            show: true,
+            user_id: user_id.to_string(),
+            org_hash: org_hash.to_string(),
+            format: String::from("png"),
+            width: 640,
+            height: 480,
+            has_high: false,
+            has_medium: false,
+            has_low: false,
+            modified_at: 2_500,
+            created_at: 2_000,
+        }
+    }
+
+    #[test]
+    fn discord_names_become_usernames() {
+        assert_eq!(legacy_username("hey adora!", "1"), "hey_adora");
+        assert_eq!(legacy_username("ユーザー", "42"), "discord-42");
+        assert_eq!(legacy_username(&"a".repeat(40), "1").len(), 32);
+    }
+
+    #[tokio::test]
+    async fn users_are_imported_once_with_their_legacy_login() {
+        let importer = test_importer().await;
+        let legacy_acc = LegacyAcc {
+            email: String::from(" Hey@Example.com"),
+            verified_email: true,
+            discord: Some(LegacyAccDiscord {
+                user_id: String::from("1"),
+            }),
+            created_at: 500,
+        };
+        let outcome = importer
+            .import_user(&user("1", "hey"), Some(&legacy_acc))
+            .await
+            .unwrap();
+        assert_eq!(outcome, Outcome::Imported);
+        let outcome = importer
+            .import_user(&user("1", "hey"), Some(&legacy_acc))
+            .await
+            .unwrap();
+        assert_eq!(outcome, Outcome::Existing);
+        let outcome = importer.import_user(&user("2", "hey"), None).await.unwrap();
+        assert_eq!(outcome, Outcome::Imported);
+
+        let db = &importer.state.db;
+        let hey = acc::get_by_discord_id(db, "1").await.unwrap().unwrap();
+        assert_eq!(hey.username, "hey");
+        assert_eq!(hey.email, "hey@example.com");
+        assert!(hey.verified_email);
+        assert_eq!(hey.created_at, 500);
+        let other = acc::get_by_discord_id(db, "2").await.unwrap().unwrap();
+        assert_eq!(other.username, "hey-2");
+        assert!(!other.verified_email);
+    }
+
+    #[tokio::test]
+    async fn imgs_keep_their_size_and_time_and_are_skipped_when_unusable() {
+        let importer = test_importer().await;
+        importer.import_user(&user("1", "hey"), None).await.unwrap();
+        let gallery = &importer.config.gallery_dir;
+        std::fs::write(gallery.join("org_abc.png"), b"first").unwrap();
+        std::fs::write(gallery.join("org_def.png"), b"first").unwrap();
+
+        let cat = img("0000-0001", "1", "abc");
+        assert_eq!(importer.import_img(&cat).await.unwrap(), Outcome::Imported);
+        assert_eq!(importer.import_img(&cat).await.unwrap(), Outcome::Existing);
+
+        let artwork = artwork::get(&importer.state.db, "00000001")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(artwork.acc, "hey");
+        assert_eq!(artwork.title, LEGACY_TITLE);
+        assert_eq!((artwork.width, artwork.height), (640, 480));
+        assert_eq!((artwork.created_at, artwork.modified_at), (2_000, 2_500));
+        assert_eq!(artwork.sha256.len(), 64);
+        let stored = std::fs::read(importer.state.settings.media_dir.join(&artwork.file)).unwrap();
+        assert_eq!(stored, b"first");
+
+        let hidden = LegacyImg {
+            show: false,
+            ..img("2", "1", "abc")
+        };
+        let outcomes = [
+            importer.import_img(&hidden).await.unwrap(),
+            importer.import_img(&img("3", "9", "abc")).await.unwrap(),
+            importer.import_img(&img("4", "1", "gone")).await.unwrap(),
+            importer.import_img(&img("5", "1", "def")).await.unwrap(),
+        ];
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::Skipped(Skip::Hidden),
+                Outcome::Skipped(Skip::UnknownUser(String::from("9"))),
+                Outcome::Skipped(Skip::MissingFile),
+                Outcome::Skipped(Skip::Duplicate(String::from("00000001"))),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn checkpoints_survive_a_restart() {
+        let importer = test_importer().await;
+        let path = &importer.config.checkpoint;
+        assert_eq!(Checkpoint::load(path).await.unwrap(), Checkpoint::default());
+        let checkpoint = Checkpoint {
+            user: Some(String::from("a")),
+            img: None,
+        };
+        checkpoint.save(path).await.unwrap();
+        assert_eq!(Checkpoint::load(path).await.unwrap(), checkpoint);
+    }
+}
@@ -0,0 +1,14 @@
+pub mod api_v1;
+pub mod client_logs;
+pub mod discord;
+pub mod feeds;
+pub mod img;
+pub mod import;
+pub mod media;
+pub mod messages;
+pub mod metrics;
+pub mod oembed;
+pub mod telemetry;
+pub mod throttle;
+pub mod uploads;
+pub mod webhook;
@@ -1,30 +1,171 @@
-use std::sync::LazyLock;
+#![recursion_limit = "256"]
 
-use artbounty_web_frontend::{app::App, shell};
+use std::net::SocketAddr;
+
+use artbounty_web_backend::{
+    api_v1::ApiV1,
+    client_logs::ClientLogRoutes,
+    discord::{DiscordBotLink, DiscordOAuth, DiscordOAuthConfig},
+    feeds::FeedRoutes,
+    import::{self, ImportConfig, Importer},
+    media::{self, MediaRoutes, MediaWorker},
+    messages::MessagePush,
+    metrics,
+    oembed::OEmbedRoutes,
+    telemetry,
+    throttle::{
+        Clock, Threshold, delta_minutes,
+        layer::{RouteThreshold, ThrottleConfig, ThrottleLayer},
+    },
+    uploads::{self, UploadRoutes, UploadSweeper},
+    webhook::{self, WebhookWorker},
+};
+use artbounty_web_frontend::{
+    api::{ADMIN_ROLE, CLIENT_LOG_PATH},
+    app::App,
+    db,
+    server::{ServerState, Settings, email},
+    shell,
+};
 use axum::Router;
+use chrono::{TimeDelta, Utc};
 use leptos::{logging, prelude::*};
 use leptos_axum::{LeptosRoutes, generate_route_list};
-use surrealdb::{Surreal, engine::remote::ws};
 use tower_http::compression::CompressionLayer;
-use tracing::{info, trace, trace_span};
-
-static DB: LazyLock<Surreal<ws::Client>> = LazyLock::new(Surreal::init);
+use tracing::{info, trace, trace_span, warn};
 
 #[allow(clippy::needless_return)]
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .event_format(
-            tracing_subscriber::fmt::format()
-                .with_file(true)
-                .with_line_number(true),
-        )
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .try_init()
-        .unwrap();
+    telemetry::init_logger();
+    let metrics_handle = metrics::install();
 
     trace!("started!");
 
+    // falling back to an in-memory db would lose every account on restart without a word
+    let db_addr = std::env::var("DB_ADDR")
+        .expect("DB_ADDR is not set, use mem:// for a throwaway in-memory database");
+    let db_root = std::env::var("DB_USER")
+        .ok()
+        .filter(|user| !user.is_empty())
+        .zip(std::env::var("DB_PASS").ok());
+    let db = db::connect(
+        &db_addr,
+        db_root
+            .as_ref()
+            .map(|(user, pass)| (user.as_str(), pass.as_str())),
+    )
+    .await
+    .unwrap();
+
+    let admins = std::env::var("ADMIN_USERNAMES").unwrap_or_default();
+    for username in admins
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let promoted = db::acc::set_role(&db, username, ADMIN_ROLE, Utc::now().timestamp_millis())
+            .await
+            .unwrap();
+        trace!("admin {}: {}", username, promoted.is_some());
+    }
+
+    let server_state = ServerState::new(
+        db.clone(),
+        email::from_env().unwrap(),
+        Settings::from_env().unwrap(),
+    );
+
+    if std::env::args().nth(1).as_deref() == Some(import::SUBCOMMAND) {
+        let config = ImportConfig::from_env().unwrap();
+        let report = Importer::new(config, server_state).run().await.unwrap();
+        print!("{}", report);
+        return;
+    }
+
+    let discord_routes = DiscordOAuthConfig::from_env(&server_state.settings.site_url)
+        .map(|config| DiscordOAuth::new(config, server_state.clone()).routes());
+    trace!("discord oauth enabled: {}", discord_routes.is_some());
+    let discord_bot_routes =
+        DiscordBotLink::from_env(server_state.clone()).map(DiscordBotLink::routes);
+    trace!(
+        "discord bot linking enabled: {}",
+        discord_bot_routes.is_some()
+    );
+
+    let api_v1_routes = ApiV1::new(server_state.clone()).routes();
+    let message_routes = MessagePush::new(server_state.clone()).routes();
+    let media_routes = MediaRoutes::new(server_state.clone()).routes();
+    let upload_routes = UploadRoutes::new(server_state.clone()).routes();
+    let client_log_routes = ClientLogRoutes::new(server_state.clone()).routes();
+    let oembed_routes = OEmbedRoutes::new(server_state.clone()).routes();
+    let feed_routes = FeedRoutes::new(server_state.clone()).routes();
+
+    let auth_threshold = RouteThreshold {
+        block: Threshold::new_const(10, TimeDelta::try_minutes(1)),
+        ban: Threshold::new_const(5, TimeDelta::try_minutes(1)),
+        ban_duration: delta_minutes(30),
+        max_concurrent: Some(2),
+    };
+    let throttle_config = [
+        "/api/register",
+        "/api/login",
+        "/api/login_totp",
+        "/api/totp_enable",
+        "/api/totp_disable",
+        "/api/totp_recovery_codes",
+        "/api/resend_verification",
+        "/api/request_password_reset",
+        "/api/reset_password",
+        "/api/discord_link_code",
+        "/api/api_token_create",
+        "/api/webhook_create",
+        "/api/webhook_test",
+        "/api/conversation_start",
+        "/api/message_attachment_upload",
+        "/api/commission_create",
+        "/api/revision_upload",
+        "/api/watermark_image_upload",
+        "/uploads",
+        "/auth/discord/callback",
+        "/auth/discord/redeem",
+    ]
+    .into_iter()
+    .fold(ThrottleConfig::default(), |config, route| {
+        config.set_route(route, auth_threshold.clone())
+    })
+    .set_route(
+        CLIENT_LOG_PATH,
+        RouteThreshold {
+            block: Threshold::new_const(30, TimeDelta::try_minutes(1)),
+            ban: Threshold::new_const(10, TimeDelta::try_minutes(1)),
+            ban_duration: delta_minutes(30),
+            max_concurrent: Some(2),
+        },
+    );
+    let throttle_layer = ThrottleLayer::new(throttle_config, Clock, Some(db.clone()));
+    let loaded_bans = throttle_layer.load_bans().await.unwrap();
+    trace!("loaded {} bans", loaded_bans);
+
+    WebhookWorker::from_env(db.clone()).spawn(webhook::POLL_INTERVAL);
+    MediaWorker::new(server_state.clone()).spawn(media::POLL_INTERVAL);
+    UploadSweeper::new(server_state.clone()).spawn(uploads::SWEEP_INTERVAL);
+
+    match std::env::var(metrics::METRICS_ADDR_ENV) {
+        Ok(metrics_addr) => {
+            metrics::serve(metrics_handle, &metrics_addr).await.unwrap();
+            info!(
+                "serving metrics on http://{}{}",
+                metrics_addr,
+                metrics::METRICS_PATH
+            );
+        }
+        Err(_) => warn!(
+            "{} is not set, metrics are not served",
+            metrics::METRICS_ADDR_ENV
+        ),
+    }
+
     let conf = get_configuration(None).unwrap();
     let leptos_options = conf.leptos_options;
     let addr = leptos_options.site_addr;
@@ -33,17 +174,35 @@ async fn main() {
     let comppression_layer = CompressionLayer::new().zstd(true).gzip(true).deflate(true);
 
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, {
-            let leptos_options = leptos_options.clone();
-            move || shell(leptos_options.clone())
-        })
+        .merge(discord_routes.unwrap_or_default())
+        .merge(discord_bot_routes.unwrap_or_default())
+        .merge(api_v1_routes)
+        .merge(message_routes)
+        .merge(media_routes)
+        .merge(upload_routes)
+        .merge(client_log_routes)
+        .merge(oembed_routes)
+        .merge(feed_routes)
+        .leptos_routes_with_context(
+            &leptos_options,
+            routes,
+            move || provide_context(server_state.clone()),
+            {
+                let leptos_options = leptos_options.clone();
+                move || shell(leptos_options.clone())
+            },
+        )
         .fallback(leptos_axum::file_and_error_handler(shell))
         .with_state(leptos_options)
-        .layer(comppression_layer);
+        .layer(throttle_layer);
+    let app = telemetry::with_request_tracing(app).layer(comppression_layer);
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     logging::log!("listening on http://{}", &addr);
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,640 @@
+use std::time::Duration;
+
+use artbounty_web_frontend::{
+    api::{ARTWORK_MEDIA_PATH, ARTWORK_VARIANT_WIDTHS, REVISION_MEDIA_PATH},
+    db::{
+        DbError,
+        artwork::{self, DbArtwork},
+        commission as db_commission,
+        revision::{self, DbRevision, PREVIEW_FAILED, PREVIEW_READY},
+        watermark::{KIND_IMAGE, PLACEMENT_CORNER},
+    },
+    server::{
+        ServerState,
+        artwork::{self as server_artwork, ErrorArtwork},
+        auth,
+        commission::{self, ErrorCommission},
+        message::sniff_image,
+        palette, watermark,
+    },
+};
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, COOKIE, X_CONTENT_TYPE_OPTIONS},
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::Utc;
+use tokio::task::JoinHandle;
+use tracing::{error, trace, warn};
+
+use crate::{
+    img::{self, ErrorImg, ImgData, Mark, Placement, Watermark},
+    metrics,
+};
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+pub const BATCH_SIZE: u32 = 10;
+pub const QUEUE_NAME: &str = "media";
+pub const PREVIEW_HEIGHT: u32 = 1280;
+/// Shown blurred on tiles of content the viewer wants blurred, too small to be worth saving.
+pub const LOW_HEIGHT: u32 = 48;
+/// Palettes are extracted from a thumbnail at most this wide and tall.
+pub const PALETTE_SAMPLE_SIZE: u32 = 64;
+/// Horizontal and vertical BlurHash components, encoded from the same thumbnail as the palette.
+pub const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// Renders previews of uploaded revisions, see `artbounty_web_frontend::server::commission` for the upload side.
+#[derive(Clone)]
+pub struct MediaWorker {
+    state: ServerState,
+}
+
+impl MediaWorker {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    /// Renders every pending revision and artwork preview, returns how many were processed.
+    pub async fn run_once(&self, time: i64) -> Result<usize, DbError> {
+        let revisions = revision::get_pending_previews(&self.state.db, BATCH_SIZE).await?;
+        let artworks = artwork::get_pending_previews(&self.state.db, BATCH_SIZE).await?;
+        let depth = revision::count_pending_previews(&self.state.db).await?
+            + artwork::count_pending_previews(&self.state.db).await?;
+        metrics::set_job_queue_depth(QUEUE_NAME, depth);
+
+        let mut processed = 0;
+        for revision in revisions {
+            let (status, width, height) = match self.render_revision(&revision).await {
+                Ok((width, height)) => (PREVIEW_READY, width, height),
+                Err(err) => {
+                    warn!(
+                        "failed to render preview of revision {}: {}",
+                        revision.revision_id, err
+                    );
+                    (PREVIEW_FAILED, 0, 0)
+                }
+            };
+            revision::set_preview(
+                &self.state.db,
+                &revision.revision_id,
+                status,
+                width,
+                height,
+                time,
+            )
+            .await?;
+            processed += 1;
+        }
+        for artwork in artworks {
+            let status = match self.render_artwork(&artwork, time).await {
+                Ok(()) => artwork::PREVIEW_READY,
+                Err(err) => {
+                    warn!(
+                        "failed to render preview of artwork {}: {}",
+                        artwork.artwork_id, err
+                    );
+                    artwork::PREVIEW_FAILED
+                }
+            };
+            artwork::set_preview_status(&self.state.db, &artwork.artwork_id, status, time).await?;
+            processed += 1;
+        }
+
+        Ok(processed)
+    }
+
+    /// Watermark configured by `acc`, an image one falls back to text if its png is unreadable.
+    async fn watermark(&self, acc: &str) -> Result<Watermark, ErrorImg> {
+        let settings = watermark::get(&self.state.db, acc)
+            .await
+            .map_err(std::io::Error::other)?;
+        let mark = if settings.kind == KIND_IMAGE {
+            let path = watermark::image_path(&self.state.settings, acc);
+            match tokio::fs::read(&path)
+                .await
+                .map_err(ErrorImg::from)
+                .and_then(|bytes| img::decode(&bytes))
+            {
+                Ok(mark) => Mark::Image(mark.to_rgba8()),
+                Err(err) => {
+                    warn!("failed to read watermark of {}: {}", acc, err);
+                    Mark::Text(watermark::default_for(acc).text)
+                }
+            }
+        } else {
+            Mark::Text(settings.text)
+        };
+        let placement = if settings.placement == PLACEMENT_CORNER {
+            Placement::Corner
+        } else {
+            Placement::Tiled
+        };
+        Ok(Watermark {
+            mark,
+            placement,
+            opacity: settings.opacity as f32 / 100.0,
+        })
+    }
+
+    /// Writes the preview next to the original, returns the size of the original.
+    async fn render_revision(&self, revision: &DbRevision) -> Result<(u32, u32), ErrorImg> {
+        let watermark = if revision.watermark {
+            let artist = db_commission::get(&self.state.db, &revision.commission_id)
+                .await
+                .map_err(std::io::Error::other)?
+                .map(|commission| commission.artist)
+                .unwrap_or_default();
+            Some(self.watermark(&artist).await?)
+        } else {
+            None
+        };
+        let path = commission::revision_path(&self.state.settings, &revision.revision_id);
+        let Rendered { size, variants, .. } = render(
+            tokio::fs::read(&path).await?,
+            &[PREVIEW_HEIGHT],
+            &[],
+            watermark,
+        )
+        .await?;
+
+        let path = commission::preview_path(&self.state.settings, &revision.revision_id);
+        tokio::fs::write(&path, &variants[0]).await?;
+        trace!(
+            "preview of revision {} rendered ({}x{})",
+            revision.revision_id, size.0, size.1
+        );
+        Ok(size)
+    }
+
+    /// Public variants of an artwork, watermarked while it's protected, and its palette.
+    async fn render_artwork(&self, artwork: &DbArtwork, time: i64) -> Result<(), ErrorImg> {
+        let watermark = if artwork.protected {
+            Some(self.watermark(&artwork.acc).await?)
+        } else {
+            None
+        };
+        let path = self.state.settings.media_dir.join(&artwork.file);
+        let Rendered {
+            size,
+            variants,
+            palette,
+            blurhash,
+        } = render(
+            tokio::fs::read(&path).await?,
+            &[PREVIEW_HEIGHT, LOW_HEIGHT],
+            &ARTWORK_VARIANT_WIDTHS,
+            watermark,
+        )
+        .await?;
+
+        let path = server_artwork::preview_path(&self.state.settings, &artwork.artwork_id);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&path, &variants[0]).await?;
+        let path = server_artwork::low_path(&self.state.settings, &artwork.artwork_id);
+        tokio::fs::write(&path, &variants[1]).await?;
+        for (width, variant) in ARTWORK_VARIANT_WIDTHS.iter().zip(&variants[2..]) {
+            let path =
+                server_artwork::variant_path(&self.state.settings, &artwork.artwork_id, *width);
+            tokio::fs::write(&path, variant).await?;
+        }
+        // uploads only learn their size here
+        if (artwork.width, artwork.height) != size {
+            artwork::set_size(&self.state.db, &artwork.artwork_id, size.0, size.1, time)
+                .await
+                .map_err(std::io::Error::other)?;
+        }
+        artwork::set_palette(&self.state.db, &artwork.artwork_id, palette, time)
+            .await
+            .map_err(std::io::Error::other)?;
+        artwork::set_blurhash(&self.state.db, &artwork.artwork_id, &blurhash, time)
+            .await
+            .map_err(std::io::Error::other)?;
+        trace!("preview of artwork {} rendered", artwork.artwork_id);
+        Ok(())
+    }
+
+    pub fn spawn(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.run_once(Utc::now().timestamp_millis()).await {
+                    error!("media worker error: {}", err);
+                }
+            }
+        })
+    }
+}
+
+struct Rendered {
+    /// Size of the original.
+    size: (u32, u32),
+    /// A webp per requested height, then per requested width.
+    variants: Vec<Vec<u8>>,
+    /// Palette of the original, before any watermark.
+    palette: Vec<String>,
+    /// BlurHash of the original, empty if it couldn't be encoded.
+    blurhash: String,
+}
+
+/// Decodes, scales to each of `heights` and `widths` and watermarks off the async runtime.
+/// Neither upscales.
+async fn render(
+    bytes: Vec<u8>,
+    heights: &'static [u32],
+    widths: &'static [u32],
+    watermark: Option<Watermark>,
+) -> Result<Rendered, ErrorImg> {
+    tokio::task::spawn_blocking(move || {
+        let img = img::decode(&bytes)?;
+        let ratio = img.height() as f32 / img.width().max(1) as f32;
+        let variants = heights
+            .iter()
+            .copied()
+            .chain(
+                widths
+                    .iter()
+                    .map(|width| ((*width as f32 * ratio).ceil() as u32).max(1)),
+            )
+            .map(|height| ImgData::new(&img, height, watermark.as_ref()).encode_webp())
+            .collect::<Result<Vec<_>, _>>()?;
+        let sample = img
+            .thumbnail(PALETTE_SAMPLE_SIZE, PALETTE_SAMPLE_SIZE)
+            .to_rgba8();
+        let pixels = sample
+            .pixels()
+            .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2]])
+            .collect::<Vec<_>>();
+        let blurhash = blurhash::encode(
+            BLURHASH_COMPONENTS.0,
+            BLURHASH_COMPONENTS.1,
+            sample.width(),
+            sample.height(),
+            sample.as_raw(),
+        )
+        .unwrap_or_else(|err| {
+            warn!("failed to encode blurhash: {}", err);
+            String::new()
+        });
+        Ok(Rendered {
+            size: (img.width(), img.height()),
+            variants,
+            palette: palette::extract(&pixels),
+            blurhash,
+        })
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// Access checked downloads of revision and artwork variants, only artwork previews and low variants are public.
+#[derive(Clone)]
+pub struct MediaRoutes {
+    state: ServerState,
+}
+
+impl MediaRoutes {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(
+                &format!("{}/:revision_id/:variant", REVISION_MEDIA_PATH),
+                get(revision_media),
+            )
+            .route(
+                &format!("{}/:artwork_id/:variant", ARTWORK_MEDIA_PATH),
+                get(artwork_media),
+            )
+            .with_state(self)
+    }
+
+    async fn session_acc(&self, headers: &HeaderMap) -> Option<String> {
+        let token = headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(auth::get_session_token)?;
+        match auth::get_session_acc(&self.state, token, Utc::now().timestamp_millis()).await {
+            Ok(acc) => acc.map(|acc| acc.username),
+            Err(err) => {
+                error!("failed to read session: {}", err);
+                None
+            }
+        }
+    }
+}
+
+async fn revision_media(
+    State(media): State<MediaRoutes>,
+    Path((revision_id, variant)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let original = match variant.as_str() {
+        "original" => true,
+        "preview" => false,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let Some(username) = media.session_acc(&headers).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let (mime, path) = match commission::open_revision_media(
+        &media.state,
+        &username,
+        &revision_id,
+        original,
+    )
+    .await
+    {
+        Ok(media) => media,
+        Err(ErrorCommission::RevisionNotFound | ErrorCommission::Processing) => {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Err(ErrorCommission::Forbidden) => return StatusCode::FORBIDDEN.into_response(),
+        Err(err) => {
+            error!("failed to open revision {}: {}", revision_id, err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let data = match tokio::fs::read(&path).await {
+        Ok(data) => data,
+        Err(err) => {
+            error!("failed to read revision {}: {}", path.display(), err);
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    };
+    (
+        [
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_str(&mime)
+                    .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+            ),
+            // access changes on approval, so don't let the browser keep the preview around for long
+            (CACHE_CONTROL, HeaderValue::from_static("private, no-cache")),
+            (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+async fn artwork_media(
+    State(media): State<MediaRoutes>,
+    Path((artwork_id, variant)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let width = variant
+        .strip_prefix('w')
+        .and_then(|width| width.parse::<u32>().ok())
+        .filter(|width| ARTWORK_VARIANT_WIDTHS.contains(width));
+    let (original, low) = match variant.as_str() {
+        "original" => (true, false),
+        "preview" => (false, false),
+        "low" => (false, true),
+        _ if width.is_some() => (false, false),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let username = if original {
+        media.session_acc(&headers).await
+    } else {
+        None
+    };
+    let path =
+        match server_artwork::open_media(&media.state, username.as_deref(), &artwork_id, original)
+            .await
+        {
+            Ok(_) if low => server_artwork::low_path(&media.state.settings, &artwork_id),
+            Ok(path) => match width {
+                // rendered before the ladder existed, the preview is the closest there is
+                Some(width) => {
+                    let variant =
+                        server_artwork::variant_path(&media.state.settings, &artwork_id, width);
+                    if tokio::fs::try_exists(&variant).await.unwrap_or(false) {
+                        variant
+                    } else {
+                        path
+                    }
+                }
+                None => path,
+            },
+            Err(ErrorArtwork::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+            Err(ErrorArtwork::Forbidden) if username.is_none() => {
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+            Err(ErrorArtwork::Forbidden) => return StatusCode::FORBIDDEN.into_response(),
+            Err(err) => {
+                error!("failed to open artwork {}: {}", artwork_id, err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+    let data = match tokio::fs::read(&path).await {
+        Ok(data) => data,
+        Err(err) => {
+            error!("failed to read artwork {}: {}", path.display(), err);
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    };
+    let mime = sniff_image(&data).unwrap_or("application/octet-stream");
+    // previews get rendered again when the watermark changes, so they can't be cached for long
+    let cache = if original {
+        "private, no-cache"
+    } else {
+        "public, max-age=300"
+    };
+    (
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(mime)),
+            (CACHE_CONTROL, HeaderValue::from_static(cache)),
+            (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod media_tests {
+    use std::io::Cursor;
+
+    use artbounty_web_frontend::{
+        app::components::gallery::placeholder_url,
+        db::{
+            artwork::get as get_artwork,
+            revision::{self, PREVIEW_READY},
+        },
+        server::{
+            ServerState, artwork,
+            auth::{LoginStep, login, register},
+            commission,
+            test_support::test_state,
+        },
+    };
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use chrono::Utc;
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+    use tower::ServiceExt;
+
+    use super::{MediaRoutes, MediaWorker};
+
+    async fn session(state: &ServerState, username: &str, time: i64) -> String {
+        let email = format!("{}@example.com", username);
+        register(state, username, &email, "password123", time)
+            .await
+            .unwrap();
+        let Ok(LoginStep::Session(session, _)) =
+            login(state, &email, "password123", "", "", time).await
+        else {
+            panic!("expected a session");
+        };
+        format!("session={}", session)
+    }
+
+    async fn get(app: &axum::Router, uri: &str, cookie: &str) -> StatusCode {
+        app.clone()
+            .oneshot(
+                Request::get(uri)
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn previews_are_rendered_and_originals_checked() {
+        let (state, _) = test_state().await;
+        // the routes read the wall clock, so the sessions have to be fresh
+        let time = Utc::now().timestamp_millis();
+        let hey = session(&state, "hey", time).await;
+        let fox = session(&state, "fox", time).await;
+        let owl = session(&state, "owl", time).await;
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 20, Rgb([10, 20, 30])))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let order = commission::create(&state.db, "hey", "fox", "Cat", None, time)
+            .await
+            .unwrap();
+        let upload = commission::upload_revision(
+            &state,
+            "fox",
+            &order.commission_id,
+            "final",
+            "",
+            true,
+            &png,
+            time,
+        )
+        .await
+        .unwrap();
+        let app = MediaRoutes::new(state.clone()).routes::<()>();
+        let preview = format!("/media/revisions/{}/preview", upload.revision_id);
+        let original = format!("/media/revisions/{}/original", upload.revision_id);
+        assert_eq!(get(&app, &preview, &hey).await, StatusCode::NOT_FOUND);
+
+        let worker = MediaWorker::new(state.clone());
+        assert_eq!(worker.run_once(time).await.unwrap(), 1);
+        assert_eq!(worker.run_once(time).await.unwrap(), 0);
+        let rendered = revision::get(&state.db, &upload.revision_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rendered.preview_status, PREVIEW_READY);
+        assert_eq!((rendered.width, rendered.height), (40, 20));
+
+        assert_eq!(get(&app, &preview, &hey).await, StatusCode::OK);
+        assert_eq!(get(&app, &original, &hey).await, StatusCode::FORBIDDEN);
+        assert_eq!(get(&app, &original, &fox).await, StatusCode::OK);
+        assert_eq!(get(&app, &preview, &owl).await, StatusCode::NOT_FOUND);
+
+        commission::review(&state.db, "hey", &upload.revision_id, true, "", time)
+            .await
+            .unwrap();
+        assert_eq!(get(&app, &original, &hey).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn protected_artworks_serve_watermarked_previews() {
+        let (state, _) = test_state().await;
+        let time = Utc::now().timestamp_millis();
+        let hey = session(&state, "hey", time).await;
+        let fox = session(&state, "fox", time).await;
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 100, Rgb([0, 0, 0])))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        std::fs::create_dir_all(&state.settings.media_dir).unwrap();
+        std::fs::write(state.settings.media_dir.join("cat.png"), &png).unwrap();
+
+        let cat = artwork::create(
+            &state.db,
+            "hey",
+            "Cat",
+            "",
+            &[],
+            "sfw",
+            "cat.png",
+            200,
+            100,
+            time,
+        )
+        .await
+        .unwrap();
+        artwork::set_protected(&state.db, "hey", &cat.artwork_id, true, time)
+            .await
+            .unwrap();
+        let app = MediaRoutes::new(state.clone()).routes::<()>();
+        let preview = format!("/media/artworks/{}/preview", cat.artwork_id);
+        let original = format!("/media/artworks/{}/original", cat.artwork_id);
+        assert_eq!(get(&app, &preview, "").await, StatusCode::NOT_FOUND);
+
+        let worker = MediaWorker::new(state.clone());
+        assert_eq!(worker.run_once(time).await.unwrap(), 1);
+        let rendered = get_artwork(&state.db, &cat.artwork_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rendered.preview_status, PREVIEW_READY);
+        // taken from the original, the watermark doesn't count
+        assert_eq!(rendered.palette, ["#000000"]);
+        assert!(placeholder_url(&rendered.blurhash).is_some());
+        let webp = std::fs::read(artwork::preview_path(&state.settings, &cat.artwork_id)).unwrap();
+        let preview_img = image::load_from_memory(&webp).unwrap().to_rgb8();
+        assert!(preview_img.pixels().any(|pixel| pixel.0[0] > 40));
+
+        assert_eq!(get(&app, &preview, "").await, StatusCode::OK);
+        let low = format!("/media/artworks/{}/low", cat.artwork_id);
+        assert_eq!(get(&app, &low, "").await, StatusCode::OK);
+        let webp =
+            std::fs::read(artwork::variant_path(&state.settings, &cat.artwork_id, 320)).unwrap();
+        assert_eq!(image::load_from_memory(&webp).unwrap().width(), 200);
+        let w320 = format!("/media/artworks/{}/w320", cat.artwork_id);
+        assert_eq!(get(&app, &w320, "").await, StatusCode::OK);
+        let w100 = format!("/media/artworks/{}/w100", cat.artwork_id);
+        assert_eq!(get(&app, &w100, "").await, StatusCode::NOT_FOUND);
+        assert_eq!(get(&app, &original, "").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(&app, &original, &fox).await, StatusCode::FORBIDDEN);
+        assert_eq!(get(&app, &original, &hey).await, StatusCode::OK);
+
+        artwork::grant_access(&state.db, "hey", &cat.artwork_id, "fox", time)
+            .await
+            .unwrap();
+        assert_eq!(get(&app, &original, &fox).await, StatusCode::OK);
+    }
+}
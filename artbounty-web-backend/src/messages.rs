@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use artbounty_web_frontend::{
+    api::{ATTACHMENT_PATH, MESSAGE_EVENTS_PATH},
+    server::{
+        ServerState, auth,
+        message::{self, ErrorMessage},
+    },
+};
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, COOKIE, X_CONTENT_TYPE_OPTIONS},
+    },
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::get,
+};
+use chrono::Utc;
+use futures::{Stream, stream};
+use tracing::{error, trace};
+
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(20);
+/// Name of every server-sent event, its data is a json [`artbounty_web_frontend::api::MessageEvent`].
+pub const EVENT_NAME: &str = "message";
+
+/// Live message events and access checked attachment downloads, the rest of messaging goes through server fns.
+#[derive(Clone)]
+pub struct MessagePush {
+    state: ServerState,
+}
+
+impl MessagePush {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(MESSAGE_EVENTS_PATH, get(events))
+            .route(
+                &format!("{}/:attachment_id", ATTACHMENT_PATH),
+                get(attachment),
+            )
+            .with_state(self)
+    }
+
+    async fn session_acc(&self, headers: &HeaderMap) -> Option<String> {
+        let token = headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(auth::get_session_token)?;
+        match auth::get_session_acc(&self.state, token, Utc::now().timestamp_millis()).await {
+            Ok(acc) => acc.map(|acc| acc.username),
+            Err(err) => {
+                error!("failed to read session: {}", err);
+                None
+            }
+        }
+    }
+}
+
+async fn events(
+    State(push): State<MessagePush>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    let Some(username) = push.session_acc(&headers).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    trace!("{} subscribed to message events", username);
+    let subscription = push.state.message_hub.subscribe(&username);
+    let stream = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.recv().await?;
+        let event = Event::default().event(EVENT_NAME).json_data(event);
+        Some((event, subscription))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL)))
+}
+
+async fn attachment(
+    State(push): State<MessagePush>,
+    Path(attachment_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(username) = push.session_acc(&headers).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let (attachment, path) =
+        match message::open_attachment(&push.state, &username, &attachment_id).await {
+            Ok(attachment) => attachment,
+            Err(ErrorMessage::AttachmentNotFound) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => {
+                error!("failed to open attachment {}: {}", attachment_id, err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+    let data = match tokio::fs::read(&path).await {
+        Ok(data) => data,
+        Err(err) => {
+            error!("failed to read attachment {}: {}", path.display(), err);
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    };
+    (
+        [
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_str(&attachment.mime)
+                    .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+            ),
+            (
+                CACHE_CONTROL,
+                HeaderValue::from_static("private, max-age=86400, immutable"),
+            ),
+            (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod messages_tests {
+    use artbounty_web_frontend::{
+        api::MessageEvent,
+        server::{
+            ServerState,
+            auth::{LoginStep, login, register},
+            message,
+            test_support::test_state,
+        },
+    };
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use chrono::Utc;
+    use futures::StreamExt;
+    use tower::ServiceExt;
+
+    use super::MessagePush;
+
+    async fn session(state: &ServerState, username: &str, time: i64) -> String {
+        let email = format!("{}@example.com", username);
+        register(state, username, &email, "password123", time)
+            .await
+            .unwrap();
+        let Ok(LoginStep::Session(session, _)) =
+            login(state, &email, "password123", "", "", time).await
+        else {
+            panic!("expected a session");
+        };
+        format!("session={}", session)
+    }
+
+    #[tokio::test]
+    async fn events_are_pushed_and_attachments_checked() {
+        let (state, _) = test_state().await;
+        // the routes read the wall clock, so the sessions have to be fresh
+        let time = Utc::now().timestamp_millis();
+        let fox = session(&state, "fox", time).await;
+        let owl = session(&state, "owl", time).await;
+        register(&state, "hey", "hey@example.com", "password123", time)
+            .await
+            .unwrap();
+        let app = MessagePush::new(state.clone()).routes::<()>();
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::get("/messages/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::get("/messages/events")
+                    .header(header::COOKIE, &fox)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let mut events = res.into_body().into_data_stream();
+
+        let first = message::start(&state, "hey", "fox", "hello", time)
+            .await
+            .unwrap();
+        let chunk = events.next().await.unwrap().unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .unwrap();
+        let event: MessageEvent = serde_json::from_str(data).unwrap();
+        assert!(matches!(
+            event,
+            MessageEvent::Message { message, muted: false } if message.body == "hello"
+        ));
+
+        let attachment =
+            message::save_attachment(&state, "hey", &first.conversation_id, b"GIF89a....", time)
+                .await
+                .unwrap();
+        let uri = format!("/messages/attachments/{}", attachment.attachment_id);
+        let res = app
+            .clone()
+            .oneshot(
+                Request::get(&uri)
+                    .header(header::COOKIE, &fox)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "image/gif");
+
+        let res = app
+            .oneshot(
+                Request::get(&uri)
+                    .header(header::COOKIE, &owl)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    future::ready,
+    time::{Duration, Instant},
+};
+
+use ::metrics::{
+    Unit, counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram,
+};
+use artbounty_web_frontend::{db::DB_QUERY_DURATION, server::upload::UPLOAD_BYTES_TOTAL};
+use axum::{
+    Router,
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+    routing::get,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tracing::error;
+
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION: &str = "http_request_duration_seconds";
+pub const JOB_QUEUE_DEPTH: &str = "job_queue_depth";
+/// Listener address for `/metrics`, scrapes stay off the public listener.
+pub const METRICS_ADDR_ENV: &str = "METRICS_ADDR";
+pub const METRICS_PATH: &str = "/metrics";
+
+const UNMATCHED_ROUTE: &str = "unmatched";
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+const HTTP_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+const DB_BUCKETS: &[f64] = &[0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+pub fn builder() -> PrometheusBuilder {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(HTTP_REQUEST_DURATION.to_string()),
+            HTTP_BUCKETS,
+        )
+        .unwrap()
+        .set_buckets_for_metric(Matcher::Full(DB_QUERY_DURATION.to_string()), DB_BUCKETS)
+        .unwrap()
+}
+
+pub fn describe() {
+    describe_counter!(HTTP_REQUESTS_TOTAL, "Total number of http requests handled");
+    describe_histogram!(
+        HTTP_REQUEST_DURATION,
+        Unit::Seconds,
+        "Time spent handling http requests"
+    );
+    describe_histogram!(
+        DB_QUERY_DURATION,
+        Unit::Seconds,
+        "Time spent waiting on database queries"
+    );
+    describe_counter!(
+        UPLOAD_BYTES_TOTAL,
+        Unit::Bytes,
+        "Total bytes uploaded by kind"
+    );
+    describe_gauge!(JOB_QUEUE_DEPTH, "Number of jobs waiting in a queue");
+}
+
+/// Installs the global prometheus recorder and spawns the histogram upkeep task.
+pub fn install() -> PrometheusHandle {
+    let handle = builder().install_recorder().unwrap();
+    describe();
+
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                handle.run_upkeep();
+            }
+        }
+    });
+
+    handle
+}
+
+pub fn routes(handle: PrometheusHandle) -> Router {
+    Router::new().route(METRICS_PATH, get(move || ready(handle.render())))
+}
+
+/// Serves [`routes`] on its own listener at `addr`.
+pub async fn serve(handle: PrometheusHandle, addr: &str) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, routes(handle)).await {
+            error!("metrics listener failed: {}", err);
+        }
+    });
+    Ok(())
+}
+
+pub async fn track_http(req: Request, next: Next) -> Response {
+    let started_at = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let res = next.run(req).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", res.status().as_u16().to_string()),
+    ];
+    counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+    histogram!(HTTP_REQUEST_DURATION, &labels).record(started_at.elapsed());
+
+    res
+}
+
+/// `depth` is everything waiting in the queue, not only the batch a worker picked up.
+pub fn set_job_queue_depth(queue: &'static str, depth: u64) {
+    gauge!(JOB_QUEUE_DEPTH, "queue" => queue).set(depth as f64);
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use axum::{Router, body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    use crate::telemetry::with_request_tracing;
+
+    use super::{builder, routes};
+
+    #[test]
+    fn track_http_labels_matched_route() {
+        let recorder = builder().build_recorder();
+        let handle = recorder.handle();
+
+        let res = ::metrics::with_local_recorder(&recorder, || {
+            let app =
+                with_request_tracing(Router::new().route("/items/:id", get(|| async { "ok" })));
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(app.oneshot(Request::get("/items/5").body(Body::empty()).unwrap()))
+                .unwrap()
+        });
+
+        assert!(res.headers().contains_key("x-request-id"));
+
+        let output = handle.render();
+        assert!(
+            output
+                .contains(r#"http_requests_total{method="GET",route="/items/:id",status="200"} 1"#)
+        );
+        assert!(output.contains("http_request_duration_seconds_bucket"));
+    }
+
+    #[tokio::test]
+    async fn metrics_are_served_by_their_own_router() {
+        let recorder = builder().build_recorder();
+        let handle = recorder.handle();
+        ::metrics::with_local_recorder(&recorder, || {
+            artbounty_web_frontend::server::upload::record_bytes("artwork", 10)
+        });
+
+        let res = routes(handle)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(
+            String::from_utf8_lossy(&body).contains(r#"upload_bytes_total{kind="artwork"} 10"#)
+        );
+    }
+}
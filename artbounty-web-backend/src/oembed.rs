@@ -0,0 +1,108 @@
+use artbounty_web_frontend::{
+    api::OEMBED_PATH,
+    server::{
+        ServerState,
+        embed::{self, ErrorEmbed, OEmbed},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use serde::Deserialize;
+use tracing::error;
+
+/// oEmbed provider for artwork and profile pages, so chat apps can unfurl their links.
+#[derive(Clone)]
+pub struct OEmbedRoutes {
+    state: ServerState,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OEmbedQuery {
+    pub url: String,
+    pub maxwidth: Option<u32>,
+    pub maxheight: Option<u32>,
+    pub format: Option<String>,
+}
+
+impl OEmbedRoutes {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(OEMBED_PATH, get(oembed))
+            .with_state(self)
+    }
+}
+
+/// Only json is provided, the spec asks for 501 on any other format.
+async fn oembed(
+    State(routes): State<OEmbedRoutes>,
+    Query(query): Query<OEmbedQuery>,
+) -> Result<Json<OEmbed>, StatusCode> {
+    if query
+        .format
+        .as_deref()
+        .is_some_and(|format| format != "json")
+    {
+        return Err(StatusCode::NOT_IMPLEMENTED);
+    }
+    let state = &routes.state;
+    embed::oembed(
+        &state.db,
+        &state.settings,
+        &query.url,
+        query.maxwidth,
+        query.maxheight,
+    )
+    .await
+    .map(Json)
+    .map_err(|err| match err {
+        ErrorEmbed::Url | ErrorEmbed::NotFound => StatusCode::NOT_FOUND,
+        err => {
+            error!("failed to embed {}: {}", query.url, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })
+}
+
+#[cfg(test)]
+mod oembed_tests {
+    use artbounty_web_frontend::server::test_support::test_state;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use super::OEmbedRoutes;
+
+    #[tokio::test]
+    async fn unknown_pages_and_formats_are_refused() {
+        let app = OEmbedRoutes::new(test_state().await.0).routes::<()>();
+        let get = |uri: &str| {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+        let url = "http%3A%2F%2Flocalhost%3A3000%2Fartwork%2Fcat";
+        assert_eq!(
+            get(&format!("/oembed?url={}&format=xml", url)).await,
+            StatusCode::NOT_IMPLEMENTED
+        );
+        assert_eq!(
+            get(&format!("/oembed?url={}", url)).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get("/oembed?url=https%3A%2F%2Fexample.com%2Fartwork%2Fcat").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get("/oembed").await, StatusCode::BAD_REQUEST);
+    }
+}
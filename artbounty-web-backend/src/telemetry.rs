@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use axum::{Router, extract::MatchedPath, http::Request, middleware, response::Response};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{Span, field, info, info_span};
+
+use crate::metrics;
+
+pub const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Logs as json when `LOG_FORMAT=json`, otherwise as plain text.
+pub fn init_logger() {
+    let json = std::env::var(LOG_FORMAT_ENV).is_ok_and(|v| v.eq_ignore_ascii_case("json"));
+    let env_filter = tracing_subscriber::EnvFilter::from_default_env();
+
+    if json {
+        tracing_subscriber::fmt()
+            .json()
+            .with_file(true)
+            .with_line_number(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_env_filter(env_filter)
+            .try_init()
+            .unwrap();
+    } else {
+        tracing_subscriber::fmt()
+            .event_format(
+                tracing_subscriber::fmt::format()
+                    .with_file(true)
+                    .with_line_number(true),
+            )
+            .with_env_filter(env_filter)
+            .try_init()
+            .unwrap();
+    }
+}
+
+/// Wraps every route in a request span, records http metrics and propagates `x-request-id`.
+pub fn with_request_tracing<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(middleware::from_fn(metrics::track_http))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(make_request_span)
+                .on_request(())
+                .on_response(on_response),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+fn make_request_span<B>(req: &Request<B>) -> Span {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_default();
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        route,
+        request_id,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    )
+}
+
+fn on_response<B>(res: &Response<B>, latency: Duration, span: &Span) {
+    span.record("status", res.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    info!("finished");
+}
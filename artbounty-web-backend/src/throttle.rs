@@ -0,0 +1,432 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use thiserror::Error;
+use tracing::trace;
+
+pub mod layer;
+
+pub trait TimeMiddleware {
+    fn get_time(&self) -> impl std::future::Future<Output = DateTime<Utc>> + Send;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock;
+
+impl TimeMiddleware for Clock {
+    async fn get_time(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BanReason {
+    TooManyRequests,
+    TooManyConcurrentRequests,
+    Other(String),
+}
+
+#[derive(Error, Debug)]
+#[error("invalid ban reason \"{0}\"")]
+pub struct InvalidBanReason(String);
+
+impl Display for BanReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BanReason::TooManyRequests => write!(f, "TooManyRequests"),
+            BanReason::TooManyConcurrentRequests => write!(f, "TooManyConcurrentRequests"),
+            BanReason::Other(reason) => write!(f, "Other({})", reason),
+        }
+    }
+}
+
+impl FromStr for BanReason {
+    type Err = InvalidBanReason;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "TooManyRequests" => Ok(BanReason::TooManyRequests),
+            "TooManyConcurrentRequests" => Ok(BanReason::TooManyConcurrentRequests),
+            other => other
+                .strip_prefix("Other(")
+                .and_then(|v| v.strip_suffix(')'))
+                .map(|v| BanReason::Other(v.to_string()))
+                .ok_or_else(|| InvalidBanReason(other.to_string())),
+        }
+    }
+}
+
+pub type BanType = Option<(DateTime<Utc>, BanReason)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowCon {
+    Allow,
+    Blocked,
+    AlreadyBanned,
+    Banned((DateTime<Utc>, BanReason)),
+    UnbannedAndAllow,
+    UnbannedAndBlocked,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IsBanned {
+    Banned,
+    NotBanned,
+    UnBanned,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdTracker {
+    pub amount: u64,
+    pub started_at: DateTime<Utc>,
+}
+
+impl ThresholdTracker {
+    pub fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            amount: 0,
+            started_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    pub amount: u64,
+    pub delta: TimeDelta,
+}
+
+impl Threshold {
+    pub const fn new_const(amount: u64, delta: Option<TimeDelta>) -> Self {
+        let delta = match delta {
+            Some(delta) => delta,
+            None => panic!("failed to create delta"),
+        };
+
+        Self { amount, delta }
+    }
+}
+
+pub const fn delta_seconds(time: i64) -> TimeDelta {
+    match TimeDelta::try_seconds(time) {
+        Some(delta) => delta,
+        None => panic!("invalid delta"),
+    }
+}
+
+pub const fn delta_minutes(time: i64) -> TimeDelta {
+    match TimeDelta::try_minutes(time) {
+        Some(delta) => delta,
+        None => panic!("invalid delta"),
+    }
+}
+
+pub const fn delta_hours(time: i64) -> TimeDelta {
+    match TimeDelta::try_hours(time) {
+        Some(delta) => delta,
+        None => panic!("invalid delta"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn double_throttle(
+    block_tracker: &mut ThresholdTracker,
+    ban_tracker: &mut ThresholdTracker,
+    block_threshold: &Threshold,
+    ban_threshold: &Threshold,
+    ban_reason: &BanReason,
+    ban_duration: &TimeDelta,
+    time: &DateTime<Utc>,
+    banned_until: &mut BanType,
+) -> AllowCon {
+    let ban_status = is_banned(banned_until, time);
+    match ban_status {
+        IsBanned::Banned => {
+            return AllowCon::AlreadyBanned;
+        }
+        IsBanned::UnBanned => {
+            ban_tracker.started_at = *time;
+            ban_tracker.amount = 0;
+
+            block_tracker.started_at = *time;
+            block_tracker.amount = 0;
+        }
+        IsBanned::NotBanned => {}
+    }
+
+    if !threshold_allow(ban_tracker, ban_threshold, time) {
+        let ban_until = *time + *ban_duration;
+        *banned_until = Some((ban_until, ban_reason.clone()));
+        return AllowCon::Banned((ban_until, ban_reason.clone()));
+    }
+
+    if !threshold_allow(block_tracker, block_threshold, time) {
+        ban_tracker.amount += 1;
+        return if ban_status == IsBanned::UnBanned {
+            AllowCon::UnbannedAndBlocked
+        } else {
+            AllowCon::Blocked
+        };
+    } else {
+        block_tracker.amount += 1;
+    }
+
+    if ban_status == IsBanned::UnBanned {
+        AllowCon::UnbannedAndAllow
+    } else {
+        AllowCon::Allow
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn ranged_throttle(
+    max: &u64,
+    current: &mut u64,
+    tracker: &mut ThresholdTracker,
+    threshold: &Threshold,
+    ban_reason: &BanReason,
+    ban_duration: &TimeDelta,
+    time: &DateTime<Utc>,
+    banned_until: &mut BanType,
+) -> AllowCon {
+    let ban_status = is_banned(banned_until, time);
+    trace!("ranged throttle: ban status: {:?}", ban_status);
+
+    match ban_status {
+        IsBanned::Banned => {
+            return AllowCon::AlreadyBanned;
+        }
+        IsBanned::UnBanned => {
+            tracker.started_at = *time;
+            tracker.amount = 0;
+        }
+        IsBanned::NotBanned => {}
+    }
+
+    if *current >= *max {
+        let allow = threshold_allow(tracker, threshold, time);
+        trace!("ranged throttle: allow: {}", allow);
+
+        if !allow {
+            let ban_until = *time + *ban_duration;
+            *banned_until = Some((ban_until, ban_reason.clone()));
+            return AllowCon::Banned((ban_until, ban_reason.clone()));
+        }
+
+        tracker.amount += 1;
+
+        return if ban_status == IsBanned::UnBanned {
+            AllowCon::UnbannedAndBlocked
+        } else {
+            AllowCon::Blocked
+        };
+    }
+
+    *current += 1;
+
+    if ban_status == IsBanned::UnBanned {
+        AllowCon::UnbannedAndAllow
+    } else {
+        AllowCon::Allow
+    }
+}
+
+pub fn simple_throttle(
+    tracker: &mut ThresholdTracker,
+    threshold: &Threshold,
+    ban_duration: &TimeDelta,
+    ban_reason: &BanReason,
+    time: &DateTime<Utc>,
+    banned_until: &mut BanType,
+) -> AllowCon {
+    match is_banned(banned_until, time) {
+        IsBanned::Banned => {
+            return AllowCon::AlreadyBanned;
+        }
+        IsBanned::UnBanned => {
+            tracker.started_at = *time;
+            tracker.amount = 0;
+            return AllowCon::UnbannedAndAllow;
+        }
+        _ => {}
+    }
+    let allow = threshold_allow(tracker, threshold, time);
+    if !allow {
+        let ban = (*time + *ban_duration, ban_reason.clone());
+        *banned_until = Some(ban.clone());
+        return AllowCon::Banned(ban);
+    }
+
+    AllowCon::Allow
+}
+
+pub fn threshold_allow(
+    tracker: &mut ThresholdTracker,
+    threshold: &Threshold,
+    time: &DateTime<Utc>,
+) -> bool {
+    let max_reatched = tracker.amount >= threshold.amount;
+    let time_passed = (*time - tracker.started_at) >= threshold.delta;
+    trace!(
+        "threshold_allow: max_reatched: {}({}/{}), time_passed: {}",
+        max_reatched, tracker.amount, threshold.amount, time_passed
+    );
+
+    if time_passed {
+        tracker.started_at = *time;
+        tracker.amount = 0;
+    }
+    !max_reatched || time_passed
+}
+
+/// Time left until `tracker` starts a new window for `threshold`.
+pub fn threshold_retry_after(
+    tracker: &ThresholdTracker,
+    threshold: &Threshold,
+    time: &DateTime<Utc>,
+) -> TimeDelta {
+    (tracker.started_at + threshold.delta - *time).max(TimeDelta::zero())
+}
+
+pub fn compare_pick_worst(a: AllowCon, b: AllowCon) -> AllowCon {
+    let get_order = |v: &AllowCon| match v {
+        AllowCon::AlreadyBanned => 5,
+        AllowCon::Banned(_) => 4,
+        AllowCon::UnbannedAndBlocked => 3,
+        AllowCon::Blocked => 2,
+        AllowCon::UnbannedAndAllow => 1,
+        AllowCon::Allow => 0,
+    };
+    let a_level = get_order(&a);
+    let b_level = get_order(&b);
+    if a_level >= b_level { a } else { b }
+}
+
+pub fn is_banned(banned_until: &mut BanType, time: &DateTime<Utc>) -> IsBanned {
+    let Some((date, reason)) = banned_until else {
+        trace!("is_banned: entry doesnt exist");
+        return IsBanned::NotBanned;
+    };
+
+    let un_banned = time >= date;
+
+    trace!(
+        "is_banned: {} >= {} = {}, reason: {:?}",
+        time, date, !un_banned, reason,
+    );
+
+    if un_banned {
+        *banned_until = None;
+        return IsBanned::UnBanned;
+    }
+    IsBanned::Banned
+}
+
+#[cfg(test)]
+mod throttle_tests {
+    use chrono::{TimeDelta, Utc};
+
+    use super::{
+        AllowCon, BanReason, BanType, Threshold, ThresholdTracker, double_throttle,
+        ranged_throttle, threshold_allow,
+    };
+
+    #[test]
+    fn threshold_allow_test() {
+        let mut time = Utc::now();
+        let threshold = Threshold::new_const(2, TimeDelta::try_minutes(1));
+        let mut tracker = ThresholdTracker::new(time);
+
+        assert!(threshold_allow(&mut tracker, &threshold, &time));
+        tracker.amount += 1;
+        assert!(threshold_allow(&mut tracker, &threshold, &time));
+        tracker.amount += 1;
+        assert!(!threshold_allow(&mut tracker, &threshold, &time));
+
+        time += TimeDelta::try_minutes(1).unwrap();
+        assert!(threshold_allow(&mut tracker, &threshold, &time));
+        assert_eq!(tracker.amount, 0);
+    }
+
+    #[test]
+    fn double_throttle_test() {
+        let mut time = Utc::now();
+        let block_threshold = Threshold::new_const(2, TimeDelta::try_minutes(1));
+        let ban_threshold = Threshold::new_const(2, TimeDelta::try_minutes(1));
+        let ban_duration = TimeDelta::try_minutes(10).unwrap();
+        let ban_reason = BanReason::TooManyRequests;
+        let mut block_tracker = ThresholdTracker::new(time);
+        let mut ban_tracker = ThresholdTracker::new(time);
+        let mut banned_until: BanType = None;
+
+        let mut throttle = |time| {
+            double_throttle(
+                &mut block_tracker,
+                &mut ban_tracker,
+                &block_threshold,
+                &ban_threshold,
+                &ban_reason,
+                &ban_duration,
+                &time,
+                &mut banned_until,
+            )
+        };
+
+        assert_eq!(throttle(time), AllowCon::Allow);
+        assert_eq!(throttle(time), AllowCon::Allow);
+        assert_eq!(throttle(time), AllowCon::Blocked);
+        assert_eq!(throttle(time), AllowCon::Blocked);
+        assert_eq!(
+            throttle(time),
+            AllowCon::Banned((time + ban_duration, BanReason::TooManyRequests))
+        );
+        assert_eq!(throttle(time), AllowCon::AlreadyBanned);
+
+        time += ban_duration;
+        assert_eq!(throttle(time), AllowCon::UnbannedAndAllow);
+        assert_eq!(throttle(time), AllowCon::Allow);
+    }
+
+    #[test]
+    fn ranged_throttle_test() {
+        let time = Utc::now();
+        let threshold = Threshold::new_const(1, TimeDelta::try_minutes(1));
+        let ban_duration = TimeDelta::try_minutes(1).unwrap();
+        let ban_reason = BanReason::TooManyConcurrentRequests;
+        let mut tracker = ThresholdTracker::new(time);
+        let mut banned_until: BanType = None;
+        let mut current = 0;
+
+        let mut throttle = |current: &mut u64| {
+            ranged_throttle(
+                &2,
+                current,
+                &mut tracker,
+                &threshold,
+                &ban_reason,
+                &ban_duration,
+                &time,
+                &mut banned_until,
+            )
+        };
+
+        assert_eq!(throttle(&mut current), AllowCon::Allow);
+        assert_eq!(throttle(&mut current), AllowCon::Allow);
+        assert_eq!(throttle(&mut current), AllowCon::Blocked);
+        current -= 1;
+        assert_eq!(throttle(&mut current), AllowCon::Allow);
+        assert!(matches!(throttle(&mut current), AllowCon::Banned(_)));
+    }
+
+    #[test]
+    fn ban_reason_round_trip() {
+        for reason in [
+            BanReason::TooManyRequests,
+            BanReason::TooManyConcurrentRequests,
+            BanReason::Other(String::from("spam")),
+        ] {
+            assert_eq!(reason.to_string().parse::<BanReason>().unwrap(), reason);
+        }
+    }
+}
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use artbounty_web_frontend::db::{self, Db, DbError, ban::DbBan};
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::{
+        StatusCode,
+        header::{COOKIE, RETRY_AFTER},
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use thiserror::Error;
+use tower::{Layer, Service};
+use tracing::{debug, error, trace, warn};
+
+use super::{
+    AllowCon, BanReason, BanType, Threshold, ThresholdTracker, TimeMiddleware, compare_pick_worst,
+    delta_minutes, double_throttle, ranged_throttle, threshold_retry_after,
+};
+
+const UNMATCHED_ROUTE: &str = "unmatched";
+const PRUNE_AT: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThrottleKey {
+    Ip(IpAddr),
+    Session(String),
+}
+
+#[derive(Error, Debug)]
+#[error("invalid throttle key \"{0}\"")]
+pub struct InvalidThrottleKey(String);
+
+impl Display for ThrottleKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrottleKey::Ip(ip) => write!(f, "ip:{}", ip),
+            ThrottleKey::Session(session) => write!(f, "session:{}", session),
+        }
+    }
+}
+
+impl FromStr for ThrottleKey {
+    type Err = InvalidThrottleKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("ip", ip)) => ip
+                .parse::<IpAddr>()
+                .map(ThrottleKey::Ip)
+                .map_err(|_| InvalidThrottleKey(s.to_string())),
+            Some(("session", session)) => Ok(ThrottleKey::Session(session.to_string())),
+            _ => Err(InvalidThrottleKey(s.to_string())),
+        }
+    }
+}
+
+/// What a request is counted against.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum KeyKind {
+    #[default]
+    Ip,
+    /// Uses the value of the session cookie, falling back to the ip when it is missing.
+    Session { cookie: String },
+}
+
+impl KeyKind {
+    pub fn extract(&self, req: &Request) -> ThrottleKey {
+        if let KeyKind::Session { cookie } = self
+            && let Some(session) = get_cookie(req, cookie)
+        {
+            return ThrottleKey::Session(session.to_string());
+        }
+
+        let ip = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        ThrottleKey::Ip(ip)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteThreshold {
+    pub block: Threshold,
+    pub ban: Threshold,
+    pub ban_duration: TimeDelta,
+    pub max_concurrent: Option<u64>,
+}
+
+impl Default for RouteThreshold {
+    fn default() -> Self {
+        Self {
+            block: Threshold::new_const(100, TimeDelta::try_minutes(1)),
+            ban: Threshold::new_const(10, TimeDelta::try_minutes(1)),
+            ban_duration: delta_minutes(10),
+            max_concurrent: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleConfig {
+    pub key: KeyKind,
+    pub fallback: RouteThreshold,
+    pub routes: HashMap<String, RouteThreshold>,
+}
+
+impl ThrottleConfig {
+    pub fn set_key(mut self, key: KeyKind) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn set_fallback(mut self, threshold: RouteThreshold) -> Self {
+        self.fallback = threshold;
+        self
+    }
+
+    /// Overrides the threshold for a matched route path, e.g. `/api/*fn_name`.
+    pub fn set_route(mut self, route: impl Into<String>, threshold: RouteThreshold) -> Self {
+        self.routes.insert(route.into(), threshold);
+        self
+    }
+
+    pub fn get_threshold(&self, route: &str) -> &RouteThreshold {
+        self.routes.get(route).unwrap_or(&self.fallback)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RouteTracker {
+    block_tracker: ThresholdTracker,
+    ban_tracker: ThresholdTracker,
+    concurrent_tracker: ThresholdTracker,
+    concurrent: u64,
+}
+
+impl RouteTracker {
+    fn new(time: DateTime<Utc>) -> Self {
+        Self {
+            block_tracker: ThresholdTracker::new(time),
+            ban_tracker: ThresholdTracker::new(time),
+            concurrent_tracker: ThresholdTracker::new(time),
+            concurrent: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Trackers {
+    routes: HashMap<(ThrottleKey, String), RouteTracker>,
+    bans: HashMap<ThrottleKey, BanType>,
+}
+
+struct Verdict {
+    allow: AllowCon,
+    retry_after: TimeDelta,
+    acquired: bool,
+}
+
+pub struct ThrottleState<T> {
+    config: ThrottleConfig,
+    time: T,
+    db: Option<Db>,
+    trackers: Mutex<Trackers>,
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorLoadBans {
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl<T: TimeMiddleware> ThrottleState<T> {
+    fn check(&self, key: &ThrottleKey, route: &str, time: &DateTime<Utc>) -> Verdict {
+        let threshold = self.config.get_threshold(route);
+        let mut trackers = self.trackers.lock().unwrap();
+        if trackers.routes.len() > PRUNE_AT {
+            self.prune(&mut trackers, time);
+        }
+        let Trackers { routes, bans } = &mut *trackers;
+
+        let banned_until = bans.entry(key.clone()).or_default();
+        let tracker = routes
+            .entry((key.clone(), route.to_string()))
+            .or_insert_with(|| RouteTracker::new(*time));
+
+        let mut allow = double_throttle(
+            &mut tracker.block_tracker,
+            &mut tracker.ban_tracker,
+            &threshold.block,
+            &threshold.ban,
+            &BanReason::TooManyRequests,
+            &threshold.ban_duration,
+            time,
+            banned_until,
+        );
+        let mut acquired = false;
+
+        if let (Some(max), AllowCon::Allow | AllowCon::UnbannedAndAllow) =
+            (threshold.max_concurrent, &allow)
+        {
+            let concurrent_allow = ranged_throttle(
+                &max,
+                &mut tracker.concurrent,
+                &mut tracker.concurrent_tracker,
+                &threshold.ban,
+                &BanReason::TooManyConcurrentRequests,
+                &threshold.ban_duration,
+                time,
+                banned_until,
+            );
+            acquired = matches!(
+                concurrent_allow,
+                AllowCon::Allow | AllowCon::UnbannedAndAllow
+            );
+            allow = compare_pick_worst(allow, concurrent_allow);
+        }
+
+        let retry_after = match (&allow, &banned_until) {
+            (AllowCon::Banned(_) | AllowCon::AlreadyBanned, Some((until, _))) => *until - *time,
+            (AllowCon::Blocked | AllowCon::UnbannedAndBlocked, _) => {
+                threshold_retry_after(&tracker.block_tracker, &threshold.block, time)
+            }
+            _ => TimeDelta::zero(),
+        };
+
+        Verdict {
+            allow,
+            retry_after,
+            acquired,
+        }
+    }
+
+    fn prune(&self, trackers: &mut Trackers, time: &DateTime<Utc>) {
+        let before = trackers.routes.len();
+        trackers.routes.retain(|(_, route), tracker| {
+            let threshold = self.config.get_threshold(route);
+            let window = threshold.block.delta.max(threshold.ban.delta);
+            tracker.concurrent > 0
+                || *time - tracker.block_tracker.started_at < window
+                || *time - tracker.ban_tracker.started_at < window
+        });
+        trackers
+            .bans
+            .retain(|_, banned_until| banned_until.as_ref().is_some_and(|(until, _)| until > time));
+        debug!(
+            "pruned throttle trackers: {} -> {}",
+            before,
+            trackers.routes.len()
+        );
+    }
+
+    async fn persist_ban(&self, key: &ThrottleKey, until: &DateTime<Utc>, reason: &BanReason) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        let time = self.time.get_time().await.timestamp_millis();
+        let ban = DbBan {
+            key: key.to_string(),
+            reason: reason.to_string(),
+            banned_until: until.timestamp_millis(),
+            modified_at: time,
+            created_at: time,
+        };
+        if let Err(err) = db::ban::upsert(db, ban).await {
+            error!("failed to persist ban for {}: {}", key, err);
+        }
+    }
+
+    async fn remove_ban(&self, key: &ThrottleKey) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        if let Err(err) = db::ban::remove(db, &key.to_string()).await {
+            error!("failed to remove ban for {}: {}", key, err);
+        }
+    }
+}
+
+impl<T> ThrottleState<T> {
+    fn release(&self, key: &ThrottleKey, route: &str) {
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(tracker) = trackers.routes.get_mut(&(key.clone(), route.to_string())) {
+            tracker.concurrent = tracker.concurrent.saturating_sub(1);
+        }
+    }
+}
+
+/// Concurrency slot taken by a request, given back once the request is done or dropped.
+struct Slot<T> {
+    state: Arc<ThrottleState<T>>,
+    key: ThrottleKey,
+    route: String,
+}
+
+impl<T> Drop for Slot<T> {
+    fn drop(&mut self) {
+        self.state.release(&self.key, &self.route);
+    }
+}
+
+#[derive(Clone)]
+pub struct ThrottleLayer<T> {
+    state: Arc<ThrottleState<T>>,
+}
+
+impl<T: TimeMiddleware> ThrottleLayer<T> {
+    pub fn new(config: ThrottleConfig, time: T, db: Option<Db>) -> Self {
+        Self {
+            state: Arc::new(ThrottleState {
+                config,
+                time,
+                db,
+                trackers: Mutex::new(Trackers::default()),
+            }),
+        }
+    }
+
+    /// Restores bans that are still active from the db, returns how many were loaded.
+    pub async fn load_bans(&self) -> Result<usize, ErrorLoadBans> {
+        let Some(db) = &self.state.db else {
+            return Ok(0);
+        };
+        let time = self.state.time.get_time().await;
+        let saved_bans = db::ban::get_active(db, time.timestamp_millis()).await?;
+        let mut trackers = self.state.trackers.lock().unwrap();
+        let mut loaded = 0;
+
+        for saved_ban in saved_bans {
+            let (Ok(key), Ok(reason), Some(until)) = (
+                saved_ban.key.parse::<ThrottleKey>(),
+                saved_ban.reason.parse::<BanReason>(),
+                DateTime::<Utc>::from_timestamp_millis(saved_ban.banned_until),
+            ) else {
+                warn!("skipping invalid saved ban: {:?}", saved_ban);
+                continue;
+            };
+            trackers.bans.insert(key, Some((until, reason)));
+            loaded += 1;
+        }
+
+        trace!("loaded {} bans", loaded);
+        Ok(loaded)
+    }
+}
+
+impl<S, T> Layer<S> for ThrottleLayer<T> {
+    type Service = Throttle<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Throttle {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Throttle<S, T> {
+    inner: S,
+    state: Arc<ThrottleState<T>>,
+}
+
+impl<S, T> Service<Request> for Throttle<S, T>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    T: TimeMiddleware + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let state = self.state.clone();
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let time = state.time.get_time().await;
+            let key = state.config.key.extract(&req);
+            let route = req
+                .extensions()
+                .get::<MatchedPath>()
+                .map(|path| path.as_str().to_string())
+                .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+            let verdict = state.check(&key, &route, &time);
+            trace!("throttle {} {}: {:?}", key, route, verdict.allow);
+            let _slot = verdict.acquired.then(|| Slot {
+                state: state.clone(),
+                key: key.clone(),
+                route: route.clone(),
+            });
+
+            match &verdict.allow {
+                AllowCon::Allow => {}
+                AllowCon::UnbannedAndAllow => {
+                    state.remove_ban(&key).await;
+                }
+                AllowCon::Blocked => {
+                    return Ok(reject(StatusCode::TOO_MANY_REQUESTS, verdict.retry_after));
+                }
+                AllowCon::UnbannedAndBlocked => {
+                    state.remove_ban(&key).await;
+                    return Ok(reject(StatusCode::TOO_MANY_REQUESTS, verdict.retry_after));
+                }
+                AllowCon::Banned((until, reason)) => {
+                    warn!("banned {} until {} for {}", key, until, reason);
+                    state.persist_ban(&key, until, reason).await;
+                    return Ok(reject(StatusCode::FORBIDDEN, verdict.retry_after));
+                }
+                AllowCon::AlreadyBanned => {
+                    return Ok(reject(StatusCode::FORBIDDEN, verdict.retry_after));
+                }
+            }
+
+            inner.call(req).await
+        })
+    }
+}
+
+fn reject(status: StatusCode, retry_after: TimeDelta) -> Response {
+    let retry_after = (retry_after + TimeDelta::milliseconds(999))
+        .num_seconds()
+        .max(1);
+    (status, [(RETRY_AFTER, retry_after.to_string())]).into_response()
+}
+
+fn get_cookie<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|v| v.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod throttle_layer_tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use artbounty_web_frontend::db;
+    use axum::{
+        Router,
+        body::Body,
+        extract::{Query, connect_info::MockConnectInfo},
+        http::{Request, StatusCode, header::RETRY_AFTER},
+        routing::get,
+    };
+    use chrono::{DateTime, TimeDelta, Utc};
+    use tower::ServiceExt;
+
+    use super::{KeyKind, RouteThreshold, ThrottleConfig, ThrottleLayer};
+    use crate::throttle::{Threshold, TimeMiddleware, delta_minutes};
+
+    #[derive(Clone)]
+    struct TestClock(Arc<Mutex<DateTime<Utc>>>);
+
+    impl TestClock {
+        fn advance(&self, delta: TimeDelta) {
+            *self.0.lock().unwrap() += delta;
+        }
+    }
+
+    impl TimeMiddleware for TestClock {
+        async fn get_time(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn config() -> ThrottleConfig {
+        ThrottleConfig::default().set_route(
+            "/login",
+            RouteThreshold {
+                block: Threshold::new_const(2, TimeDelta::try_minutes(1)),
+                ban: Threshold::new_const(1, TimeDelta::try_minutes(1)),
+                ban_duration: delta_minutes(5),
+                max_concurrent: None,
+            },
+        )
+    }
+
+    fn test_app(layer: ThrottleLayer<TestClock>) -> Router {
+        Router::new()
+            .route("/login", get(|| async { "ok" }))
+            .route("/", get(|| async { "ok" }))
+            .layer(layer)
+            .layer(MockConnectInfo(SocketAddr::from(([1, 1, 1, 69], 1234))))
+    }
+
+    async fn send(app: &Router, uri: &str, cookie: Option<&str>) -> (StatusCode, Option<String>) {
+        let mut req = Request::get(uri);
+        if let Some(cookie) = cookie {
+            req = req.header("cookie", cookie);
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .map(|v| v.to_str().unwrap().to_string());
+        (res.status(), retry_after)
+    }
+
+    #[tokio::test]
+    async fn blocks_then_bans_and_persists() {
+        let db = db::connect("mem://", None).await.unwrap();
+        let clock = TestClock(Arc::new(Mutex::new(Utc::now())));
+        let app = test_app(ThrottleLayer::new(
+            config(),
+            clock.clone(),
+            Some(db.clone()),
+        ));
+
+        assert_eq!(send(&app, "/login", None).await.0, StatusCode::OK);
+        assert_eq!(send(&app, "/login", None).await.0, StatusCode::OK);
+        assert_eq!(
+            send(&app, "/login", None).await,
+            (StatusCode::TOO_MANY_REQUESTS, Some(String::from("60")))
+        );
+        assert_eq!(
+            send(&app, "/login", None).await,
+            (StatusCode::FORBIDDEN, Some(String::from("300")))
+        );
+        assert_eq!(send(&app, "/", None).await.0, StatusCode::FORBIDDEN);
+
+        let restarted = ThrottleLayer::new(config(), clock.clone(), Some(db.clone()));
+        assert_eq!(restarted.load_bans().await.unwrap(), 1);
+        let restarted_app = test_app(restarted);
+        assert_eq!(
+            send(&restarted_app, "/", None).await.0,
+            StatusCode::FORBIDDEN
+        );
+
+        clock.advance(delta_minutes(5));
+        assert_eq!(send(&restarted_app, "/login", None).await.0, StatusCode::OK);
+        let saved_bans = db::ban::get_active(&db, 0).await.unwrap();
+        assert!(saved_bans.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_requests_give_back_their_slot() {
+        let clock = TestClock(Arc::new(Mutex::new(Utc::now())));
+        let config = ThrottleConfig::default().set_route(
+            "/slow",
+            RouteThreshold {
+                block: Threshold::new_const(10, TimeDelta::try_minutes(1)),
+                ban: Threshold::new_const(10, TimeDelta::try_minutes(1)),
+                ban_duration: delta_minutes(5),
+                max_concurrent: Some(1),
+            },
+        );
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|Query(query): Query<HashMap<String, String>>| async move {
+                    if query.contains_key("hang") {
+                        std::future::pending::<()>().await;
+                    }
+                    "ok"
+                }),
+            )
+            .layer(ThrottleLayer::new(config, clock, None))
+            .layer(MockConnectInfo(SocketAddr::from(([1, 1, 1, 69], 1234))));
+
+        for _ in 0..3 {
+            let aborted =
+                tokio::time::timeout(Duration::from_millis(20), send(&app, "/slow?hang", None))
+                    .await;
+            assert!(aborted.is_err());
+        }
+        assert_eq!(send(&app, "/slow", None).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn session_keys_are_tracked_separately() {
+        let clock = TestClock(Arc::new(Mutex::new(Utc::now())));
+        let config = config().set_key(KeyKind::Session {
+            cookie: String::from("session"),
+        });
+        let app = test_app(ThrottleLayer::new(config, clock, None));
+
+        for _ in 0..2 {
+            assert_eq!(
+                send(&app, "/login", Some("session=a")).await.0,
+                StatusCode::OK
+            );
+        }
+        assert_eq!(
+            send(&app, "/login", Some("session=a")).await.0,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            send(&app, "/login", Some("theme=dark; session=b")).await.0,
+            StatusCode::OK
+        );
+    }
+}
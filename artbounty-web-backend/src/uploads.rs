@@ -0,0 +1,316 @@
+use std::time::Duration;
+
+use artbounty_web_frontend::{
+    api::{
+        MAXIMUM_UPLOAD_CHUNK_SIZE, UPLOAD_CHUNK_CONTENT_TYPE, UPLOAD_LENGTH_HEADER,
+        UPLOAD_OFFSET_HEADER, UPLOAD_PATH,
+    },
+    server::{
+        ServerError, ServerState, auth,
+        upload::{self, ErrorUpload},
+    },
+};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, State},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, COOKIE, LOCATION},
+    },
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use chrono::Utc;
+use tokio::task::JoinHandle;
+use tracing::{error, trace};
+
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 10);
+pub const UPLOAD_OFFSET: HeaderName = HeaderName::from_static(UPLOAD_OFFSET_HEADER);
+pub const UPLOAD_LENGTH: HeaderName = HeaderName::from_static(UPLOAD_LENGTH_HEADER);
+
+/// Resumable uploads modeled on tus, finishing one goes through the `upload_finalize` server fn.
+#[derive(Clone)]
+pub struct UploadRoutes {
+    state: ServerState,
+}
+
+impl UploadRoutes {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn routes<S>(self) -> Router<S> {
+        Router::new()
+            .route(UPLOAD_PATH, post(create))
+            .route(
+                &format!("{}/:upload_id", UPLOAD_PATH),
+                axum::routing::head(offset).patch(append),
+            )
+            .layer(DefaultBodyLimit::max(MAXIMUM_UPLOAD_CHUNK_SIZE))
+            .with_state(self)
+    }
+
+    async fn session_acc(&self, headers: &HeaderMap) -> Option<String> {
+        let token = headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(auth::get_session_token)?;
+        match auth::get_session_acc(&self.state, token, Utc::now().timestamp_millis()).await {
+            Ok(acc) => acc.map(|acc| acc.username),
+            Err(err) => {
+                error!("failed to read session: {}", err);
+                None
+            }
+        }
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+fn error_response(err: ErrorUpload) -> Response {
+    match err {
+        ErrorUpload::NotFound => StatusCode::NOT_FOUND.into_response(),
+        ErrorUpload::Offset(offset) => {
+            (StatusCode::CONFLICT, [(UPLOAD_OFFSET, offset.to_string())]).into_response()
+        }
+        ErrorUpload::Length | ErrorUpload::Chunk => StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        err if err.is_internal() => {
+            error!("upload failed: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        err => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+async fn create(State(routes): State<UploadRoutes>, headers: HeaderMap) -> Response {
+    let Some(username) = routes.session_acc(&headers).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let Some(length) = header_u64(&headers, &UPLOAD_LENGTH) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match upload::create(
+        &routes.state,
+        &username,
+        length,
+        Utc::now().timestamp_millis(),
+    )
+    .await
+    {
+        Ok(created) => (
+            StatusCode::CREATED,
+            [
+                (LOCATION, format!("{}/{}", UPLOAD_PATH, created.upload_id)),
+                (UPLOAD_OFFSET, String::from("0")),
+            ],
+        )
+            .into_response(),
+        Err(err) => error_response(err),
+    }
+}
+
+async fn offset(
+    State(routes): State<UploadRoutes>,
+    Path(upload_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(username) = routes.session_acc(&headers).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    match upload::get(
+        &routes.state,
+        &username,
+        &upload_id,
+        Utc::now().timestamp_millis(),
+    )
+    .await
+    {
+        Ok(found) => (
+            [
+                (UPLOAD_OFFSET, found.offset.to_string()),
+                (UPLOAD_LENGTH, found.length.to_string()),
+                (CACHE_CONTROL, String::from("no-store")),
+            ],
+            StatusCode::OK,
+        )
+            .into_response(),
+        Err(err) => error_response(err),
+    }
+}
+
+async fn append(
+    State(routes): State<UploadRoutes>,
+    Path(upload_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(username) = routes.session_acc(&headers).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if headers.get(CONTENT_TYPE) != Some(&HeaderValue::from_static(UPLOAD_CHUNK_CONTENT_TYPE)) {
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+    let Some(offset) = header_u64(&headers, &UPLOAD_OFFSET) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match upload::append(
+        &routes.state,
+        &username,
+        &upload_id,
+        offset,
+        &body,
+        Utc::now().timestamp_millis(),
+    )
+    .await
+    {
+        Ok(offset) => (
+            StatusCode::NO_CONTENT,
+            [(UPLOAD_OFFSET, offset.to_string())],
+        )
+            .into_response(),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Drops uploads nobody finished before they expired.
+pub struct UploadSweeper {
+    state: ServerState,
+}
+
+impl UploadSweeper {
+    pub fn new(state: ServerState) -> Self {
+        Self { state }
+    }
+
+    pub fn spawn(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match upload::sweep(&self.state, Utc::now().timestamp_millis()).await {
+                    Ok(0) => {}
+                    Ok(swept) => trace!("swept {} expired uploads", swept),
+                    Err(err) => error!("upload sweep error: {}", err),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod uploads_tests {
+    use artbounty_web_frontend::{
+        api::UPLOAD_CHUNK_CONTENT_TYPE,
+        server::{
+            ServerState,
+            auth::{LoginStep, login, register},
+            test_support::test_state,
+        },
+    };
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode, header},
+        response::Response,
+    };
+    use chrono::Utc;
+    use tower::ServiceExt;
+
+    use super::UploadRoutes;
+
+    async fn session(state: &ServerState, username: &str) -> String {
+        let email = format!("{}@example.com", username);
+        let time = Utc::now().timestamp_millis();
+        register(state, username, &email, "password123", time)
+            .await
+            .unwrap();
+        let Ok(LoginStep::Session(session, _)) =
+            login(state, &email, "password123", "", "", time).await
+        else {
+            panic!("expected a session");
+        };
+        format!("session={}", session)
+    }
+
+    async fn send(
+        app: &axum::Router,
+        method: Method,
+        uri: &str,
+        cookie: &str,
+        headers: &[(&str, &str)],
+        body: &'static [u8],
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, cookie);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        app.clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn offset(response: &Response) -> &str {
+        response.headers()["upload-offset"].to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn chunks_are_appended_at_the_reported_offset() {
+        let (state, _) = test_state().await;
+        let hey = session(&state, "hey").await;
+        let fox = session(&state, "fox").await;
+        let app = UploadRoutes::new(state).routes::<()>();
+
+        let created = send(
+            &app,
+            Method::POST,
+            "/uploads",
+            "",
+            &[("upload-length", "8")],
+            b"",
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::UNAUTHORIZED);
+        let created = send(
+            &app,
+            Method::POST,
+            "/uploads",
+            &hey,
+            &[("upload-length", "8")],
+            b"",
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let location = created.headers()[header::LOCATION].to_str().unwrap();
+
+        let chunk = |offset| {
+            [
+                ("content-type", UPLOAD_CHUNK_CONTENT_TYPE),
+                ("upload-offset", offset),
+            ]
+        };
+        let patched = send(&app, Method::PATCH, location, &hey, &chunk("0"), b"abcd").await;
+        assert_eq!(patched.status(), StatusCode::NO_CONTENT);
+        assert_eq!(offset(&patched), "4");
+        let stale = send(&app, Method::PATCH, location, &hey, &chunk("0"), b"abcd").await;
+        assert_eq!(stale.status(), StatusCode::CONFLICT);
+        assert_eq!(offset(&stale), "4");
+        let too_long = send(&app, Method::PATCH, location, &hey, &chunk("4"), b"efghi").await;
+        assert_eq!(too_long.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let head = send(&app, Method::HEAD, location, &hey, &[], b"").await;
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(offset(&head), "4");
+        let head = send(&app, Method::HEAD, location, &fox, &[], b"").await;
+        assert_eq!(head.status(), StatusCode::NOT_FOUND);
+
+        let patched = send(&app, Method::PATCH, location, &hey, &chunk("4"), b"efgh").await;
+        assert_eq!(offset(&patched), "8");
+    }
+}
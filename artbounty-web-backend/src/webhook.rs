@@ -0,0 +1,312 @@
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use artbounty_web_frontend::{
+    db::{
+        Db,
+        webhook::{self, DbWebhook},
+        webhook_delivery::{self, DbWebhookAttempt, DbWebhookDelivery},
+    },
+    server::webhook::{
+        DELIVERY_HEADER, EVENT_HEADER, ErrorWebhook, SIGNATURE_HEADER, is_public_ip,
+        record_attempt, sign,
+    },
+};
+use chrono::Utc;
+use reqwest::{
+    Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+};
+use tokio::task::JoinHandle;
+use tracing::{error, trace};
+
+use crate::metrics;
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE: u32 = 50;
+pub const QUEUE_NAME: &str = "webhook";
+/// Lets deliveries reach loopback and private addresses, only meant for local receivers in tests
+/// and development.
+pub const ALLOW_PRIVATE_HOSTS_ENV: &str = "WEBHOOK_ALLOW_PRIVATE_HOSTS";
+const PRIVATE_HOST_ERROR: &str = "webhook host resolves to a private address";
+
+/// Resolves webhook hosts and refuses them when any address is internal, the connection then uses
+/// exactly the checked addresses so a second lookup can't point it elsewhere.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .collect::<Vec<_>>();
+            if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+                return Err(PRIVATE_HOST_ERROR.into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Sends queued webhook deliveries, see `artbounty_web_frontend::server::webhook` for the queueing side.
+#[derive(Clone)]
+pub struct WebhookWorker {
+    db: Db,
+    http: reqwest::Client,
+    allow_private_hosts: bool,
+}
+
+impl WebhookWorker {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            http: client(false),
+            allow_private_hosts: false,
+        }
+    }
+
+    pub fn allow_private_hosts(self, allow: bool) -> Self {
+        Self {
+            http: client(allow),
+            allow_private_hosts: allow,
+            ..self
+        }
+    }
+
+    pub fn from_env(db: Db) -> Self {
+        let allow = std::env::var(ALLOW_PRIVATE_HOSTS_ENV).is_ok_and(|v| v == "true" || v == "1");
+        Self::new(db).allow_private_hosts(allow)
+    }
+
+    /// Sends every delivery due at `time`, returns how many were attempted.
+    pub async fn run_once(&self, time: i64) -> Result<usize, ErrorWebhook> {
+        let due = webhook_delivery::get_due(&self.db, time, BATCH_SIZE).await?;
+        metrics::set_job_queue_depth(QUEUE_NAME, webhook_delivery::count_pending(&self.db).await?);
+
+        let mut attempted = 0;
+        for delivery in due {
+            let attempt = match webhook::get(&self.db, &delivery.webhook_id).await? {
+                Some(webhook) if webhook.enabled => self.send(&webhook, &delivery).await,
+                _ => DbWebhookAttempt {
+                    response_code: None,
+                    error: Some(String::from("webhook removed or disabled")),
+                    duration_ms: 0,
+                    created_at: time,
+                },
+            };
+            trace!(
+                "webhook delivery {} attempt: {:?}",
+                delivery.delivery_id, attempt
+            );
+            record_attempt(&self.db, delivery, attempt, time).await?;
+            attempted += 1;
+        }
+
+        Ok(attempted)
+    }
+
+    async fn send(&self, webhook: &DbWebhook, delivery: &DbWebhookDelivery) -> DbWebhookAttempt {
+        let started_at = Instant::now();
+        let now = Utc::now();
+        if !self.allow_private_hosts && is_private_literal(&webhook.url) {
+            return DbWebhookAttempt {
+                response_code: None,
+                error: Some(PRIVATE_HOST_ERROR.to_string()),
+                duration_ms: 0,
+                created_at: now.timestamp_millis(),
+            };
+        }
+        let signature = sign(&webhook.secret, now.timestamp(), &delivery.payload);
+
+        let res = self
+            .http
+            .post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, &delivery.delivery_id)
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        let (response_code, error) = match res {
+            Ok(res) => (Some(res.status().as_u16()), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        DbWebhookAttempt {
+            response_code,
+            error,
+            duration_ms: started_at.elapsed().as_millis() as i64,
+            created_at: now.timestamp_millis(),
+        }
+    }
+
+    pub fn spawn(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.run_once(Utc::now().timestamp_millis()).await {
+                    error!("webhook worker error: {}", err);
+                }
+            }
+        })
+    }
+}
+
+fn client(allow_private_hosts: bool) -> reqwest::Client {
+    let builder = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent("ArtBounty-Webhook/1");
+    let builder = if allow_private_hosts {
+        builder
+    } else {
+        builder.dns_resolver(Arc::new(PublicResolver))
+    };
+    builder.build().unwrap()
+}
+
+/// Hosts written as an address skip the resolver, so they're checked up front.
+fn is_private_literal(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.host_str()
+                .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+                .and_then(|host| host.parse::<IpAddr>().ok())
+        })
+        .is_some_and(|ip| !is_public_ip(ip))
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use std::sync::{Arc, Mutex};
+
+    use artbounty_web_frontend::{
+        db::webhook_delivery::{self, STATUS_DELIVERED, STATUS_PENDING},
+        server::{
+            auth::register,
+            bounty,
+            test_support::test_state,
+            webhook::{self, SIGNATURE_HEADER, verify_signature},
+        },
+    };
+    use axum::{
+        Router,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+    };
+    use chrono::Utc;
+
+    use super::WebhookWorker;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// Local receiver that records requests and answers with `status`.
+    async fn receiver(status: StatusCode) -> (String, Received) {
+        let received = Received::default();
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let received = received.clone();
+                move |headers: HeaderMap, body: String| async move {
+                    received.lock().unwrap().push((headers, body));
+                    status
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/hook", addr), received)
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_signed_and_retried() {
+        let (state, _) = test_state().await;
+        let db = state.db.clone();
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let (ok_url, ok_received) = receiver(StatusCode::OK).await;
+        let (bad_url, bad_received) = receiver(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let ok_hook = webhook::create(&db, &acc, &ok_url, "bounty.created", 0)
+            .await
+            .unwrap();
+        let bad_hook = webhook::create(&db, &acc, &bad_url, "bounty.created", 0)
+            .await
+            .unwrap();
+        let worker = WebhookWorker::new(db.clone()).allow_private_hosts(true);
+
+        bounty::create(&db, "hey", "Draw my cat", "", &[], 100, "sfw", 0)
+            .await
+            .unwrap();
+        webhook::send_test(&db, &acc, &ok_hook.webhook_id, 0)
+            .await
+            .unwrap();
+        assert_eq!(worker.run_once(0).await.unwrap(), 3);
+
+        {
+            let received = ok_received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            let now = Utc::now().timestamp();
+            for (headers, body) in received.iter() {
+                let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+                assert!(verify_signature(&ok_hook.secret, signature, body, now, 300));
+            }
+            let events = received
+                .iter()
+                .map(|(headers, _)| headers["x-artbounty-event"].to_str().unwrap())
+                .collect::<Vec<&str>>();
+            assert!(events.contains(&"bounty.created"));
+            assert!(events.contains(&"ping"));
+        }
+        let delivered = webhook_delivery::get_page_for_webhook(&db, &ok_hook.webhook_id, 10)
+            .await
+            .unwrap();
+        assert!(delivered.iter().all(|d| d.status == STATUS_DELIVERED));
+        assert_eq!(delivered[0].attempts[0].response_code, Some(200));
+
+        // failed delivery waits for its backoff before the next attempt, still in the queue
+        assert_eq!(webhook_delivery::count_pending(&db).await.unwrap(), 1);
+        assert_eq!(worker.run_once(1_000).await.unwrap(), 0);
+        assert_eq!(worker.run_once(30_000).await.unwrap(), 1);
+        assert_eq!(bad_received.lock().unwrap().len(), 2);
+        let failed = webhook_delivery::get_page_for_webhook(&db, &bad_hook.webhook_id, 10)
+            .await
+            .unwrap();
+        assert_eq!(failed[0].status, STATUS_PENDING);
+        assert_eq!(failed[0].attempts.len(), 2);
+        assert_eq!(failed[0].attempts[1].response_code, Some(500));
+        assert_eq!(failed[0].next_attempt_at, 30_000 + 60_000);
+    }
+
+    #[tokio::test]
+    async fn internal_hosts_are_refused() {
+        let (state, _) = test_state().await;
+        let db = state.db.clone();
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let (url, received) = receiver(StatusCode::OK).await;
+        let port = url.split(':').nth(2).unwrap();
+        let localhost_url = format!("http://localhost:{}", port);
+        for url in [&url, &localhost_url] {
+            let hook = webhook::create(&db, &acc, url, "bounty.created", 0)
+                .await
+                .unwrap();
+            webhook::send_test(&db, &acc, &hook.webhook_id, 0)
+                .await
+                .unwrap();
+        }
+        let worker = WebhookWorker::new(db.clone());
+        assert_eq!(worker.run_once(0).await.unwrap(), 2);
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,2016 @@
+use leptos::prelude::*;
+use leptos::server_fn::codec::Rkyv;
+use serde::{Deserialize, Serialize};
+
+pub const MINIMUM_PASSWORD_LENGTH: usize = 10;
+pub const MINIMUM_USERNAME_LENGTH: usize = 3;
+pub const MAXIMUM_USERNAME_LENGTH: usize = 32;
+pub const ADMIN_ROLE: &str = "admin";
+pub const MAXIMUM_TITLE_LENGTH: usize = 120;
+pub const MAXIMUM_DESCRIPTION_LENGTH: usize = 5000;
+pub const MAXIMUM_TAGS: usize = 16;
+pub const MAXIMUM_TAG_LENGTH: usize = 32;
+pub const MAXIMUM_API_TOKENS: usize = 20;
+pub const DEFAULT_API_TOKEN_RATE_LIMIT: u32 = 60;
+pub const MAXIMUM_API_TOKEN_RATE_LIMIT: u32 = 600;
+
+pub const MAXIMUM_WEBHOOKS: usize = 10;
+/// Sent by the "send test event" action, every webhook receives it.
+pub const WEBHOOK_TEST_EVENT: &str = "ping";
+
+/// Events a webhook can subscribe to, all about the subscribing account's own artworks and bounties.
+pub const WEBHOOK_EVENTS: [(&str, &str); 4] = [
+    ("artwork.created", "One of your artworks was published"),
+    (
+        "artwork.updated",
+        "Title, description or tags of your artwork changed",
+    ),
+    ("bounty.created", "You created a bounty"),
+    ("bounty.closed", "One of your bounties was closed"),
+];
+
+pub const MAXIMUM_PARTICIPANTS: usize = 10;
+pub const MAXIMUM_MESSAGE_LENGTH: usize = 4000;
+pub const MAXIMUM_ATTACHMENTS: usize = 4;
+pub const MAXIMUM_ATTACHMENT_SIZE: usize = 1024 * 1024 * 8;
+pub const MESSAGE_PAGE_SIZE: u32 = 50;
+pub const CONVERSATION_PAGE_SIZE: u32 = 50;
+/// Server-sent events stream of [`MessageEvent`] for the logged in account.
+pub const MESSAGE_EVENTS_PATH: &str = "/messages/events";
+pub const ATTACHMENT_PATH: &str = "/messages/attachments";
+
+/// Stages a commissioned piece goes through, in order.
+pub const REVISION_STAGES: [(&str, &str); 3] = [
+    ("sketch", "Sketch"),
+    ("lineart", "Lineart"),
+    ("final", "Final"),
+];
+pub const FINAL_STAGE: &str = "final";
+pub const MAXIMUM_REVISION_SIZE: usize = 1024 * 1024 * 32;
+/// Resumable uploads, `POST` with `Upload-Length` creates one at `{UPLOAD_PATH}/{upload_id}`,
+/// `HEAD` returns its `Upload-Offset` and `PATCH` appends a chunk at that offset.
+pub const UPLOAD_PATH: &str = "/uploads";
+pub const UPLOAD_OFFSET_HEADER: &str = "upload-offset";
+pub const UPLOAD_LENGTH_HEADER: &str = "upload-length";
+pub const UPLOAD_CHUNK_CONTENT_TYPE: &str = "application/offset+octet-stream";
+pub const MAXIMUM_UPLOAD_SIZE: u64 = 1024 * 1024 * 256;
+pub const MAXIMUM_UPLOAD_CHUNK_SIZE: usize = 1024 * 1024 * 2;
+/// What the uploader sends per `PATCH`, below the limit so proxies don't get in the way.
+pub const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+/// Unfinished uploads are dropped after this long without a chunk.
+pub const UPLOAD_EXPIRY: i64 = 1000 * 60 * 60 * 24;
+/// Picked images with a longer side are downscaled in the browser before they're uploaded.
+pub const MAXIMUM_UPLOAD_DIMENSION: u32 = 4096;
+pub const MAXIMUM_COMMENT_LENGTH: usize = 2000;
+pub const WATERMARK_KINDS: [(&str, &str); 2] = [("text", "Text"), ("image", "Uploaded PNG")];
+pub const WATERMARK_PLACEMENTS: [(&str, &str); 2] = [
+    ("tiled", "Tiled across the image"),
+    ("corner", "Bottom right corner"),
+];
+pub const MAXIMUM_WATERMARK_TEXT_LENGTH: usize = 40;
+/// Symbols the watermark font has glyphs for, on top of ascii letters and digits.
+pub const WATERMARK_TEXT_SYMBOLS: &str = " @.-_:/!?#";
+pub const MAXIMUM_WATERMARK_IMAGE_SIZE: usize = 1024 * 1024;
+pub const DEFAULT_WATERMARK_OPACITY: u8 = 35;
+pub const ARTWORK_PAGE_SIZE: u32 = 50;
+/// `{ARTWORK_MEDIA_PATH}/{artwork_id}/preview`, the tiny `/low` and `/w{width}` of
+/// [`ARTWORK_VARIANT_WIDTHS`] are public, `/original` only for those allowed.
+pub const ARTWORK_MEDIA_PATH: &str = "/media/artworks";
+/// Widths rendered next to the preview so tiles can load the smallest one that is sharp enough.
+pub const ARTWORK_VARIANT_WIDTHS: [u32; 4] = [320, 640, 960, 1440];
+/// Access checked revision images, `{REVISION_MEDIA_PATH}/{revision_id}/preview` or `/original`.
+pub const REVISION_MEDIA_PATH: &str = "/media/revisions";
+
+pub const RATING_SFW: &str = "sfw";
+pub const RATING_MATURE: &str = "mature";
+pub const RATING_EXPLICIT: &str = "explicit";
+/// Content ratings of artworks and bounties, mildest first.
+pub const RATINGS: [(&str, &str); 3] = [
+    (RATING_SFW, "Safe for work"),
+    (RATING_MATURE, "Mature"),
+    (RATING_EXPLICIT, "Explicit"),
+];
+pub const CONTENT_HIDE: &str = "hide";
+pub const CONTENT_BLUR: &str = "blur";
+pub const CONTENT_SHOW: &str = "show";
+/// What a viewer can choose to do with mature or explicit content.
+pub const CONTENT_PREFS: [(&str, &str); 3] = [
+    (CONTENT_HIDE, "Hide"),
+    (CONTENT_BLUR, "Blur until clicked"),
+    (CONTENT_SHOW, "Show"),
+];
+/// Used for logged out visitors and accounts that never changed their preferences.
+pub const DEFAULT_MATURE_PREF: &str = CONTENT_BLUR;
+pub const DEFAULT_EXPLICIT_PREF: &str = CONTENT_HIDE;
+pub const MAXIMUM_RATING_REASON_LENGTH: usize = 500;
+/// Largest CIE76 delta E between the searched color and a palette color that still matches.
+pub const COLOR_MATCH_DISTANCE: f32 = 20.0;
+/// Color search ranks this many of the newest artworks, the distance isn't indexable.
+pub const COLOR_SEARCH_SCAN: u32 = 2000;
+pub const RATING_AUDIT_PAGE_SIZE: u32 = 50;
+/// Browsers post batches of [`ClientLogEvent`] here, it's throttled like the auth routes.
+pub const CLIENT_LOG_PATH: &str = "/client_logs";
+pub const CLIENT_LOG_BATCH_SIZE: usize = 20;
+/// A page stops forwarding after this many events, a loop shouldn't flood the server logs.
+pub const CLIENT_LOG_PAGE_LIMIT: usize = 200;
+pub const CLIENT_LOG_FLUSH_DELAY_MS: u64 = 5000;
+pub const MAXIMUM_CLIENT_LOG_FIELD_LENGTH: usize = 4000;
+/// Largest body a batch of [`ClientLogEvent`]s is sent in, browsers refuse beacons over 64 KiB.
+pub const CLIENT_LOG_BEACON_SIZE: usize = 60 * 1024;
+pub const SITE_NAME: &str = "ArtBounty";
+/// Public page of an artwork, `{ARTWORK_PAGE_PATH}/{artwork_id}`.
+pub const ARTWORK_PAGE_PATH: &str = "/artwork";
+/// Public page of an account, `{PROFILE_PAGE_PATH}/{username}`, its Atom feed is at
+/// `{PROFILE_PAGE_PATH}/{username}/{FEED_FILE}`.
+pub const PROFILE_PAGE_PATH: &str = "/u";
+/// Public page of a bounty, `{BOUNTY_PAGE_PATH}/{bounty_id}`.
+pub const BOUNTY_PAGE_PATH: &str = "/bounty";
+/// Sitemap index, pointing at `{SITEMAP_CHUNK_PATH}/{kind}/{chunk}` of artworks, profiles and
+/// bounties.
+pub const SITEMAP_PATH: &str = "/sitemap.xml";
+pub const SITEMAP_CHUNK_PATH: &str = "/sitemap";
+/// Urls per sitemap chunk, well below the 50000 search engines accept.
+pub const SITEMAP_CHUNK_SIZE: u32 = 10_000;
+pub const FEED_FILE: &str = "feed.atom";
+/// Atom feed of artworks with a tag, `{TAG_FEED_PATH}/{tag}/{FEED_FILE}`.
+pub const TAG_FEED_PATH: &str = "/tag";
+/// Atom feed of the open bounties.
+pub const BOUNTY_FEED_PATH: &str = "/bounties/feed.atom";
+pub const FEED_SIZE: u32 = 50;
+/// oEmbed JSON of the artwork or profile page given as `?url=`.
+pub const OEMBED_PATH: &str = "/oembed";
+/// Link previews cut descriptions down to this many characters.
+pub const MAXIMUM_META_DESCRIPTION_LENGTH: usize = 200;
+
+/// Scopes a personal access token can be granted, with a description for the settings page.
+pub const API_SCOPES: [(&str, &str); 6] = [
+    ("account:read", "Read your account, including email"),
+    ("users:read", "Look up and search users"),
+    ("artworks:read", "List, view and search artworks"),
+    (
+        "artworks:write",
+        "Edit titles, descriptions and tags of your artworks",
+    ),
+    ("bounties:read", "List, view and search bounties"),
+    ("bounties:write", "Create and close your bounties"),
+];
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct AccInfo {
+    pub username: String,
+    pub email: String,
+    pub verified_email: bool,
+    pub role: String,
+    pub totp_enabled: bool,
+    pub recovery_codes_left: usize,
+    pub discord_user_id: Option<String>,
+    pub theme: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub enum LoginResult {
+    LoggedIn(AccInfo),
+    /// Account has 2FA, send the challenge back to [`login_totp`] with a code.
+    Totp {
+        challenge: String,
+    },
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub uri: String,
+    pub qr_svg: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct AdminAccInfo {
+    pub acc: AccInfo,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ApiTokenInfo {
+    pub token_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub rate_limit: u32,
+    pub last_used: Option<i64>,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct NewApiToken {
+    /// Plain token, shown once.
+    pub token: String,
+    pub info: ApiTokenInfo,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct WebhookInfo {
+    pub webhook_id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub failures: u32,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct NewWebhook {
+    /// Signing secret, shown once.
+    pub secret: String,
+    pub info: WebhookInfo,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct WebhookDeliveryInfo {
+    pub delivery_id: String,
+    pub event: String,
+    pub status: String,
+    pub attempts: u32,
+    pub last_response_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ParticipantInfo {
+    pub username: String,
+    pub last_read_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct MessageInfo {
+    pub message_id: String,
+    pub conversation_id: String,
+    pub author: String,
+    pub body: String,
+    /// Urls of the attached images, only served to participants.
+    pub attachments: Vec<String>,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ConversationInfo {
+    pub conversation_id: String,
+    pub participants: Vec<ParticipantInfo>,
+    pub muted: bool,
+    pub unread: u32,
+    pub last_message: Option<MessageInfo>,
+    pub last_message_at: i64,
+}
+
+/// Browser WARN or ERROR tracing event, or a panic with level `PANIC`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientLogEvent {
+    pub level: String,
+    pub message: String,
+    pub target: String,
+    /// Span chain from the outermost span in.
+    pub spans: String,
+    /// `file:line` of the event.
+    pub origin: String,
+    pub url: String,
+    pub user_agent: String,
+    pub build: String,
+}
+
+impl ClientLogEvent {
+    /// Every field cut down to [`MAXIMUM_CLIENT_LOG_FIELD_LENGTH`], the server drops the rest.
+    pub fn clipped(self) -> Self {
+        let clip = |value: String| clip_client_log_field(&value).to_string();
+        Self {
+            level: clip(self.level),
+            message: clip(self.message),
+            target: clip(self.target),
+            spans: clip(self.spans),
+            origin: clip(self.origin),
+            url: clip(self.url),
+            user_agent: clip(self.user_agent),
+            build: clip(self.build),
+        }
+    }
+}
+
+/// Cuts `value` down to the client log field limit on a char boundary.
+pub fn clip_client_log_field(value: &str) -> &str {
+    value
+        .char_indices()
+        .nth(MAXIMUM_CLIENT_LOG_FIELD_LENGTH)
+        .map(|(i, _)| &value[..i])
+        .unwrap_or(value)
+}
+
+/// Pushed over [`MESSAGE_EVENTS_PATH`] to every participant of the conversation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageEvent {
+    Message {
+        message: MessageInfo,
+        /// Whether the receiving participant muted the conversation.
+        muted: bool,
+    },
+    Read {
+        conversation_id: String,
+        username: String,
+        read_at: i64,
+    },
+    /// Events were dropped, refetch everything.
+    Resync,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct CommissionInfo {
+    pub commission_id: String,
+    pub bounty_id: Option<String>,
+    pub client: String,
+    pub artist: String,
+    pub title: String,
+    pub status: String,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct RevisionCommentInfo {
+    pub comment_id: String,
+    pub author: String,
+    pub body: String,
+    /// Pin position relative to the image size, 0.0-1.0 from the top left corner.
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct RevisionInfo {
+    pub revision_id: String,
+    pub version: u32,
+    pub stage: String,
+    pub note: String,
+    pub watermark: bool,
+    /// What the viewer should be shown, `None` while the preview is being rendered.
+    pub image_url: Option<String>,
+    /// Only set when the viewer may download the clean original.
+    pub original_url: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub status: String,
+    pub artwork_id: Option<String>,
+    pub comments: Vec<RevisionCommentInfo>,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct CommissionDetail {
+    pub commission: CommissionInfo,
+    /// Full history, oldest version first.
+    pub revisions: Vec<RevisionInfo>,
+    pub is_artist: bool,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct WatermarkInfo {
+    pub kind: String,
+    pub text: String,
+    pub placement: String,
+    pub opacity: u8,
+    pub has_image: bool,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct OwnArtworkInfo {
+    pub artwork_id: String,
+    pub title: String,
+    pub protected: bool,
+    pub rating: String,
+    pub preview_url: Option<String>,
+    /// Accounts allowed to download the original while protected.
+    pub buyers: Vec<String>,
+    pub created_at: i64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ArtworkInfo {
+    pub artwork_id: String,
+    pub author: String,
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub rating: String,
+    /// Viewer asked for content of this rating to be blurred until clicked.
+    pub blurred: bool,
+    pub preview_url: Option<String>,
+    pub low_url: Option<String>,
+    /// Hex colors, the dominant one first. Empty until the preview is rendered.
+    pub palette: Vec<String>,
+    /// Painted blurred while the preview loads.
+    pub blurhash: Option<String>,
+    /// Narrowest first, widths are those of the rendered file.
+    pub variants: Vec<ArtworkVariant>,
+    /// Viewer marked it as a favorite.
+    pub favorited: bool,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ArtworkVariant {
+    pub width: u32,
+    pub url: String,
+}
+
+/// OpenGraph and Twitter card of a page, the urls are absolute.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct PageMeta {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    /// `og:type`, `article` or `profile`.
+    pub kind: String,
+    pub author: Option<String>,
+    /// Left out for mature and explicit artworks so unfurled links don't show them.
+    pub image: Option<MetaImage>,
+    pub oembed_url: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct MetaImage {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ArtworkPageInfo {
+    pub artwork: ArtworkInfo,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub created_at: i64,
+    pub meta: PageMeta,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct BountyPageInfo {
+    pub bounty_id: String,
+    pub author: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// In cents.
+    pub reward: u64,
+    pub status: String,
+    pub rating: String,
+    /// Viewer asked for content of this rating to be blurred until clicked.
+    pub blurred: bool,
+    pub created_at: i64,
+    pub meta: PageMeta,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ProfileInfo {
+    pub username: String,
+    pub created_at: i64,
+    /// Newest first, without the ratings the viewer hides.
+    pub artworks: Vec<ArtworkInfo>,
+    pub meta: PageMeta,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct ContentPrefsInfo {
+    pub mature: String,
+    pub explicit: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct RatingAuditInfo {
+    /// `artwork` or `bounty`.
+    pub kind: String,
+    pub target_id: String,
+    pub moderator: String,
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+    pub created_at: i64,
+}
+
+/// Message meant for the user, without the server fn error prefix.
+pub fn error_message(err: &ServerFnError) -> String {
+    match err {
+        ServerFnError::ServerError(msg) => msg.clone(),
+        err => err.to_string(),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use std::net::SocketAddr;
+
+    use axum::{
+        extract::ConnectInfo,
+        http::{
+            HeaderMap, HeaderValue,
+            header::{COOKIE, SET_COOKIE, USER_AGENT},
+        },
+    };
+    use leptos::prelude::*;
+    use leptos_axum::ResponseOptions;
+    use tracing::error;
+
+    use crate::{
+        db::{
+            acc::DbAcc,
+            api_token::DbApiToken,
+            commission::DbCommission,
+            message::DbMessage,
+            rating_audit::DbRatingAudit,
+            revision_comment::DbRevisionComment,
+            webhook::DbWebhook,
+            webhook_delivery::{DbWebhookDelivery, STATUS_PENDING},
+        },
+        server::{
+            ServerError, ServerState,
+            auth::{self, ErrorAuth},
+        },
+    };
+
+    use super::{
+        ATTACHMENT_PATH, AccInfo, ApiTokenInfo, CommissionInfo, MessageInfo, RatingAuditInfo,
+        RevisionCommentInfo, WebhookDeliveryInfo, WebhookInfo,
+    };
+
+    pub fn now() -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    pub fn state() -> Result<ServerState, ServerFnError> {
+        use_context::<ServerState>()
+            .ok_or_else(|| ServerFnError::new("server state is missing from context"))
+    }
+
+    pub fn into_server_error(err: impl ServerError) -> ServerFnError {
+        if err.is_internal() {
+            error!("server error: {}", err);
+            return ServerFnError::new("internal server error");
+        }
+        ServerFnError::new(err)
+    }
+
+    pub async fn get_headers() -> Result<HeaderMap, ServerFnError> {
+        leptos_axum::extract::<HeaderMap>().await
+    }
+
+    pub async fn get_ip() -> String {
+        leptos_axum::extract::<ConnectInfo<SocketAddr>>()
+            .await
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn get_agent(headers: &HeaderMap) -> String {
+        headers
+            .get(USER_AGENT)
+            .and_then(|agent| agent.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    pub fn get_session_token(headers: &HeaderMap) -> Option<String> {
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find_map(auth::get_session_token)
+            .map(String::from)
+    }
+
+    pub fn set_cookie(cookie: String) -> Result<(), ServerFnError> {
+        let res = expect_context::<ResponseOptions>();
+        let cookie = HeaderValue::from_str(&cookie).map_err(ServerFnError::new)?;
+        res.append_header(SET_COOKIE, cookie);
+        Ok(())
+    }
+
+    /// Account of the current session, if any.
+    pub async fn get_session_acc(state: &ServerState) -> Result<Option<DbAcc>, ServerFnError> {
+        let headers = get_headers().await?;
+        let Some(token) = get_session_token(&headers) else {
+            return Ok(None);
+        };
+        auth::get_session_acc(state, &token, now())
+            .await
+            .map_err(into_server_error)
+    }
+
+    /// Like [`get_session_acc`] but fails when logged out.
+    pub async fn require_acc(state: &ServerState) -> Result<DbAcc, ServerFnError> {
+        get_session_acc(state)
+            .await?
+            .ok_or_else(|| into_server_error(ErrorAuth::Unauthorized))
+    }
+
+    pub async fn require_admin(state: &ServerState) -> Result<DbAcc, ServerFnError> {
+        let acc = require_acc(state).await?;
+        if acc.role != super::ADMIN_ROLE {
+            return Err(into_server_error(ErrorAuth::Forbidden));
+        }
+        Ok(acc)
+    }
+
+    impl From<DbAcc> for AccInfo {
+        fn from(acc: DbAcc) -> Self {
+            let totp = acc.totp.filter(|totp| totp.enabled);
+            Self {
+                username: acc.username,
+                email: acc.email,
+                verified_email: acc.verified_email,
+                role: acc.role,
+                totp_enabled: totp.is_some(),
+                recovery_codes_left: totp.map(|totp| totp.recovery_codes.len()).unwrap_or(0),
+                discord_user_id: acc.discord.map(|discord| discord.user_id),
+                theme: acc.theme,
+            }
+        }
+    }
+
+    impl From<DbWebhook> for WebhookInfo {
+        fn from(webhook: DbWebhook) -> Self {
+            Self {
+                webhook_id: webhook.webhook_id,
+                url: webhook.url,
+                events: webhook.events,
+                enabled: webhook.enabled,
+                failures: webhook.failures,
+                created_at: webhook.created_at,
+            }
+        }
+    }
+
+    impl From<DbWebhookDelivery> for WebhookDeliveryInfo {
+        fn from(delivery: DbWebhookDelivery) -> Self {
+            let last = delivery.attempts.last();
+            Self {
+                last_response_code: last.and_then(|attempt| attempt.response_code),
+                last_error: last.and_then(|attempt| attempt.error.clone()),
+                attempts: delivery.attempts.len() as u32,
+                next_attempt_at: if delivery.status == STATUS_PENDING {
+                    delivery.next_attempt_at
+                } else {
+                    0
+                },
+                delivery_id: delivery.delivery_id,
+                event: delivery.event,
+                status: delivery.status,
+                created_at: delivery.created_at,
+            }
+        }
+    }
+
+    impl From<DbMessage> for MessageInfo {
+        fn from(message: DbMessage) -> Self {
+            Self {
+                message_id: message.message_id,
+                conversation_id: message.conversation_id,
+                author: message.acc,
+                body: message.body,
+                attachments: message
+                    .attachments
+                    .into_iter()
+                    .map(|attachment_id| format!("{}/{}", ATTACHMENT_PATH, attachment_id))
+                    .collect(),
+                created_at: message.created_at,
+            }
+        }
+    }
+
+    impl From<DbRatingAudit> for RatingAuditInfo {
+        fn from(audit: DbRatingAudit) -> Self {
+            Self {
+                kind: audit.kind,
+                target_id: audit.target_id,
+                moderator: audit.moderator,
+                from: audit.from,
+                to: audit.to,
+                reason: audit.reason,
+                created_at: audit.created_at,
+            }
+        }
+    }
+
+    impl From<DbCommission> for CommissionInfo {
+        fn from(commission: DbCommission) -> Self {
+            Self {
+                commission_id: commission.commission_id,
+                bounty_id: commission.bounty_id,
+                client: commission.client,
+                artist: commission.artist,
+                title: commission.title,
+                status: commission.status,
+                created_at: commission.created_at,
+            }
+        }
+    }
+
+    impl From<DbRevisionComment> for RevisionCommentInfo {
+        fn from(comment: DbRevisionComment) -> Self {
+            Self {
+                comment_id: comment.comment_id,
+                author: comment.acc,
+                body: comment.body,
+                x: comment.x,
+                y: comment.y,
+                created_at: comment.created_at,
+            }
+        }
+    }
+
+    impl From<DbApiToken> for ApiTokenInfo {
+        fn from(token: DbApiToken) -> Self {
+            Self {
+                token_id: token.token_id,
+                name: token.name,
+                scopes: token.scopes,
+                rate_limit: token.rate_limit,
+                last_used: token.last_used,
+                created_at: token.created_at,
+            }
+        }
+    }
+}
+
+#[server(prefix = "/api", endpoint = "register", output = Rkyv)]
+pub async fn register(
+    username: String,
+    email: String,
+    password: String,
+) -> Result<(), ServerFnError> {
+    use crate::server::auth;
+    use ssr::*;
+
+    let state = state()?;
+    auth::register(&state, &username, &email, &password, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(())
+}
+
+#[server(prefix = "/api", endpoint = "login", output = Rkyv)]
+pub async fn login(email: String, password: String) -> Result<LoginResult, ServerFnError> {
+    use crate::{
+        server::auth::{self, LoginStep},
+        theme::theme_cookie,
+    };
+    use ssr::*;
+
+    let state = state()?;
+    let headers = get_headers().await?;
+    let ip = get_ip().await;
+    let agent = get_agent(&headers);
+    let step = auth::login(&state, &email, &password, &ip, &agent, now())
+        .await
+        .map_err(into_server_error)?;
+    match step {
+        LoginStep::Session(token, acc) => {
+            set_cookie(auth::session_cookie(&state, &token))?;
+            if let Some(theme) = &acc.theme {
+                set_cookie(theme_cookie(theme))?;
+            }
+            Ok(LoginResult::LoggedIn((*acc).into()))
+        }
+        LoginStep::Totp(challenge) => Ok(LoginResult::Totp { challenge }),
+    }
+}
+
+#[server(prefix = "/api", endpoint = "login_totp", output = Rkyv)]
+pub async fn login_totp(challenge: String, code: String) -> Result<AccInfo, ServerFnError> {
+    use crate::{server::auth, theme::theme_cookie};
+    use ssr::*;
+
+    let state = state()?;
+    let headers = get_headers().await?;
+    let ip = get_ip().await;
+    let agent = get_agent(&headers);
+    let (token, acc) = auth::login_totp(&state, &challenge, &code, &ip, &agent, now())
+        .await
+        .map_err(into_server_error)?;
+    set_cookie(auth::session_cookie(&state, &token))?;
+    if let Some(theme) = &acc.theme {
+        set_cookie(theme_cookie(theme))?;
+    }
+    Ok(acc.into())
+}
+
+#[server(prefix = "/api", endpoint = "logout", output = Rkyv)]
+pub async fn logout() -> Result<(), ServerFnError> {
+    use crate::server::auth;
+    use ssr::*;
+
+    let state = state()?;
+    let headers = get_headers().await?;
+    if let Some(token) = get_session_token(&headers) {
+        auth::logout(&state, &token)
+            .await
+            .map_err(into_server_error)?;
+    }
+    set_cookie(auth::clear_session_cookie())?;
+    Ok(())
+}
+
+/// Remembers the picked locale in a cookie, pages rendered after it use it.
+#[server(prefix = "/api", endpoint = "locale_set", output = Rkyv)]
+pub async fn locale_set(locale: String) -> Result<(), ServerFnError> {
+    use crate::i18n::locale_cookie;
+    use ssr::*;
+
+    set_cookie(locale_cookie(&locale))
+}
+
+/// Remembers the picked theme in a cookie, and on the account when logged in so other devices
+/// get it on their next login.
+#[server(prefix = "/api", endpoint = "theme_set", output = Rkyv)]
+pub async fn theme_set(theme: String) -> Result<(), ServerFnError> {
+    use crate::{db::acc, theme};
+    use ssr::*;
+
+    let theme = theme::supported(&theme);
+    set_cookie(theme::theme_cookie(theme))?;
+    let state = state()?;
+    if let Some(acc) = get_session_acc(&state).await? {
+        acc::set_theme(&state.db, &acc.username, theme, now())
+            .await
+            .map_err(into_server_error)?;
+    }
+    Ok(())
+}
+
+#[server(prefix = "/api", endpoint = "get_acc", output = Rkyv)]
+pub async fn get_acc() -> Result<Option<AccInfo>, ServerFnError> {
+    use ssr::*;
+
+    let state = state()?;
+    let acc = get_session_acc(&state).await?;
+    Ok(acc.map(AccInfo::from))
+}
+
+#[server(prefix = "/api", endpoint = "verify_email", output = Rkyv)]
+pub async fn verify_email(token: String) -> Result<AccInfo, ServerFnError> {
+    use crate::server::auth;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = auth::verify_email(&state, &token, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(acc.into())
+}
+
+#[server(prefix = "/api", endpoint = "resend_verification", output = Rkyv)]
+pub async fn resend_verification(email: String) -> Result<(), ServerFnError> {
+    use crate::server::auth;
+    use ssr::*;
+
+    let state = state()?;
+    auth::resend_verification(&state, &email, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "request_password_reset", output = Rkyv)]
+pub async fn request_password_reset(email: String) -> Result<(), ServerFnError> {
+    use crate::server::auth;
+    use ssr::*;
+
+    let state = state()?;
+    auth::request_password_reset(&state, &email, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "reset_password", output = Rkyv)]
+pub async fn reset_password(token: String, password: String) -> Result<(), ServerFnError> {
+    use crate::server::auth;
+    use ssr::*;
+
+    let state = state()?;
+    auth::reset_password(&state, &token, &password, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "totp_begin", output = Rkyv)]
+pub async fn totp_begin() -> Result<TotpEnrollment, ServerFnError> {
+    use crate::server::two_factor;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let enrollment = two_factor::begin(&state, &acc, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(TotpEnrollment {
+        secret: enrollment.secret,
+        uri: enrollment.uri,
+        qr_svg: enrollment.qr_svg,
+    })
+}
+
+/// Returns the recovery codes, they are not shown again.
+#[server(prefix = "/api", endpoint = "totp_enable", output = Rkyv)]
+pub async fn totp_enable(code: String) -> Result<Vec<String>, ServerFnError> {
+    use crate::server::two_factor;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    two_factor::enable(&state, &acc, &code, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "totp_disable", output = Rkyv)]
+pub async fn totp_disable(code: String) -> Result<(), ServerFnError> {
+    use crate::server::two_factor;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    two_factor::disable(&state, acc, &code, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "totp_recovery_codes", output = Rkyv)]
+pub async fn totp_recovery_codes(code: String) -> Result<Vec<String>, ServerFnError> {
+    use crate::server::two_factor;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    two_factor::regenerate_recovery_codes(&state, acc, &code, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "admin_get_accs", output = Rkyv)]
+pub async fn admin_get_accs(page: u32) -> Result<Vec<AdminAccInfo>, ServerFnError> {
+    use crate::db::acc;
+    use ssr::*;
+
+    const PAGE_SIZE: u32 = 50;
+
+    let state = state()?;
+    require_admin(&state).await?;
+    let accs = acc::get_page(&state.db, PAGE_SIZE, page * PAGE_SIZE)
+        .await
+        .map_err(into_server_error)?;
+    Ok(accs
+        .into_iter()
+        .map(|acc| AdminAccInfo {
+            created_at: acc.created_at,
+            acc: acc.into(),
+        })
+        .collect())
+}
+
+/// Code for the bot's `/link` command, the alternative to linking through discord oauth.
+#[server(prefix = "/api", endpoint = "discord_link_code", output = Rkyv)]
+pub async fn discord_link_code() -> Result<String, ServerFnError> {
+    use crate::server::discord;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    discord::new_link_code(&state.db, &acc, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "discord_unlink", output = Rkyv)]
+pub async fn discord_unlink() -> Result<(), ServerFnError> {
+    use crate::server::discord;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    discord::unlink(&state.db, &acc.username, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "api_tokens", output = Rkyv)]
+pub async fn api_tokens() -> Result<Vec<ApiTokenInfo>, ServerFnError> {
+    use crate::db::api_token;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let tokens = api_token::get_all_for_acc(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)?;
+    Ok(tokens.into_iter().map(ApiTokenInfo::from).collect())
+}
+
+/// `scopes` are space separated, see [`API_SCOPES`].
+#[server(prefix = "/api", endpoint = "api_token_create", output = Rkyv)]
+pub async fn api_token_create(
+    name: String,
+    scopes: String,
+    rate_limit: u32,
+) -> Result<NewApiToken, ServerFnError> {
+    use crate::server::api_token;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let (token, info) = api_token::create(&state.db, &acc, &name, &scopes, rate_limit, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(NewApiToken {
+        token,
+        info: info.into(),
+    })
+}
+
+#[server(prefix = "/api", endpoint = "api_token_revoke", output = Rkyv)]
+pub async fn api_token_revoke(token_id: String) -> Result<(), ServerFnError> {
+    use crate::server::api_token;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    api_token::revoke(&state.db, &acc, &token_id)
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "webhooks", output = Rkyv)]
+pub async fn webhooks() -> Result<Vec<WebhookInfo>, ServerFnError> {
+    use crate::db::webhook;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let webhooks = webhook::get_all_for_acc(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)?;
+    Ok(webhooks.into_iter().map(WebhookInfo::from).collect())
+}
+
+/// `events` are space separated, see [`WEBHOOK_EVENTS`].
+#[server(prefix = "/api", endpoint = "webhook_create", output = Rkyv)]
+pub async fn webhook_create(url: String, events: String) -> Result<NewWebhook, ServerFnError> {
+    use crate::server::webhook;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let webhook = webhook::create(&state.db, &acc, &url, &events, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(NewWebhook {
+        secret: webhook.secret.clone(),
+        info: webhook.into(),
+    })
+}
+
+#[server(prefix = "/api", endpoint = "webhook_delete", output = Rkyv)]
+pub async fn webhook_delete(webhook_id: String) -> Result<(), ServerFnError> {
+    use crate::server::webhook;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    webhook::remove(&state.db, &acc, &webhook_id)
+        .await
+        .map_err(into_server_error)
+}
+
+/// Re-enables a webhook that was disabled after repeated failures.
+#[server(prefix = "/api", endpoint = "webhook_enable", output = Rkyv)]
+pub async fn webhook_enable(webhook_id: String) -> Result<(), ServerFnError> {
+    use crate::server::webhook;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    webhook::enable(&state.db, &acc, &webhook_id, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "webhook_test", output = Rkyv)]
+pub async fn webhook_test(webhook_id: String) -> Result<(), ServerFnError> {
+    use crate::server::webhook;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    webhook::send_test(&state.db, &acc, &webhook_id, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "webhook_deliveries", output = Rkyv)]
+pub async fn webhook_deliveries(
+    webhook_id: String,
+) -> Result<Vec<WebhookDeliveryInfo>, ServerFnError> {
+    use crate::server::webhook;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let deliveries = webhook::recent_deliveries(&state.db, &acc, &webhook_id)
+        .await
+        .map_err(into_server_error)?;
+    Ok(deliveries
+        .into_iter()
+        .map(WebhookDeliveryInfo::from)
+        .collect())
+}
+
+#[server(prefix = "/api", endpoint = "conversations", output = Rkyv)]
+pub async fn conversations() -> Result<Vec<ConversationInfo>, ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    message::conversations(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)
+}
+
+/// `participants` are usernames separated by spaces or commas, an existing conversation with exactly them is reused.
+#[server(prefix = "/api", endpoint = "conversation_start", output = Rkyv)]
+pub async fn conversation_start(
+    participants: String,
+    body: String,
+) -> Result<String, ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let message = message::start(&state, &acc.username, &participants, &body, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(message.conversation_id)
+}
+
+#[server(prefix = "/api", endpoint = "messages", output = Rkyv)]
+pub async fn messages(
+    conversation_id: String,
+    before: Option<i64>,
+) -> Result<Vec<MessageInfo>, ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let messages = message::messages(&state.db, &acc.username, &conversation_id, before)
+        .await
+        .map_err(into_server_error)?;
+    Ok(messages.into_iter().map(MessageInfo::from).collect())
+}
+
+/// `attachments` are space separated ids from [`message_attachment_upload`].
+#[server(prefix = "/api", endpoint = "message_send", output = Rkyv)]
+pub async fn message_send(
+    conversation_id: String,
+    body: String,
+    attachments: String,
+) -> Result<MessageInfo, ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let attachments = attachments
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let message = message::send(
+        &state,
+        &acc.username,
+        &conversation_id,
+        &body,
+        &attachments,
+        now(),
+    )
+    .await
+    .map_err(into_server_error)?;
+    Ok(message.into())
+}
+
+/// Uploads an image to attach to the next message, returns the attachment id.
+#[server(prefix = "/api", endpoint = "message_attachment_upload", input = Rkyv, output = Rkyv)]
+pub async fn message_attachment_upload(
+    conversation_id: String,
+    data: Vec<u8>,
+) -> Result<String, ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let attachment =
+        message::save_attachment(&state, &acc.username, &conversation_id, &data, now())
+            .await
+            .map_err(into_server_error)?;
+    Ok(attachment.attachment_id)
+}
+
+#[server(prefix = "/api", endpoint = "conversation_read", output = Rkyv)]
+pub async fn conversation_read(conversation_id: String) -> Result<(), ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    message::read(&state, &acc.username, &conversation_id, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "conversation_mute", output = Rkyv)]
+pub async fn conversation_mute(conversation_id: String, muted: bool) -> Result<(), ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    message::mute(&state.db, &acc.username, &conversation_id, muted, now())
+        .await
+        .map_err(into_server_error)
+}
+
+/// Unread messages outside of muted conversations, zero when logged out.
+#[server(prefix = "/api", endpoint = "unread_count", output = Rkyv)]
+pub async fn unread_count() -> Result<u32, ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let Some(acc) = get_session_acc(&state).await? else {
+        return Ok(0);
+    };
+    message::unread_total(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "blocked_users", output = Rkyv)]
+pub async fn blocked_users() -> Result<Vec<String>, ServerFnError> {
+    use crate::db::block;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let blocks = block::get_all_for_acc(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)?;
+    Ok(blocks.into_iter().map(|block| block.blocked).collect())
+}
+
+#[server(prefix = "/api", endpoint = "user_block", output = Rkyv)]
+pub async fn user_block(username: String) -> Result<(), ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    message::block(&state.db, &acc.username, &username, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "user_unblock", output = Rkyv)]
+pub async fn user_unblock(username: String) -> Result<(), ServerFnError> {
+    use crate::server::message;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    message::unblock(&state.db, &acc.username, &username)
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "commissions", output = Rkyv)]
+pub async fn commissions() -> Result<Vec<CommissionInfo>, ServerFnError> {
+    use crate::db::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let commissions = commission::get_all_for_acc(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)?;
+    Ok(commissions.into_iter().map(CommissionInfo::from).collect())
+}
+
+/// Orders work from `artist`, `bounty_id` may be empty or one of your own bounties.
+#[server(prefix = "/api", endpoint = "commission_create", output = Rkyv)]
+pub async fn commission_create(
+    artist: String,
+    title: String,
+    bounty_id: String,
+) -> Result<String, ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let bounty_id = Some(bounty_id.trim()).filter(|bounty_id| !bounty_id.is_empty());
+    let commission =
+        commission::create(&state.db, &acc.username, &artist, &title, bounty_id, now())
+            .await
+            .map_err(into_server_error)?;
+    Ok(commission.commission_id)
+}
+
+#[server(prefix = "/api", endpoint = "commission", output = Rkyv)]
+pub async fn commission(commission_id: String) -> Result<CommissionDetail, ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    commission::detail(&state.db, &acc.username, &commission_id)
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "commission_cancel", output = Rkyv)]
+pub async fn commission_cancel(commission_id: String) -> Result<(), ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    commission::cancel(&state.db, &acc.username, &commission_id, now())
+        .await
+        .map_err(into_server_error)
+}
+
+/// Uploads the next version of a deliverable, only the artist may do so.
+#[server(prefix = "/api", endpoint = "revision_upload", input = Rkyv, output = Rkyv)]
+pub async fn revision_upload(
+    commission_id: String,
+    stage: String,
+    note: String,
+    watermark: bool,
+    data: Vec<u8>,
+) -> Result<String, ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let revision = commission::upload_revision(
+        &state,
+        &acc.username,
+        &commission_id,
+        &stage,
+        &note,
+        watermark,
+        &data,
+        now(),
+    )
+    .await
+    .map_err(into_server_error)?;
+    Ok(revision.revision_id)
+}
+
+/// Approves a revision or requests changes to it, only the client may do so.
+#[server(prefix = "/api", endpoint = "revision_review", output = Rkyv)]
+pub async fn revision_review(
+    revision_id: String,
+    approve: bool,
+    comment: String,
+) -> Result<(), ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    commission::review(
+        &state.db,
+        &acc.username,
+        &revision_id,
+        approve,
+        &comment,
+        now(),
+    )
+    .await
+    .map_err(into_server_error)?;
+    Ok(())
+}
+
+/// `x` and `y` pin the comment to a point of the image, relative to its size.
+#[server(prefix = "/api", endpoint = "revision_comment", output = Rkyv)]
+pub async fn revision_comment(
+    revision_id: String,
+    body: String,
+    x: Option<f32>,
+    y: Option<f32>,
+) -> Result<RevisionCommentInfo, ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let comment = commission::comment(&state.db, &acc.username, &revision_id, &body, x, y, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(comment.into())
+}
+
+/// Publishes a complete resumable upload to the gallery, returns the artwork id.
+#[server(prefix = "/api", endpoint = "upload_finalize", output = Rkyv)]
+pub async fn upload_finalize(
+    upload_id: String,
+    title: String,
+    description: String,
+    tags: String,
+    rating: String,
+    sha256: String,
+) -> Result<String, ServerFnError> {
+    use crate::server::upload;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let tags = tags
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let artwork = upload::finalize(
+        &state,
+        &acc.username,
+        &upload_id,
+        &title,
+        &description,
+        &tags,
+        &rating,
+        &sha256,
+        now(),
+    )
+    .await
+    .map_err(into_server_error)?;
+    Ok(artwork.artwork_id)
+}
+
+/// Own artwork with the same picked file, checked before uploading it again.
+#[server(prefix = "/api", endpoint = "upload_duplicate", output = Rkyv)]
+pub async fn upload_duplicate(sha256: String) -> Result<Option<String>, ServerFnError> {
+    use crate::server::upload;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    upload::duplicate(&state.db, &acc.username, &sha256)
+        .await
+        .map_err(into_server_error)
+}
+
+/// Publishes an approved final revision to the artist's gallery, returns the artwork id.
+#[server(prefix = "/api", endpoint = "revision_publish", output = Rkyv)]
+pub async fn revision_publish(
+    revision_id: String,
+    title: String,
+    description: String,
+    tags: String,
+    rating: String,
+) -> Result<String, ServerFnError> {
+    use crate::server::commission;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let tags = tags
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let artwork = commission::publish(
+        &state.db,
+        &acc.username,
+        &revision_id,
+        &title,
+        &description,
+        &tags,
+        &rating,
+        now(),
+    )
+    .await
+    .map_err(into_server_error)?;
+    Ok(artwork.artwork_id)
+}
+
+#[server(prefix = "/api", endpoint = "watermark", output = Rkyv)]
+pub async fn watermark() -> Result<WatermarkInfo, ServerFnError> {
+    use crate::server::watermark;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let settings = watermark::get(&state.db, &acc.username)
+        .await
+        .map_err(into_server_error)?;
+    let has_image = tokio::fs::try_exists(watermark::image_path(&state.settings, &acc.username))
+        .await
+        .unwrap_or(false);
+    Ok(WatermarkInfo {
+        kind: settings.kind,
+        text: settings.text,
+        placement: settings.placement,
+        opacity: settings.opacity,
+        has_image,
+    })
+}
+
+#[server(prefix = "/api", endpoint = "watermark_set", output = Rkyv)]
+pub async fn watermark_set(
+    kind: String,
+    text: String,
+    placement: String,
+    opacity: u8,
+) -> Result<(), ServerFnError> {
+    use crate::server::watermark;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    watermark::set(
+        &state,
+        &acc.username,
+        &kind,
+        &text,
+        &placement,
+        opacity,
+        now(),
+    )
+    .await
+    .map_err(into_server_error)?;
+    Ok(())
+}
+
+#[server(prefix = "/api", endpoint = "watermark_image_upload", input = Rkyv, output = Rkyv)]
+pub async fn watermark_image_upload(data: Vec<u8>) -> Result<(), ServerFnError> {
+    use crate::server::watermark;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    watermark::save_image(&state, &acc.username, &data, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "own_artworks", output = Rkyv)]
+pub async fn own_artworks(page: u32) -> Result<Vec<OwnArtworkInfo>, ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    artwork::own_artworks(&state.db, &acc.username, page)
+        .await
+        .map_err(into_server_error)
+}
+
+/// Newest artworks, without the ratings the viewer hides. With a `#rrggbb` `color` only those
+/// with a similar palette color, closest first.
+#[server(prefix = "/api", endpoint = "artworks", output = Rkyv)]
+pub async fn artworks(page: u32, color: Option<String>) -> Result<Vec<ArtworkInfo>, ServerFnError> {
+    use crate::server::{artwork, palette};
+    use ssr::*;
+
+    let state = state()?;
+    let acc = get_session_acc(&state).await?;
+    let mut artworks = match color.filter(|color| !color.is_empty()) {
+        Some(color) => palette::search(&state.db, acc.as_ref(), &color, page)
+            .await
+            .map_err(into_server_error)?,
+        None => artwork::gallery(&state.db, acc.as_ref(), page)
+            .await
+            .map_err(into_server_error)?,
+    };
+    artwork::mark_favorites(&state.db, acc.as_ref(), &mut artworks)
+        .await
+        .map_err(into_server_error)?;
+    Ok(artworks)
+}
+
+/// Favorites the artwork or takes the favorite back, returns whether it's now a favorite.
+#[server(prefix = "/api", endpoint = "artwork_favorite", output = Rkyv)]
+pub async fn artwork_favorite(artwork_id: String) -> Result<bool, ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    artwork::toggle_favorite(&state.db, &acc.username, &artwork_id, now())
+        .await
+        .map_err(into_server_error)
+}
+
+/// Artwork with its link preview, `None` when it doesn't exist or the viewer hides its rating.
+#[server(prefix = "/api", endpoint = "artwork_page", output = Rkyv)]
+pub async fn artwork_page(artwork_id: String) -> Result<Option<ArtworkPageInfo>, ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = get_session_acc(&state).await?;
+    artwork::page(&state.db, &state.settings, acc.as_ref(), &artwork_id)
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "profile", output = Rkyv)]
+pub async fn profile(username: String) -> Result<Option<ProfileInfo>, ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = get_session_acc(&state).await?;
+    artwork::profile(&state.db, &state.settings, acc.as_ref(), &username)
+        .await
+        .map_err(into_server_error)
+}
+
+/// Bounty with its link preview, `None` when it doesn't exist or the viewer hides its rating.
+#[server(prefix = "/api", endpoint = "bounty_page", output = Rkyv)]
+pub async fn bounty_page(bounty_id: String) -> Result<Option<BountyPageInfo>, ServerFnError> {
+    use crate::server::bounty;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = get_session_acc(&state).await?;
+    bounty::page(&state.db, &state.settings, acc.as_ref(), &bounty_id)
+        .await
+        .map_err(into_server_error)
+}
+
+/// Preferences of the logged in account, or the defaults when logged out.
+#[server(prefix = "/api", endpoint = "content_prefs", output = Rkyv)]
+pub async fn content_prefs() -> Result<ContentPrefsInfo, ServerFnError> {
+    use crate::server::rating;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = get_session_acc(&state).await?;
+    let prefs = rating::prefs_for(acc.as_ref());
+    Ok(ContentPrefsInfo {
+        mature: prefs.mature,
+        explicit: prefs.explicit,
+    })
+}
+
+#[server(prefix = "/api", endpoint = "content_prefs_set", output = Rkyv)]
+pub async fn content_prefs_set(mature: String, explicit: String) -> Result<(), ServerFnError> {
+    use crate::server::rating;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    rating::set_prefs(&state.db, &acc.username, &mature, &explicit, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(())
+}
+
+/// Rates an artwork or bounty, `kind` is `artwork` or `bounty`. Admins changing someone else's
+/// rating must give a `reason`, it ends up in the audit log.
+#[server(prefix = "/api", endpoint = "rating_set", output = Rkyv)]
+pub async fn rating_set(
+    kind: String,
+    target_id: String,
+    rating: String,
+    reason: String,
+) -> Result<(), ServerFnError> {
+    use crate::{
+        db::rating_audit::{KIND_ARTWORK, KIND_BOUNTY},
+        server::rating,
+    };
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    let target_id = target_id.trim();
+    let result = match kind.as_str() {
+        KIND_ARTWORK => {
+            rating::set_artwork_rating(&state.db, &acc, target_id, &rating, &reason, now())
+                .await
+                .map(|_| ())
+        }
+        KIND_BOUNTY => {
+            rating::set_bounty_rating(&state.db, &acc, target_id, &rating, &reason, now())
+                .await
+                .map(|_| ())
+        }
+        _ => Err(rating::ErrorRating::NotFound),
+    };
+    result.map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "rating_audits", output = Rkyv)]
+pub async fn rating_audits(page: u32) -> Result<Vec<RatingAuditInfo>, ServerFnError> {
+    use crate::server::rating;
+    use ssr::*;
+
+    let state = state()?;
+    require_admin(&state).await?;
+    let audits = rating::audits(&state.db, page)
+        .await
+        .map_err(into_server_error)?;
+    Ok(audits.into_iter().map(RatingAuditInfo::from).collect())
+}
+
+#[server(prefix = "/api", endpoint = "artwork_protect", output = Rkyv)]
+pub async fn artwork_protect(artwork_id: String, protected: bool) -> Result<(), ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    artwork::set_protected(&state.db, &acc.username, &artwork_id, protected, now())
+        .await
+        .map_err(into_server_error)?;
+    Ok(())
+}
+
+#[server(prefix = "/api", endpoint = "artwork_access_grant", output = Rkyv)]
+pub async fn artwork_access_grant(
+    artwork_id: String,
+    username: String,
+) -> Result<(), ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    artwork::grant_access(&state.db, &acc.username, &artwork_id, &username, now())
+        .await
+        .map_err(into_server_error)
+}
+
+#[server(prefix = "/api", endpoint = "artwork_access_revoke", output = Rkyv)]
+pub async fn artwork_access_revoke(
+    artwork_id: String,
+    username: String,
+) -> Result<(), ServerFnError> {
+    use crate::server::artwork;
+    use ssr::*;
+
+    let state = state()?;
+    let acc = require_acc(&state).await?;
+    artwork::revoke_access(&state.db, &acc.username, &artwork_id, &username)
+        .await
+        .map_err(into_server_error)
+}
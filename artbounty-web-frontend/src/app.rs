@@ -1,14 +1,24 @@
-use components::gallery::Img;
+use components::{
+    gallery::Img, message_events::MessageEvents, nav::Nav, shortcuts::Shortcuts, uploader::Uploads,
+};
 use indextree::Arena;
 use indextree::NodeId;
 use leptos::prelude::*;
+use leptos_meta::provide_meta_context;
 use leptos_router::components::*;
-use leptos_router::path;
-use page::home;
+use leptos_router::{SsrMode, path};
+use page::{
+    admin, artwork, bounty, commissions, home, login, messages, profile, register, reset_password,
+    settings, upload, verify_email,
+};
 use reactive_stores::Store;
 use tracing::trace;
 
-use crate::toolbox::prelude::*;
+use crate::{
+    i18n::{self, I18n},
+    theme::{self, Theme},
+    toolbox::prelude::*,
+};
 
 pub mod components;
 pub mod page;
@@ -31,10 +41,19 @@ pub struct GlobalState {
 
 #[component]
 pub fn App() -> impl IntoView {
+    provide_meta_context();
     provide_context(GlobalState::default());
+    provide_context(MessageEvents::new());
+    provide_context(Uploads::new());
+    provide_context(I18n::new(i18n::request_locale()));
+    let theme = Theme::new(theme::request_theme());
+    provide_context(theme);
+    theme.apply();
+    let shortcuts = Shortcuts::new();
+    provide_context(shortcuts);
+    Effect::new(move || shortcuts.listen());
 
-    resize_observer::init_global_state();
-    //intersection_observer::init_global_state();
+    observer::init_global_state();
 
     // Effect::new(move || {
     //     use indextree::Arena;
@@ -59,17 +78,23 @@ pub fn App() -> impl IntoView {
         <Router>
             <Routes fallback=|| "not found">
                 <Route path=path!("") view=home::Page />
+                <Route path=path!("register") view=register::Page />
+                <Route path=path!("login") view=login::Page />
+                <Route path=path!("verify_email") view=verify_email::Page />
+                <Route path=path!("reset_password") view=reset_password::Page />
+                <Route path=path!("settings") view=settings::Page />
+                <Route path=path!("messages") view=messages::Page />
+                <Route path=path!("upload") view=upload::Page />
+                <Route path=path!("commissions") view=commissions::Page />
+                <Route path=path!("commissions/:id") view=commissions::Detail />
+                <Route path=path!("admin") view=admin::Page />
+                <Route path=path!("artwork/:id") view=artwork::Page ssr=SsrMode::Async />
+                <Route path=path!("u/:handle") view=profile::Page ssr=SsrMode::Async />
+                <Route path=path!("bounty/:id") view=bounty::Page ssr=SsrMode::Async />
                 <Route
                     path=path!("two")
                     view=move || {
-                        view! {
-                            <nav class="text-gray-200 pb-1">
-                                <a href="/" class="font-black text-xl">
-                                    "ArtBounty"
-                                </a>
-                                <a href="/two">"two"</a>
-                            </nav>
-                        }
+                        view! { <Nav /> }
                     }
                 />
             </Routes>
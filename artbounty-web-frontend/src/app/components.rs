@@ -2,15 +2,32 @@ pub mod gallery {
     use leptos::{
         html::{self, Div, Main, div},
         prelude::*,
+        task::spawn_local,
     };
     use std::default::Default;
     use std::fmt::Debug;
-    use tracing::trace;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use tracing::{trace, warn};
     use web_sys::HtmlDivElement;
 
-    use crate::toolbox::{prelude::*, random::random_u64};
+    use base64::{Engine, engine::general_purpose::STANDARD};
+
+    use crate::{
+        api::{ArtworkInfo, ArtworkVariant, artwork_favorite},
+        t,
+        toolbox::{prelude::*, random::random_u64},
+    };
+
+    use super::{
+        shortcuts::{GALLERY_SCOPE, VIEWER_SCOPE, use_shortcuts},
+        uploader::UploadEntry,
+    };
 
     pub const NEW_IMG_HEIGHT: u32 = 250;
+    /// Background of artworks whose palette isn't extracted yet.
+    pub const PLACEHOLDER_COLOR: &str = "#1f2937";
+    /// BlurHashes are decoded this small and stretched by the browser, it's all low frequencies.
+    pub const PLACEHOLDER_SIZE: u32 = 16;
 
     #[component]
     pub fn Gallery(imgs: RwSignal<Vec<Img>>) -> impl IntoView {
@@ -43,6 +60,42 @@ pub mod gallery {
             });
         });
 
+        // j/k walk the tiles, Enter opens the selected one over the page
+        let selected = RwSignal::new(None::<usize>);
+        let viewing = RwSignal::new(false);
+        let selected_img = Signal::derive(move || {
+            let index = selected.get()?;
+            imgs.with(|imgs| imgs.get(index).cloned())
+        });
+        let step = move |by: isize| {
+            let len = imgs.with_untracked(Vec::len) as isize;
+            if len == 0 {
+                return;
+            }
+            selected.update(|selected| {
+                *selected = Some(match *selected {
+                    Some(index) => (index as isize + by).clamp(0, len - 1) as usize,
+                    None => 0,
+                })
+            });
+        };
+        let shortcuts = use_shortcuts();
+        shortcuts.bind(GALLERY_SCOPE, "j", "shortcut-next", move || step(1));
+        shortcuts.bind(GALLERY_SCOPE, "k", "shortcut-previous", move || step(-1));
+        shortcuts.bind(GALLERY_SCOPE, "f", "shortcut-favorite", move || {
+            if let Some(img) = selected_img.get_untracked() {
+                spawn_local(toggle_favorite(img));
+            }
+        });
+        shortcuts.bind(GALLERY_SCOPE, "Enter", "shortcut-open", move || {
+            if selected.get_untracked().is_some() {
+                viewing.set(true);
+            }
+        });
+        shortcuts.bind(GALLERY_SCOPE, "Escape", "shortcut-clear", move || {
+            selected.set(None)
+        });
+
         top_bar_ref.observe_intersection_with_options(
             move |entry, observer| {
                 trace!("wowza, its intersecting");
@@ -75,19 +128,74 @@ pub mod gallery {
                     each=get_imgs
                     key=|img| img.1.id
                     children=move |(i, img)| {
-                        view! { <GalleryImg index=i img /> }
+                        let selected = Signal::derive(move || selected.get() == Some(i));
+                        view! { <GalleryImg index=i img selected /> }
                     }
                 />
+                <Show when=move || viewing.get()>
+                    <Viewer img=selected_img open=viewing />
+                </Show>
             </div>
         };
 
         a
     }
 
+    /// Favorites the artwork of `img` or takes it back, tiles of unfinished uploads are skipped.
+    async fn toggle_favorite(img: Img) {
+        let Some(artwork_id) = img.artwork_id.clone() else {
+            return;
+        };
+        match artwork_favorite(artwork_id).await {
+            Ok(favorited) => img.favorited.set(favorited),
+            Err(err) => warn!("failed to favorite: {}", err),
+        }
+    }
+
+    /// Selected artwork over the whole page, j/k keep moving through the gallery under it.
     #[component]
-    pub fn GalleryImg(img: Img, index: usize) -> impl IntoView {
+    fn Viewer(img: Signal<Option<Img>>, open: RwSignal<bool>) -> impl IntoView {
+        use_shortcuts().bind(
+            VIEWER_SCOPE,
+            "Escape",
+            "shortcut-close-artwork",
+            move || open.set(false),
+        );
+        let src = move || {
+            img.get().and_then(|img| {
+                img.variants
+                    .last()
+                    .map(|variant| variant.url.clone())
+                    .or(img.src)
+            })
+        };
+        view! {
+            <div
+                class="fixed inset-0 z-40 grid place-items-center bg-black/80"
+                on:click=move |_| open.set(false)
+            >
+                <img class="max-w-full max-h-full object-contain" src=src />
+            </div>
+        }
+    }
+
+    #[component]
+    pub fn GalleryImg(
+        img: Img,
+        index: usize,
+        #[prop(into, optional)] selected: Signal<bool>,
+    ) -> impl IntoView {
         let gallery_img_ref = NodeRef::<Div>::new();
 
+        Effect::new(move || {
+            if !selected.get() {
+                return;
+            }
+            if let Some(gallery_img_ref) = gallery_img_ref.get_untracked() {
+                gallery_img_ref.scroll_into_view();
+            }
+        });
+
         gallery_img_ref.on_load(move |e| {
             trace!("did i load or what? o.O");
         });
@@ -116,26 +224,93 @@ pub mod gallery {
         let img_width = img.width;
         let img_height = img.height;
 
-        let fn_background =
-            move || format!("rgb({}, {}, {})", random_u8(), random_u8(), random_u8());
+        let background = img.color.clone();
         let fn_left = move || format!("{}px", view_left.get());
         let fn_top = move || format!("{}px", view_top.get() + 100.0);
         let fn_width = move || format!("{}px", view_width.get());
         let fn_height = move || format!("{}px", view_height.get());
         let fn_text = move || format!("{}x{}", img_width, img_height);
+        let revealed = RwSignal::new(img.blurred_src.is_none());
+        let loaded = RwSignal::new(false);
+        let src_ref = NodeRef::<html::Img>::new();
+        // a cached or server rendered image can finish before hydration attaches on:load
+        src_ref.on_load(move |img| {
+            if img.complete() {
+                loaded.set(true);
+            }
+        });
+        // device pixels the tile needs, only grows so a shrinking window doesn't refetch
+        let needed = RwSignal::new(0_u32);
+        gallery_img_ref.add_resize_observer(move |entry, _observer| {
+            let ratio = window().device_pixel_ratio();
+            let width = (entry.content_rect().width() * ratio).ceil() as u32;
+            if width > needed.get_untracked() {
+                needed.set(width);
+            }
+        });
+        let variants = img.variants.clone();
+        let preview_src = img.src.clone();
+        let variant_src = Memo::new(move |_| {
+            pick_variant(&variants, needed.get())
+                .map(|variant| variant.url.clone())
+                .or_else(|| preview_src.clone())
+        });
+        let favorited = img.favorited;
+        let src = img.src.clone();
+        let blurred_src = img.blurred_src.clone();
+        let placeholder = img.placeholder.clone();
+        let fn_content = move || {
+            match (revealed.get(), src.clone(), blurred_src.clone()) {
+            (true, Some(_), _) => view! {
+                {placeholder.clone().map(|placeholder| {
+                    view! {
+                        <img
+                            class="absolute inset-0 w-full h-full object-cover transition-opacity duration-500"
+                            class:opacity-0=move || loaded.get()
+                            src=placeholder
+                        />
+                    }
+                })}
+                <img
+                    node_ref=src_ref
+                    class="relative w-full h-full object-cover transition-opacity duration-500"
+                    class:opacity-0=move || !loaded.get()
+                    on:load=move |_| loaded.set(true)
+                    src=variant_src
+                />
+            }
+            .into_any(),
+            (false, _, Some(blurred_src)) => view! {
+                <img class="w-full h-full object-cover blur-xl scale-110" src=blurred_src />
+                <button
+                    class="absolute inset-0 grid place-items-center bg-black/40 text-sm"
+                    on:click=move |_| revealed.set(true)
+                >
+                    {t!("gallery-reveal")}
+                </button>
+            }
+            .into_any(),
+            _ => fn_text().into_any(),
+        }
+        };
 
         view! {
             <div
                 node_ref=gallery_img_ref
                 // node_ref=first_ref
                 class="text-white grid place-items-center bg-blue-950 absolute border border-red-600 overflow-hidden"
-                style:background-color=fn_background
+                class=("ring-4", move || selected.get())
+                class=("ring-white", move || selected.get())
+                style:background-color=background
                 style:left=fn_left
                 style:top=fn_top
                 style:width=fn_width
                 style:height=fn_height
             >
-                { fn_text }
+                { fn_content }
+                <Show when=move || favorited.get()>
+                    <span class="absolute top-1 right-1 text-yellow-400">"★"</span>
+                </Show>
             </div>
         }
     }
@@ -143,8 +318,20 @@ pub mod gallery {
     #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub struct Img {
         pub id: u64,
+        /// None for tiles of uploads that aren't artworks yet.
+        pub artwork_id: Option<String>,
         pub width: u32,
         pub height: u32,
+        pub src: Option<String>,
+        /// Tiny variant shown blurred instead of `src` until the tile is clicked.
+        pub blurred_src: Option<String>,
+        /// Placeholder background while the image loads.
+        pub color: String,
+        /// Data url of the decoded BlurHash, cross-faded into `src` once it loads.
+        pub placeholder: Option<String>,
+        /// Narrowest first, the tile loads the first one covering its size in device pixels.
+        pub variants: Vec<ArtworkVariant>,
+        pub favorited: RwSignal<bool>,
         pub view_width: RwSignal<f32>,
         pub view_height: RwSignal<f32>,
         pub view_pos_x: RwSignal<f32>,
@@ -159,8 +346,66 @@ pub mod gallery {
 
             Self {
                 id,
+                artwork_id: None,
                 width,
                 height,
+                src: None,
+                blurred_src: None,
+                color: format!("rgb({}, {}, {})", random_u8(), random_u8(), random_u8()),
+                placeholder: None,
+                variants: Vec::new(),
+                favorited: RwSignal::new(false),
+                view_width: RwSignal::new(0.0),
+                view_height: RwSignal::new(0.0),
+                view_pos_x: RwSignal::new(0.0),
+                view_pos_y: RwSignal::new(0.0),
+            }
+        }
+
+        pub fn from_artwork(artwork: ArtworkInfo) -> Self {
+            let mut hasher = DefaultHasher::new();
+            artwork.artwork_id.hash(&mut hasher);
+            Self {
+                id: hasher.finish(),
+                favorited: RwSignal::new(artwork.favorited),
+                width: artwork.width.max(1),
+                height: artwork.height.max(1),
+                blurred_src: if artwork.blurred {
+                    artwork.low_url
+                } else {
+                    None
+                },
+                src: artwork.preview_url,
+                placeholder: artwork.blurhash.as_deref().and_then(placeholder_url),
+                variants: artwork.variants,
+                artwork_id: Some(artwork.artwork_id),
+                color: artwork
+                    .palette
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| String::from(PLACEHOLDER_COLOR)),
+                view_width: RwSignal::new(0.0),
+                view_height: RwSignal::new(0.0),
+                view_pos_x: RwSignal::new(0.0),
+                view_pos_y: RwSignal::new(0.0),
+            }
+        }
+
+        /// Tile of a file being uploaded, shown from its object url until the gallery reloads.
+        pub fn from_upload(entry: &UploadEntry) -> Self {
+            let mut hasher = DefaultHasher::new();
+            entry.sha256.hash(&mut hasher);
+            Self {
+                id: hasher.finish(),
+                artwork_id: None,
+                width: entry.width.max(1),
+                height: entry.height.max(1),
+                src: Some(entry.preview_url.clone()),
+                blurred_src: None,
+                color: String::from(PLACEHOLDER_COLOR),
+                placeholder: None,
+                variants: Vec::new(),
+                favorited: RwSignal::new(false),
                 view_width: RwSignal::new(0.0),
                 view_height: RwSignal::new(0.0),
                 view_pos_x: RwSignal::new(0.0),
@@ -177,6 +422,45 @@ pub mod gallery {
         }
     }
 
+    /// Narrowest variant at least `width` wide, the widest if none is.
+    pub fn pick_variant(variants: &[ArtworkVariant], width: u32) -> Option<&ArtworkVariant> {
+        variants
+            .iter()
+            .find(|variant| variant.width >= width)
+            .or(variants.last())
+    }
+
+    /// Decodes a BlurHash into a bmp data url, so it also renders on the server without a canvas.
+    pub fn placeholder_url(blurhash: &str) -> Option<String> {
+        let size = PLACEHOLDER_SIZE;
+        let rgba = blurhash::decode(blurhash, size, size, 1.0).ok()?;
+        let row = (size * 3).div_ceil(4) * 4;
+        let pixels_len = row * size;
+        let mut bmp = Vec::with_capacity(54 + pixels_len as usize);
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&(54 + pixels_len).to_le_bytes());
+        bmp.extend_from_slice(&0u32.to_le_bytes());
+        bmp.extend_from_slice(&54u32.to_le_bytes());
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&size.to_le_bytes());
+        bmp.extend_from_slice(&size.to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes());
+        bmp.extend_from_slice(&24u16.to_le_bytes());
+        bmp.extend_from_slice(&0u32.to_le_bytes());
+        bmp.extend_from_slice(&pixels_len.to_le_bytes());
+        bmp.extend_from_slice(&[0; 16]);
+        // rows go bottom up, pixels are bgr
+        for y in (0..size).rev() {
+            let start = bmp.len();
+            for x in 0..size {
+                let i = ((y * size + x) * 4) as usize;
+                bmp.extend_from_slice(&[rgba[i + 2], rgba[i + 1], rgba[i]]);
+            }
+            bmp.resize(start + row as usize, 0);
+        }
+        Some(format!("data:image/bmp;base64,{}", STANDARD.encode(bmp)))
+    }
+
     pub fn resize_img(
         top: &mut f32,
         max_width: u32,
@@ -290,3 +574,1145 @@ pub mod gallery {
         (width * height) / (NEW_IMG_HEIGHT * NEW_IMG_HEIGHT)
     }
 }
+
+pub mod message_events {
+    use leptos::prelude::*;
+    use send_wrapper::SendWrapper;
+    use tracing::warn;
+    use wasm_bindgen::{JsCast, prelude::Closure};
+    use web_sys::EventSource;
+
+    use crate::api::{MESSAGE_EVENTS_PATH, MessageEvent};
+
+    /// Latest event pushed by the server, `version` changes with every event so resources can refetch on it.
+    #[derive(Debug, Clone, Copy)]
+    pub struct MessageEvents {
+        pub last: RwSignal<Option<MessageEvent>>,
+        pub version: RwSignal<u64>,
+    }
+
+    impl MessageEvents {
+        pub fn new() -> Self {
+            Self {
+                last: RwSignal::new(None),
+                version: RwSignal::new(0),
+            }
+        }
+    }
+
+    impl Default for MessageEvents {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    pub fn use_message_events() -> MessageEvents {
+        use_context::<MessageEvents>().unwrap_or_default()
+    }
+
+    /// Opens the event stream until the current owner is cleaned up, call it from an effect.
+    pub fn listen() {
+        let events = use_message_events();
+        let source = match EventSource::new(MESSAGE_EVENTS_PATH) {
+            Ok(source) => source,
+            Err(err) => {
+                warn!("failed to open message events: {:?}", err);
+                return;
+            }
+        };
+        let on_message =
+            Closure::<dyn Fn(web_sys::MessageEvent)>::new(move |ev: web_sys::MessageEvent| {
+                let Some(data) = ev.data().as_string() else {
+                    return;
+                };
+                match serde_json::from_str::<MessageEvent>(&data) {
+                    Ok(event) => {
+                        events.last.set(Some(event));
+                        events.version.update(|version| *version += 1);
+                    }
+                    Err(err) => warn!("invalid message event: {}", err),
+                }
+            });
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        let source = SendWrapper::new((source, on_message));
+        on_cleanup(move || {
+            let (source, _on_message) = source.take();
+            source.close();
+        });
+    }
+}
+
+pub mod uploader {
+    use leptos::{prelude::*, task::spawn_local};
+    use send_wrapper::SendWrapper;
+    use sha2::{Digest, Sha256};
+    use tracing::warn;
+    use wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{
+        Blob, CanvasRenderingContext2d, File, Headers, HtmlCanvasElement, ImageBitmap, RequestInit,
+        Response, Url,
+        js_sys::{Promise, Uint8Array},
+    };
+
+    use crate::{
+        api::{
+            MAXIMUM_UPLOAD_DIMENSION, MAXIMUM_UPLOAD_SIZE, UPLOAD_CHUNK_CONTENT_TYPE,
+            UPLOAD_CHUNK_SIZE, UPLOAD_LENGTH_HEADER, UPLOAD_OFFSET_HEADER, UPLOAD_PATH,
+            upload_duplicate,
+        },
+        toolbox::prelude::*,
+    };
+
+    /// Upload ids are kept in local storage under this prefix, so a reload can resume them.
+    const STORAGE_PREFIX: &str = "upload:";
+    /// Browsers without a webp encoder fall back to png.
+    const DOWNSCALE_TYPE: &str = "image/webp";
+    const DOWNSCALE_QUALITY: f64 = 0.92;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum UploadStatus {
+        Uploading,
+        Paused,
+        /// Id of an own artwork uploaded from the same file.
+        Duplicate(String),
+        Done(String),
+        Failed(String),
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct UploadEntry {
+        pub id: usize,
+        pub name: String,
+        /// What gets uploaded, the picked file or its downscaled copy.
+        pub blob: SendWrapper<Blob>,
+        /// Hex SHA-256 of the picked file.
+        pub sha256: String,
+        /// Object url of `blob` for local previews.
+        pub preview_url: String,
+        pub width: u32,
+        pub height: u32,
+        pub offset: RwSignal<u64>,
+        pub status: RwSignal<UploadStatus>,
+        pub running: RwSignal<bool>,
+    }
+
+    /// Uploads of this tab, shared by the gallery drop zone and the upload page.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Uploads {
+        pub entries: RwSignal<Vec<UploadEntry>>,
+    }
+
+    impl Uploads {
+        pub fn new() -> Self {
+            Self {
+                entries: RwSignal::new(Vec::new()),
+            }
+        }
+
+        /// Decodes, hashes and if needed downscales `file`, then starts uploading it unless the
+        /// server already has it.
+        pub async fn add(self, file: File) -> Result<UploadEntry, String> {
+            let name = file.name();
+            let sha256 = hash(&file).await?;
+            let bitmap = decode(&file).await?;
+            let longer = bitmap.width().max(bitmap.height());
+            let (blob, width, height) = if longer > MAXIMUM_UPLOAD_DIMENSION {
+                downscale(&bitmap).await?
+            } else {
+                (Blob::from(file), bitmap.width(), bitmap.height())
+            };
+            bitmap.close();
+            let preview_url = Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+            let entry = UploadEntry {
+                id: self.entries.with_untracked(|entries| entries.len()),
+                name,
+                blob: SendWrapper::new(blob),
+                sha256,
+                preview_url,
+                width,
+                height,
+                offset: RwSignal::new(0),
+                status: RwSignal::new(UploadStatus::Paused),
+                running: RwSignal::new(false),
+            };
+            let duplicate = match upload_duplicate(entry.sha256.clone()).await {
+                Ok(duplicate) => duplicate,
+                Err(err) => {
+                    warn!("duplicate check failed: {}", err);
+                    None
+                }
+            };
+            if entry.blob.size() as u64 > MAXIMUM_UPLOAD_SIZE {
+                entry.status.set(UploadStatus::Failed(format!(
+                    "larger than {} MiB",
+                    MAXIMUM_UPLOAD_SIZE / 1024 / 1024
+                )));
+            } else if let Some(artwork_id) = duplicate {
+                entry.status.set(UploadStatus::Duplicate(artwork_id));
+            } else {
+                resume(entry.clone());
+            }
+            self.entries.update(|entries| entries.push(entry.clone()));
+            Ok(entry)
+        }
+    }
+
+    impl Default for Uploads {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    pub fn use_uploads() -> Uploads {
+        use_context::<Uploads>().unwrap_or_default()
+    }
+
+    /// The same file hashes the same after a reload, so its upload can be found again.
+    pub fn storage_key(entry: &UploadEntry) -> String {
+        format!("{}{}", STORAGE_PREFIX, entry.sha256)
+    }
+
+    pub fn storage() -> Option<web_sys::Storage> {
+        window().local_storage().ok().flatten()
+    }
+
+    fn js_error(err: JsValue) -> String {
+        err.as_string().unwrap_or_else(|| format!("{:?}", err))
+    }
+
+    async fn hash(blob: &Blob) -> Result<String, String> {
+        let reader = blob.get_file_stream().map_err(|err| err.to_string())?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = reader
+            .get_stream_chunk()
+            .await
+            .map_err(|err| err.to_string())?
+        {
+            hasher.update(chunk.to_vec());
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    async fn decode(blob: &Blob) -> Result<ImageBitmap, String> {
+        let promise = window()
+            .create_image_bitmap_with_blob(blob)
+            .map_err(js_error)?;
+        JsFuture::from(promise)
+            .await
+            .map_err(|_| String::from("not an image this browser can decode"))?
+            .dyn_into::<ImageBitmap>()
+            .map_err(js_error)
+    }
+
+    /// Redraws `bitmap` with its longer side at `MAXIMUM_UPLOAD_DIMENSION`.
+    async fn downscale(bitmap: &ImageBitmap) -> Result<(Blob, u32, u32), String> {
+        let scale = MAXIMUM_UPLOAD_DIMENSION as f64 / bitmap.width().max(bitmap.height()) as f64;
+        let width = ((bitmap.width() as f64 * scale).round() as u32).max(1);
+        let height = ((bitmap.height() as f64 * scale).round() as u32).max(1);
+        let canvas = document()
+            .create_element("canvas")
+            .map_err(js_error)?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| String::from("failed to create a canvas"))?;
+        canvas.set_width(width);
+        canvas.set_height(height);
+        let context = canvas
+            .get_context("2d")
+            .map_err(js_error)?
+            .ok_or_else(|| String::from("canvas has no 2d context"))?
+            .dyn_into::<CanvasRenderingContext2d>()
+            .map_err(|_| String::from("canvas has no 2d context"))?;
+        context
+            .draw_image_with_image_bitmap_and_dw_and_dh(
+                bitmap,
+                0.0,
+                0.0,
+                width as f64,
+                height as f64,
+            )
+            .map_err(js_error)?;
+        let encoded = Promise::new(&mut |resolve, reject| {
+            let on_blob = Closure::once_into_js(move |blob: JsValue| {
+                let _ = resolve.call1(&JsValue::NULL, &blob);
+            });
+            if let Err(err) = canvas.to_blob_with_type_and_encoder_options(
+                on_blob.unchecked_ref(),
+                DOWNSCALE_TYPE,
+                &JsValue::from_f64(DOWNSCALE_QUALITY),
+            ) {
+                let _ = reject.call1(&JsValue::NULL, &err);
+            }
+        });
+        let blob = JsFuture::from(encoded)
+            .await
+            .map_err(js_error)?
+            .dyn_into::<Blob>()
+            .map_err(|_| String::from("failed to encode the downscaled image"))?;
+        Ok((blob, width, height))
+    }
+
+    async fn request(
+        method: &str,
+        url: &str,
+        headers: &[(&str, String)],
+        body: Option<&[u8]>,
+    ) -> Result<Response, String> {
+        let init = RequestInit::new();
+        init.set_method(method);
+        let request_headers = Headers::new().map_err(js_error)?;
+        for (name, value) in headers {
+            request_headers.set(name, value).map_err(js_error)?;
+        }
+        init.set_headers(&request_headers);
+        if let Some(body) = body {
+            init.set_body(&Uint8Array::from(body));
+        }
+        JsFuture::from(window().fetch_with_str_and_init(url, &init))
+            .await
+            .map_err(js_error)?
+            .dyn_into::<Response>()
+            .map_err(js_error)
+    }
+
+    fn response_header(response: &Response, name: &str) -> Option<u64> {
+        response.headers().get(name).ok().flatten()?.parse().ok()
+    }
+
+    /// Upload to continue and its offset, the stored one if the server still has it. A
+    /// downscaled copy may encode differently after a reload, its length must still match.
+    async fn start(entry: &UploadEntry) -> Result<(String, u64), String> {
+        let key = storage_key(entry);
+        let length = entry.blob.size() as u64;
+        if let Some(upload_id) = storage().and_then(|storage| storage.get_item(&key).ok().flatten())
+        {
+            let url = format!("{}/{}", UPLOAD_PATH, upload_id);
+            let response = request("HEAD", &url, &[], None).await?;
+            if response.ok()
+                && response_header(&response, UPLOAD_LENGTH_HEADER) == Some(length)
+                && let Some(offset) = response_header(&response, UPLOAD_OFFSET_HEADER)
+            {
+                return Ok((upload_id, offset));
+            }
+        }
+        let headers = [(UPLOAD_LENGTH_HEADER, length.to_string())];
+        let response = request("POST", UPLOAD_PATH, &headers, None).await?;
+        if !response.ok() {
+            return Err(format!("upload failed ({})", response.status()));
+        }
+        let upload_id = response
+            .headers()
+            .get("location")
+            .ok()
+            .flatten()
+            .and_then(|location| location.rsplit('/').next().map(String::from))
+            .ok_or_else(|| String::from("upload has no location"))?;
+        if let Some(storage) = storage() {
+            let _ = storage.set_item(&key, &upload_id);
+        }
+        Ok((upload_id, 0))
+    }
+
+    /// Streams the blob from where the server is at, returns the upload id once complete or
+    /// `None` if paused.
+    async fn run(entry: &UploadEntry) -> Result<Option<String>, String> {
+        let blob = &*entry.blob;
+        let length = blob.size() as u64;
+        let (upload_id, mut offset) = start(entry).await?;
+        entry.offset.set(offset);
+        let url = format!("{}/{}", UPLOAD_PATH, upload_id);
+        let reader = blob.get_file_stream().map_err(|err| err.to_string())?;
+        // the stream always starts at 0, what the server already has is read and dropped
+        let mut skip = offset;
+        let mut buffer = Vec::<u8>::new();
+        let mut ended = false;
+        while offset < length {
+            if entry.status.get_untracked() == UploadStatus::Paused {
+                let _ = reader.cancel();
+                return Ok(None);
+            }
+            if !ended && buffer.len() < UPLOAD_CHUNK_SIZE {
+                match reader
+                    .get_stream_chunk()
+                    .await
+                    .map_err(|err| err.to_string())?
+                {
+                    Some(chunk) => {
+                        chunk.push_to_vec(&mut buffer);
+                        let skipped = buffer.len().min(skip as usize);
+                        buffer.drain(..skipped);
+                        skip -= skipped as u64;
+                    }
+                    None => ended = true,
+                }
+                continue;
+            }
+            let end = buffer.len().min(UPLOAD_CHUNK_SIZE);
+            if end == 0 {
+                return Err(String::from("file changed while uploading"));
+            }
+            let headers = [
+                ("content-type", UPLOAD_CHUNK_CONTENT_TYPE.to_string()),
+                (UPLOAD_OFFSET_HEADER, offset.to_string()),
+            ];
+            let response = request("PATCH", &url, &headers, Some(&buffer[..end])).await?;
+            if !response.ok() {
+                return Err(format!("upload failed ({})", response.status()));
+            }
+            buffer.drain(..end);
+            offset =
+                response_header(&response, UPLOAD_OFFSET_HEADER).unwrap_or(offset + end as u64);
+            entry.offset.set(offset);
+        }
+        Ok(Some(upload_id))
+    }
+
+    pub fn resume(entry: UploadEntry) {
+        if entry.running.get_untracked() {
+            return;
+        }
+        entry.running.set(true);
+        entry.status.set(UploadStatus::Uploading);
+        spawn_local(async move {
+            match run(&entry).await {
+                Ok(Some(upload_id)) => entry.status.set(UploadStatus::Done(upload_id)),
+                Ok(None) => {}
+                Err(err) => entry.status.set(UploadStatus::Failed(err)),
+            }
+            entry.running.set(false);
+        });
+    }
+}
+
+pub mod shortcuts {
+    use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+    use leptos::{ev, prelude::*};
+    use thiserror::Error;
+    use tracing::warn;
+    use wasm_bindgen::JsCast;
+    use web_sys::{HtmlElement, HtmlInputElement, KeyboardEvent};
+
+    use crate::{t, toolbox::prelude::*};
+
+    /// Longest pause between the keys of a chord.
+    pub const CHORD_TIMEOUT_MS: f64 = 1000.0;
+    pub const GLOBAL_SCOPE: &str = "global";
+    pub const GALLERY_SCOPE: &str = "gallery";
+    pub const VIEWER_SCOPE: &str = "viewer";
+    pub const CHEAT_SHEET_SCOPE: &str = "cheat-sheet";
+    /// Inputs of these types take typed text, keys pressed in them are never shortcuts.
+    pub const TEXT_INPUT_TYPES: [&str; 12] = [
+        "text",
+        "search",
+        "email",
+        "password",
+        "url",
+        "tel",
+        "number",
+        "date",
+        "datetime-local",
+        "month",
+        "week",
+        "time",
+    ];
+
+    #[derive(Error, Debug, PartialEq)]
+    pub enum ErrorShortcut {
+        #[error("shortcut has no keys")]
+        Empty,
+
+        #[error("`{0}` clashes with `{1}` of the {2} shortcuts")]
+        Conflict(String, String, &'static str),
+    }
+
+    /// Keys pressed one after another, written like `g h`, `?` or `ctrl+k`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Chord(Vec<String>);
+
+    impl Chord {
+        pub fn parse(keys: &str) -> Self {
+            Self(keys.split_whitespace().map(String::from).collect())
+        }
+
+        pub fn keys(&self) -> &[String] {
+            &self.0
+        }
+
+        /// One of the chords is the start of the other.
+        pub fn clashes(&self, other: &Chord) -> bool {
+            let len = self.0.len().min(other.0.len());
+            self.0[..len] == other.0[..len]
+        }
+    }
+
+    impl Display for Chord {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0.join(" "))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Binding {
+        pub id: u64,
+        pub scope: &'static str,
+        pub chord: Chord,
+        /// Message id, translated when it's listed.
+        pub description: &'static str,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Resolved {
+        Run(u64),
+        /// The keys so far start a chord, wait for the next one.
+        Pending,
+        Unbound,
+    }
+
+    /// Bindings of the mounted scopes, oldest first.
+    #[derive(Debug, Clone, Default)]
+    pub struct Keymap {
+        bindings: Vec<Binding>,
+        next_id: u64,
+    }
+
+    impl Keymap {
+        /// A chord already bound in another scope is shadowed by the new binding until it's unbound.
+        /// One bound in the same scope, or one that starts or is started by another, is refused.
+        pub fn bind(
+            &mut self,
+            scope: &'static str,
+            keys: &str,
+            description: &'static str,
+        ) -> Result<u64, ErrorShortcut> {
+            let chord = Chord::parse(keys);
+            if chord.0.is_empty() {
+                return Err(ErrorShortcut::Empty);
+            }
+            if let Some(other) = self.bindings.iter().find(|binding| {
+                binding.chord.clashes(&chord) && (binding.scope == scope || binding.chord != chord)
+            }) {
+                return Err(ErrorShortcut::Conflict(
+                    chord.to_string(),
+                    other.chord.to_string(),
+                    other.scope,
+                ));
+            }
+            self.next_id += 1;
+            self.bindings.push(Binding {
+                id: self.next_id,
+                scope,
+                chord,
+                description,
+            });
+            Ok(self.next_id)
+        }
+
+        pub fn unbind(&mut self, id: u64) {
+            self.bindings.retain(|binding| binding.id != id);
+        }
+
+        /// What the keys pressed so far lead to.
+        pub fn resolve(&self, pressed: &[String]) -> Resolved {
+            if let Some(binding) = self
+                .bindings
+                .iter()
+                .rev()
+                .find(|binding| binding.chord.0 == pressed)
+            {
+                return Resolved::Run(binding.id);
+            }
+            if self
+                .bindings
+                .iter()
+                .any(|binding| binding.chord.0.starts_with(pressed))
+            {
+                return Resolved::Pending;
+            }
+            Resolved::Unbound
+        }
+
+        /// Bindings that would run, without the shadowed ones.
+        pub fn active(&self) -> Vec<&Binding> {
+            self.bindings
+                .iter()
+                .enumerate()
+                .filter(|(i, binding)| {
+                    !self.bindings[i + 1..]
+                        .iter()
+                        .any(|newer| newer.chord == binding.chord)
+                })
+                .map(|(_, binding)| binding)
+                .collect()
+        }
+    }
+
+    /// `KeyboardEvent.key` with `ctrl+`, `alt+` and `meta+` in front of it, shift already
+    /// shows in the key itself. Modifiers pressed on their own aren't keys.
+    pub fn key_name(key: &str, ctrl: bool, alt: bool, meta: bool) -> Option<String> {
+        if matches!(
+            key,
+            "Control" | "Shift" | "Alt" | "AltGraph" | "Meta" | "CapsLock" | "Dead" | "Process"
+        ) || key.is_empty()
+        {
+            return None;
+        }
+        let mut name = String::new();
+        for (held, prefix) in [(ctrl, "ctrl+"), (alt, "alt+"), (meta, "meta+")] {
+            if held {
+                name.push_str(prefix);
+            }
+        }
+        name.push_str(if key == " " { "space" } else { key });
+        Some(name)
+    }
+
+    /// Whether keys pressed in an element with `tag` are typed text.
+    pub fn is_text_field(tag: &str, input_type: &str, editable: bool) -> bool {
+        editable
+            || match tag.to_ascii_uppercase().as_str() {
+                "TEXTAREA" | "SELECT" => true,
+                "INPUT" => TEXT_INPUT_TYPES.contains(&input_type.to_ascii_lowercase().as_str()),
+                _ => false,
+            }
+    }
+
+    fn is_typing(ev: &KeyboardEvent) -> bool {
+        let Some(target) = ev
+            .target()
+            .and_then(|target| target.dyn_into::<HtmlElement>().ok())
+        else {
+            return false;
+        };
+        let input_type = target
+            .dyn_ref::<HtmlInputElement>()
+            .map(|input| input.type_())
+            .unwrap_or_default();
+        is_text_field(
+            &target.tag_name(),
+            &input_type,
+            target.is_content_editable(),
+        )
+    }
+
+    type Action = Rc<RefCell<dyn FnMut()>>;
+
+    /// Keyboard shortcuts of the mounted components, run from a single window listener.
+    #[derive(Clone, Copy)]
+    pub struct Shortcuts {
+        keymap: RwSignal<Keymap>,
+        actions: StoredValue<HashMap<u64, Action>, LocalStorage>,
+        /// Keys of an unfinished chord and when the last one was pressed.
+        pending: StoredValue<(Vec<String>, f64)>,
+        pub sheet_open: RwSignal<bool>,
+    }
+
+    impl Shortcuts {
+        pub fn new() -> Self {
+            Self {
+                keymap: RwSignal::new(Keymap::default()),
+                actions: StoredValue::new_local(HashMap::new()),
+                pending: StoredValue::new((Vec::new(), 0.0)),
+                sheet_open: RwSignal::new(false),
+            }
+        }
+
+        /// Runs `action` on `keys` while the current owner lives. Conflicts are logged and the
+        /// binding is left out.
+        pub fn bind(
+            self,
+            scope: &'static str,
+            keys: &'static str,
+            description: &'static str,
+            action: impl FnMut() + 'static,
+        ) {
+            let mut action = Some(action);
+            // bound in an effect so server renders don't keep the actions around
+            Effect::new(move || {
+                let Some(action) = action.take() else {
+                    return;
+                };
+                let id = match self
+                    .keymap
+                    .try_update(|keymap| keymap.bind(scope, keys, description))
+                {
+                    Some(Ok(id)) => id,
+                    Some(Err(err)) => {
+                        warn!("shortcut skipped: {}", err);
+                        return;
+                    }
+                    None => return,
+                };
+                let action: Action = Rc::new(RefCell::new(action));
+                self.actions.update_value(|actions| {
+                    actions.insert(id, action);
+                });
+                on_cleanup(move || {
+                    self.keymap.try_update(|keymap| keymap.unbind(id));
+                    self.actions.try_update_value(|actions| {
+                        actions.remove(&id);
+                    });
+                });
+            });
+        }
+
+        pub fn active(self) -> Vec<Binding> {
+            self.keymap
+                .with(|keymap| keymap.active().into_iter().cloned().collect())
+        }
+
+        /// Listens for keys until the current owner is cleaned up, call it from an effect.
+        pub fn listen(self) {
+            window().add_event_listener(ev::keydown, move |ev: KeyboardEvent| self.on_keydown(ev));
+        }
+
+        fn on_keydown(self, ev: KeyboardEvent) {
+            if ev.default_prevented() || ev.is_composing() || is_typing(&ev) {
+                return;
+            }
+            let Some(key) = key_name(&ev.key(), ev.ctrl_key(), ev.alt_key(), ev.meta_key()) else {
+                return;
+            };
+            let time = ev.time_stamp();
+            let mut pressed = self.pending.with_value(|(keys, at)| {
+                if time - at <= CHORD_TIMEOUT_MS {
+                    keys.clone()
+                } else {
+                    Vec::new()
+                }
+            });
+            pressed.push(key.clone());
+            let mut resolved = self
+                .keymap
+                .with_untracked(|keymap| keymap.resolve(&pressed));
+            if resolved == Resolved::Unbound && pressed.len() > 1 {
+                // the key that broke a chord may start one of its own
+                pressed = vec![key];
+                resolved = self
+                    .keymap
+                    .with_untracked(|keymap| keymap.resolve(&pressed));
+            }
+            match resolved {
+                Resolved::Run(id) => {
+                    ev.prevent_default();
+                    self.pending.set_value((Vec::new(), time));
+                    let action = self.actions.with_value(|actions| actions.get(&id).cloned());
+                    if let Some(action) = action {
+                        (*action.borrow_mut())();
+                    }
+                }
+                Resolved::Pending => {
+                    ev.prevent_default();
+                    self.pending.set_value((pressed, time));
+                }
+                Resolved::Unbound => self.pending.set_value((Vec::new(), time)),
+            }
+        }
+    }
+
+    impl Default for Shortcuts {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    pub fn use_shortcuts() -> Shortcuts {
+        use_context::<Shortcuts>().unwrap_or_default()
+    }
+
+    /// Shortcuts that would run right now, toggled with `?`.
+    #[component]
+    pub fn CheatSheet() -> impl IntoView {
+        let shortcuts = use_shortcuts();
+        view! {
+            <Show when=move || shortcuts.sheet_open.get()>
+                <CheatSheetDialog />
+            </Show>
+        }
+    }
+
+    #[component]
+    fn CheatSheetDialog() -> impl IntoView {
+        let shortcuts = use_shortcuts();
+        shortcuts.bind(
+            CHEAT_SHEET_SCOPE,
+            "Escape",
+            "shortcut-close-shortcuts",
+            move || shortcuts.sheet_open.set(false),
+        );
+        let rows = move || {
+            shortcuts
+                .active()
+                .into_iter()
+                .map(|binding| {
+                    view! {
+                        <tr>
+                            <td class="pr-4 text-gray-500 dark:text-gray-400">
+                                {t!(&format!("shortcut-scope-{}", binding.scope))}
+                            </td>
+                            <td class="pr-4">
+                                <kbd class="font-mono bg-gray-200 dark:bg-gray-800 px-1">
+                                    {binding.chord.to_string()}
+                                </kbd>
+                            </td>
+                            <td>{t!(binding.description)}</td>
+                        </tr>
+                    }
+                })
+                .collect_view()
+        };
+        view! {
+            <div
+                class="fixed inset-0 z-50 grid place-items-center bg-black/60"
+                on:click=move |_| shortcuts.sheet_open.set(false)
+            >
+                <div
+                    class="bg-white text-gray-800 dark:bg-gray-900 dark:text-gray-200 p-4 max-h-[80vh] overflow-y-auto"
+                    on:click=|ev| ev.stop_propagation()
+                >
+                    <h2 class="font-bold pb-2">{t!("shortcuts-title")}</h2>
+                    <table class="text-sm">
+                        <tbody>{rows}</tbody>
+                    </table>
+                </div>
+            </div>
+        }
+    }
+
+    #[cfg(test)]
+    mod shortcuts_tests {
+        use super::{ErrorShortcut, Keymap, Resolved, is_text_field, key_name};
+
+        fn keys(keys: &str) -> Vec<String> {
+            keys.split_whitespace().map(String::from).collect()
+        }
+
+        #[test]
+        fn chords_resolve_and_conflicts_are_refused() {
+            let mut keymap = Keymap::default();
+            let home = keymap.bind("global", "g h", "home").unwrap();
+            let close = keymap.bind("gallery", "Escape", "clear").unwrap();
+            assert_eq!(keymap.resolve(&keys("g")), Resolved::Pending);
+            assert_eq!(keymap.resolve(&keys("g h")), Resolved::Run(home));
+            assert_eq!(keymap.resolve(&keys("g x")), Resolved::Unbound);
+
+            assert_eq!(
+                keymap.bind("global", "g h", "again"),
+                Err(ErrorShortcut::Conflict(
+                    String::from("g h"),
+                    String::from("g h"),
+                    "global"
+                ))
+            );
+            assert!(matches!(
+                keymap.bind("gallery", "g", "prefix"),
+                Err(ErrorShortcut::Conflict(..))
+            ));
+            assert_eq!(
+                keymap.bind("gallery", " ", "blank"),
+                Err(ErrorShortcut::Empty)
+            );
+
+            // a newer scope shadows the same chord until it's gone
+            let viewer = keymap.bind("viewer", "Escape", "close").unwrap();
+            assert_eq!(keymap.resolve(&keys("Escape")), Resolved::Run(viewer));
+            assert_eq!(keymap.active().len(), 2);
+            keymap.unbind(viewer);
+            assert_eq!(keymap.resolve(&keys("Escape")), Resolved::Run(close));
+            assert_eq!(keymap.active().len(), 2);
+        }
+
+        #[test]
+        fn keys_are_named_and_text_fields_skipped() {
+            assert_eq!(key_name("?", false, false, false).as_deref(), Some("?"));
+            assert_eq!(
+                key_name("k", true, false, true).as_deref(),
+                Some("ctrl+meta+k")
+            );
+            assert_eq!(key_name(" ", false, false, false).as_deref(), Some("space"));
+            assert_eq!(key_name("Shift", false, false, false), None);
+
+            assert!(is_text_field("INPUT", "text", false));
+            assert!(is_text_field("TEXTAREA", "", false));
+            assert!(is_text_field("DIV", "", true));
+            assert!(!is_text_field("INPUT", "checkbox", false));
+            assert!(!is_text_field("BUTTON", "", false));
+        }
+    }
+}
+
+pub mod nav {
+    use leptos::prelude::*;
+    use leptos_router::hooks::use_navigate;
+
+    use crate::{
+        api::{ADMIN_ROLE, LocaleSet, Logout, ThemeSet, get_acc, unread_count},
+        app::components::{
+            message_events::{listen, use_message_events},
+            shortcuts::{CheatSheet, GLOBAL_SCOPE, use_shortcuts},
+        },
+        i18n::{LOCALES, use_i18n},
+        t,
+        theme::{self, use_theme},
+    };
+
+    /// `g` followed by the first letter of the page.
+    pub const NAV_SHORTCUTS: [(&str, &str, &str); 5] = [
+        ("g h", "/", "shortcut-go-gallery"),
+        ("g u", "/upload", "shortcut-go-upload"),
+        ("g m", "/messages", "shortcut-go-messages"),
+        ("g c", "/commissions", "shortcut-go-commissions"),
+        ("g s", "/settings", "shortcut-go-settings"),
+    ];
+
+    #[component]
+    pub fn Nav() -> impl IntoView {
+        let shortcuts = use_shortcuts();
+        let navigate = use_navigate();
+        for (keys, path, description) in NAV_SHORTCUTS {
+            let navigate = navigate.clone();
+            shortcuts.bind(GLOBAL_SCOPE, keys, description, move || {
+                navigate(path, Default::default())
+            });
+        }
+        shortcuts.bind(GLOBAL_SCOPE, "?", "shortcut-show-shortcuts", move || {
+            shortcuts.sheet_open.update(|open| *open = !*open)
+        });
+
+        // the cookie is read while rendering, so the page is rendered again with it
+        let i18n = use_i18n();
+        let locale_set = ServerAction::<LocaleSet>::new();
+        Effect::new(move || {
+            if matches!(locale_set.value().get(), Some(Ok(()))) {
+                let _ = window().location().reload();
+            }
+        });
+        let locale_options = LOCALES
+            .map(|(locale, name, _)| {
+                view! {
+                    <option value=locale selected=locale == i18n.locale>
+                        {name}
+                    </option>
+                }
+            })
+            .collect_view();
+
+        let logout = ServerAction::<Logout>::new();
+        let acc = Resource::new(move || logout.version().get(), |_| get_acc());
+        let events = use_message_events();
+        let unread = Resource::new(
+            move || (logout.version().get(), events.version.get()),
+            |_| unread_count(),
+        );
+
+        Effect::new(move || {
+            if matches!(acc.get(), Some(Ok(Some(_)))) {
+                listen();
+            }
+        });
+
+        // the theme is applied right away, the cookie and the account catch up in the background
+        let theme = use_theme();
+        let theme_set = ServerAction::<ThemeSet>::new();
+        let toggle_theme = move |_| {
+            let next = theme::next(theme.current.get_untracked());
+            theme.set(next);
+            theme_set.dispatch(ThemeSet {
+                theme: next.to_string(),
+            });
+        };
+        Effect::new(move || {
+            let Some(Ok(Some(acc))) = acc.get() else {
+                return;
+            };
+            let Some(saved) = acc.theme.as_deref().map(theme::supported) else {
+                return;
+            };
+            if saved != theme.current.get_untracked() {
+                theme.set(saved);
+                theme_set.dispatch(ThemeSet {
+                    theme: saved.to_string(),
+                });
+            }
+        });
+        let theme_label = move || i18n.t(&format!("theme-{}", theme.current.get()), None);
+
+        let messages_label = move || match unread.get() {
+            Some(Ok(unread)) if unread > 0 => t!("nav-messages-unread", count = unread),
+            _ => t!("nav-messages"),
+        };
+        let messages_title = move || match unread.get() {
+            Some(Ok(unread)) if unread > 0 => Some(t!("nav-messages-unread-title", count = unread)),
+            _ => None,
+        };
+
+        view! {
+            <nav class="text-gray-800 dark:text-gray-200 pb-1 flex gap-2 items-center">
+                <a href="/" class="font-black text-xl">
+                    "ArtBounty"
+                </a>
+                <a href="/two">"two"</a>
+                <div class="ml-auto flex gap-2 items-center">
+                    <Transition>
+                        {move || {
+                            acc.get()
+                                .map(|acc| match acc {
+                                    Ok(Some(acc)) => {
+                                        let is_admin = acc.role == ADMIN_ROLE;
+                                        view! {
+                                            <Show when=move || is_admin>
+                                                <a href="/admin">{t!("nav-admin")}</a>
+                                            </Show>
+                                            <a href="/messages" title=messages_title>
+                                                {messages_label}
+                                            </a>
+                                            <a href="/upload">{t!("nav-upload")}</a>
+                                            <a href="/commissions">{t!("nav-commissions")}</a>
+                                            <a href="/settings">{acc.username}</a>
+                                            <ActionForm action=logout>
+                                                <button type="submit">{t!("nav-logout")}</button>
+                                            </ActionForm>
+                                        }
+                                            .into_any()
+                                    }
+                                    _ => {
+                                        view! {
+                                            <a href="/login">{t!("nav-login")}</a>
+                                            <a href="/register">{t!("nav-register")}</a>
+                                        }
+                                            .into_any()
+                                    }
+                                })
+                        }}
+                    </Transition>
+                    <select
+                        class="bg-transparent"
+                        aria-label=t!("nav-language")
+                        on:change=move |ev| {
+                            locale_set
+                                .dispatch(LocaleSet {
+                                    locale: event_target_value(&ev),
+                                });
+                        }
+                    >
+                        {locale_options}
+                    </select>
+                    <button
+                        type="button"
+                        title=move || t!("theme-toggle")
+                        on:click=toggle_theme
+                    >
+                        {theme_label}
+                    </button>
+                </div>
+                <CheatSheet />
+            </nav>
+        }
+    }
+}
+
+pub mod form {
+    use leptos::{
+        prelude::*,
+        server_fn::{ServerFn, error::NoCustomError},
+    };
+
+    use crate::{
+        api::{RATINGS, error_message},
+        t,
+    };
+
+    pub const INPUT_CLASS: &str = "bg-white text-gray-800 dark:bg-gray-900 dark:text-gray-200 border border-gray-300 dark:border-gray-700 px-2 py-1";
+    pub const BUTTON_CLASS: &str =
+        "bg-gray-800 text-gray-100 dark:bg-gray-200 dark:text-gray-950 font-bold px-2 py-1";
+
+    /// Shows the `success` message or the server error of the last submission of `action`.
+    #[component]
+    pub fn FormResult<I, O>(
+        action: ServerAction<I>,
+        #[prop(optional)] success: Option<&'static str>,
+    ) -> impl IntoView
+    where
+        I: ServerFn<Output = O, Error = NoCustomError> + Clone + Send + Sync + 'static,
+        O: Send + Sync + 'static,
+    {
+        let success = success.map(|key| t!(key));
+        move || {
+            action.value().with(|value| match value {
+                Some(Ok(_)) => success
+                    .clone()
+                    .map(|success| view! { <p class="text-green-400">{success}</p> }.into_any()),
+                Some(Err(err)) => {
+                    Some(view! { <p class="text-red-400">{error_message(err)}</p> }.into_any())
+                }
+                None => None,
+            })
+        }
+    }
+
+    /// Content rating picker submitted as `rating`.
+    #[component]
+    pub fn RatingSelect(#[prop(into)] selected: String) -> impl IntoView {
+        let options = RATINGS
+            .map(|(rating, _)| {
+                view! {
+                    <option value=rating selected=selected == rating>
+                        {t!(&format!("rating-{}", rating))}
+                    </option>
+                }
+            })
+            .collect_view();
+        view! {
+            <select class=INPUT_CLASS name="rating">
+                {options}
+            </select>
+        }
+    }
+}
+
+pub mod meta {
+    use leptos::prelude::*;
+    use leptos_meta::{Link, Meta, Title};
+
+    use crate::api::{PageMeta, SITE_NAME};
+
+    /// OpenGraph and Twitter card of the page, the server renders them into `<head>` so link
+    /// previews pick them up.
+    #[component]
+    pub fn PageMetaTags(meta: PageMeta) -> impl IntoView {
+        let card = if meta.image.is_some() {
+            "summary_large_image"
+        } else {
+            "summary"
+        };
+        let image = meta.image.map(|image| {
+            view! {
+                <Meta property="og:image" content=image.url.clone() />
+                <Meta property="og:image:width" content=image.width.to_string() />
+                <Meta property="og:image:height" content=image.height.to_string() />
+                <Meta name="twitter:image" content=image.url />
+            }
+        });
+        let author = meta
+            .author
+            .map(|author| view! { <Meta name="author" content=author /> });
+
+        view! {
+            <Title text=format!("{} - {}", meta.title, SITE_NAME) />
+            <Meta name="description" content=meta.description.clone() />
+            <Meta property="og:site_name" content=SITE_NAME />
+            <Meta property="og:type" content=meta.kind />
+            <Meta property="og:title" content=meta.title.clone() />
+            <Meta property="og:description" content=meta.description.clone() />
+            <Meta property="og:url" content=meta.url.clone() />
+            <Meta name="twitter:card" content=card />
+            <Meta name="twitter:title" content=meta.title />
+            <Meta name="twitter:description" content=meta.description />
+            {author}
+            {image}
+            <Link rel="canonical" href=meta.url />
+            <Link rel="alternate" type_="application/json+oembed" href=meta.oembed_url />
+        }
+    }
+}
@@ -1,13 +1,21 @@
 pub mod home {
     use crate::toolbox::prelude::*;
-    use leptos::prelude::*;
+    use leptos::{prelude::*, task::spawn_local};
     use reactive_stores::Store;
-    use tracing::trace;
+    use tracing::warn;
     use web_sys::{HtmlDivElement, HtmlElement};
 
-    use crate::app::{
-        GlobalState,
-        components::gallery::{Gallery, Img, resize_imgs},
+    use crate::{
+        api::artworks,
+        app::{
+            GlobalState,
+            components::{
+                gallery::{Gallery, Img},
+                nav::Nav,
+                uploader::{UploadStatus, use_uploads},
+            },
+        },
+        t,
     };
 
     #[component]
@@ -15,24 +23,42 @@ pub mod home {
         let main_ref = NodeRef::new();
         let global_state = expect_context::<GlobalState>();
         let imgs = global_state.imgs;
+        let uploads = use_uploads();
+
+        let color = RwSignal::new(None::<String>);
+        let artworks = Resource::new(move || color.get(), |color| artworks(0, color));
 
         Effect::new(move || {
-            let new_imgs = Img::rand_vec(100);
-            imgs.set(new_imgs);
+            if let Some(Ok(artworks)) = artworks.get() {
+                imgs.set(artworks.into_iter().map(Img::from_artwork).collect());
+            }
         });
 
-        main_ref.on_file_drop(async |event, data| {
-            for file in data.get_files() {
-                let stream = file.get_file_stream()?;
-                let mut data = Vec::<u8>::new();
-                while let Some(chunk) = stream.get_stream_chunk().await? {
-                    chunk.push_to_vec(&mut data);
-                }
-                let data_str = String::from_utf8_lossy(&data);
-                trace!("file: {}", data_str);
+        main_ref.on_file_drop(move |event, data| {
+            // the drop zone holds its callback until the returned future is done, so files are
+            // prepared in their own tasks
+            let files = match event {
+                dropzone::Event::Drop => data.get_files(),
+                _ => Vec::new(),
+            };
+            for file in files {
+                spawn_local(async move {
+                    match uploads.add(file).await {
+                        Ok(entry)
+                            if !matches!(
+                                entry.status.get_untracked(),
+                                UploadStatus::Duplicate(_)
+                            ) =>
+                        {
+                            imgs.update(|imgs| imgs.insert(0, Img::from_upload(&entry)));
+                        }
+                        Ok(_) => {}
+                        Err(err) => warn!("dropped file skipped: {}", err),
+                    }
+                });
             }
 
-            Ok(())
+            async { Ok(()) }
             // for file in data.files().iter() {
             //     let data = file.data().await;
             //     let data = data.map(|data| String::from_utf8_lossy(&data).to_string());
@@ -54,15 +80,2523 @@ pub mod home {
         // };
 
         view! {
-            <main node_ref=main_ref class="grid grid-rows-[auto_1fr] h-screen">
-                <nav class="text-gray-200 pb-1">
-                    <a href="/" class="font-black text-xl">
-                        "ArtBounty"
-                    </a>
-                    <a href="/two">"two"</a>
-                </nav>
+            <main node_ref=main_ref class="grid grid-rows-[auto_auto_1fr] h-screen">
+                <Nav />
+                <div class="flex gap-2 items-center text-gray-800 dark:text-gray-200">
+                    <label for="color">{t!("home-color")}</label>
+                    <input
+                        type="color"
+                        id="color"
+                        prop:value=move || color.get().unwrap_or_else(|| String::from("#000000"))
+                        on:change=move |ev| color.set(Some(event_target_value(&ev)))
+                    />
+                    <Show when=move || color.get().is_some()>
+                        <button on:click=move |_| color.set(None)>{t!("home-clear")}</button>
+                    </Show>
+                </div>
                 <Gallery imgs=imgs />
             </main>
         }
     }
 }
+
+pub mod register {
+    use leptos::prelude::*;
+
+    use crate::{
+        api::{MAXIMUM_USERNAME_LENGTH, MINIMUM_PASSWORD_LENGTH, Register},
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS},
+            nav::Nav,
+        },
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let register = ServerAction::<Register>::new();
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <ActionForm action=register attr:class="flex flex-col gap-2 mx-auto w-80 text-gray-800 dark:text-gray-200">
+                    <h1 class="font-bold text-lg">{t!("register-title")}</h1>
+                    <input
+                        class=INPUT_CLASS
+                        type="text"
+                        name="username"
+                        placeholder=t!("form-username")
+                        maxlength=MAXIMUM_USERNAME_LENGTH
+                        required
+                    />
+                    <input class=INPUT_CLASS type="email" name="email" placeholder=t!("form-email") required />
+                    <input
+                        class=INPUT_CLASS
+                        type="password"
+                        name="password"
+                        placeholder=t!("form-password")
+                        minlength=MINIMUM_PASSWORD_LENGTH
+                        required
+                    />
+                    <button class=BUTTON_CLASS type="submit">
+                        {t!("register-submit")}
+                    </button>
+                    <FormResult
+                        action=register
+                        success="register-done"
+                    />
+                    <a href="/login">{t!("register-login")}</a>
+                </ActionForm>
+            </main>
+        }
+    }
+}
+
+pub mod login {
+    use leptos::prelude::*;
+    use leptos_router::hooks::use_navigate;
+
+    use crate::{
+        api::{Login, LoginResult, LoginTotp},
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS},
+            nav::Nav,
+        },
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let login = ServerAction::<Login>::new();
+        let login_totp = ServerAction::<LoginTotp>::new();
+        let navigate = use_navigate();
+
+        let challenge = move || match login.value().get() {
+            Some(Ok(LoginResult::Totp { challenge })) => Some(challenge),
+            _ => None,
+        };
+
+        Effect::new(move || {
+            let logged_in = matches!(login.value().get(), Some(Ok(LoginResult::LoggedIn(_))))
+                || matches!(login_totp.value().get(), Some(Ok(_)));
+            if logged_in {
+                navigate("/", Default::default());
+            }
+        });
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <Show
+                    when=move || challenge().is_some()
+                    fallback=move || {
+                        view! {
+                            <ActionForm
+                                action=login
+                                attr:class="flex flex-col gap-2 mx-auto w-80 text-gray-800 dark:text-gray-200"
+                            >
+                                <h1 class="font-bold text-lg">{t!("login-title")}</h1>
+                                <input
+                                    class=INPUT_CLASS
+                                    type="email"
+                                    name="email"
+                                    placeholder=t!("form-email")
+                                    required
+                                />
+                                <input
+                                    class=INPUT_CLASS
+                                    type="password"
+                                    name="password"
+                                    placeholder=t!("form-password")
+                                    required
+                                />
+                                <button class=BUTTON_CLASS type="submit">
+                                    {t!("login-submit")}
+                                </button>
+                                <FormResult action=login success="login-done" />
+                                <a href="/reset_password">{t!("login-forgot-password")}</a>
+                                <a href="/register">{t!("login-register")}</a>
+                            </ActionForm>
+                        }
+                    }
+                >
+                    <ActionForm
+                        action=login_totp
+                        attr:class="flex flex-col gap-2 mx-auto w-80 text-gray-800 dark:text-gray-200"
+                    >
+                        <h1 class="font-bold text-lg">{t!("login-totp-title")}</h1>
+                        <p>{t!("login-totp-hint")}</p>
+                        <input type="hidden" name="challenge" value=challenge />
+                        <input
+                            class=INPUT_CLASS
+                            type="text"
+                            name="code"
+                            placeholder="123456"
+                            autocomplete="one-time-code"
+                            required
+                        />
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("form-verify")}
+                        </button>
+                        <FormResult action=login_totp success="login-done" />
+                    </ActionForm>
+                </Show>
+            </main>
+        }
+    }
+}
+
+pub mod verify_email {
+    use leptos::prelude::*;
+    use leptos_router::hooks::use_query_map;
+
+    use crate::{
+        api::{ResendVerification, VerifyEmail},
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS},
+            nav::Nav,
+        },
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let query = use_query_map();
+        let token = move || query.read().get("token").unwrap_or_default();
+        let verify = ServerAction::<VerifyEmail>::new();
+        let resend = ServerAction::<ResendVerification>::new();
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <div class="flex flex-col gap-6 mx-auto w-80 text-gray-800 dark:text-gray-200">
+                    <Show when=move || !token().is_empty()>
+                        <ActionForm action=verify attr:class="flex flex-col gap-2">
+                            <h1 class="font-bold text-lg">{t!("verify-title")}</h1>
+                            <input type="hidden" name="token" value=token />
+                            <button class=BUTTON_CLASS type="submit">
+                                {t!("form-verify")}
+                            </button>
+                            <FormResult action=verify success="verify-done" />
+                        </ActionForm>
+                    </Show>
+                    <ActionForm action=resend attr:class="flex flex-col gap-2">
+                        <h2 class="font-bold">{t!("verify-resend-title")}</h2>
+                        <input class=INPUT_CLASS type="email" name="email" placeholder=t!("form-email") required />
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("verify-resend")}
+                        </button>
+                        <FormResult
+                            action=resend
+                            success="verify-resend-done"
+                        />
+                    </ActionForm>
+                </div>
+            </main>
+        }
+    }
+}
+
+pub mod reset_password {
+    use leptos::prelude::*;
+    use leptos_router::hooks::use_query_map;
+
+    use crate::{
+        api::{MINIMUM_PASSWORD_LENGTH, RequestPasswordReset, ResetPassword},
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS},
+            nav::Nav,
+        },
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let query = use_query_map();
+        let token = move || query.read().get("token").unwrap_or_default();
+        let request = ServerAction::<RequestPasswordReset>::new();
+        let reset = ServerAction::<ResetPassword>::new();
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <div class="flex flex-col gap-2 mx-auto w-80 text-gray-800 dark:text-gray-200">
+                    <h1 class="font-bold text-lg">{t!("reset-title")}</h1>
+                    <Show
+                        when=move || !token().is_empty()
+                        fallback=move || {
+                            view! {
+                                <ActionForm action=request attr:class="flex flex-col gap-2">
+                                    <input
+                                        class=INPUT_CLASS
+                                        type="email"
+                                        name="email"
+                                        placeholder=t!("form-email")
+                                        required
+                                    />
+                                    <button class=BUTTON_CLASS type="submit">
+                                        {t!("reset-send")}
+                                    </button>
+                                    <FormResult
+                                        action=request
+                                        success="reset-sent"
+                                    />
+                                </ActionForm>
+                            }
+                        }
+                    >
+                        <ActionForm action=reset attr:class="flex flex-col gap-2">
+                            <input type="hidden" name="token" value=token />
+                            <input
+                                class=INPUT_CLASS
+                                type="password"
+                                name="password"
+                                placeholder=t!("reset-new-password")
+                                minlength=MINIMUM_PASSWORD_LENGTH
+                                required
+                            />
+                            <button class=BUTTON_CLASS type="submit">
+                                {t!("reset-submit")}
+                            </button>
+                            <FormResult action=reset success="reset-done" />
+                        </ActionForm>
+                    </Show>
+                </div>
+            </main>
+        }
+    }
+}
+
+pub mod settings {
+    use leptos::{html, prelude::*, task::spawn_local};
+    use leptos_router::hooks::use_query_map;
+
+    use crate::{
+        api::{
+            API_SCOPES, ApiTokenCreate, ApiTokenRevoke, ArtworkAccessGrant, ArtworkAccessRevoke,
+            ArtworkProtect, CONTENT_PREFS, ContentPrefsSet, DEFAULT_API_TOKEN_RATE_LIMIT,
+            DiscordLinkCode, DiscordUnlink, MAXIMUM_API_TOKEN_RATE_LIMIT,
+            MAXIMUM_WATERMARK_IMAGE_SIZE, MAXIMUM_WATERMARK_TEXT_LENGTH, RatingSet, TotpBegin,
+            TotpDisable, TotpEnable, TotpRecoveryCodes, WATERMARK_KINDS, WATERMARK_PLACEMENTS,
+            WEBHOOK_EVENTS, WatermarkSet, WebhookCreate, WebhookDelete, WebhookEnable, WebhookTest,
+            api_tokens, content_prefs, error_message, get_acc, own_artworks, watermark,
+            watermark_image_upload, webhook_deliveries, webhooks,
+        },
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS, RatingSelect},
+            nav::Nav,
+        },
+        i18n::use_i18n,
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let begin = ServerAction::<TotpBegin>::new();
+        let enable = ServerAction::<TotpEnable>::new();
+        let disable = ServerAction::<TotpDisable>::new();
+        let regenerate = ServerAction::<TotpRecoveryCodes>::new();
+        let link_code = ServerAction::<DiscordLinkCode>::new();
+        let unlink = ServerAction::<DiscordUnlink>::new();
+        let token_create = ServerAction::<ApiTokenCreate>::new();
+        let token_revoke = ServerAction::<ApiTokenRevoke>::new();
+        let token_scopes = RwSignal::new(Vec::<String>::new());
+        let webhook_create = ServerAction::<WebhookCreate>::new();
+        let webhook_delete = ServerAction::<WebhookDelete>::new();
+        let webhook_enable = ServerAction::<WebhookEnable>::new();
+        let webhook_test = ServerAction::<WebhookTest>::new();
+        let webhook_events = RwSignal::new(Vec::<String>::new());
+        let webhook_selected = RwSignal::new(None::<String>);
+        let query = use_query_map();
+        let acc = Resource::new(
+            move || {
+                (
+                    enable.version().get(),
+                    disable.version().get(),
+                    unlink.version().get(),
+                )
+            },
+            |_| get_acc(),
+        );
+
+        let discord_status = move || {
+            query
+                .read()
+                .get("discord")
+                .map(|status| match status.as_str() {
+                    "linked" => (
+                        String::from("text-green-400"),
+                        t!("settings-discord-linked"),
+                    ),
+                    error => (
+                        String::from("text-red-400"),
+                        t!("settings-discord-failed", error = error.replace('_', " ")),
+                    ),
+                })
+                .map(|(class, msg)| view! { <p class=class>{msg}</p> })
+        };
+
+        let discord = move || {
+            acc.get().map(|acc| match acc {
+                Ok(Some(acc)) => match acc.discord_user_id {
+                    Some(user_id) => view! {
+                        <p>{t!("settings-discord-linked-to", user = user_id)}</p>
+                        <ActionForm action=unlink>
+                            <button class=BUTTON_CLASS type="submit">
+                                {t!("settings-discord-unlink")}
+                            </button>
+                        </ActionForm>
+                    }
+                    .into_any(),
+                    None => view! {
+                        <a class=BUTTON_CLASS href="/auth/discord/start" rel="external">
+                            {t!("settings-discord-link")}
+                        </a>
+                        <ActionForm action=link_code>
+                            <button type="submit">{t!("settings-discord-get-code")}</button>
+                        </ActionForm>
+                        {move || {
+                            link_code
+                                .value()
+                                .get()
+                                .map(|code| match code {
+                                    Ok(code) => {
+                                        view! {
+                                            <p>
+                                                {t!("settings-discord-code-run")}
+                                                " "
+                                                <code>{format!("/link code:{}", code)}</code>
+                                                " "
+                                                {t!("settings-discord-code-where")}
+                                            </p>
+                                        }
+                                            .into_any()
+                                    }
+                                    Err(err) => {
+                                        view! { <p class="text-red-400">{error_message(&err)}</p> }
+                                            .into_any()
+                                    }
+                                })
+                        }}
+                    }
+                    .into_any(),
+                },
+                _ => ().into_any(),
+            })
+        };
+
+        let tokens = Resource::new(
+            move || (token_create.version().get(), token_revoke.version().get()),
+            |_| api_tokens(),
+        );
+
+        let new_token = move || {
+            let token = token_create.value().get()?.ok()?;
+            Some(view! {
+                <div class="flex flex-col gap-1">
+                    <p>{t!("settings-token-copy")}</p>
+                    <code class="break-all">{token.token}</code>
+                </div>
+            })
+        };
+
+        let token_list = move || {
+            tokens.get().map(|tokens| match tokens {
+                Ok(tokens) => tokens
+                    .into_iter()
+                    .map(|token| {
+                        let last_used = token
+                            .last_used
+                            .map(|time| use_i18n().time(time))
+                            .unwrap_or_else(|| t!("settings-token-never-used"));
+                        view! {
+                            <li class="flex flex-col gap-1 border-b border-gray-300 dark:border-gray-700 pb-2">
+                                <span class="font-bold">{token.name}</span>
+                                <span class="text-sm">{token.scopes.join(" ")}</span>
+                                <span class="text-sm">
+                                    {t!(
+                                        "settings-token-usage",
+                                        rate_limit = token.rate_limit,
+                                        last_used = last_used,
+                                    )}
+                                </span>
+                                <ActionForm action=token_revoke>
+                                    <input type="hidden" name="token_id" value=token.token_id />
+                                    <button type="submit">{t!("form-revoke")}</button>
+                                </ActionForm>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(_) => ().into_any(),
+            })
+        };
+
+        let scope_checkboxes = API_SCOPES
+            .map(|(scope, description)| {
+                let on_change = move |ev| {
+                    let checked = event_target_checked(&ev);
+                    token_scopes.update(|scopes| {
+                        scopes.retain(|s| s != scope);
+                        if checked {
+                            scopes.push(scope.to_string());
+                        }
+                    });
+                };
+                view! {
+                    <label class="flex gap-2" title=description>
+                        <input type="checkbox" on:change=on_change />
+                        <span>{scope}</span>
+                    </label>
+                }
+            })
+            .collect_view();
+
+        let hooks = Resource::new(
+            move || {
+                (
+                    webhook_create.version().get(),
+                    webhook_delete.version().get(),
+                    webhook_enable.version().get(),
+                )
+            },
+            |_| webhooks(),
+        );
+
+        let deliveries = Resource::new(
+            move || (webhook_selected.get(), webhook_test.version().get()),
+            |(webhook_id, _)| async move {
+                match webhook_id {
+                    Some(webhook_id) => webhook_deliveries(webhook_id).await,
+                    None => Ok(Vec::new()),
+                }
+            },
+        );
+
+        let new_webhook = move || {
+            let webhook = webhook_create.value().get()?.ok()?;
+            Some(view! {
+                <div class="flex flex-col gap-1">
+                    <p>{t!("settings-webhook-copy")}</p>
+                    <code class="break-all">{webhook.secret}</code>
+                </div>
+            })
+        };
+
+        let delivery_list = move || {
+            deliveries.get().map(|deliveries| match deliveries {
+                Ok(deliveries) => deliveries
+                    .into_iter()
+                    .map(|delivery| {
+                        let created_at = use_i18n().time(delivery.created_at);
+                        let response = delivery
+                            .last_response_code
+                            .map(|code| code.to_string())
+                            .or(delivery.last_error)
+                            .unwrap_or_else(|| t!("settings-webhook-not-sent"));
+                        view! {
+                            <li class="text-sm">
+                                {t!(
+                                    "settings-webhook-delivery",
+                                    created_at = created_at,
+                                    event = delivery.event,
+                                    status = delivery.status,
+                                    attempts = delivery.attempts,
+                                    response = response,
+                                )}
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(err) => {
+                    view! { <li class="text-red-400">{error_message(&err)}</li> }.into_any()
+                }
+            })
+        };
+
+        let webhook_list = move || {
+            hooks.get().map(|hooks| match hooks {
+                Ok(hooks) => hooks
+                    .into_iter()
+                    .map(|hook| {
+                        let webhook_id = hook.webhook_id.clone();
+                        let status = if hook.enabled {
+                            t!("settings-webhook-enabled", failures = hook.failures)
+                        } else {
+                            t!("settings-webhook-disabled")
+                        };
+                        let show_deliveries = move |_| webhook_selected.set(Some(webhook_id.clone()));
+                        let toggle_id = hook.webhook_id.clone();
+                        let toggle = if hook.enabled {
+                            view! {
+                                <ActionForm action=webhook_test>
+                                    <input type="hidden" name="webhook_id" value=toggle_id />
+                                    <button type="submit">{t!("settings-webhook-test")}</button>
+                                </ActionForm>
+                            }
+                            .into_any()
+                        } else {
+                            view! {
+                                <ActionForm action=webhook_enable>
+                                    <input type="hidden" name="webhook_id" value=toggle_id />
+                                    <button type="submit">{t!("form-enable")}</button>
+                                </ActionForm>
+                            }
+                            .into_any()
+                        };
+                        view! {
+                            <li class="flex flex-col gap-1 border-b border-gray-300 dark:border-gray-700 pb-2">
+                                <span class="font-bold break-all">{hook.url}</span>
+                                <span class="text-sm">{hook.events.join(" ")}</span>
+                                <span class="text-sm">{status}</span>
+                                <div class="flex gap-2">
+                                    {toggle}
+                                    <button on:click=show_deliveries>{t!("settings-webhook-deliveries")}</button>
+                                    <ActionForm action=webhook_delete>
+                                        <input type="hidden" name="webhook_id" value=hook.webhook_id />
+                                        <button type="submit">{t!("settings-webhook-delete")}</button>
+                                    </ActionForm>
+                                </div>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(_) => ().into_any(),
+            })
+        };
+
+        let event_checkboxes = WEBHOOK_EVENTS
+            .map(|(event, description)| {
+                let on_change = move |ev| {
+                    let checked = event_target_checked(&ev);
+                    webhook_events.update(|events| {
+                        events.retain(|e| e != event);
+                        if checked {
+                            events.push(event.to_string());
+                        }
+                    });
+                };
+                view! {
+                    <label class="flex gap-2" title=description>
+                        <input type="checkbox" on:change=on_change />
+                        <span>{event}</span>
+                    </label>
+                }
+            })
+            .collect_view();
+
+        let recovery_codes = move || {
+            let codes = enable
+                .value()
+                .get()
+                .or_else(|| regenerate.value().get())
+                .and_then(|codes| codes.ok())?;
+            Some(view! {
+                <div class="flex flex-col gap-1">
+                    <p>{t!("settings-totp-recovery-codes")}</p>
+                    <ul class="font-mono">
+                        {codes.into_iter().map(|code| view! { <li>{code}</li> }).collect_view()}
+                    </ul>
+                </div>
+            })
+        };
+
+        let enrollment = move || {
+            let enrollment = begin.value().get()?;
+            Some(match enrollment {
+                Ok(enrollment) => view! {
+                    <div class="flex flex-col gap-2">
+                        <p>{t!("settings-totp-scan")}</p>
+                        <div class="w-[200px]" inner_html=enrollment.qr_svg></div>
+                        <code class="break-all">{enrollment.secret}</code>
+                        <ActionForm action=enable attr:class="flex flex-col gap-2">
+                            <input
+                                class=INPUT_CLASS
+                                type="text"
+                                name="code"
+                                placeholder="123456"
+                                autocomplete="one-time-code"
+                                required
+                            />
+                            <button class=BUTTON_CLASS type="submit">
+                                {t!("form-enable")}
+                            </button>
+                            <FormResult action=enable success="settings-totp-enable-done" />
+                        </ActionForm>
+                    </div>
+                }
+                .into_any(),
+                Err(err) => view! { <p class="text-red-400">{error_message(&err)}</p> }.into_any(),
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <div class="flex flex-col gap-4 mx-auto w-80 text-gray-800 dark:text-gray-200">
+                    <h1 class="font-bold text-lg">{t!("settings-title")}</h1>
+                    <h2 class="font-bold">{t!("settings-totp-title")}</h2>
+                    <Transition>
+                        {move || {
+                            acc.get()
+                                .map(|acc| match acc {
+                                    Ok(Some(acc)) if acc.totp_enabled => {
+                                        view! {
+                                            <p>
+                                                {t!(
+                                                    "settings-totp-enabled",
+                                                    count = acc.recovery_codes_left,
+                                                )}
+                                            </p>
+                                            <ActionForm action=regenerate attr:class="flex flex-col gap-2">
+                                                <input
+                                                    class=INPUT_CLASS
+                                                    type="text"
+                                                    name="code"
+                                                    placeholder=t!("form-code")
+                                                    required
+                                                />
+                                                <button class=BUTTON_CLASS type="submit">
+                                                    {t!("settings-totp-new-codes")}
+                                                </button>
+                                                <FormResult action=regenerate />
+                                            </ActionForm>
+                                            <ActionForm action=disable attr:class="flex flex-col gap-2">
+                                                <input
+                                                    class=INPUT_CLASS
+                                                    type="text"
+                                                    name="code"
+                                                    placeholder=t!("form-code")
+                                                    required
+                                                />
+                                                <button class=BUTTON_CLASS type="submit">
+                                                    {t!("settings-totp-disable")}
+                                                </button>
+                                                <FormResult
+                                                    action=disable
+                                                    success="settings-totp-disabled"
+                                                />
+                                            </ActionForm>
+                                        }
+                                            .into_any()
+                                    }
+                                    Ok(Some(_)) => {
+                                        view! {
+                                            <ActionForm action=begin>
+                                                <button class=BUTTON_CLASS type="submit">
+                                                    {t!("settings-totp-set-up")}
+                                                </button>
+                                            </ActionForm>
+                                            {enrollment}
+                                        }
+                                            .into_any()
+                                    }
+                                    _ => view! { <a href="/login">{t!("settings-login")}</a> }.into_any(),
+                                })
+                        }}
+                    </Transition>
+                    {recovery_codes}
+                    <h2 class="font-bold">{t!("settings-discord-title")}</h2>
+                    {discord_status}
+                    <Transition>{discord}</Transition>
+                    <h2 class="font-bold">{t!("settings-tokens-title")}</h2>
+                    <p class="text-sm">{t!("settings-tokens-hint")}</p>
+                    <ActionForm action=token_create attr:class="flex flex-col gap-2">
+                        <input class=INPUT_CLASS type="text" name="name" placeholder=t!("settings-token-name") required />
+                        {scope_checkboxes}
+                        <input type="hidden" name="scopes" prop:value=move || token_scopes.get().join(" ") />
+                        <label class="flex flex-col gap-1">
+                            {t!("settings-token-rate-limit")}
+                            <input
+                                class=INPUT_CLASS
+                                type="number"
+                                name="rate_limit"
+                                min=1
+                                max=MAXIMUM_API_TOKEN_RATE_LIMIT
+                                value=DEFAULT_API_TOKEN_RATE_LIMIT
+                                required
+                            />
+                        </label>
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("settings-token-create")}
+                        </button>
+                        <FormResult action=token_create />
+                    </ActionForm>
+                    {new_token}
+                    <ul class="flex flex-col gap-2">
+                        <Transition>{token_list}</Transition>
+                    </ul>
+                    <h2 class="font-bold">{t!("settings-webhooks-title")}</h2>
+                    <p class="text-sm">
+                        {t!("settings-webhooks-hint")}
+                    </p>
+                    <ActionForm action=webhook_create attr:class="flex flex-col gap-2">
+                        <input
+                            class=INPUT_CLASS
+                            type="url"
+                            name="url"
+                            placeholder="https://example.com/hook"
+                            required
+                        />
+                        {event_checkboxes}
+                        <input type="hidden" name="events" prop:value=move || webhook_events.get().join(" ") />
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("settings-webhook-add")}
+                        </button>
+                        <FormResult action=webhook_create />
+                    </ActionForm>
+                    {new_webhook}
+                    <FormResult action=webhook_test success="settings-webhook-test-done" />
+                    <ul class="flex flex-col gap-2">
+                        <Transition>{webhook_list}</Transition>
+                    </ul>
+                    <ul class="flex flex-col gap-1">
+                        <Transition>{delivery_list}</Transition>
+                    </ul>
+                    <ContentPrefs />
+                    <Watermark />
+                </div>
+            </main>
+        }
+    }
+
+    /// Reads the picked png and stores it as the watermark image.
+    async fn upload_watermark(file: Option<web_sys::File>) -> Result<(), String> {
+        let Some(file) = file else {
+            return Err(String::from("pick a png"));
+        };
+        if file.size() as usize > MAXIMUM_WATERMARK_IMAGE_SIZE {
+            return Err(format!("{} is too large", file.name()));
+        }
+        let data = gloo::file::futures::read_as_bytes(&gloo::file::File::from(file))
+            .await
+            .map_err(|err| err.to_string())?;
+        watermark_image_upload(data)
+            .await
+            .map_err(|err| error_message(&err))
+    }
+
+    /// Watermark used on public previews of protected artworks, and who may download the originals.
+    #[component]
+    fn Watermark() -> impl IntoView {
+        let set = ServerAction::<WatermarkSet>::new();
+        let protect = ServerAction::<ArtworkProtect>::new();
+        let grant = ServerAction::<ArtworkAccessGrant>::new();
+        let revoke = ServerAction::<ArtworkAccessRevoke>::new();
+        let rate = ServerAction::<RatingSet>::new();
+        let uploaded = RwSignal::new(0_u64);
+        let upload_error = RwSignal::new(None::<String>);
+        let file_ref = NodeRef::<html::Input>::new();
+
+        let current = Resource::new(
+            move || (set.version().get(), uploaded.get()),
+            |_| watermark(),
+        );
+        let artworks = Resource::new(
+            move || {
+                (
+                    protect.version().get(),
+                    grant.version().get(),
+                    revoke.version().get(),
+                    rate.version().get(),
+                )
+            },
+            |_| own_artworks(0),
+        );
+
+        let on_upload = move |ev: leptos::ev::SubmitEvent| {
+            ev.prevent_default();
+            let file = file_ref
+                .get_untracked()
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            upload_error.set(None);
+            spawn_local(async move {
+                match upload_watermark(file).await {
+                    Ok(()) => uploaded.update(|uploaded| *uploaded += 1),
+                    Err(err) => upload_error.set(Some(err)),
+                }
+            });
+        };
+
+        let settings_form = move || {
+            current.get().map(|current| match current {
+                Ok(current) => {
+                    let kinds = WATERMARK_KINDS
+                        .map(|(kind, _)| {
+                            view! {
+                                <option value=kind selected=current.kind == kind>
+                                    {t!(&format!("watermark-kind-{}", kind))}
+                                </option>
+                            }
+                        })
+                        .collect_view();
+                    let placements = WATERMARK_PLACEMENTS
+                        .map(|(placement, _)| {
+                            view! {
+                                <option value=placement selected=current.placement == placement>
+                                    {t!(&format!("watermark-placement-{}", placement))}
+                                </option>
+                            }
+                        })
+                        .collect_view();
+                    let image_status = if current.has_image {
+                        t!("watermark-has-image")
+                    } else {
+                        t!("watermark-no-image")
+                    };
+                    view! {
+                        <ActionForm action=set attr:class="flex flex-col gap-2">
+                            <select class=INPUT_CLASS name="kind">
+                                {kinds}
+                            </select>
+                            <input
+                                class=INPUT_CLASS
+                                type="text"
+                                name="text"
+                                placeholder=t!("watermark-text")
+                                maxlength=MAXIMUM_WATERMARK_TEXT_LENGTH
+                                value=current.text
+                            />
+                            <select class=INPUT_CLASS name="placement">
+                                {placements}
+                            </select>
+                            <label class="flex flex-col gap-1">
+                                {t!("watermark-opacity")}
+                                <input
+                                    class=INPUT_CLASS
+                                    type="number"
+                                    name="opacity"
+                                    min=1
+                                    max=100
+                                    value=current.opacity
+                                    required
+                                />
+                            </label>
+                            <button class=BUTTON_CLASS type="submit">
+                                {t!("watermark-save")}
+                            </button>
+                            <FormResult action=set success="watermark-done" />
+                        </ActionForm>
+                        <p class="text-sm">{image_status}</p>
+                    }
+                    .into_any()
+                }
+                Err(_) => ().into_any(),
+            })
+        };
+
+        let artwork_list = move || {
+            artworks.get().map(|artworks| match artworks {
+                Ok(artworks) => artworks
+                    .into_iter()
+                    .map(|artwork| {
+                        let protect_id = artwork.artwork_id.clone();
+                        let grant_id = artwork.artwork_id.clone();
+                        let rate_id = artwork.artwork_id.clone();
+                        let (status, toggle_label) = if artwork.protected {
+                            (t!("watermark-protected"), t!("watermark-unprotect"))
+                        } else {
+                            (t!("watermark-public"), t!("watermark-protect"))
+                        };
+                        let buyers = artwork
+                            .buyers
+                            .into_iter()
+                            .map(|buyer| {
+                                let artwork_id = artwork.artwork_id.clone();
+                                view! {
+                                    <li class="flex gap-2 text-sm">
+                                        <span>{format!("@{}", buyer)}</span>
+                                        <ActionForm action=revoke>
+                                            <input type="hidden" name="artwork_id" value=artwork_id />
+                                            <input type="hidden" name="username" value=buyer />
+                                            <button type="submit">{t!("form-revoke")}</button>
+                                        </ActionForm>
+                                    </li>
+                                }
+                            })
+                            .collect_view();
+                        view! {
+                            <li class="flex flex-col gap-1 border-b border-gray-300 dark:border-gray-700 pb-2">
+                                {artwork
+                                    .preview_url
+                                    .map(|url| view! { <img class="max-h-24 self-start" src=url /> })}
+                                <span class="font-bold">{artwork.title}</span>
+                                <span class="text-sm">{status}</span>
+                                <ActionForm action=protect>
+                                    <input type="hidden" name="artwork_id" value=protect_id />
+                                    <input
+                                        type="hidden"
+                                        name="protected"
+                                        value=(!artwork.protected).to_string()
+                                    />
+                                    <button type="submit">{toggle_label}</button>
+                                </ActionForm>
+                                <ActionForm action=rate attr:class="flex gap-2">
+                                    <input type="hidden" name="kind" value="artwork" />
+                                    <input type="hidden" name="target_id" value=rate_id />
+                                    <input type="hidden" name="reason" value="" />
+                                    <RatingSelect selected=artwork.rating />
+                                    <button type="submit">{t!("watermark-rate")}</button>
+                                </ActionForm>
+                                <ul class="flex flex-col gap-1">{buyers}</ul>
+                                <ActionForm action=grant attr:class="flex gap-2">
+                                    <input type="hidden" name="artwork_id" value=grant_id />
+                                    <input
+                                        class=INPUT_CLASS
+                                        type="text"
+                                        name="username"
+                                        placeholder=t!("watermark-buyer")
+                                        required
+                                    />
+                                    <button type="submit">{t!("watermark-grant")}</button>
+                                </ActionForm>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(_) => ().into_any(),
+            })
+        };
+
+        view! {
+            <h2 class="font-bold">{t!("watermark-title")}</h2>
+            <p class="text-sm">
+                {t!("watermark-hint")}
+            </p>
+            <Transition>{settings_form}</Transition>
+            <form class="flex flex-col gap-2" on:submit=on_upload>
+                <input class=INPUT_CLASS type="file" accept="image/png" node_ref=file_ref />
+                <button class=BUTTON_CLASS type="submit">
+                    {t!("watermark-upload")}
+                </button>
+                {move || upload_error.get().map(|err| view! { <p class="text-red-400">{err}</p> })}
+            </form>
+            <h2 class="font-bold">{t!("watermark-artworks-title")}</h2>
+            <FormResult action=grant />
+            <FormResult action=rate />
+            <ul class="flex flex-col gap-2">
+                <Transition>{artwork_list}</Transition>
+            </ul>
+        }
+    }
+
+    /// What happens to mature and explicit artworks and bounties in listings.
+    #[component]
+    fn ContentPrefs() -> impl IntoView {
+        let set = ServerAction::<ContentPrefsSet>::new();
+        let prefs = Resource::new(move || set.version().get(), |_| content_prefs());
+
+        let select = |name: &'static str, selected: String| {
+            let options = CONTENT_PREFS
+                .map(|(pref, _)| {
+                    view! {
+                        <option value=pref selected=selected == pref>
+                            {t!(&format!("content-{}", pref))}
+                        </option>
+                    }
+                })
+                .collect_view();
+            view! {
+                <select class=INPUT_CLASS name=name>
+                    {options}
+                </select>
+            }
+        };
+
+        let form = move || {
+            prefs.get().map(|prefs| match prefs {
+                Ok(prefs) => view! {
+                    <ActionForm action=set attr:class="flex flex-col gap-2">
+                        <label class="flex flex-col gap-1">
+                            {t!("content-mature")}
+                            {select("mature", prefs.mature)}
+                        </label>
+                        <label class="flex flex-col gap-1">
+                            {t!("content-explicit")}
+                            {select("explicit", prefs.explicit)}
+                        </label>
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("form-save")}
+                        </button>
+                        <FormResult action=set success="content-done" />
+                    </ActionForm>
+                }
+                .into_any(),
+                Err(_) => ().into_any(),
+            })
+        };
+
+        view! {
+            <h2 class="font-bold">{t!("content-title")}</h2>
+            <Transition>{form}</Transition>
+        }
+    }
+}
+pub mod messages {
+    use leptos::{html, prelude::*, task::spawn_local};
+    use leptos_router::hooks::{use_navigate, use_query_map};
+
+    use crate::{
+        api::{
+            ConversationStart, MAXIMUM_ATTACHMENT_SIZE, MAXIMUM_ATTACHMENTS, UserBlock,
+            UserUnblock, blocked_users, conversation_mute, conversation_read, conversations,
+            error_message, get_acc, message_attachment_upload, message_send, messages,
+        },
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS},
+            message_events::use_message_events,
+            nav::Nav,
+        },
+        i18n::use_i18n,
+        t,
+    };
+
+    /// Uploads the picked images, then sends the message with them attached.
+    async fn send_with_attachments(
+        conversation_id: String,
+        body: String,
+        files: Vec<web_sys::File>,
+    ) -> Result<(), String> {
+        if files.len() > MAXIMUM_ATTACHMENTS {
+            return Err(format!(
+                "at most {} images per message",
+                MAXIMUM_ATTACHMENTS
+            ));
+        }
+        let mut attachments = Vec::with_capacity(files.len());
+        for file in files {
+            if file.size() as usize > MAXIMUM_ATTACHMENT_SIZE {
+                return Err(format!("{} is too large", file.name()));
+            }
+            let data = gloo::file::futures::read_as_bytes(&gloo::file::File::from(file))
+                .await
+                .map_err(|err| err.to_string())?;
+            let attachment_id = message_attachment_upload(conversation_id.clone(), data)
+                .await
+                .map_err(|err| error_message(&err))?;
+            attachments.push(attachment_id);
+        }
+        message_send(conversation_id, body, attachments.join(" "))
+            .await
+            .map_err(|err| error_message(&err))?;
+        Ok(())
+    }
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let query = use_query_map();
+        let navigate = use_navigate();
+        let events = use_message_events();
+        let start = ServerAction::<ConversationStart>::new();
+        let block = ServerAction::<UserBlock>::new();
+        let unblock = ServerAction::<UserUnblock>::new();
+        let sent = RwSignal::new(0_u64);
+        let muted = RwSignal::new(0_u64);
+        let body = RwSignal::new(String::new());
+        let sending = RwSignal::new(false);
+        let send_error = RwSignal::new(None::<String>);
+        let files_ref = NodeRef::<html::Input>::new();
+
+        let selected = move || query.read().get("c");
+        let acc = Resource::new(|| (), |_| get_acc());
+        let list = Resource::new(
+            move || {
+                (
+                    events.version.get(),
+                    start.version().get(),
+                    sent.get(),
+                    muted.get(),
+                )
+            },
+            |_| conversations(),
+        );
+        let thread = Resource::new(
+            move || (selected(), events.version.get(), sent.get()),
+            |(conversation_id, _, _)| async move {
+                match conversation_id {
+                    Some(conversation_id) => messages(conversation_id, None).await,
+                    None => Ok(Vec::new()),
+                }
+            },
+        );
+        let blocked = Resource::new(
+            move || (block.version().get(), unblock.version().get()),
+            |_| blocked_users(),
+        );
+
+        Effect::new(move || {
+            if let Some(Ok(conversation_id)) = start.value().get() {
+                navigate(
+                    &format!("/messages?c={}", conversation_id),
+                    Default::default(),
+                );
+            }
+        });
+
+        // opening a conversation, or a message arriving while it's open, marks it read
+        Effect::new(move || {
+            let Some(Ok(messages)) = thread.get() else {
+                return;
+            };
+            let Some(conversation_id) = selected() else {
+                return;
+            };
+            if messages.is_empty() {
+                return;
+            }
+            spawn_local(async move {
+                let _ = conversation_read(conversation_id).await;
+            });
+        });
+
+        let username = move || {
+            acc.get()
+                .and_then(|acc| acc.ok().flatten())
+                .map(|acc| acc.username)
+                .unwrap_or_default()
+        };
+
+        let current = move || {
+            let conversation_id = selected()?;
+            list.get()?
+                .ok()?
+                .into_iter()
+                .find(|conversation| conversation.conversation_id == conversation_id)
+        };
+
+        let on_send = move |ev: leptos::ev::SubmitEvent| {
+            ev.prevent_default();
+            let Some(conversation_id) = selected() else {
+                return;
+            };
+            let files = files_ref
+                .get_untracked()
+                .and_then(|input| input.files())
+                .map(|files| (0..files.length()).filter_map(|i| files.get(i)).collect())
+                .unwrap_or_default();
+            sending.set(true);
+            send_error.set(None);
+            spawn_local(async move {
+                match send_with_attachments(conversation_id, body.get_untracked(), files).await {
+                    Ok(()) => {
+                        body.set(String::new());
+                        if let Some(input) = files_ref.get_untracked() {
+                            input.set_value("");
+                        }
+                        sent.update(|sent| *sent += 1);
+                    }
+                    Err(err) => send_error.set(Some(err)),
+                }
+                sending.set(false);
+            });
+        };
+
+        let toggle_mute = move |_| {
+            let Some(conversation) = current() else {
+                return;
+            };
+            spawn_local(async move {
+                let _ = conversation_mute(conversation.conversation_id, !conversation.muted).await;
+                muted.update(|muted| *muted += 1);
+            });
+        };
+
+        let conversation_list = move || {
+            let me = username();
+            list.get().map(|list| match list {
+                Ok(list) => list
+                    .into_iter()
+                    .map(|conversation| {
+                        let active = selected().as_ref() == Some(&conversation.conversation_id);
+                        let names = conversation
+                            .participants
+                            .iter()
+                            .map(|participant| participant.username.as_str())
+                            .filter(|name| *name != me)
+                            .collect::<Vec<&str>>()
+                            .join(", ");
+                        let preview = conversation
+                            .last_message
+                            .map(|message| format!("{}: {}", message.author, message.body))
+                            .unwrap_or_default();
+                        let unread = (conversation.unread > 0).then(|| {
+                            view! {
+                                <span class="ml-auto bg-gray-800 text-gray-100 dark:bg-gray-200 dark:text-gray-950 px-1 text-sm">
+                                    {conversation.unread}
+                                </span>
+                            }
+                        });
+                        view! {
+                            <li>
+                                <a
+                                    class="flex flex-col px-2 py-1 border-b border-gray-300 dark:border-gray-700"
+                                    class=("bg-gray-200", active)
+                                    class=("dark:bg-gray-800", active)
+                                    href=format!("/messages?c={}", conversation.conversation_id)
+                                >
+                                    <span class="flex gap-2 font-bold">
+                                        {names}
+                                        {conversation.muted.then(|| t!("messages-muted"))}
+                                        {unread}
+                                    </span>
+                                    <span class="text-sm truncate">{preview}</span>
+                                </a>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(err) => {
+                    view! { <li class="text-red-400">{error_message(&err)}</li> }.into_any()
+                }
+            })
+        };
+
+        let message_list = move || {
+            let me = username();
+            let participants = current()
+                .map(|conversation| conversation.participants)
+                .unwrap_or_default();
+            thread.get().map(|thread| match thread {
+                Ok(thread) => {
+                    let last_at = thread.last().map(|message| message.created_at);
+                    let seen_by = participants
+                        .iter()
+                        .filter(|participant| participant.username != me)
+                        .filter(|participant| last_at.is_some_and(|at| participant.last_read_at >= at))
+                        .map(|participant| participant.username.clone())
+                        .collect::<Vec<String>>();
+                    let receipt = (!seen_by.is_empty())
+                        .then(|| view! { <p class="text-sm text-right">{t!("messages-seen-by", usernames = seen_by.join(", "))}</p> });
+                    view! {
+                        {thread
+                            .into_iter()
+                            .map(|message| {
+                                let own = message.author == me;
+                                view! {
+                                    <div class="flex flex-col gap-1 max-w-[70%]" class=("self-end", own)>
+                                        <span class="text-sm text-gray-500 dark:text-gray-400">
+                                            {format!("{} {}", message.author, use_i18n().time(message.created_at))}
+                                        </span>
+                                        <p class="whitespace-pre-wrap bg-gray-200 dark:bg-gray-800 px-2 py-1">{message.body}</p>
+                                        {message
+                                            .attachments
+                                            .into_iter()
+                                            .map(|url| {
+                                                let href = url.clone();
+                                                view! {
+                                                    <a href=href target="_blank" rel="external">
+                                                        <img class="max-w-64 max-h-64" src=url />
+                                                    </a>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </div>
+                                }
+                            })
+                            .collect_view()}
+                        {receipt}
+                    }
+                        .into_any()
+                }
+                Err(err) => view! { <p class="text-red-400">{error_message(&err)}</p> }.into_any(),
+            })
+        };
+
+        let blocked_list = move || {
+            blocked.get().map(|blocked| match blocked {
+                Ok(blocked) => blocked
+                    .into_iter()
+                    .map(|username| {
+                        view! {
+                            <li class="flex gap-2">
+                                {username.clone()}
+                                <ActionForm action=unblock>
+                                    <input type="hidden" name="username" value=username />
+                                    <button type="submit">{t!("messages-unblock")}</button>
+                                </ActionForm>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(_) => ().into_any(),
+            })
+        };
+
+        let sidebar = view! {
+            <aside class="flex flex-col gap-2 min-h-0 overflow-y-auto border-r border-gray-300 dark:border-gray-700 px-2">
+                <h1 class="font-bold text-lg">{t!("messages-title")}</h1>
+                <ActionForm action=start attr:class="flex flex-col gap-2">
+                    <input
+                        class=INPUT_CLASS
+                        type="text"
+                        name="participants"
+                        placeholder=t!("messages-usernames")
+                        required
+                    />
+                    <textarea class=INPUT_CLASS name="body" placeholder=t!("form-message") required></textarea>
+                    <button class=BUTTON_CLASS type="submit">
+                        {t!("messages-start")}
+                    </button>
+                    <FormResult action=start />
+                </ActionForm>
+                <ul class="flex flex-col">
+                    <Transition>{conversation_list}</Transition>
+                </ul>
+                <h2 class="font-bold">{t!("messages-blocked-title")}</h2>
+                <ActionForm action=block attr:class="flex gap-2">
+                    <input
+                        class=INPUT_CLASS
+                        type="text"
+                        name="username"
+                        placeholder=t!("form-username")
+                        required
+                    />
+                    <button type="submit">{t!("messages-block")}</button>
+                </ActionForm>
+                <FormResult action=block />
+                <ul class="flex flex-col gap-1">
+                    <Transition>{blocked_list}</Transition>
+                </ul>
+            </aside>
+        }
+        .into_any();
+
+        let conversation_pane = view! {
+            <section class="grid grid-rows-[auto_1fr_auto] min-h-0">
+                <Show
+                    when=move || selected().is_some()
+                    fallback=|| view! { <p class="p-2">{t!("messages-pick")}</p> }
+                >
+                    <div class="flex gap-2 items-center border-b border-gray-300 dark:border-gray-700 px-2 py-1">
+                        <Transition>
+                            {move || {
+                                current()
+                                    .map(|conversation| {
+                                        let label = if conversation.muted {
+                                            t!("messages-unmute")
+                                        } else {
+                                            t!("messages-mute")
+                                        };
+                                        view! { <button on:click=toggle_mute>{label}</button> }
+                                    })
+                            }}
+                        </Transition>
+                    </div>
+                    <div class="flex flex-col gap-2 overflow-y-auto p-2">
+                        <Transition>{message_list}</Transition>
+                    </div>
+                    <form class="flex flex-col gap-2 border-t border-gray-300 dark:border-gray-700 p-2" on:submit=on_send>
+                        <textarea
+                            class=INPUT_CLASS
+                            placeholder=t!("form-message")
+                            prop:value=move || body.get()
+                            on:input=move |ev| body.set(event_target_value(&ev))
+                        ></textarea>
+                        <div class="flex gap-2 items-center">
+                            <input node_ref=files_ref type="file" accept="image/*" multiple />
+                            <button class=BUTTON_CLASS type="submit" disabled=move || sending.get()>
+                                {t!("messages-send")}
+                            </button>
+                        </div>
+                        {move || send_error.get().map(|err| view! { <p class="text-red-400">{err}</p> })}
+                    </form>
+                </Show>
+            </section>
+        }
+        .into_any();
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <div class="grid grid-cols-[20rem_1fr] min-h-0 text-gray-800 dark:text-gray-200">
+                    {sidebar}
+                    {conversation_pane}
+                </div>
+            </main>
+        }
+    }
+}
+
+pub mod upload {
+    use leptos::{html, prelude::*, task::spawn_local};
+
+    use crate::{
+        api::{RATING_SFW, UploadFinalize},
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS, RatingSelect},
+            nav::Nav,
+            uploader::{UploadEntry, UploadStatus, resume, storage, storage_key, use_uploads},
+        },
+        t,
+    };
+
+    #[component]
+    fn Upload(entry: UploadEntry) -> impl IntoView {
+        let finalize = ServerAction::<UploadFinalize>::new();
+        let name = entry.name.clone();
+        let size = entry.blob.size() as u64;
+        let key = storage_key(&entry);
+        let sha256 = entry.sha256.clone();
+        let preview_url = entry.preview_url.clone();
+        let title = name
+            .rsplit_once('.')
+            .map(|(stem, _)| stem.to_string())
+            .unwrap_or_else(|| name.clone());
+        let offset = entry.offset;
+        let status = entry.status;
+
+        Effect::new(move || {
+            if let Some(Ok(_)) = finalize.value().get()
+                && let Some(storage) = storage()
+            {
+                let _ = storage.remove_item(&key);
+            }
+        });
+
+        let percent = move || (offset.get() * 100).checked_div(size).unwrap_or(100);
+        let status_label = move || match status.get() {
+            UploadStatus::Uploading => format!("{}%", percent()),
+            UploadStatus::Paused => t!("upload-paused", percent = percent()),
+            UploadStatus::Duplicate(_) => t!("upload-duplicate"),
+            UploadStatus::Done(_) => t!("upload-done"),
+            UploadStatus::Failed(err) => err,
+        };
+        let controls = move || {
+            let entry = entry.clone();
+            match status.get() {
+                UploadStatus::Uploading => view! {
+                    <button class=BUTTON_CLASS on:click=move |_| status.set(UploadStatus::Paused)>
+                        {t!("upload-pause")}
+                    </button>
+                }
+                .into_any(),
+                UploadStatus::Paused | UploadStatus::Failed(_) => view! {
+                    <button
+                        class=BUTTON_CLASS
+                        disabled=move || entry.running.get()
+                        on:click=move |_| resume(entry.clone())
+                    >
+                        {t!("upload-resume")}
+                    </button>
+                }
+                .into_any(),
+                UploadStatus::Duplicate(_) => view! {
+                    <button class=BUTTON_CLASS on:click=move |_| resume(entry.clone())>
+                        {t!("upload-anyway")}
+                    </button>
+                }
+                .into_any(),
+                UploadStatus::Done(upload_id) => {
+                    let title = title.clone();
+                    let sha256 = sha256.clone();
+                    view! {
+                        <ActionForm action=finalize attr:class="flex flex-col gap-2 w-full">
+                            <input type="hidden" name="upload_id" value=upload_id />
+                            <input type="hidden" name="sha256" value=sha256 />
+                            <input class=INPUT_CLASS type="text" name="title" value=title required />
+                            <textarea
+                                class=INPUT_CLASS
+                                name="description"
+                                placeholder=t!("form-description")
+                            ></textarea>
+                            <input class=INPUT_CLASS type="text" name="tags" placeholder=t!("form-tags") />
+                            <RatingSelect selected=RATING_SFW />
+                            <button class=BUTTON_CLASS type="submit">
+                                {t!("upload-publish")}
+                            </button>
+                            <FormResult action=finalize success="upload-published" />
+                        </ActionForm>
+                    }
+                    .into_any()
+                }
+            }
+        };
+
+        view! {
+            <li class="flex gap-2 border-b border-gray-300 dark:border-gray-700 pb-2">
+                <img class="w-24 h-24 object-cover" src=preview_url alt="" />
+                <div class="flex flex-col gap-1 grow">
+                    <div class="flex gap-2 items-center">
+                        <span class="font-bold truncate">{name}</span>
+                        <span class="ml-auto text-sm">{status_label}</span>
+                    </div>
+                    <progress class="w-full" max=size value=move || offset.get()></progress>
+                    <div class="flex gap-2">{controls}</div>
+                </div>
+            </li>
+        }
+    }
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let uploads = use_uploads();
+        let error = RwSignal::new(None::<String>);
+        let file_ref = NodeRef::<html::Input>::new();
+
+        let on_pick = move |_| {
+            let Some(files) = file_ref.get_untracked().and_then(|input| input.files()) else {
+                return;
+            };
+            for file in (0..files.length()).filter_map(|i| files.get(i)) {
+                spawn_local(async move {
+                    let name = file.name();
+                    if let Err(err) = uploads.add(file).await {
+                        error.set(Some(format!("{}: {}", name, err)));
+                    }
+                });
+            }
+            if let Some(input) = file_ref.get_untracked() {
+                input.set_value("");
+            }
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <section class="flex flex-col gap-4 p-2 text-gray-800 dark:text-gray-200 max-w-3xl">
+                    <h1 class="font-bold text-lg">{t!("upload-title")}</h1>
+                    <p class="text-sm">
+                        {t!("upload-hint")}
+                    </p>
+                    <input
+                        node_ref=file_ref
+                        class=INPUT_CLASS
+                        type="file"
+                        accept="image/png,image/jpeg,image/gif,image/webp"
+                        multiple
+                        on:change=on_pick
+                    />
+                    <p class="text-red-400">{move || error.get()}</p>
+                    <ul class="flex flex-col gap-2">
+                        <For
+                            each=move || uploads.entries.get()
+                            key=|entry| entry.id
+                            children=move |entry| view! { <Upload entry /> }
+                        />
+                    </ul>
+                </section>
+            </main>
+        }
+    }
+}
+
+pub mod commissions {
+    use leptos::{html, prelude::*, task::spawn_local};
+    use leptos_router::hooks::{use_navigate, use_params_map};
+
+    use crate::{
+        api::{
+            CommissionCancel, CommissionCreate, MAXIMUM_REVISION_SIZE, RATING_SFW, REVISION_STAGES,
+            RevisionComment, RevisionInfo, RevisionPublish, RevisionReview, commission,
+            commissions, error_message, revision_upload,
+        },
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS, RatingSelect},
+            nav::Nav,
+        },
+        i18n::use_i18n,
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let navigate = use_navigate();
+        let create = ServerAction::<CommissionCreate>::new();
+        let list = Resource::new(move || create.version().get(), |_| commissions());
+
+        Effect::new(move || {
+            if let Some(Ok(commission_id)) = create.value().get() {
+                navigate(
+                    &format!("/commissions/{}", commission_id),
+                    Default::default(),
+                );
+            }
+        });
+
+        let commission_list = move || {
+            list.get().map(|list| match list {
+                Ok(list) if list.is_empty() => view! { <li>{t!("commissions-empty")}</li> }.into_any(),
+                Ok(list) => list
+                    .into_iter()
+                    .map(|commission| {
+                        view! {
+                            <li>
+                                <a
+                                    class="flex gap-2 px-2 py-1 border-b border-gray-300 dark:border-gray-700"
+                                    href=format!("/commissions/{}", commission.commission_id)
+                                >
+                                    <span class="font-bold">{commission.title}</span>
+                                    <span>
+                                        {format!("{} → {}", commission.client, commission.artist)}
+                                    </span>
+                                    <span class="ml-auto text-sm">{commission.status}</span>
+                                </a>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(err) => {
+                    view! { <li class="text-red-400">{error_message(&err)}</li> }.into_any()
+                }
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <section class="flex flex-col gap-4 p-2 text-gray-800 dark:text-gray-200 max-w-3xl">
+                    <h1 class="font-bold text-lg">{t!("commissions-title")}</h1>
+                    <ActionForm action=create attr:class="flex flex-col gap-2">
+                        <input
+                            class=INPUT_CLASS
+                            type="text"
+                            name="artist"
+                            placeholder=t!("commissions-artist")
+                            required
+                        />
+                        <input class=INPUT_CLASS type="text" name="title" placeholder=t!("form-title") required />
+                        <input
+                            class=INPUT_CLASS
+                            type="text"
+                            name="bounty_id"
+                            placeholder=t!("commissions-bounty")
+                        />
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("commissions-order")}
+                        </button>
+                        <FormResult action=create />
+                    </ActionForm>
+                    <ul class="flex flex-col">
+                        <Transition>{commission_list}</Transition>
+                    </ul>
+                </section>
+            </main>
+        }
+    }
+
+    /// Reads the picked file and uploads it as the next revision.
+    async fn upload(
+        commission_id: String,
+        stage: String,
+        note: String,
+        watermark: bool,
+        file: Option<web_sys::File>,
+    ) -> Result<(), String> {
+        let Some(file) = file else {
+            return Err(String::from("pick an image"));
+        };
+        if file.size() as usize > MAXIMUM_REVISION_SIZE {
+            return Err(format!("{} is too large", file.name()));
+        }
+        let data = gloo::file::futures::read_as_bytes(&gloo::file::File::from(file))
+            .await
+            .map_err(|err| err.to_string())?;
+        revision_upload(commission_id, stage, note, watermark, data)
+            .await
+            .map_err(|err| error_message(&err))?;
+        Ok(())
+    }
+
+    #[component]
+    pub fn Detail() -> impl IntoView {
+        let params = use_params_map();
+        let cancel = ServerAction::<CommissionCancel>::new();
+        let review = ServerAction::<RevisionReview>::new();
+        let comment = ServerAction::<RevisionComment>::new();
+        let publish = ServerAction::<RevisionPublish>::new();
+        let uploaded = RwSignal::new(0_u64);
+        let uploading = RwSignal::new(false);
+        let upload_error = RwSignal::new(None::<String>);
+        let stage = RwSignal::new(String::from(REVISION_STAGES[0].0));
+        let note = RwSignal::new(String::new());
+        let watermark = RwSignal::new(true);
+        let file_ref = NodeRef::<html::Input>::new();
+        // revision and position the next comment gets pinned to
+        let pin = RwSignal::new(None::<(String, f32, f32)>);
+
+        let commission_id = move || params.read().get("id").unwrap_or_default();
+        let detail = Resource::new(
+            move || {
+                (
+                    commission_id(),
+                    cancel.version().get(),
+                    review.version().get(),
+                    comment.version().get(),
+                    publish.version().get(),
+                    uploaded.get(),
+                )
+            },
+            |(commission_id, ..)| commission(commission_id),
+        );
+
+        Effect::new(move || {
+            if let Some(Ok(_)) = comment.value().get() {
+                pin.set(None);
+            }
+        });
+
+        let on_upload = move |ev: leptos::ev::SubmitEvent| {
+            ev.prevent_default();
+            let file = file_ref
+                .get_untracked()
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            uploading.set(true);
+            upload_error.set(None);
+            spawn_local(async move {
+                let result = upload(
+                    commission_id(),
+                    stage.get_untracked(),
+                    note.get_untracked(),
+                    watermark.get_untracked(),
+                    file,
+                )
+                .await;
+                match result {
+                    Ok(()) => {
+                        note.set(String::new());
+                        if let Some(input) = file_ref.get_untracked() {
+                            input.set_value("");
+                        }
+                        uploaded.update(|uploaded| *uploaded += 1);
+                    }
+                    Err(err) => upload_error.set(Some(err)),
+                }
+                uploading.set(false);
+            });
+        };
+
+        let revision_view = move |revision: RevisionInfo,
+                                  is_artist: bool,
+                                  open: bool,
+                                  title: String| {
+            let revision_id = revision.revision_id.clone();
+            let pinning = {
+                let revision_id = revision_id.clone();
+                move || {
+                    pin.get()
+                        .filter(|(pinned, _, _)| *pinned == revision_id)
+                        .map(|(_, x, y)| (x, y))
+                }
+            };
+            let on_pin = {
+                let revision_id = revision_id.clone();
+                move |ev: leptos::ev::MouseEvent| {
+                    let target = event_target::<web_sys::Element>(&ev);
+                    let (width, height) = (target.client_width(), target.client_height());
+                    if width == 0 || height == 0 {
+                        return;
+                    }
+                    let x = (ev.offset_x() as f32 / width as f32).clamp(0.0, 1.0);
+                    let y = (ev.offset_y() as f32 / height as f32).clamp(0.0, 1.0);
+                    pin.set(Some((revision_id.clone(), x, y)));
+                }
+            };
+            let pins = revision
+                .comments
+                .iter()
+                .enumerate()
+                .filter_map(|(i, comment)| Some((i + 1, comment.x?, comment.y?)))
+                .map(|(n, x, y)| {
+                    view! {
+                        <span
+                            class="absolute -translate-x-1/2 -translate-y-1/2 bg-gray-800 text-gray-100 dark:bg-gray-200 dark:text-gray-950 text-xs font-bold px-1 pointer-events-none"
+                            style=format!("left: {}%; top: {}%", x * 100.0, y * 100.0)
+                        >
+                            {n}
+                        </span>
+                    }
+                })
+                .collect_view();
+            let new_pin = {
+                let pinning = pinning.clone();
+                move || {
+                    pinning().map(|(x, y)| {
+                        view! {
+                            <span
+                                class="absolute -translate-x-1/2 -translate-y-1/2 bg-red-400 text-gray-950 text-xs font-bold px-1 pointer-events-none"
+                                style=format!("left: {}%; top: {}%", x * 100.0, y * 100.0)
+                            >
+                                "+"
+                            </span>
+                        }
+                    })
+                }
+            };
+            let image = match revision.image_url.clone() {
+                Some(url) => view! {
+                    <div class="relative inline-block self-start">
+                        <img class="max-w-full max-h-[70vh] cursor-crosshair" src=url on:click=on_pin />
+                        {pins}
+                        {new_pin}
+                    </div>
+                }
+                .into_any(),
+                None => view! { <p class="text-sm">{t!("revision-rendering")}</p> }.into_any(),
+            };
+            let original = revision.original_url.clone().map(|url| {
+                view! {
+                    <a class="text-sm underline" href=url target="_blank" rel="external">
+                        {t!("revision-original")}
+                    </a>
+                }
+            });
+            let comments = revision
+                .comments
+                .iter()
+                .enumerate()
+                .map(|(i, comment)| {
+                    let marker = comment.x.map(|_| format!("#{} ", i + 1)).unwrap_or_default();
+                    view! {
+                        <li class="text-sm">
+                            <span class="text-gray-500 dark:text-gray-400">
+                                {format!("{}{} {}", marker, comment.author, use_i18n().time(comment.created_at))}
+                            </span>
+                            <p class="whitespace-pre-wrap">{comment.body.clone()}</p>
+                        </li>
+                    }
+                })
+                .collect_view();
+            let pin_inputs = move || {
+                pinning().map(|(x, y)| {
+                    view! {
+                        <input type="hidden" name="x" value=x.to_string() />
+                        <input type="hidden" name="y" value=y.to_string() />
+                        <span class="text-sm">{t!("revision-pinned")}</span>
+                        <button type="button" class="text-sm underline" on:click=move |_| pin.set(None)>
+                            {t!("revision-unpin")}
+                        </button>
+                    }
+                })
+            };
+            let pending = revision.status == "pending";
+            let review_forms = (!is_artist && open && pending).then(|| {
+                let approve_id = revision_id.clone();
+                let changes_id = revision_id.clone();
+                view! {
+                    <div class="flex gap-2 items-start">
+                        <ActionForm action=review>
+                            <input type="hidden" name="revision_id" value=approve_id />
+                            <input type="hidden" name="approve" value="true" />
+                            <input type="hidden" name="comment" value="" />
+                            <button class=BUTTON_CLASS type="submit">{t!("revision-approve")}</button>
+                        </ActionForm>
+                        <ActionForm action=review attr:class="flex flex-col gap-2 grow">
+                            <input type="hidden" name="revision_id" value=changes_id />
+                            <input type="hidden" name="approve" value="false" />
+                            <textarea class=INPUT_CLASS name="comment" placeholder=t!("revision-changes")></textarea>
+                            <button type="submit">{t!("revision-request-changes")}</button>
+                        </ActionForm>
+                    </div>
+                }
+            });
+            let publish_form = (is_artist
+                && revision.stage == "final"
+                && revision.status == "approved"
+                && revision.artwork_id.is_none())
+            .then(|| {
+                let publish_id = revision_id.clone();
+                view! {
+                    <ActionForm action=publish attr:class="flex flex-col gap-2">
+                        <input type="hidden" name="revision_id" value=publish_id />
+                        <input class=INPUT_CLASS type="text" name="title" value=title required />
+                        <textarea class=INPUT_CLASS name="description" placeholder=t!("form-description")></textarea>
+                        <input class=INPUT_CLASS type="text" name="tags" placeholder=t!("form-tags") />
+                        <RatingSelect selected=RATING_SFW />
+                        <button class=BUTTON_CLASS type="submit">{t!("revision-publish")}</button>
+                    </ActionForm>
+                }
+            });
+            let published = revision
+                .artwork_id
+                .clone()
+                .map(|_| view! { <p class="text-sm">{t!("revision-published")}</p> });
+            let comment_id = revision_id.clone();
+            view! {
+                <article class="flex flex-col gap-2 border-b border-gray-300 dark:border-gray-700 pb-4">
+                    <h2 class="flex gap-2 items-baseline">
+                        <span class="font-bold">
+                            {format!(
+                                "v{} {}",
+                                revision.version,
+                                t!(&format!("revision-stage-{}", revision.stage)),
+                            )}
+                        </span>
+                        <span class="text-sm">{revision.status.replace('_', " ")}</span>
+                        <span class="text-sm text-gray-500 dark:text-gray-400">{use_i18n().time(revision.created_at)}</span>
+                        {revision.watermark.then(|| t!("revision-watermarked"))}
+                        {original}
+                    </h2>
+                    <p class="whitespace-pre-wrap">{revision.note.clone()}</p>
+                    {image}
+                    <ul class="flex flex-col gap-1">{comments}</ul>
+                    <ActionForm action=comment attr:class="flex flex-col gap-2">
+                        <input type="hidden" name="revision_id" value=comment_id />
+                        <textarea
+                            class=INPUT_CLASS
+                            name="body"
+                            placeholder=t!("revision-comment-hint")
+                            required
+                        ></textarea>
+                        <div class="flex gap-2 items-center">
+                            {pin_inputs}
+                            <button type="submit">{t!("revision-comment")}</button>
+                        </div>
+                    </ActionForm>
+                    {review_forms}
+                    {publish_form}
+                    {published}
+                </article>
+            }
+            .into_any()
+        };
+
+        let can_upload = move || {
+            detail
+                .get()
+                .and_then(|detail| detail.ok())
+                .is_some_and(|detail| detail.is_artist && detail.commission.status == "open")
+        };
+
+        let upload_form = view! {
+            <form
+                class="flex flex-col gap-2 border-b border-gray-300 dark:border-gray-700 pb-4"
+                class=("hidden", move || !can_upload())
+                on:submit=on_upload
+            >
+                <h2 class="font-bold">{t!("revision-upload-title")}</h2>
+                <select
+                    class=INPUT_CLASS
+                    on:change=move |ev| stage.set(event_target_value(&ev))
+                    prop:value=move || stage.get()
+                >
+                    {REVISION_STAGES
+                        .iter()
+                        .map(|(name, _)| {
+                            view! { <option value=*name>{t!(&format!("revision-stage-{}", name))}</option> }
+                        })
+                        .collect_view()}
+                </select>
+                <textarea
+                    class=INPUT_CLASS
+                    placeholder=t!("revision-note")
+                    prop:value=move || note.get()
+                    on:input=move |ev| note.set(event_target_value(&ev))
+                ></textarea>
+                <label class="flex gap-2">
+                    <input
+                        type="checkbox"
+                        prop:checked=move || watermark.get()
+                        on:change=move |ev| watermark.set(event_target_checked(&ev))
+                    />
+                    {t!("revision-watermark")}
+                </label>
+                <input node_ref=file_ref type="file" accept="image/*" />
+                <button class=BUTTON_CLASS type="submit" disabled=move || uploading.get()>
+                    {t!("revision-upload")}
+                </button>
+                {move || upload_error.get().map(|err| view! { <p class="text-red-400">{err}</p> })}
+            </form>
+        }
+        .into_any();
+
+        let content = move || {
+            detail.get().map(|detail| match detail {
+                Ok(detail) => {
+                    let info = detail.commission;
+                    let open = info.status == "open";
+                    let is_artist = detail.is_artist;
+                    let cancel_id = info.commission_id.clone();
+                    let cancel_form = open.then(|| {
+                        view! {
+                            <ActionForm action=cancel>
+                                <input type="hidden" name="commission_id" value=cancel_id />
+                                <button type="submit">{t!("commission-cancel")}</button>
+                            </ActionForm>
+                        }
+                    });
+                    let title = info.title.clone();
+                    let revisions = detail
+                        .revisions
+                        .into_iter()
+                        .rev()
+                        .map(|revision| revision_view(revision, is_artist, open, title.clone()))
+                        .collect_view();
+                    view! {
+                        <header class="flex gap-2 items-baseline">
+                            <h1 class="font-bold text-lg">{info.title}</h1>
+                            <span>{format!("{} → {}", info.client, info.artist)}</span>
+                            <span class="text-sm">{info.status}</span>
+                            {cancel_form}
+                        </header>
+                        {revisions}
+                    }
+                    .into_any()
+                }
+                Err(err) => view! { <p class="text-red-400">{error_message(&err)}</p> }.into_any(),
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <section class="flex flex-col gap-4 p-2 text-gray-800 dark:text-gray-200 max-w-4xl overflow-y-auto">
+                    {upload_form}
+                    <Transition>{content}</Transition>
+                    <FormResult action=review />
+                    <FormResult action=comment />
+                    <FormResult action=publish />
+                    <FormResult action=cancel />
+                </section>
+            </main>
+        }
+    }
+}
+
+pub mod admin {
+    use chrono::DateTime;
+    use leptos::prelude::*;
+
+    use crate::{
+        api::{
+            MAXIMUM_RATING_REASON_LENGTH, RatingSet, admin_get_accs, error_message, rating_audits,
+        },
+        app::components::{
+            form::{BUTTON_CLASS, FormResult, INPUT_CLASS, RatingSelect},
+            nav::Nav,
+        },
+        i18n::use_i18n,
+        t,
+    };
+
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let accs = Resource::new(|| (), |_| admin_get_accs(0));
+        let rate = ServerAction::<RatingSet>::new();
+        let audits = Resource::new(move || rate.version().get(), |_| rating_audits(0));
+
+        let audit_list = move || {
+            audits.get().map(|audits| match audits {
+                Ok(audits) => audits
+                    .into_iter()
+                    .map(|audit| {
+                        let created_at = use_i18n().time(audit.created_at);
+                        view! {
+                            <tr>
+                                <td>{created_at}</td>
+                                <td>{audit.moderator}</td>
+                                <td>{format!("{} {}", audit.kind, audit.target_id)}</td>
+                                <td>{format!("{} -> {}", audit.from, audit.to)}</td>
+                                <td>{audit.reason}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()
+                    .into_any(),
+                Err(err) => view! {
+                    <tr>
+                        <td class="text-red-400">{error_message(&err)}</td>
+                    </tr>
+                }
+                .into_any(),
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <div class="text-gray-800 dark:text-gray-200 px-2">
+                    <h1 class="font-bold text-lg">{t!("admin-accounts-title")}</h1>
+                    <Transition>
+                        {move || {
+                            accs.get()
+                                .map(|accs| match accs {
+                                    Ok(accs) => {
+                                        view! {
+                                            <table class="text-left">
+                                                <tr>
+                                                    <th>{t!("admin-username")}</th>
+                                                    <th>{t!("admin-email")}</th>
+                                                    <th>{t!("admin-role")}</th>
+                                                    <th>{t!("admin-verified")}</th>
+                                                    <th>{t!("admin-totp")}</th>
+                                                    <th>{t!("admin-created")}</th>
+                                                </tr>
+                                                {accs
+                                                    .into_iter()
+                                                    .map(|info| {
+                                                        let created_at = DateTime::from_timestamp_millis(info.created_at)
+                                                            .map(|time| time.format("%Y-%m-%d").to_string())
+                                                            .unwrap_or_default();
+                                                        let totp = if info.acc.totp_enabled {
+                                                            t!("admin-totp-on", count = info.acc.recovery_codes_left)
+                                                        } else {
+                                                            t!("admin-totp-off")
+                                                        };
+                                                        view! {
+                                                            <tr>
+                                                                <td>{info.acc.username}</td>
+                                                                <td>{info.acc.email}</td>
+                                                                <td>{info.acc.role}</td>
+                                                                <td>{info.acc.verified_email}</td>
+                                                                <td>{totp}</td>
+                                                                <td>{created_at}</td>
+                                                            </tr>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </table>
+                                        }
+                                            .into_any()
+                                    }
+                                    Err(err) => {
+                                        view! { <p class="text-red-400">{error_message(&err)}</p> }
+                                            .into_any()
+                                    }
+                                })
+                        }}
+                    </Transition>
+                    <h1 class="font-bold text-lg">{t!("admin-ratings-title")}</h1>
+                    <ActionForm action=rate attr:class="flex flex-col gap-2 w-80">
+                        <select class=INPUT_CLASS name="kind">
+                            <option value="artwork">{t!("admin-kind-artwork")}</option>
+                            <option value="bounty">{t!("admin-kind-bounty")}</option>
+                        </select>
+                        <input class=INPUT_CLASS type="text" name="target_id" placeholder=t!("admin-target-id") required />
+                        <RatingSelect selected="" />
+                        <textarea
+                            class=INPUT_CLASS
+                            name="reason"
+                            placeholder=t!("admin-reason")
+                            maxlength=MAXIMUM_RATING_REASON_LENGTH
+                            required
+                        ></textarea>
+                        <button class=BUTTON_CLASS type="submit">
+                            {t!("admin-change-rating")}
+                        </button>
+                        <FormResult action=rate success="admin-rating-changed" />
+                    </ActionForm>
+                    <table class="text-left">
+                        <tr>
+                            <th>{t!("admin-when")}</th>
+                            <th>{t!("admin-moderator")}</th>
+                            <th>{t!("admin-target")}</th>
+                            <th>{t!("admin-rating")}</th>
+                            <th>{t!("admin-reason")}</th>
+                        </tr>
+                        <Transition>{audit_list}</Transition>
+                    </table>
+                </div>
+            </main>
+        }
+    }
+}
+
+pub mod artwork {
+    use leptos::prelude::*;
+    use leptos_router::hooks::use_params_map;
+
+    use crate::{
+        api::{ArtworkInfo, ArtworkPageInfo, PROFILE_PAGE_PATH, artwork_page, error_message},
+        app::components::{meta::PageMetaTags, nav::Nav},
+        i18n::use_i18n,
+        t,
+    };
+
+    /// `srcset` of the width variants, for images that aren't rendered yet only the preview.
+    pub fn srcset(artwork: &ArtworkInfo) -> String {
+        artwork
+            .variants
+            .iter()
+            .map(|variant| format!("{} {}w", variant.url, variant.width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Served with async rendering so the link preview tags are in the first response.
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let params = use_params_map();
+        let page = Resource::new(
+            move || params.read().get("id").unwrap_or_default(),
+            artwork_page,
+        );
+
+        let content = move || {
+            page.get().map(|page| match page {
+                Ok(Some(page)) => view! { <Artwork page /> }.into_any(),
+                Ok(None) => view! { <p>{t!("artwork-not-found")}</p> }.into_any(),
+                Err(err) => view! { <p class="text-red-400">{error_message(&err)}</p> }.into_any(),
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <section class="flex flex-col gap-2 p-2 min-h-0 text-gray-800 dark:text-gray-200">
+                    <Suspense>{content}</Suspense>
+                </section>
+            </main>
+        }
+    }
+
+    #[component]
+    fn Artwork(page: ArtworkPageInfo) -> impl IntoView {
+        let ArtworkPageInfo {
+            artwork,
+            description,
+            tags,
+            created_at,
+            meta,
+        } = page;
+        let revealed = RwSignal::new(!artwork.blurred);
+        let srcset = srcset(&artwork);
+        let src = artwork.preview_url.clone().unwrap_or_default();
+        let low = artwork.low_url.clone().unwrap_or_default();
+        let alt = artwork.title.clone();
+        let author_url = format!("{}/{}", PROFILE_PAGE_PATH, artwork.author);
+        let tags = tags
+            .into_iter()
+            .map(|tag| view! { <li class="text-sm">{format!("#{}", tag)}</li> })
+            .collect_view();
+
+        view! {
+            <PageMetaTags meta />
+            <div class="relative flex justify-center min-h-0">
+                <Show
+                    when=move || revealed.get()
+                    fallback=move || {
+                        view! {
+                            <button
+                                class="relative"
+                                on:click=move |_| revealed.set(true)
+                            >
+                                <img
+                                    class="max-h-[70vh] object-contain blur-xl"
+                                    src=low.clone()
+                                    width=artwork.width
+                                    height=artwork.height
+                                />
+                                <span class="absolute inset-0 flex items-center justify-center bg-black/40 text-white">
+                                    {t!("gallery-reveal")}
+                                </span>
+                            </button>
+                        }
+                    }
+                >
+                    <img
+                        class="max-h-[70vh] object-contain"
+                        src=src.clone()
+                        srcset=srcset.clone()
+                        sizes="100vw"
+                        alt=alt.clone()
+                        width=artwork.width
+                        height=artwork.height
+                    />
+                </Show>
+            </div>
+            <h1 class="font-bold text-lg">{artwork.title}</h1>
+            <p class="text-sm text-gray-500 dark:text-gray-400">
+                <a href=author_url>{artwork.author}</a>
+                " · "
+                {use_i18n().time(created_at)}
+            </p>
+            <p class="whitespace-pre-wrap">{description}</p>
+            <ul class="flex gap-2">{tags}</ul>
+        }
+    }
+}
+
+pub mod profile {
+    use leptos::prelude::*;
+    use leptos_meta::Link;
+    use leptos_router::hooks::use_params_map;
+
+    use crate::{
+        api::{
+            ARTWORK_PAGE_PATH, ArtworkInfo, FEED_FILE, PROFILE_PAGE_PATH, ProfileInfo,
+            error_message, profile,
+        },
+        app::components::{meta::PageMetaTags, nav::Nav},
+        i18n::use_i18n,
+        t,
+    };
+
+    /// Served with async rendering so the link preview tags are in the first response.
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let params = use_params_map();
+        let page = Resource::new(
+            move || params.read().get("handle").unwrap_or_default(),
+            profile,
+        );
+
+        let content = move || {
+            page.get().map(|page| match page {
+                Ok(Some(page)) => view! { <Profile page /> }.into_any(),
+                Ok(None) => view! { <p>{t!("profile-not-found")}</p> }.into_any(),
+                Err(err) => view! { <p class="text-red-400">{error_message(&err)}</p> }.into_any(),
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <section class="flex flex-col gap-4 p-2 min-h-0 overflow-y-auto text-gray-800 dark:text-gray-200">
+                    <Suspense>{content}</Suspense>
+                </section>
+            </main>
+        }
+    }
+
+    #[component]
+    fn Profile(page: ProfileInfo) -> impl IntoView {
+        let ProfileInfo {
+            username,
+            created_at,
+            artworks,
+            meta,
+        } = page;
+        let joined = use_i18n().time(created_at);
+        let feed_url = format!("{}/{}/{}", PROFILE_PAGE_PATH, username, FEED_FILE);
+        let tiles = if artworks.is_empty() {
+            view! { <p>{t!("profile-empty")}</p> }.into_any()
+        } else {
+            let tiles = artworks
+                .into_iter()
+                .map(|artwork| view! { <Tile artwork /> })
+                .collect_view();
+            view! { <ul class="grid grid-cols-[repeat(auto-fill,minmax(10rem,1fr))] gap-2">{tiles}</ul> }
+                .into_any()
+        };
+
+        view! {
+            <PageMetaTags meta />
+            <Link rel="alternate" type_="application/atom+xml" href=feed_url />
+            <div>
+                <h1 class="font-bold text-lg">{username}</h1>
+                <p class="text-sm text-gray-500 dark:text-gray-400">
+                    {t!("profile-joined", date = joined)}
+                </p>
+            </div>
+            {tiles}
+        }
+    }
+
+    /// Blurred tiles only show the tiny variant, the artwork page reveals them.
+    #[component]
+    fn Tile(artwork: ArtworkInfo) -> impl IntoView {
+        let src = if artwork.blurred {
+            artwork.low_url.clone()
+        } else {
+            artwork
+                .variants
+                .first()
+                .map(|variant| variant.url.clone())
+                .or(artwork.preview_url.clone())
+        };
+        let class = if artwork.blurred {
+            "aspect-square w-full object-cover blur-md"
+        } else {
+            "aspect-square w-full object-cover"
+        };
+
+        let href = format!("{}/{}", ARTWORK_PAGE_PATH, artwork.artwork_id);
+        let title = artwork.title.clone();
+
+        view! {
+            <li>
+                <a href=href title=title>
+                    <img class=class src=src alt=artwork.title />
+                </a>
+            </li>
+        }
+    }
+}
+
+pub mod bounty {
+    use leptos::prelude::*;
+    use leptos_router::hooks::use_params_map;
+
+    use crate::{
+        api::{BountyPageInfo, PROFILE_PAGE_PATH, bounty_page, error_message},
+        app::components::{meta::PageMetaTags, nav::Nav},
+        i18n::use_i18n,
+        t,
+    };
+
+    /// Served with async rendering so the link preview tags are in the first response.
+    #[component]
+    pub fn Page() -> impl IntoView {
+        let params = use_params_map();
+        let page = Resource::new(
+            move || params.read().get("id").unwrap_or_default(),
+            bounty_page,
+        );
+
+        let content = move || {
+            page.get().map(|page| match page {
+                Ok(Some(page)) => view! { <Bounty page /> }.into_any(),
+                Ok(None) => view! { <p>{t!("bounty-not-found")}</p> }.into_any(),
+                Err(err) => view! { <p class="text-red-400">{error_message(&err)}</p> }.into_any(),
+            })
+        };
+
+        view! {
+            <main class="grid grid-rows-[auto_1fr] h-screen">
+                <Nav />
+                <section class="flex flex-col gap-2 p-2 max-w-3xl text-gray-800 dark:text-gray-200">
+                    <Suspense>{content}</Suspense>
+                </section>
+            </main>
+        }
+    }
+
+    #[component]
+    fn Bounty(page: BountyPageInfo) -> impl IntoView {
+        let i18n = use_i18n();
+        let revealed = RwSignal::new(!page.blurred);
+        let author_url = format!("{}/{}", PROFILE_PAGE_PATH, page.author);
+        let status = t!(&format!("bounty-status-{}", page.status));
+        let tags = page
+            .tags
+            .into_iter()
+            .map(|tag| view! { <li class="text-sm">{format!("#{}", tag)}</li> })
+            .collect_view();
+        let description = page.description;
+
+        view! {
+            <PageMetaTags meta=page.meta />
+            <h1 class="font-bold text-lg">{page.title}</h1>
+            <p class="text-sm text-gray-500 dark:text-gray-400">
+                <a href=author_url>{page.author}</a>
+                " · "
+                {i18n.time(page.created_at)}
+                " · "
+                {status}
+            </p>
+            <p class="font-bold">{t!("bounty-reward", reward = i18n.price(page.reward))}</p>
+            <Show
+                when=move || revealed.get()
+                fallback=move || {
+                    view! {
+                        <button class="text-left italic" on:click=move |_| revealed.set(true)>
+                            {t!("gallery-reveal")}
+                        </button>
+                    }
+                }
+            >
+                <p class="whitespace-pre-wrap">{description.clone()}</p>
+            </Show>
+            <ul class="flex gap-2">{tags}</ul>
+        }
+    }
+}
@@ -0,0 +1,2431 @@
+use std::{future::Future, time::Instant};
+
+use surrealdb::{
+    Surreal,
+    engine::any::{self, Any},
+    opt::auth::Root,
+};
+use tracing::trace;
+
+pub const DB_QUERY_DURATION: &str = "db_query_duration_seconds";
+pub const NAMESPACE: &str = "artbounty";
+pub const DATABASE: &str = "artbounty";
+
+pub type Db = Surreal<Any>;
+/// Boxed so results of queries stay small, the error itself is over a hundred bytes.
+pub type DbError = Box<surrealdb::Error>;
+
+pub const ACC_EMAIL_INDEX: &str = "acc_email";
+pub const ACC_DISCORD_INDEX: &str = "acc_discord";
+
+const MIGRATIONS: &str = "
+    DEFINE INDEX IF NOT EXISTS acc_email ON TABLE acc FIELDS email UNIQUE;
+    DEFINE INDEX IF NOT EXISTS session_acc ON TABLE session FIELDS acc;
+    DEFINE INDEX IF NOT EXISTS acc_discord ON TABLE acc FIELDS discord.user_id UNIQUE;
+    DEFINE INDEX IF NOT EXISTS artwork_acc ON TABLE artwork FIELDS acc;
+    DEFINE INDEX IF NOT EXISTS bounty_status ON TABLE bounty FIELDS status;
+    DEFINE INDEX IF NOT EXISTS api_token_acc ON TABLE api_token FIELDS acc;
+    DEFINE INDEX IF NOT EXISTS webhook_acc ON TABLE webhook FIELDS acc;
+    DEFINE INDEX IF NOT EXISTS webhook_delivery_due ON TABLE webhook_delivery FIELDS status, next_attempt_at;
+    DEFINE INDEX IF NOT EXISTS webhook_delivery_webhook ON TABLE webhook_delivery FIELDS webhook_id;
+    DEFINE INDEX IF NOT EXISTS conversation_participants ON TABLE conversation FIELDS participants;
+    DEFINE INDEX IF NOT EXISTS conversation_member_acc ON TABLE conversation_member FIELDS acc;
+    DEFINE INDEX IF NOT EXISTS conversation_member_conversation ON TABLE conversation_member FIELDS conversation_id;
+    DEFINE INDEX IF NOT EXISTS message_conversation ON TABLE message FIELDS conversation_id, created_at;
+    DEFINE INDEX IF NOT EXISTS block_acc ON TABLE block FIELDS acc;
+    DEFINE INDEX IF NOT EXISTS block_blocked ON TABLE block FIELDS blocked;
+    DEFINE INDEX IF NOT EXISTS commission_client ON TABLE commission FIELDS client;
+    DEFINE INDEX IF NOT EXISTS commission_artist ON TABLE commission FIELDS artist;
+    DEFINE INDEX IF NOT EXISTS revision_commission ON TABLE revision FIELDS commission_id, version;
+    DEFINE INDEX IF NOT EXISTS revision_preview_status ON TABLE revision FIELDS preview_status;
+    DEFINE INDEX IF NOT EXISTS revision_comment_revision ON TABLE revision_comment FIELDS revision_id;
+    DEFINE INDEX IF NOT EXISTS artwork_preview_status ON TABLE artwork FIELDS preview_status;
+    DEFINE INDEX IF NOT EXISTS artwork_access_artwork ON TABLE artwork_access FIELDS artwork_id;
+    DEFINE INDEX IF NOT EXISTS rating_audit_created ON TABLE rating_audit FIELDS created_at;
+    DEFINE INDEX IF NOT EXISTS upload_expires ON TABLE upload FIELDS expires_at;
+    DEFINE INDEX IF NOT EXISTS artwork_sha256 ON TABLE artwork FIELDS acc, sha256;
+    DEFINE INDEX IF NOT EXISTS favorite_acc ON TABLE favorite FIELDS acc, artwork_id;
+    DEFINE INDEX IF NOT EXISTS artwork_tags ON TABLE artwork FIELDS tags;
+";
+
+/// Connects to `addr` (`mem://`, `surrealkv://path`, `ws://host:port`, ...) and selects the artbounty namespace.
+pub async fn connect(addr: &str, root: Option<(&str, &str)>) -> Result<Db, DbError> {
+    let db = any::connect(addr).await?;
+    if let Some((username, password)) = root {
+        db.signin(Root { username, password }).await?;
+    }
+    db.use_ns(NAMESPACE).use_db(DATABASE).await?;
+    db.query(MIGRATIONS).await?.check()?;
+    trace!("connected to db at {}", addr);
+    Ok(db)
+}
+
+/// Awaits a query future and records how long it took under `db_query_duration_seconds{query}`.
+pub async fn timed<T>(
+    query: &'static str,
+    fut: impl Future<Output = Result<T, surrealdb::Error>>,
+) -> Result<T, DbError> {
+    let started_at = Instant::now();
+    let output = fut.await;
+    metrics::histogram!(DB_QUERY_DURATION, "query" => query).record(started_at.elapsed());
+    output.map_err(DbError::from)
+}
+
+/// What an insert collided with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    /// A record with the same id.
+    Record,
+    /// An entry of the unique index with this name.
+    Index(String),
+}
+
+/// Duplicate behind an insert error, `None` for any other error. Remote engines only send the
+/// message, so that is parsed too.
+pub fn conflict(err: &surrealdb::Error) -> Option<Conflict> {
+    match err {
+        surrealdb::Error::Db(surrealdb::error::Db::RecordExists { .. }) => Some(Conflict::Record),
+        surrealdb::Error::Db(surrealdb::error::Db::IndexExists { index, .. }) => {
+            Some(Conflict::Index(index.clone()))
+        }
+        surrealdb::Error::Api(surrealdb::error::Api::Query(message)) => {
+            if message.starts_with("Database record `") && message.ends_with("` already exists") {
+                return Some(Conflict::Record);
+            }
+            let index = message.strip_prefix("Database index `")?;
+            let (index, rest) = index.split_once('`')?;
+            rest.starts_with(" already contains ")
+                .then(|| Conflict::Index(index.to_string()))
+        }
+        _ => None,
+    }
+}
+
+pub mod ban {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "ban";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbBan {
+        pub key: String,
+        pub reason: String,
+        pub banned_until: i64,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn upsert(db: &Db, ban: DbBan) -> Result<(), DbError> {
+        timed("ban_upsert", async {
+            let _: Option<DbBan> = db
+                .upsert((TABLE, ban.key.as_str()))
+                .content(ban.clone())
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove(db: &Db, key: &str) -> Result<(), DbError> {
+        timed("ban_remove", async {
+            let _: Option<DbBan> = db.delete((TABLE, key)).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_active(db: &Db, time: i64) -> Result<Vec<DbBan>, DbError> {
+        timed("ban_get_active", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE banned_until > $time")
+                .bind(("table", TABLE))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod acc {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "acc";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbAcc {
+        pub username: String,
+        pub email: String,
+        pub password: String,
+        pub verified_email: bool,
+        pub role: String,
+        #[serde(default)]
+        pub totp: Option<DbAccTotp>,
+        #[serde(default)]
+        pub discord: Option<DbAccDiscord>,
+        /// Unset until the account picks its own, the logged out defaults apply meanwhile.
+        #[serde(default)]
+        pub content_prefs: Option<DbAccContentPrefs>,
+        /// Color theme picked while logged in, restored as the cookie on the next login.
+        #[serde(default)]
+        pub theme: Option<String>,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    /// Second factor of an account, `enabled` only after the first code was confirmed.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbAccTotp {
+        pub secret: String,
+        pub enabled: bool,
+        pub last_step: Option<i64>,
+        pub recovery_codes: Vec<String>,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbAccDiscord {
+        pub user_id: String,
+        pub token: String,
+    }
+
+    /// Whether mature and explicit content gets hidden, blurred or shown.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbAccContentPrefs {
+        pub mature: String,
+        pub explicit: String,
+    }
+
+    pub async fn insert(db: &Db, acc: DbAcc) -> Result<(), DbError> {
+        timed("acc_insert", async {
+            let _: Option<DbAcc> = db
+                .create((TABLE, acc.username.as_str()))
+                .content(acc)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_by_username(db: &Db, username: &str) -> Result<Option<DbAcc>, DbError> {
+        timed("acc_get_by_username", async {
+            db.select((TABLE, username)).await
+        })
+        .await
+    }
+
+    pub async fn get_by_email(db: &Db, email: &str) -> Result<Option<DbAcc>, DbError> {
+        timed("acc_get_by_email", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE email = $email LIMIT 1")
+                .bind(("table", TABLE))
+                .bind(("email", email.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_verified_email(db: &Db, username: &str, time: i64) -> Result<(), DbError> {
+        timed("acc_set_verified_email", async {
+            db.query("UPDATE type::thing($table, $username) SET verified_email = true, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_by_discord_id(db: &Db, user_id: &str) -> Result<Option<DbAcc>, DbError> {
+        timed("acc_get_by_discord_id", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE discord.user_id = $user_id LIMIT 1")
+                .bind(("table", TABLE))
+                .bind(("user_id", user_id.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_discord(
+        db: &Db,
+        username: &str,
+        discord: Option<DbAccDiscord>,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("acc_set_discord", async {
+            db.query(
+                "UPDATE type::thing($table, $username) SET discord = $discord, modified_at = $time",
+            )
+            .bind(("table", TABLE))
+            .bind(("username", username.to_string()))
+            .bind(("discord", discord))
+            .bind(("time", time))
+            .await?
+            .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_content_prefs(
+        db: &Db,
+        username: &str,
+        content_prefs: DbAccContentPrefs,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("acc_set_content_prefs", async {
+            db.query("UPDATE type::thing($table, $username) SET content_prefs = $content_prefs, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("content_prefs", content_prefs))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_theme(db: &Db, username: &str, theme: &str, time: i64) -> Result<(), DbError> {
+        timed("acc_set_theme", async {
+            db.query(
+                "UPDATE type::thing($table, $username) SET theme = $theme, modified_at = $time",
+            )
+            .bind(("table", TABLE))
+            .bind(("username", username.to_string()))
+            .bind(("theme", theme.to_string()))
+            .bind(("time", time))
+            .await?
+            .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_page(db: &Db, limit: u32, offset: u32) -> Result<Vec<DbAcc>, DbError> {
+        timed("acc_get_page", async {
+            db.query("SELECT * OMIT id FROM type::table($table) ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn search(
+        db: &Db,
+        query: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbAcc>, DbError> {
+        timed("acc_search", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE string::lowercase(username) CONTAINS $query ORDER BY username LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("query", query.to_lowercase()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_role(
+        db: &Db,
+        username: &str,
+        role: &str,
+        time: i64,
+    ) -> Result<Option<DbAcc>, DbError> {
+        timed("acc_set_role", async {
+            db.query("UPDATE type::thing($table, $username) SET role = $role, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("role", role.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_totp(
+        db: &Db,
+        username: &str,
+        totp: Option<DbAccTotp>,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("acc_set_totp", async {
+            db.query("UPDATE type::thing($table, $username) SET totp = $totp, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("totp", totp))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Records `step` as the last accepted totp step, `None` unless it is newer than the stored one.
+    pub async fn use_totp_step(
+        db: &Db,
+        username: &str,
+        step: i64,
+        time: i64,
+    ) -> Result<Option<DbAcc>, DbError> {
+        timed("acc_use_totp_step", async {
+            db.query("UPDATE type::thing($table, $username) SET totp.last_step = $step, totp.modified_at = $time, modified_at = $time WHERE totp.enabled = true AND (totp.last_step = NONE OR totp.last_step < $step) RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("step", step))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Removes a hashed recovery code, `None` if the account doesn't have it.
+    pub async fn use_recovery_code(
+        db: &Db,
+        username: &str,
+        code_hash: &str,
+        time: i64,
+    ) -> Result<Option<DbAcc>, DbError> {
+        timed("acc_use_recovery_code", async {
+            db.query("UPDATE type::thing($table, $username) SET totp.recovery_codes -= $code_hash, totp.modified_at = $time, modified_at = $time WHERE totp.enabled = true AND totp.recovery_codes CONTAINS $code_hash RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("code_hash", code_hash.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_password(
+        db: &Db,
+        username: &str,
+        password: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("acc_set_password", async {
+            db.query("UPDATE type::thing($table, $username) SET password = $password, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("username", username.to_string()))
+                .bind(("password", password.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub mod session {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "session";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbSession {
+        pub token_hash: String,
+        pub acc: String,
+        pub ip: String,
+        pub agent: String,
+        pub expires_at: i64,
+        pub last_used: i64,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, session: DbSession) -> Result<(), DbError> {
+        timed("session_insert", async {
+            let _: Option<DbSession> = db
+                .create((TABLE, session.token_hash.as_str()))
+                .content(session)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, token_hash: &str) -> Result<Option<DbSession>, DbError> {
+        timed("session_get", async {
+            db.select((TABLE, token_hash)).await
+        })
+        .await
+    }
+
+    pub async fn touch(db: &Db, token_hash: &str, time: i64) -> Result<(), DbError> {
+        timed("session_touch", async {
+            db.query("UPDATE type::thing($table, $token_hash) SET last_used = $time")
+                .bind(("table", TABLE))
+                .bind(("token_hash", token_hash.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove(db: &Db, token_hash: &str) -> Result<(), DbError> {
+        timed("session_remove", async {
+            let _: Option<DbSession> = db.delete((TABLE, token_hash)).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove_all_for_acc(db: &Db, acc: &str) -> Result<(), DbError> {
+        timed("session_remove_all_for_acc", async {
+            db.query("DELETE type::table($table) WHERE acc = $acc")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub mod login_challenge {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "login_challenge";
+
+    /// Pending second login step, taken once a code is accepted so a challenge logs in once.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbLoginChallenge {
+        pub nonce: String,
+        pub acc: String,
+        /// Fingerprint of the password hash, a password change voids the challenge.
+        pub fingerprint: String,
+        pub expires_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, challenge: DbLoginChallenge) -> Result<(), DbError> {
+        timed("login_challenge_insert", async {
+            let _: Option<DbLoginChallenge> = db
+                .create((TABLE, challenge.nonce.as_str()))
+                .content(challenge)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, nonce: &str) -> Result<Option<DbLoginChallenge>, DbError> {
+        timed("login_challenge_get", async {
+            db.select((TABLE, nonce)).await
+        })
+        .await
+    }
+
+    /// Deletes and returns the challenge, so it can only be used once.
+    pub async fn take(db: &Db, nonce: &str) -> Result<Option<DbLoginChallenge>, DbError> {
+        timed("login_challenge_take", async {
+            db.delete((TABLE, nonce)).await
+        })
+        .await
+    }
+}
+
+pub mod discord_link_code {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "discord_link_code";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbDiscordLinkCode {
+        pub code_hash: String,
+        pub acc: String,
+        pub expires_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, code: DbDiscordLinkCode) -> Result<(), DbError> {
+        timed("discord_link_code_insert", async {
+            let _: Option<DbDiscordLinkCode> = db
+                .create((TABLE, code.code_hash.as_str()))
+                .content(code)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deletes and returns the code, so it can only be redeemed once.
+    pub async fn take(db: &Db, code_hash: &str) -> Result<Option<DbDiscordLinkCode>, DbError> {
+        timed("discord_link_code_take", async {
+            db.delete((TABLE, code_hash)).await
+        })
+        .await
+    }
+}
+
+pub mod artwork {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "artwork";
+    pub const PREVIEW_PENDING: &str = "pending";
+    pub const PREVIEW_READY: &str = "ready";
+    pub const PREVIEW_FAILED: &str = "failed";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbArtwork {
+        pub artwork_id: String,
+        pub acc: String,
+        pub title: String,
+        pub description: String,
+        pub tags: Vec<String>,
+        /// Path of the image relative to the media dir.
+        pub file: String,
+        pub width: u32,
+        pub height: u32,
+        /// Public preview gets watermarked and the original is only served to the author and buyers.
+        pub protected: bool,
+        /// Public preview rendered by the backend media worker.
+        pub preview_status: String,
+        /// `sfw`, `mature` or `explicit`.
+        pub rating: String,
+        /// Hex colors extracted along with the preview, the dominant one first.
+        pub palette: Vec<String>,
+        /// BlurHash of the original, empty until the preview is rendered.
+        pub blurhash: String,
+        /// Hex SHA-256 of the file the uploader picked, before any downscaling. Empty for
+        /// published commissions.
+        pub sha256: String,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, artwork: DbArtwork) -> Result<(), DbError> {
+        timed("artwork_insert", async {
+            let _: Option<DbArtwork> = db
+                .create((TABLE, artwork.artwork_id.as_str()))
+                .content(artwork)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, artwork_id: &str) -> Result<Option<DbArtwork>, DbError> {
+        timed("artwork_get", async {
+            db.select((TABLE, artwork_id)).await
+        })
+        .await
+    }
+
+    /// Newest first, only artworks rated one of `ratings`.
+    pub async fn get_page(
+        db: &Db,
+        ratings: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbArtwork>, DbError> {
+        timed("artwork_get_page", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE rating INSIDE $ratings ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn get_page_by_acc(
+        db: &Db,
+        acc: &str,
+        ratings: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbArtwork>, DbError> {
+        timed("artwork_get_page_by_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc AND rating INSIDE $ratings ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn get_page_by_tag(
+        db: &Db,
+        tag: &str,
+        ratings: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbArtwork>, DbError> {
+        timed("artwork_get_page_by_tag", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE tags CONTAINS $tag AND rating INSIDE $ratings ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("tag", tag.to_string()))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Matches `query` against the title or an exact tag, both lowercase.
+    pub async fn search(
+        db: &Db,
+        query: &str,
+        ratings: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbArtwork>, DbError> {
+        timed("artwork_search", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE (string::lowercase(title) CONTAINS $query OR tags CONTAINS $query) AND rating INSIDE $ratings ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("query", query.to_lowercase()))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_info(
+        db: &Db,
+        artwork_id: &str,
+        title: &str,
+        description: &str,
+        tags: Vec<String>,
+        time: i64,
+    ) -> Result<Option<DbArtwork>, DbError> {
+        timed("artwork_set_info", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET title = $title, description = $description, tags = $tags, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("title", title.to_string()))
+                .bind(("description", description.to_string()))
+                .bind(("tags", tags))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_rating(
+        db: &Db,
+        artwork_id: &str,
+        rating: &str,
+        time: i64,
+    ) -> Result<Option<DbArtwork>, DbError> {
+        timed("artwork_set_rating", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET rating = $rating, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("rating", rating.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Newest artworks rated one of `ratings` that already have a palette.
+    pub async fn get_with_palette(
+        db: &Db,
+        ratings: &[String],
+        limit: u32,
+    ) -> Result<Vec<DbArtwork>, DbError> {
+        timed("artwork_get_with_palette", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE rating INSIDE $ratings AND array::len(palette) > 0 ORDER BY created_at DESC LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_palette(
+        db: &Db,
+        artwork_id: &str,
+        palette: Vec<String>,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("artwork_set_palette", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET palette = $palette, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("palette", palette))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_blurhash(
+        db: &Db,
+        artwork_id: &str,
+        blurhash: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("artwork_set_blurhash", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET blurhash = $blurhash, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("blurhash", blurhash.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_sha256(
+        db: &Db,
+        artwork_id: &str,
+        sha256: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("artwork_set_sha256", async {
+            db.query(
+                "UPDATE type::thing($table, $artwork_id) SET sha256 = $sha256, modified_at = $time",
+            )
+            .bind(("table", TABLE))
+            .bind(("artwork_id", artwork_id.to_string()))
+            .bind(("sha256", sha256.to_string()))
+            .bind(("time", time))
+            .await?
+            .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_by_sha256(
+        db: &Db,
+        acc: &str,
+        sha256: &str,
+    ) -> Result<Option<DbArtwork>, DbError> {
+        timed("artwork_get_by_sha256", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc AND sha256 = $sha256 LIMIT 1")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("sha256", sha256.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Fills in the size of uploads, it's only known once the media worker decoded them.
+    pub async fn set_size(
+        db: &Db,
+        artwork_id: &str,
+        width: u32,
+        height: u32,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("artwork_set_size", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET width = $width, height = $height, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("width", width))
+                .bind(("height", height))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Artworks waiting for their public preview, oldest first.
+    pub async fn get_pending_previews(db: &Db, limit: u32) -> Result<Vec<DbArtwork>, DbError> {
+        timed("artwork_get_pending_previews", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE preview_status = $preview_status ORDER BY created_at LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("preview_status", PREVIEW_PENDING))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// All artworks waiting for their public preview, not only the next batch.
+    pub async fn count_pending_previews(db: &Db) -> Result<u64, DbError> {
+        timed("artwork_count_pending_previews", async {
+            let count: Option<u64> = db
+                .query("RETURN count(SELECT artwork_id FROM type::table($table) WHERE preview_status = $preview_status)")
+                .bind(("table", TABLE))
+                .bind(("preview_status", PREVIEW_PENDING))
+                .await?
+                .take(0)?;
+            Ok(count.unwrap_or(0))
+        })
+        .await
+    }
+
+    pub async fn set_preview_status(
+        db: &Db,
+        artwork_id: &str,
+        preview_status: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("artwork_set_preview_status", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET preview_status = $preview_status, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("preview_status", preview_status.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Also queues the public preview to be rendered again.
+    pub async fn set_protected(
+        db: &Db,
+        artwork_id: &str,
+        protected: bool,
+        time: i64,
+    ) -> Result<Option<DbArtwork>, DbError> {
+        timed("artwork_set_protected", async {
+            db.query("UPDATE type::thing($table, $artwork_id) SET protected = $protected, preview_status = $preview_status, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("protected", protected))
+                .bind(("preview_status", PREVIEW_PENDING))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Queues every preview of `acc` to be rendered again, e.g. after their watermark changed.
+    pub async fn rerender_protected_for_acc(db: &Db, acc: &str, time: i64) -> Result<(), DbError> {
+        timed("artwork_rerender_protected_for_acc", async {
+            db.query("UPDATE type::table($table) SET preview_status = $preview_status, modified_at = $time WHERE acc = $acc AND protected = true")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("preview_status", PREVIEW_PENDING))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub mod bounty {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "bounty";
+    pub const STATUS_OPEN: &str = "open";
+    pub const STATUS_CLOSED: &str = "closed";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbBounty {
+        pub bounty_id: String,
+        pub acc: String,
+        pub title: String,
+        pub description: String,
+        pub tags: Vec<String>,
+        /// In cents.
+        pub reward: u64,
+        pub status: String,
+        /// `sfw`, `mature` or `explicit`.
+        pub rating: String,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, bounty: DbBounty) -> Result<(), DbError> {
+        timed("bounty_insert", async {
+            let _: Option<DbBounty> = db
+                .create((TABLE, bounty.bounty_id.as_str()))
+                .content(bounty)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, bounty_id: &str) -> Result<Option<DbBounty>, DbError> {
+        timed("bounty_get", async { db.select((TABLE, bounty_id)).await }).await
+    }
+
+    /// Newest first, `status` of `None` includes every bounty. Only bounties rated one of `ratings`.
+    pub async fn get_page(
+        db: &Db,
+        status: Option<&str>,
+        ratings: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbBounty>, DbError> {
+        timed("bounty_get_page", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE ($status = NONE OR status = $status) AND rating INSIDE $ratings ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("status", status.map(String::from)))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn search(
+        db: &Db,
+        query: &str,
+        ratings: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbBounty>, DbError> {
+        timed("bounty_search", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE (string::lowercase(title) CONTAINS $query OR tags CONTAINS $query) AND rating INSIDE $ratings ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("query", query.to_lowercase()))
+                .bind(("ratings", ratings.to_vec()))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_rating(
+        db: &Db,
+        bounty_id: &str,
+        rating: &str,
+        time: i64,
+    ) -> Result<Option<DbBounty>, DbError> {
+        timed("bounty_set_rating", async {
+            db.query("UPDATE type::thing($table, $bounty_id) SET rating = $rating, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("bounty_id", bounty_id.to_string()))
+                .bind(("rating", rating.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_status(
+        db: &Db,
+        bounty_id: &str,
+        status: &str,
+        time: i64,
+    ) -> Result<Option<DbBounty>, DbError> {
+        timed("bounty_set_status", async {
+            db.query("UPDATE type::thing($table, $bounty_id) SET status = $status, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("bounty_id", bounty_id.to_string()))
+                .bind(("status", status.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod api_token {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "api_token";
+
+    /// Personal access token, keyed by the hash so the plain token is never stored.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbApiToken {
+        pub token_hash: String,
+        pub token_id: String,
+        pub acc: String,
+        pub name: String,
+        pub scopes: Vec<String>,
+        /// Requests per minute.
+        pub rate_limit: u32,
+        pub last_used: Option<i64>,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, token: DbApiToken) -> Result<(), DbError> {
+        timed("api_token_insert", async {
+            let _: Option<DbApiToken> = db
+                .create((TABLE, token.token_hash.as_str()))
+                .content(token)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, token_hash: &str) -> Result<Option<DbApiToken>, DbError> {
+        timed("api_token_get", async {
+            db.select((TABLE, token_hash)).await
+        })
+        .await
+    }
+
+    pub async fn get_all_for_acc(db: &Db, acc: &str) -> Result<Vec<DbApiToken>, DbError> {
+        timed("api_token_get_all_for_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc ORDER BY created_at DESC")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn touch(db: &Db, token_hash: &str, time: i64) -> Result<(), DbError> {
+        timed("api_token_touch", async {
+            db.query("UPDATE type::thing($table, $token_hash) SET last_used = $time")
+                .bind(("table", TABLE))
+                .bind(("token_hash", token_hash.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns `false` when the account has no token with `token_id`.
+    pub async fn remove(db: &Db, acc: &str, token_id: &str) -> Result<bool, DbError> {
+        timed("api_token_remove", async {
+            let removed: Vec<DbApiToken> = db
+                .query("DELETE type::table($table) WHERE acc = $acc AND token_id = $token_id RETURN BEFORE")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("token_id", token_id.to_string()))
+                .await?
+                .take(0)?;
+            Ok(!removed.is_empty())
+        })
+        .await
+    }
+}
+
+pub mod webhook {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "webhook";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbWebhook {
+        pub webhook_id: String,
+        pub acc: String,
+        pub url: String,
+        pub events: Vec<String>,
+        pub secret: String,
+        pub enabled: bool,
+        /// Failed attempts in a row, reset by any successful delivery.
+        pub failures: u32,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, webhook: DbWebhook) -> Result<(), DbError> {
+        timed("webhook_insert", async {
+            let _: Option<DbWebhook> = db
+                .create((TABLE, webhook.webhook_id.as_str()))
+                .content(webhook)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, webhook_id: &str) -> Result<Option<DbWebhook>, DbError> {
+        timed("webhook_get", async {
+            db.select((TABLE, webhook_id)).await
+        })
+        .await
+    }
+
+    pub async fn get_all_for_acc(db: &Db, acc: &str) -> Result<Vec<DbWebhook>, DbError> {
+        timed("webhook_get_all_for_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc ORDER BY created_at DESC")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn get_enabled_for_event(
+        db: &Db,
+        acc: &str,
+        event: &str,
+    ) -> Result<Vec<DbWebhook>, DbError> {
+        timed("webhook_get_enabled_for_event", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc AND enabled = true AND events CONTAINS $event")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("event", event.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_state(
+        db: &Db,
+        webhook_id: &str,
+        enabled: bool,
+        failures: u32,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("webhook_set_state", async {
+            db.query("UPDATE type::thing($table, $webhook_id) SET enabled = $enabled, failures = $failures, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("webhook_id", webhook_id.to_string()))
+                .bind(("enabled", enabled))
+                .bind(("failures", failures))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns `false` when the account has no webhook with `webhook_id`.
+    pub async fn remove(db: &Db, acc: &str, webhook_id: &str) -> Result<bool, DbError> {
+        timed("webhook_remove", async {
+            let removed: Vec<DbWebhook> = db
+                .query("DELETE type::table($table) WHERE acc = $acc AND webhook_id = $webhook_id RETURN BEFORE")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("webhook_id", webhook_id.to_string()))
+                .await?
+                .take(0)?;
+            Ok(!removed.is_empty())
+        })
+        .await
+    }
+}
+
+pub mod webhook_delivery {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "webhook_delivery";
+    pub const STATUS_PENDING: &str = "pending";
+    pub const STATUS_DELIVERED: &str = "delivered";
+    pub const STATUS_FAILED: &str = "failed";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbWebhookDelivery {
+        pub delivery_id: String,
+        pub webhook_id: String,
+        pub event: String,
+        /// Exact body that gets signed and sent.
+        pub payload: String,
+        pub status: String,
+        pub attempts: Vec<DbWebhookAttempt>,
+        pub next_attempt_at: i64,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbWebhookAttempt {
+        pub response_code: Option<u16>,
+        pub error: Option<String>,
+        pub duration_ms: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, delivery: DbWebhookDelivery) -> Result<(), DbError> {
+        timed("webhook_delivery_insert", async {
+            let _: Option<DbWebhookDelivery> = db
+                .create((TABLE, delivery.delivery_id.as_str()))
+                .content(delivery)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Pending deliveries whose next attempt is due, oldest first.
+    pub async fn get_due(
+        db: &Db,
+        time: i64,
+        limit: u32,
+    ) -> Result<Vec<DbWebhookDelivery>, DbError> {
+        timed("webhook_delivery_get_due", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE status = $status AND next_attempt_at <= $time ORDER BY next_attempt_at LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("status", STATUS_PENDING))
+                .bind(("time", time))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Pending deliveries, including the ones waiting for a later retry.
+    pub async fn count_pending(db: &Db) -> Result<u64, DbError> {
+        timed("webhook_delivery_count_pending", async {
+            let count: Option<u64> = db
+                .query("RETURN count(SELECT delivery_id FROM type::table($table) WHERE status = $status)")
+                .bind(("table", TABLE))
+                .bind(("status", STATUS_PENDING))
+                .await?
+                .take(0)?;
+            Ok(count.unwrap_or(0))
+        })
+        .await
+    }
+
+    pub async fn get_page_for_webhook(
+        db: &Db,
+        webhook_id: &str,
+        limit: u32,
+    ) -> Result<Vec<DbWebhookDelivery>, DbError> {
+        timed("webhook_delivery_get_page_for_webhook", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE webhook_id = $webhook_id ORDER BY created_at DESC LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("webhook_id", webhook_id.to_string()))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn update(db: &Db, delivery: DbWebhookDelivery) -> Result<(), DbError> {
+        timed("webhook_delivery_update", async {
+            let _: Option<DbWebhookDelivery> = db
+                .update((TABLE, delivery.delivery_id.as_str()))
+                .content(delivery)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Gives up on everything still pending for a webhook, e.g. once it gets disabled.
+    pub async fn fail_pending_for_webhook(
+        db: &Db,
+        webhook_id: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("webhook_delivery_fail_pending_for_webhook", async {
+            db.query("UPDATE type::table($table) SET status = $failed, modified_at = $time WHERE webhook_id = $webhook_id AND status = $pending")
+                .bind(("table", TABLE))
+                .bind(("webhook_id", webhook_id.to_string()))
+                .bind(("failed", STATUS_FAILED))
+                .bind(("pending", STATUS_PENDING))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove_all_for_webhook(db: &Db, webhook_id: &str) -> Result<(), DbError> {
+        timed("webhook_delivery_remove_all_for_webhook", async {
+            db.query("DELETE type::table($table) WHERE webhook_id = $webhook_id")
+                .bind(("table", TABLE))
+                .bind(("webhook_id", webhook_id.to_string()))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub mod conversation {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "conversation";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbConversation {
+        pub conversation_id: String,
+        /// Usernames, sorted so the same group always maps to the same conversation.
+        pub participants: Vec<String>,
+        pub last_message_at: i64,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, conversation: DbConversation) -> Result<(), DbError> {
+        timed("conversation_insert", async {
+            let _: Option<DbConversation> = db
+                .create((TABLE, conversation.conversation_id.as_str()))
+                .content(conversation)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, conversation_id: &str) -> Result<Option<DbConversation>, DbError> {
+        timed("conversation_get", async {
+            db.select((TABLE, conversation_id)).await
+        })
+        .await
+    }
+
+    pub async fn get_by_participants(
+        db: &Db,
+        participants: &[String],
+    ) -> Result<Option<DbConversation>, DbError> {
+        timed("conversation_get_by_participants", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE participants CONTAINSALL $participants AND array::len(participants) = $len LIMIT 1")
+                .bind(("table", TABLE))
+                .bind(("participants", participants.to_vec()))
+                .bind(("len", participants.len()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Conversations of `acc`, most recently active first.
+    pub async fn get_page_for_acc(
+        db: &Db,
+        acc: &str,
+        limit: u32,
+    ) -> Result<Vec<DbConversation>, DbError> {
+        timed("conversation_get_page_for_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE participants CONTAINS $acc ORDER BY last_message_at DESC LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_last_message_at(
+        db: &Db,
+        conversation_id: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("conversation_set_last_message_at", async {
+            db.query("UPDATE type::thing($table, $conversation_id) SET last_message_at = $time, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("conversation_id", conversation_id.to_string()))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub mod conversation_member {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "conversation_member";
+
+    /// Per participant state of a conversation.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbConversationMember {
+        pub conversation_id: String,
+        pub acc: String,
+        /// Messages created up to this time were seen, shown to the others as a read receipt.
+        pub last_read_at: i64,
+        pub muted: bool,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    fn key(conversation_id: &str, acc: &str) -> String {
+        format!("{}_{}", conversation_id, acc)
+    }
+
+    pub async fn insert(db: &Db, member: DbConversationMember) -> Result<(), DbError> {
+        timed("conversation_member_insert", async {
+            let _: Option<DbConversationMember> = db
+                .create((TABLE, key(&member.conversation_id, &member.acc)))
+                .content(member)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(
+        db: &Db,
+        conversation_id: &str,
+        acc: &str,
+    ) -> Result<Option<DbConversationMember>, DbError> {
+        timed("conversation_member_get", async {
+            db.select((TABLE, key(conversation_id, acc))).await
+        })
+        .await
+    }
+
+    pub async fn get_all_for_conversation(
+        db: &Db,
+        conversation_id: &str,
+    ) -> Result<Vec<DbConversationMember>, DbError> {
+        timed("conversation_member_get_all_for_conversation", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE conversation_id = $conversation_id ORDER BY acc")
+                .bind(("table", TABLE))
+                .bind(("conversation_id", conversation_id.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn get_all_for_acc(db: &Db, acc: &str) -> Result<Vec<DbConversationMember>, DbError> {
+        timed("conversation_member_get_all_for_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Only moves forward, an older read receipt arriving late is ignored.
+    pub async fn set_last_read_at(
+        db: &Db,
+        conversation_id: &str,
+        acc: &str,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("conversation_member_set_last_read_at", async {
+            db.query("UPDATE type::thing($table, $key) SET last_read_at = math::max([last_read_at, $time]), modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("key", key(conversation_id, acc)))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_muted(
+        db: &Db,
+        conversation_id: &str,
+        acc: &str,
+        muted: bool,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("conversation_member_set_muted", async {
+            db.query("UPDATE type::thing($table, $key) SET muted = $muted, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("key", key(conversation_id, acc)))
+                .bind(("muted", muted))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub mod message {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "message";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbMessage {
+        pub message_id: String,
+        pub conversation_id: String,
+        pub acc: String,
+        pub body: String,
+        /// Attachment ids, see [`super::attachment`].
+        pub attachments: Vec<String>,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, message: DbMessage) -> Result<(), DbError> {
+        timed("message_insert", async {
+            let _: Option<DbMessage> = db
+                .create((TABLE, message.message_id.as_str()))
+                .content(message)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Newest first, `before` pages further back in time.
+    pub async fn get_page(
+        db: &Db,
+        conversation_id: &str,
+        before: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<DbMessage>, DbError> {
+        timed("message_get_page", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE conversation_id = $conversation_id AND created_at < $before ORDER BY created_at DESC LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("conversation_id", conversation_id.to_string()))
+                .bind(("before", before.unwrap_or(i64::MAX)))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn get_last(db: &Db, conversation_id: &str) -> Result<Option<DbMessage>, DbError> {
+        let mut messages = get_page(db, conversation_id, None, 1).await?;
+        Ok(messages.pop())
+    }
+
+    /// Messages from others created after `after`.
+    pub async fn count_unread(
+        db: &Db,
+        conversation_id: &str,
+        acc: &str,
+        after: i64,
+    ) -> Result<u32, DbError> {
+        timed("message_count_unread", async {
+            let count: Option<u32> = db
+                .query("RETURN count(SELECT message_id FROM type::table($table) WHERE conversation_id = $conversation_id AND acc != $acc AND created_at > $after)")
+                .bind(("table", TABLE))
+                .bind(("conversation_id", conversation_id.to_string()))
+                .bind(("acc", acc.to_string()))
+                .bind(("after", after))
+                .await?
+                .take(0)?;
+            Ok(count.unwrap_or(0))
+        })
+        .await
+    }
+}
+
+pub mod attachment {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "attachment";
+
+    /// Uploaded image, stored as a file named after `attachment_id` under the media dir.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbAttachment {
+        pub attachment_id: String,
+        pub conversation_id: String,
+        pub acc: String,
+        pub mime: String,
+        pub size: u64,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, attachment: DbAttachment) -> Result<(), DbError> {
+        timed("attachment_insert", async {
+            let _: Option<DbAttachment> = db
+                .create((TABLE, attachment.attachment_id.as_str()))
+                .content(attachment)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, attachment_id: &str) -> Result<Option<DbAttachment>, DbError> {
+        timed("attachment_get", async {
+            db.select((TABLE, attachment_id)).await
+        })
+        .await
+    }
+}
+
+pub mod block {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "block";
+
+    /// `acc` doesn't want to hear from `blocked`.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbBlock {
+        pub acc: String,
+        pub blocked: String,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    fn key(acc: &str, blocked: &str) -> String {
+        format!("{}_{}", acc, blocked)
+    }
+
+    pub async fn upsert(db: &Db, block: DbBlock) -> Result<(), DbError> {
+        timed("block_upsert", async {
+            let _: Option<DbBlock> = db
+                .upsert((TABLE, key(&block.acc, &block.blocked)))
+                .content(block)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove(db: &Db, acc: &str, blocked: &str) -> Result<(), DbError> {
+        timed("block_remove", async {
+            let _: Option<DbBlock> = db.delete((TABLE, key(acc, blocked))).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_all_for_acc(db: &Db, acc: &str) -> Result<Vec<DbBlock>, DbError> {
+        timed("block_get_all_for_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE acc = $acc ORDER BY blocked")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Whether any of `accs` blocked `blocked`, or `blocked` blocked any of them.
+    pub async fn exists_between(db: &Db, blocked: &str, accs: &[String]) -> Result<bool, DbError> {
+        timed("block_exists_between", async {
+            let blocks: Vec<DbBlock> = db
+                .query("SELECT * OMIT id FROM type::table($table) WHERE (blocked = $blocked AND acc IN $accs) OR (acc = $blocked AND blocked IN $accs) LIMIT 1")
+                .bind(("table", TABLE))
+                .bind(("blocked", blocked.to_string()))
+                .bind(("accs", accs.to_vec()))
+                .await?
+                .take(0)?;
+            Ok(!blocks.is_empty())
+        })
+        .await
+    }
+}
+
+pub mod commission {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "commission";
+    pub const STATUS_OPEN: &str = "open";
+    pub const STATUS_COMPLETED: &str = "completed";
+    pub const STATUS_CANCELLED: &str = "cancelled";
+
+    /// Work ordered by `client` from `artist`, optionally for one of the client's bounties.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbCommission {
+        pub commission_id: String,
+        pub bounty_id: Option<String>,
+        pub client: String,
+        pub artist: String,
+        pub title: String,
+        pub status: String,
+        /// Versions handed out to revisions so far.
+        #[serde(default)]
+        pub revision_count: u32,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, commission: DbCommission) -> Result<(), DbError> {
+        timed("commission_insert", async {
+            let _: Option<DbCommission> = db
+                .create((TABLE, commission.commission_id.as_str()))
+                .content(commission)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, commission_id: &str) -> Result<Option<DbCommission>, DbError> {
+        timed("commission_get", async {
+            db.select((TABLE, commission_id)).await
+        })
+        .await
+    }
+
+    /// Commissions where `acc` is the client or the artist, newest first.
+    pub async fn get_all_for_acc(db: &Db, acc: &str) -> Result<Vec<DbCommission>, DbError> {
+        timed("commission_get_all_for_acc", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE client = $acc OR artist = $acc ORDER BY created_at DESC")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Bumps the revision counter and returns the new version, `None` if the commission is gone.
+    pub async fn next_revision_version(
+        db: &Db,
+        commission_id: &str,
+    ) -> Result<Option<u32>, DbError> {
+        timed("commission_next_revision_version", async {
+            db.query("UPDATE type::thing($table, $commission_id) SET revision_count += 1 RETURN VALUE revision_count")
+                .bind(("table", TABLE))
+                .bind(("commission_id", commission_id.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn set_status(
+        db: &Db,
+        commission_id: &str,
+        status: &str,
+        time: i64,
+    ) -> Result<Option<DbCommission>, DbError> {
+        timed("commission_set_status", async {
+            db.query("UPDATE type::thing($table, $commission_id) SET status = $status, modified_at = $time RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("commission_id", commission_id.to_string()))
+                .bind(("status", status.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod revision {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "revision";
+    pub const STATUS_PENDING: &str = "pending";
+    pub const STATUS_APPROVED: &str = "approved";
+    pub const STATUS_CHANGES_REQUESTED: &str = "changes_requested";
+    pub const PREVIEW_PENDING: &str = "pending";
+    pub const PREVIEW_READY: &str = "ready";
+    pub const PREVIEW_FAILED: &str = "failed";
+
+    /// Versioned deliverable of a commission, the original is stored as a file named after
+    /// `revision_id` and its preview is rendered by the backend media worker.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbRevision {
+        pub revision_id: String,
+        pub commission_id: String,
+        pub version: u32,
+        /// sketch, lineart or final.
+        pub stage: String,
+        pub note: String,
+        pub mime: String,
+        /// Known once the preview is rendered.
+        pub width: u32,
+        pub height: u32,
+        /// Client only sees the watermarked preview until approval.
+        pub watermark: bool,
+        pub preview_status: String,
+        pub status: String,
+        /// Set once the artist publishes an approved final revision.
+        pub artwork_id: Option<String>,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, revision: DbRevision) -> Result<(), DbError> {
+        timed("revision_insert", async {
+            let _: Option<DbRevision> = db
+                .create((TABLE, revision.revision_id.as_str()))
+                .content(revision)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, revision_id: &str) -> Result<Option<DbRevision>, DbError> {
+        timed("revision_get", async {
+            db.select((TABLE, revision_id)).await
+        })
+        .await
+    }
+
+    /// Full history of a commission, oldest version first.
+    pub async fn get_all_for_commission(
+        db: &Db,
+        commission_id: &str,
+    ) -> Result<Vec<DbRevision>, DbError> {
+        timed("revision_get_all_for_commission", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE commission_id = $commission_id ORDER BY version")
+                .bind(("table", TABLE))
+                .bind(("commission_id", commission_id.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Revisions waiting for their preview, oldest first.
+    pub async fn get_pending_previews(db: &Db, limit: u32) -> Result<Vec<DbRevision>, DbError> {
+        timed("revision_get_pending_previews", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE preview_status = $preview_status ORDER BY created_at LIMIT $limit")
+                .bind(("table", TABLE))
+                .bind(("preview_status", PREVIEW_PENDING))
+                .bind(("limit", limit))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// All revisions waiting for their preview, not only the next batch.
+    pub async fn count_pending_previews(db: &Db) -> Result<u64, DbError> {
+        timed("revision_count_pending_previews", async {
+            let count: Option<u64> = db
+                .query("RETURN count(SELECT revision_id FROM type::table($table) WHERE preview_status = $preview_status)")
+                .bind(("table", TABLE))
+                .bind(("preview_status", PREVIEW_PENDING))
+                .await?
+                .take(0)?;
+            Ok(count.unwrap_or(0))
+        })
+        .await
+    }
+
+    pub async fn set_preview(
+        db: &Db,
+        revision_id: &str,
+        preview_status: &str,
+        width: u32,
+        height: u32,
+        time: i64,
+    ) -> Result<(), DbError> {
+        timed("revision_set_preview", async {
+            db.query("UPDATE type::thing($table, $revision_id) SET preview_status = $preview_status, width = $width, height = $height, modified_at = $time")
+                .bind(("table", TABLE))
+                .bind(("revision_id", revision_id.to_string()))
+                .bind(("preview_status", preview_status.to_string()))
+                .bind(("width", width))
+                .bind(("height", height))
+                .bind(("time", time))
+                .await?
+                .check()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Decides a pending revision, `None` if it was already decided.
+    pub async fn decide(
+        db: &Db,
+        revision_id: &str,
+        status: &str,
+        time: i64,
+    ) -> Result<Option<DbRevision>, DbError> {
+        timed("revision_decide", async {
+            db.query("UPDATE type::thing($table, $revision_id) SET status = $status, modified_at = $time WHERE status = $pending RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("revision_id", revision_id.to_string()))
+                .bind(("status", status.to_string()))
+                .bind(("pending", STATUS_PENDING))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Links the published artwork, `None` if it was already published.
+    pub async fn set_artwork(
+        db: &Db,
+        revision_id: &str,
+        artwork_id: &str,
+        time: i64,
+    ) -> Result<Option<DbRevision>, DbError> {
+        timed("revision_set_artwork", async {
+            db.query("UPDATE type::thing($table, $revision_id) SET artwork_id = $artwork_id, modified_at = $time WHERE artwork_id = NONE RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("revision_id", revision_id.to_string()))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod revision_comment {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "revision_comment";
+
+    /// Feedback on a revision, optionally pinned to a point of the image.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbRevisionComment {
+        pub comment_id: String,
+        pub revision_id: String,
+        pub acc: String,
+        pub body: String,
+        /// Relative to the image size, 0.0-1.0 from the top left corner.
+        pub x: Option<f32>,
+        pub y: Option<f32>,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, comment: DbRevisionComment) -> Result<(), DbError> {
+        timed("revision_comment_insert", async {
+            let _: Option<DbRevisionComment> = db
+                .create((TABLE, comment.comment_id.as_str()))
+                .content(comment)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Oldest first.
+    pub async fn get_all_for_revision(
+        db: &Db,
+        revision_id: &str,
+    ) -> Result<Vec<DbRevisionComment>, DbError> {
+        timed("revision_comment_get_all_for_revision", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE revision_id = $revision_id ORDER BY created_at")
+                .bind(("table", TABLE))
+                .bind(("revision_id", revision_id.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod artwork_access {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "artwork_access";
+
+    /// Buyer allowed to download the clean original of a protected artwork.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbArtworkAccess {
+        pub artwork_id: String,
+        pub acc: String,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    fn key(artwork_id: &str, acc: &str) -> String {
+        format!("{}_{}", artwork_id, acc)
+    }
+
+    pub async fn upsert(db: &Db, access: DbArtworkAccess) -> Result<(), DbError> {
+        timed("artwork_access_upsert", async {
+            let _: Option<DbArtworkAccess> = db
+                .upsert((TABLE, key(&access.artwork_id, &access.acc)))
+                .content(access)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove(db: &Db, artwork_id: &str, acc: &str) -> Result<(), DbError> {
+        timed("artwork_access_remove", async {
+            let _: Option<DbArtworkAccess> = db.delete((TABLE, key(artwork_id, acc))).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn exists(db: &Db, artwork_id: &str, acc: &str) -> Result<bool, DbError> {
+        timed("artwork_access_exists", async {
+            let access: Option<DbArtworkAccess> = db.select((TABLE, key(artwork_id, acc))).await?;
+            Ok(access.is_some())
+        })
+        .await
+    }
+
+    pub async fn get_all_for_artwork(
+        db: &Db,
+        artwork_id: &str,
+    ) -> Result<Vec<DbArtworkAccess>, DbError> {
+        timed("artwork_access_get_all_for_artwork", async {
+            db.query("SELECT * OMIT id FROM type::table($table) WHERE artwork_id = $artwork_id ORDER BY created_at")
+                .bind(("table", TABLE))
+                .bind(("artwork_id", artwork_id.to_string()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod favorite {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "favorite";
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbFavorite {
+        pub acc: String,
+        pub artwork_id: String,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    fn key(acc: &str, artwork_id: &str) -> String {
+        format!("{}_{}", acc, artwork_id)
+    }
+
+    pub async fn upsert(db: &Db, favorite: DbFavorite) -> Result<(), DbError> {
+        timed("favorite_upsert", async {
+            let _: Option<DbFavorite> = db
+                .upsert((TABLE, key(&favorite.acc, &favorite.artwork_id)))
+                .content(favorite)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns whether there was a favorite to remove.
+    pub async fn remove(db: &Db, acc: &str, artwork_id: &str) -> Result<bool, DbError> {
+        timed("favorite_remove", async {
+            let removed: Option<DbFavorite> = db.delete((TABLE, key(acc, artwork_id))).await?;
+            Ok(removed.is_some())
+        })
+        .await
+    }
+
+    /// Which of `artwork_ids` `acc` favorited.
+    pub async fn get_among(
+        db: &Db,
+        acc: &str,
+        artwork_ids: &[String],
+    ) -> Result<Vec<String>, DbError> {
+        timed("favorite_get_among", async {
+            db.query("SELECT VALUE artwork_id FROM type::table($table) WHERE acc = $acc AND artwork_id IN $artwork_ids")
+                .bind(("table", TABLE))
+                .bind(("acc", acc.to_string()))
+                .bind(("artwork_ids", artwork_ids.to_vec()))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod watermark {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "watermark";
+    pub const KIND_TEXT: &str = "text";
+    pub const KIND_IMAGE: &str = "image";
+    pub const PLACEMENT_TILED: &str = "tiled";
+    pub const PLACEMENT_CORNER: &str = "corner";
+
+    /// How an artist's previews get watermarked, keyed by `acc`. The image of the image kind
+    /// is stored as a png file named after `acc` under the media dir.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbWatermark {
+        pub acc: String,
+        pub kind: String,
+        pub text: String,
+        pub placement: String,
+        /// In percent.
+        pub opacity: u8,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn upsert(db: &Db, watermark: DbWatermark) -> Result<(), DbError> {
+        timed("watermark_upsert", async {
+            let _: Option<DbWatermark> = db
+                .upsert((TABLE, watermark.acc.as_str()))
+                .content(watermark)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, acc: &str) -> Result<Option<DbWatermark>, DbError> {
+        timed("watermark_get", async { db.select((TABLE, acc)).await }).await
+    }
+}
+
+pub mod rating_audit {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "rating_audit";
+    pub const KIND_ARTWORK: &str = "artwork";
+    pub const KIND_BOUNTY: &str = "bounty";
+
+    /// Rating change made by a moderator on someone else's artwork or bounty.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbRatingAudit {
+        pub audit_id: String,
+        pub kind: String,
+        pub target_id: String,
+        pub moderator: String,
+        pub from: String,
+        pub to: String,
+        pub reason: String,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, audit: DbRatingAudit) -> Result<(), DbError> {
+        timed("rating_audit_insert", async {
+            let _: Option<DbRatingAudit> = db
+                .create((TABLE, audit.audit_id.as_str()))
+                .content(audit)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_page(db: &Db, limit: u32, offset: u32) -> Result<Vec<DbRatingAudit>, DbError> {
+        timed("rating_audit_get_page", async {
+            db.query("SELECT * OMIT id FROM type::table($table) ORDER BY created_at DESC LIMIT $limit START $offset")
+                .bind(("table", TABLE))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
+
+pub mod upload {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    pub const TABLE: &str = "upload";
+
+    /// Resumable upload, the bytes so far are in a temp file named after `upload_id`.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbUpload {
+        pub upload_id: String,
+        pub acc: String,
+        /// Size of the whole file in bytes.
+        pub length: u64,
+        /// Bytes received so far.
+        pub offset: u64,
+        pub expires_at: i64,
+        pub modified_at: i64,
+        pub created_at: i64,
+    }
+
+    pub async fn insert(db: &Db, upload: DbUpload) -> Result<(), DbError> {
+        timed("upload_insert", async {
+            let _: Option<DbUpload> = db
+                .create((TABLE, upload.upload_id.as_str()))
+                .content(upload)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(db: &Db, upload_id: &str) -> Result<Option<DbUpload>, DbError> {
+        timed("upload_get", async { db.select((TABLE, upload_id)).await }).await
+    }
+
+    /// Moves the upload from `offset` to `end`, `None` if it was not at `offset`.
+    pub async fn set_offset(
+        db: &Db,
+        upload_id: &str,
+        offset: u64,
+        end: u64,
+        expires_at: i64,
+        time: i64,
+    ) -> Result<Option<DbUpload>, DbError> {
+        timed("upload_set_offset", async {
+            db.query("UPDATE type::thing($table, $upload_id) SET offset = $end, expires_at = $expires_at, modified_at = $time WHERE offset = $offset RETURN AFTER")
+                .bind(("table", TABLE))
+                .bind(("upload_id", upload_id.to_string()))
+                .bind(("offset", offset))
+                .bind(("end", end))
+                .bind(("expires_at", expires_at))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    /// Removes a complete upload of `acc` and returns it, `None` if it is gone or incomplete.
+    pub async fn take(
+        db: &Db,
+        acc: &str,
+        upload_id: &str,
+        time: i64,
+    ) -> Result<Option<DbUpload>, DbError> {
+        timed("upload_take", async {
+            db.query("DELETE type::thing($table, $upload_id) WHERE acc = $acc AND offset = length AND expires_at > $time RETURN BEFORE")
+                .bind(("table", TABLE))
+                .bind(("upload_id", upload_id.to_string()))
+                .bind(("acc", acc.to_string()))
+                .bind(("time", time))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+
+    pub async fn remove(db: &Db, upload_id: &str) -> Result<(), DbError> {
+        timed("upload_remove", async {
+            let _: Option<DbUpload> = db.delete((TABLE, upload_id)).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_expired(db: &Db, time: i64, limit: u32) -> Result<Vec<DbUpload>, DbError> {
+        timed("upload_get_expired", async {
+            db.query(
+                "SELECT * OMIT id FROM type::table($table) WHERE expires_at <= $time LIMIT $limit",
+            )
+            .bind(("table", TABLE))
+            .bind(("time", time))
+            .bind(("limit", limit))
+            .await?
+            .take(0)
+        })
+        .await
+    }
+}
+
+/// Record keys of a table for the sitemap, oldest first so earlier chunks rarely change.
+pub mod sitemap {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Db, DbError, timed};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct DbSitemapEntry {
+        pub key: String,
+        pub modified_at: i64,
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+    pub struct DbSitemapStats {
+        pub count: u64,
+        /// Latest `modified_at`, `None` for an empty table.
+        pub modified_at: Option<i64>,
+    }
+
+    /// `ratings` of `None` is for tables without a content rating, like accounts.
+    pub async fn get_stats(
+        db: &Db,
+        table: &str,
+        ratings: Option<&[String]>,
+    ) -> Result<DbSitemapStats, DbError> {
+        timed("sitemap_get_stats", async {
+            let stats: Option<DbSitemapStats> = db
+                .query("SELECT count() AS count, math::max(modified_at) AS modified_at FROM type::table($table) WHERE $ratings = NONE OR rating INSIDE $ratings GROUP ALL")
+                .bind(("table", table.to_string()))
+                .bind(("ratings", ratings.map(<[String]>::to_vec)))
+                .await?
+                .take(0)?;
+            Ok(stats.unwrap_or_default())
+        })
+        .await
+    }
+
+    pub async fn get_chunk(
+        db: &Db,
+        table: &str,
+        ratings: Option<&[String]>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DbSitemapEntry>, DbError> {
+        timed("sitemap_get_chunk", async {
+            db.query("SELECT record::id(id) AS key, modified_at, created_at FROM type::table($table) WHERE $ratings = NONE OR rating INSIDE $ratings ORDER BY created_at ASC LIMIT $limit START $offset")
+                .bind(("table", table.to_string()))
+                .bind(("ratings", ratings.map(<[String]>::to_vec)))
+                .bind(("limit", limit))
+                .bind(("offset", offset))
+                .await?
+                .take(0)
+        })
+        .await
+    }
+}
@@ -0,0 +1,335 @@
+use std::sync::LazyLock;
+
+use chrono::DateTime;
+use fluent_bundle::{FluentResource, concurrent::FluentBundle};
+use fluent_langneg::{NegotiationStrategy, accepted_languages, negotiate_languages};
+use leptos::prelude::*;
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentArgs;
+
+pub const LOCALE_COOKIE: &str = "locale";
+pub const DEFAULT_LOCALE: &str = "en";
+/// Code, name in its own language and messages of every supported locale.
+pub const LOCALES: [(&str, &str, &str); 2] = [
+    ("en", "English", include_str!("i18n/en.ftl")),
+    ("de", "Deutsch", include_str!("i18n/de.ftl")),
+];
+
+static BUNDLES: LazyLock<Vec<(&'static str, FluentBundle<FluentResource>)>> = LazyLock::new(|| {
+    LOCALES
+        .iter()
+        .map(|(locale, _, source)| {
+            let id = locale
+                .parse::<LanguageIdentifier>()
+                .expect("locale codes are valid");
+            let resource =
+                FluentResource::try_new(source.to_string()).expect("bundled messages parse");
+            let mut bundle = FluentBundle::new_concurrent(vec![id]);
+            // the isolation marks around arguments show up as boxes in some fonts
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(resource)
+                .expect("bundled messages have unique ids");
+            (*locale, bundle)
+        })
+        .collect()
+});
+
+/// The supported locale matching `locale`, or the default one.
+pub fn supported(locale: &str) -> &'static str {
+    LOCALES
+        .iter()
+        .find(|(code, _, _)| code.eq_ignore_ascii_case(locale))
+        .map(|(code, _, _)| *code)
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Best supported locale for an `Accept-Language` header value.
+pub fn negotiate(accept_language: &str) -> &'static str {
+    let requested = accepted_languages::parse(accept_language);
+    let available = LOCALES
+        .iter()
+        .filter_map(|(code, _, _)| code.parse::<LanguageIdentifier>().ok())
+        .collect::<Vec<_>>();
+    negotiate_languages(&requested, &available, None, NegotiationStrategy::Filtering)
+        .first()
+        .map(|id| supported(&id.to_string()))
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Finds the locale cookie in a `Cookie` header value, only if it's a supported one.
+pub fn get_locale_cookie(cookie_header: &str) -> Option<&'static str> {
+    cookie_header.split(';').find_map(|cookie| {
+        let (name, value) = cookie.trim().split_once('=')?;
+        (name == LOCALE_COOKIE)
+            .then(|| LOCALES.iter().find(|(code, _, _)| *code == value))
+            .flatten()
+            .map(|(code, _, _)| *code)
+    })
+}
+
+pub fn locale_cookie(locale: &str) -> String {
+    format!(
+        "{}={}; Path=/; SameSite=Lax; Max-Age={}",
+        LOCALE_COOKIE,
+        supported(locale),
+        60 * 60 * 24 * 365
+    )
+}
+
+/// The cookie picked by the user wins over what the browser asks for.
+#[cfg(feature = "ssr")]
+pub fn locale_from_headers(headers: &axum::http::HeaderMap) -> &'static str {
+    use axum::http::header::{ACCEPT_LANGUAGE, COOKIE};
+
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .find_map(get_locale_cookie)
+        .or_else(|| {
+            headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|accept| accept.to_str().ok())
+                .map(negotiate)
+        })
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Locale of the page being rendered. The server negotiates it from the request and writes it to
+/// `<html lang>`, hydration reads it back from there so both render the same text.
+pub fn request_locale() -> &'static str {
+    #[cfg(feature = "ssr")]
+    {
+        use_context::<axum::http::request::Parts>()
+            .map(|parts| locale_from_headers(&parts.headers))
+            .unwrap_or(DEFAULT_LOCALE)
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        document()
+            .document_element()
+            .and_then(|html| html.get_attribute("lang"))
+            .map(|lang| supported(&lang))
+            .unwrap_or(DEFAULT_LOCALE)
+    }
+}
+
+/// Message `key` in `locale`, falling back to English and then to the key itself.
+pub fn translate(locale: &str, key: &str, args: Option<&FluentArgs>) -> String {
+    for locale in [supported(locale), DEFAULT_LOCALE] {
+        let Some((_, bundle)) = BUNDLES.iter().find(|(code, _)| *code == locale) else {
+            continue;
+        };
+        let Some(pattern) = bundle.get_message(key).and_then(|message| message.value()) else {
+            continue;
+        };
+        let mut errors = Vec::new();
+        let text = bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            warn!("message {} in {}: {:?}", key, locale, errors);
+        }
+        return text.into_owned();
+    }
+    warn!("message {} is missing", key);
+    key.to_string()
+}
+
+/// Thousands and decimal separators.
+fn separators(locale: &str) -> (char, char) {
+    match supported(locale) {
+        "de" => ('.', ','),
+        _ => (',', '.'),
+    }
+}
+
+fn group(digits: &str, separator: char) -> String {
+    let mut output = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            output.push(separator);
+        }
+        output.push(digit);
+    }
+    output
+}
+
+pub fn format_number(locale: &str, value: i64) -> String {
+    let (thousands, _) = separators(locale);
+    let digits = group(&value.unsigned_abs().to_string(), thousands);
+    if value < 0 {
+        format!("-{}", digits)
+    } else {
+        digits
+    }
+}
+
+/// Dollar amount of `cents`, like bounty rewards.
+pub fn format_price(locale: &str, cents: u64) -> String {
+    let (thousands, decimal) = separators(locale);
+    let amount = format!(
+        "{}{}{:02}",
+        group(&(cents / 100).to_string(), thousands),
+        decimal,
+        cents % 100
+    );
+    match supported(locale) {
+        "de" => format!("{} $", amount),
+        _ => format!("${}", amount),
+    }
+}
+
+/// `time` in milliseconds, in UTC so the server and the browser render the same text.
+pub fn format_time(locale: &str, time: i64) -> String {
+    let format = match supported(locale) {
+        "de" => "%d.%m.%Y %H:%M",
+        _ => "%Y-%m-%d %H:%M",
+    };
+    DateTime::from_timestamp_millis(time)
+        .map(|time| time.format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Locale of the page, provided by the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I18n {
+    pub locale: &'static str,
+}
+
+impl I18n {
+    pub fn new(locale: &str) -> Self {
+        Self {
+            locale: supported(locale),
+        }
+    }
+
+    pub fn t(&self, key: &str, args: Option<&FluentArgs>) -> String {
+        translate(self.locale, key, args)
+    }
+
+    pub fn number(&self, value: i64) -> String {
+        format_number(self.locale, value)
+    }
+
+    pub fn price(&self, cents: u64) -> String {
+        format_price(self.locale, cents)
+    }
+
+    pub fn time(&self, time: i64) -> String {
+        format_time(self.locale, time)
+    }
+}
+
+impl Default for I18n {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCALE)
+    }
+}
+
+pub fn use_i18n() -> I18n {
+    use_context::<I18n>().unwrap_or_default()
+}
+
+/// Message of the page locale, `t!("nav-messages-unread", count = 3)` passes `$count`.
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::use_i18n().t($key, None)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::use_i18n().t($key, Some(&args))
+    }};
+}
+
+#[cfg(test)]
+mod i18n_tests {
+    use super::{
+        FluentArgs, LOCALES, format_number, format_price, format_time, get_locale_cookie,
+        negotiate, translate,
+    };
+
+    #[test]
+    fn locale_comes_from_cookie_or_accept_language() {
+        assert_eq!(negotiate("de-AT,de;q=0.9,en;q=0.8"), "de");
+        assert_eq!(negotiate("fr-FR,en-US;q=0.5"), "en");
+        assert_eq!(negotiate("fr-FR"), "en");
+        assert_eq!(negotiate(""), "en");
+        assert_eq!(get_locale_cookie("session=abc; locale=de"), Some("de"));
+        assert_eq!(get_locale_cookie("locale=xx"), None);
+    }
+
+    #[test]
+    fn messages_pluralize_and_fall_back() {
+        let count = |count: u32| {
+            let mut args = FluentArgs::new();
+            args.set("count", count);
+            args
+        };
+        assert_eq!(
+            translate("en", "nav-messages-unread-title", Some(&count(1))),
+            "One unread message"
+        );
+        assert_eq!(
+            translate("de", "nav-messages-unread-title", Some(&count(2))),
+            "2 ungelesene Nachrichten"
+        );
+        assert_eq!(translate("xx", "nav-login", None), "login");
+        assert_eq!(translate("de", "missing-message", None), "missing-message");
+    }
+
+    #[test]
+    fn every_locale_has_every_message() {
+        let ids = |source: &'static str| {
+            source
+                .lines()
+                .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+                .filter_map(|line| line.split_once('=').map(|(id, _)| id.trim()))
+                .collect::<Vec<_>>()
+        };
+        let (_, _, default) = LOCALES[0];
+        for (locale, _, source) in LOCALES {
+            assert_eq!(ids(source), ids(default), "messages of {}", locale);
+        }
+    }
+
+    #[test]
+    fn pages_only_use_known_messages() {
+        let (_, _, default) = LOCALES[0];
+        for page in [
+            include_str!("app/page.rs"),
+            include_str!("app/components.rs"),
+        ] {
+            // `format!("` ends the same way, so the macro has to start its own word
+            for key in ["t!(\"", "success=\""]
+                .into_iter()
+                .flat_map(|prefix| page.match_indices(prefix))
+                .filter(|(i, _)| !page[..*i].ends_with(|c: char| c.is_alphanumeric()))
+                .filter_map(|(i, prefix)| {
+                    page[i + prefix.len()..].split_once('"').map(|(key, _)| key)
+                })
+            {
+                assert!(
+                    default
+                        .lines()
+                        .any(|line| line.split_once('=').is_some_and(|(id, _)| id.trim() == key)),
+                    "missing message {}",
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn numbers_prices_and_times_are_localized() {
+        assert_eq!(format_number("en", -1234567), "-1,234,567");
+        assert_eq!(format_number("de", 999), "999");
+        assert_eq!(format_price("en", 123450), "$1,234.50");
+        assert_eq!(format_price("de", 5), "0,05 $");
+        assert_eq!(format_time("en", 0), "1970-01-01 00:00");
+        assert_eq!(format_time("de", 0), "01.01.1970 00:00");
+    }
+}
@@ -0,0 +1,251 @@
+nav-admin = Verwaltung
+nav-messages = Nachrichten
+nav-messages-unread = Nachrichten ({ $count })
+nav-messages-unread-title =
+    { $count ->
+        [one] Eine ungelesene Nachricht
+       *[other] { $count } ungelesene Nachrichten
+    }
+nav-upload = Hochladen
+nav-commissions = Aufträge
+nav-logout = Abmelden
+nav-login = Anmelden
+nav-register = Registrieren
+nav-language = Sprache
+
+home-color = Farbe
+home-clear = zurücksetzen
+
+gallery-reveal = Sensibler Inhalt, zum Anzeigen klicken
+
+shortcuts-title = Tastenkürzel
+shortcut-scope-global = überall
+shortcut-scope-gallery = Galerie
+shortcut-scope-viewer = Ansicht
+shortcut-scope-cheat-sheet = diese Liste
+shortcut-go-gallery = zur Galerie
+shortcut-go-upload = zum Hochladen
+shortcut-go-messages = zu den Nachrichten
+shortcut-go-commissions = zu den Aufträgen
+shortcut-go-settings = zu den Einstellungen
+shortcut-show-shortcuts = Tastenkürzel anzeigen
+shortcut-close-shortcuts = diese Liste schließen
+shortcut-next = nächstes Kunstwerk auswählen
+shortcut-previous = vorheriges Kunstwerk auswählen
+shortcut-favorite = ausgewähltes Kunstwerk favorisieren
+shortcut-open = ausgewähltes Kunstwerk öffnen
+shortcut-clear = Auswahl aufheben
+shortcut-close-artwork = Kunstwerk schließen
+
+theme-toggle = Farbschema wechseln
+theme-system = System
+theme-light = hell
+theme-dark = dunkel
+
+artwork-not-found = Kunstwerk nicht gefunden
+
+profile-not-found = Benutzer nicht gefunden
+profile-joined = Dabei seit { $date }
+profile-empty = Noch keine Kunstwerke
+
+bounty-not-found = Kopfgeld nicht gefunden
+bounty-reward = Belohnung: { $reward }
+bounty-status-open = offen
+bounty-status-closed = geschlossen
+
+rating-sfw = Jugendfrei
+rating-mature = Ab 16
+rating-explicit = Explizit
+
+form-username = Benutzername
+form-email = E-Mail
+form-password = Passwort
+form-code = Code
+form-verify = Bestätigen
+form-save = Speichern
+form-description = Beschreibung
+form-tags = Schlagwörter
+form-title = Titel
+form-message = Nachricht
+form-revoke = Entziehen
+form-enable = Aktivieren
+
+register-title = Registrieren
+register-submit = Registrieren
+register-done = Konto erstellt, in deiner E-Mail findest du einen Bestätigungslink.
+register-login = Schon ein Konto?
+
+login-title = Anmelden
+login-submit = Anmelden
+login-done = Angemeldet.
+login-forgot-password = Passwort vergessen?
+login-register = Konto erstellen
+login-totp-title = Zwei-Faktor-Authentifizierung
+login-totp-hint = Gib den Code aus deiner Authenticator-App oder einen deiner Wiederherstellungscodes ein.
+
+verify-title = E-Mail bestätigen
+verify-done = E-Mail bestätigt.
+verify-resend-title = Bestätigungslink erneut senden
+verify-resend = Erneut senden
+verify-resend-done = Falls die E-Mail zu einem unbestätigten Konto gehört, ist ein neuer Link unterwegs.
+
+reset-title = Passwort zurücksetzen
+reset-send = Link zum Zurücksetzen senden
+reset-sent = Falls die E-Mail registriert ist, ist ein Link zum Zurücksetzen unterwegs.
+reset-new-password = neues Passwort
+reset-submit = Neues Passwort setzen
+reset-done = Passwort geändert, du kannst dich jetzt anmelden.
+
+settings-title = Einstellungen
+settings-login = Melde dich an, um die Einstellungen zu ändern.
+settings-totp-title = Zwei-Faktor-Authentifizierung
+settings-totp-enabled =
+    { $count ->
+        [one] Aktiviert, ein Wiederherstellungscode übrig.
+       *[other] Aktiviert, { $count } Wiederherstellungscodes übrig.
+    }
+settings-totp-new-codes = Neue Wiederherstellungscodes
+settings-totp-disable = Deaktivieren
+settings-totp-disabled = Zwei-Faktor-Authentifizierung deaktiviert.
+settings-totp-set-up = Einrichten
+settings-totp-scan = Scanne den Code mit deiner Authenticator-App oder gib den Schlüssel von Hand ein.
+settings-totp-enable-done = Zwei-Faktor-Authentifizierung aktiviert.
+settings-totp-recovery-codes = Wiederherstellungscodes, jeder funktioniert einmal. Speichere sie jetzt, sie werden nicht noch einmal angezeigt:
+settings-discord-title = Discord
+settings-discord-linked = Discord-Konto verknüpft.
+settings-discord-failed = Verknüpfung mit Discord fehlgeschlagen: { $error }
+settings-discord-linked-to = Verknüpft mit Discord-Benutzer { $user }.
+settings-discord-unlink = Verknüpfung lösen
+settings-discord-link = Mit Discord verknüpfen
+settings-discord-get-code = Oder einen Code für den Bot holen
+settings-discord-code-run = Führe
+settings-discord-code-where = innerhalb von 10 Minuten auf einem Server mit dem Bot aus.
+settings-tokens-title = API-Tokens
+settings-tokens-hint = Persönliche Zugriffstokens für die JSON-API unter /api/v1.
+settings-token-name = Name
+settings-token-rate-limit = Anfragen pro Minute
+settings-token-create = Token erstellen
+settings-token-copy = Kopiere das Token jetzt, es wird nicht noch einmal angezeigt:
+settings-token-usage = { $rate_limit } Anfragen/min, zuletzt benutzt { $last_used }
+settings-token-never-used = nie
+settings-webhooks-title = Webhooks
+settings-webhooks-hint = Ereignisse werden als JSON gePOSTet, signiert mit HMAC-SHA256 im Header x-artbounty-signature.
+settings-webhook-add = Webhook hinzufügen
+settings-webhook-copy = Kopiere das Signaturgeheimnis jetzt, es wird nicht noch einmal angezeigt:
+settings-webhook-enabled =
+    { $failures ->
+        [one] aktiv, ein Fehlschlag in Folge
+       *[other] aktiv, { $failures } Fehlschläge in Folge
+    }
+settings-webhook-disabled = nach wiederholten Fehlschlägen deaktiviert
+settings-webhook-test = Testereignis senden
+settings-webhook-test-done = Testereignis eingereiht.
+settings-webhook-deliveries = Zustellungen
+settings-webhook-delete = Löschen
+settings-webhook-delivery =
+    { $attempts ->
+        [one] { $created_at } { $event } { $status }, ein Versuch, { $response }
+       *[other] { $created_at } { $event } { $status }, { $attempts } Versuche, { $response }
+    }
+settings-webhook-not-sent = nicht gesendet
+
+content-title = Inhalte
+content-mature = Ab 16
+content-explicit = Explizit
+content-done = Gespeichert.
+content-hide = Ausblenden
+content-blur = Verschwommen bis zum Klick
+content-show = Anzeigen
+
+watermark-title = Wasserzeichen
+watermark-hint = Wird über die öffentlichen Vorschauen geschützter Kunstwerke gelegt, Käufer bekommen das saubere Original.
+watermark-kind-text = Text
+watermark-kind-image = Hochgeladenes PNG
+watermark-placement-tiled = Über das ganze Bild gekachelt
+watermark-placement-corner = Ecke unten rechts
+watermark-text = Text
+watermark-opacity = Deckkraft %
+watermark-save = Wasserzeichen speichern
+watermark-done = Gespeichert, die Vorschauen werden neu erstellt.
+watermark-has-image = Ein PNG ist hochgeladen.
+watermark-no-image = Noch kein PNG hochgeladen.
+watermark-upload = PNG hochladen
+watermark-artworks-title = Kunstwerke
+watermark-protected = Geschützt, Vorschauen haben ein Wasserzeichen.
+watermark-public = Öffentlich, alle sehen das Original.
+watermark-unprotect = Schutz aufheben
+watermark-protect = Schützen
+watermark-rate = Einstufen
+watermark-buyer = Käufer
+watermark-grant = Freigeben
+
+messages-title = Nachrichten
+messages-usernames = Benutzernamen
+messages-start = Neue Unterhaltung
+messages-muted = (stumm)
+messages-seen-by = gesehen von { $usernames }
+messages-blocked-title = Blockierte Benutzer
+messages-block = blockieren
+messages-unblock = entblocken
+messages-pick = Wähle eine Unterhaltung oder beginne eine neue.
+messages-mute = stummschalten
+messages-unmute = Stummschaltung aufheben
+messages-send = Senden
+
+upload-title = Kunstwerk hochladen
+upload-hint = Uploads machen dort weiter, wo sie aufgehört haben, wähle nach dem Neuladen einfach dieselbe Datei. Bilder lassen sich auch auf die Galerie ziehen.
+upload-paused = pausiert bei { $percent } %
+upload-duplicate = schon hochgeladen
+upload-done = hochgeladen
+upload-pause = Pausieren
+upload-resume = Fortsetzen
+upload-anyway = Trotzdem hochladen
+upload-publish = Veröffentlichen
+upload-published = Veröffentlicht.
+
+commissions-title = Aufträge
+commissions-empty = Noch keine Aufträge.
+commissions-artist = Benutzername des Künstlers
+commissions-bounty = Kopfgeld-ID (optional)
+commissions-order = Auftrag erteilen
+commission-cancel = abbrechen
+revision-stage-sketch = Skizze
+revision-stage-lineart = Reinzeichnung
+revision-stage-final = Fertig
+revision-rendering = Vorschau wird erstellt…
+revision-original = Original
+revision-watermarked = mit Wasserzeichen
+revision-pinned = am Bild angeheftet,
+revision-unpin = lösen
+revision-approve = Annehmen
+revision-changes = was soll sich ändern
+revision-request-changes = Änderungen anfordern
+revision-publish = In der Galerie veröffentlichen
+revision-published = In der Galerie veröffentlicht.
+revision-comment-hint = Kommentar, klicke ins Bild, um ihn anzuheften
+revision-comment = Kommentieren
+revision-upload-title = Revision hochladen
+revision-note = Notiz
+revision-watermark = Wasserzeichen bis zur Annahme
+revision-upload = Hochladen
+
+admin-accounts-title = Konten
+admin-username = Benutzername
+admin-email = E-Mail
+admin-role = Rolle
+admin-verified = bestätigt
+admin-totp = 2FA
+admin-created = erstellt
+admin-totp-on = an ({ $count } Codes)
+admin-totp-off = aus
+admin-ratings-title = Inhaltseinstufungen
+admin-kind-artwork = Kunstwerk
+admin-kind-bounty = Kopfgeld
+admin-target-id = ID
+admin-reason = Grund
+admin-change-rating = Einstufung ändern
+admin-rating-changed = Einstufung geändert.
+admin-when = wann
+admin-moderator = Moderator
+admin-target = Ziel
+admin-rating = Einstufung
@@ -0,0 +1,251 @@
+nav-admin = admin
+nav-messages = messages
+nav-messages-unread = messages ({ $count })
+nav-messages-unread-title =
+    { $count ->
+        [one] One unread message
+       *[other] { $count } unread messages
+    }
+nav-upload = upload
+nav-commissions = commissions
+nav-logout = logout
+nav-login = login
+nav-register = register
+nav-language = Language
+
+home-color = Color
+home-clear = clear
+
+gallery-reveal = Sensitive content, click to reveal
+
+shortcuts-title = Keyboard shortcuts
+shortcut-scope-global = global
+shortcut-scope-gallery = gallery
+shortcut-scope-viewer = viewer
+shortcut-scope-cheat-sheet = this list
+shortcut-go-gallery = go to the gallery
+shortcut-go-upload = go to upload
+shortcut-go-messages = go to messages
+shortcut-go-commissions = go to commissions
+shortcut-go-settings = go to settings
+shortcut-show-shortcuts = show keyboard shortcuts
+shortcut-close-shortcuts = close this list
+shortcut-next = select the next artwork
+shortcut-previous = select the previous artwork
+shortcut-favorite = favorite the selected artwork
+shortcut-open = open the selected artwork
+shortcut-clear = clear the selection
+shortcut-close-artwork = close the artwork
+
+theme-toggle = Switch the color theme
+theme-system = system
+theme-light = light
+theme-dark = dark
+
+artwork-not-found = Artwork not found
+
+profile-not-found = User not found
+profile-joined = Joined { $date }
+profile-empty = No artworks yet
+
+bounty-not-found = Bounty not found
+bounty-reward = Reward: { $reward }
+bounty-status-open = open
+bounty-status-closed = closed
+
+rating-sfw = Safe for work
+rating-mature = Mature
+rating-explicit = Explicit
+
+form-username = username
+form-email = email
+form-password = password
+form-code = code
+form-verify = Verify
+form-save = Save
+form-description = description
+form-tags = tags
+form-title = title
+form-message = message
+form-revoke = Revoke
+form-enable = Enable
+
+register-title = Register
+register-submit = Register
+register-done = Account created, check your email for a verification link.
+register-login = Already have an account?
+
+login-title = Login
+login-submit = Login
+login-done = Logged in.
+login-forgot-password = Forgot password?
+login-register = Create an account
+login-totp-title = Two-factor authentication
+login-totp-hint = Enter the code from your authenticator app or one of your recovery codes.
+
+verify-title = Verify email
+verify-done = Email verified.
+verify-resend-title = Resend verification link
+verify-resend = Resend
+verify-resend-done = If that email belongs to an unverified account, a new link is on its way.
+
+reset-title = Reset password
+reset-send = Send reset link
+reset-sent = If that email is registered, a reset link is on its way.
+reset-new-password = new password
+reset-submit = Set new password
+reset-done = Password changed, you can login now.
+
+settings-title = Settings
+settings-login = Login to change settings.
+settings-totp-title = Two-factor authentication
+settings-totp-enabled =
+    { $count ->
+        [one] Enabled, one recovery code left.
+       *[other] Enabled, { $count } recovery codes left.
+    }
+settings-totp-new-codes = New recovery codes
+settings-totp-disable = Disable
+settings-totp-disabled = Two-factor authentication disabled.
+settings-totp-set-up = Set up
+settings-totp-scan = Scan the code with your authenticator app, or enter the key manually.
+settings-totp-enable-done = Two-factor authentication enabled.
+settings-totp-recovery-codes = Recovery codes, each works once. Save them now, they won't be shown again:
+settings-discord-title = Discord
+settings-discord-linked = Discord account linked.
+settings-discord-failed = Discord linking failed: { $error }
+settings-discord-linked-to = Linked to discord user { $user }.
+settings-discord-unlink = Unlink
+settings-discord-link = Link with Discord
+settings-discord-get-code = Or get a code for the bot
+settings-discord-code-run = Run
+settings-discord-code-where = in a server with the bot within 10 minutes.
+settings-tokens-title = API tokens
+settings-tokens-hint = Personal access tokens for the JSON API at /api/v1.
+settings-token-name = name
+settings-token-rate-limit = Requests per minute
+settings-token-create = Create token
+settings-token-copy = Copy the token now, it won't be shown again:
+settings-token-usage = { $rate_limit } requests/min, last used { $last_used }
+settings-token-never-used = never
+settings-webhooks-title = Webhooks
+settings-webhooks-hint = Events are POSTed as JSON, signed with HMAC-SHA256 in the x-artbounty-signature header.
+settings-webhook-add = Add webhook
+settings-webhook-copy = Copy the signing secret now, it won't be shown again:
+settings-webhook-enabled =
+    { $failures ->
+        [one] enabled, one failure in a row
+       *[other] enabled, { $failures } failures in a row
+    }
+settings-webhook-disabled = disabled after repeated failures
+settings-webhook-test = Send test event
+settings-webhook-test-done = Test event queued.
+settings-webhook-deliveries = Deliveries
+settings-webhook-delete = Delete
+settings-webhook-delivery =
+    { $attempts ->
+        [one] { $created_at } { $event } { $status }, one attempt, { $response }
+       *[other] { $created_at } { $event } { $status }, { $attempts } attempts, { $response }
+    }
+settings-webhook-not-sent = not sent
+
+content-title = Content
+content-mature = Mature
+content-explicit = Explicit
+content-done = Saved.
+content-hide = Hide
+content-blur = Blur until clicked
+content-show = Show
+
+watermark-title = Watermark
+watermark-hint = Drawn over the public previews of protected artworks, buyers get the clean original.
+watermark-kind-text = Text
+watermark-kind-image = Uploaded PNG
+watermark-placement-tiled = Tiled across the image
+watermark-placement-corner = Bottom right corner
+watermark-text = text
+watermark-opacity = Opacity %
+watermark-save = Save watermark
+watermark-done = Saved, previews will be rendered again.
+watermark-has-image = A png is uploaded.
+watermark-no-image = No png uploaded yet.
+watermark-upload = Upload png
+watermark-artworks-title = Artworks
+watermark-protected = Protected, previews are watermarked.
+watermark-public = Public, the original is shown to everyone.
+watermark-unprotect = Unprotect
+watermark-protect = Protect
+watermark-rate = Rate
+watermark-buyer = buyer
+watermark-grant = Grant
+
+messages-title = Messages
+messages-usernames = usernames
+messages-start = New conversation
+messages-muted = (muted)
+messages-seen-by = seen by { $usernames }
+messages-blocked-title = Blocked users
+messages-block = block
+messages-unblock = unblock
+messages-pick = Pick a conversation or start a new one.
+messages-mute = mute
+messages-unmute = unmute
+messages-send = Send
+
+upload-title = Upload artwork
+upload-hint = Uploads continue where they stopped, pick the same file again after a reload. Images can also be dropped on the gallery.
+upload-paused = paused at { $percent }%
+upload-duplicate = already uploaded
+upload-done = uploaded
+upload-pause = Pause
+upload-resume = Resume
+upload-anyway = Upload anyway
+upload-publish = Publish
+upload-published = Published.
+
+commissions-title = Commissions
+commissions-empty = No commissions yet.
+commissions-artist = artist username
+commissions-bounty = bounty id (optional)
+commissions-order = Order commission
+commission-cancel = cancel
+revision-stage-sketch = Sketch
+revision-stage-lineart = Lineart
+revision-stage-final = Final
+revision-rendering = Preview is being rendered…
+revision-original = original
+revision-watermarked = watermarked
+revision-pinned = pinned to the image,
+revision-unpin = unpin
+revision-approve = Approve
+revision-changes = what should change
+revision-request-changes = Request changes
+revision-publish = Publish to gallery
+revision-published = Published to the gallery.
+revision-comment-hint = comment, click the image to pin it
+revision-comment = Comment
+revision-upload-title = Upload revision
+revision-note = note
+revision-watermark = Watermark until approved
+revision-upload = Upload
+
+admin-accounts-title = Accounts
+admin-username = username
+admin-email = email
+admin-role = role
+admin-verified = verified
+admin-totp = 2fa
+admin-created = created
+admin-totp-on = on ({ $count } codes)
+admin-totp-off = off
+admin-ratings-title = Content ratings
+admin-kind-artwork = Artwork
+admin-kind-bounty = Bounty
+admin-target-id = id
+admin-reason = reason
+admin-change-rating = Change rating
+admin-rating-changed = Rating changed.
+admin-when = when
+admin-moderator = moderator
+admin-target = target
+admin-rating = rating
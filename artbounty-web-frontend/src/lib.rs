@@ -1,28 +1,39 @@
 use leptos::prelude::*;
+use leptos_meta::MetaTags;
 use server_fn::codec::Rkyv;
 
 use app::App;
 use tracing::trace;
 
+pub mod api;
 pub mod app;
+#[cfg(feature = "ssr")]
+pub mod db;
+pub mod i18n;
 pub mod logger;
+#[cfg(feature = "ssr")]
+pub mod server;
+pub mod theme;
 pub mod toolbox;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
+    let locale = i18n::request_locale();
+    let theme = theme::request_theme();
 
     view! {
         <!DOCTYPE html>
-        <html lang="en">
+        <html lang=locale class=theme>
             <head>
                 <meta charset="utf-8" />
                 <meta name="viewport" content="width=device-width, initial-scale=1" />
 
                 <HydrationScripts options />
+                <MetaTags />
                 <meta name="color-scheme" content="dark light" />
                 <link rel="shortcut icon" type="image/ico" href="/favicon.ico" />
                 <link rel="stylesheet" id="leptos" href="/pkg/artbounty_1.css" />
             </head>
-            <body class="bg-gray-950">
+            <body class="bg-white text-gray-900 dark:bg-gray-950 dark:text-gray-200">
                 <App />
             </body>
         </html>
@@ -32,7 +43,7 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
 pub fn hydrate() {
-    console_error_panic_hook::set_once();
     logger::simple_logger_init();
+    logger::set_panic_hook();
     leptos::mount::hydrate_body(App);
 }
@@ -1,9 +1,33 @@
+use std::{sync::Mutex, time::Duration};
+
 use tracing::span;
 use tracing_subscriber::field::RecordFields;
 use tracing_subscriber::fmt::format::PrettyVisitor;
 use tracing_subscriber::fmt::format::Writer;
 use wasm_bindgen::prelude::*;
 
+use crate::api::{
+    CLIENT_LOG_BATCH_SIZE, CLIENT_LOG_BEACON_SIZE, CLIENT_LOG_FLUSH_DELAY_MS,
+    CLIENT_LOG_PAGE_LIMIT, CLIENT_LOG_PATH, ClientLogEvent,
+};
+
+/// Identifies the wasm build in forwarded events, `BUILD_ID` at compile time or the crate version.
+pub const BUILD_ID: &str = match option_env!("BUILD_ID") {
+    Some(id) => id,
+    None => env!("CARGO_PKG_VERSION"),
+};
+
+static REMOTE: Mutex<Option<RemoteSink>> = Mutex::new(None);
+
+struct RemoteSink {
+    url: String,
+    /// Serialized events waiting for the next beacon.
+    queue: Vec<String>,
+    queued_bytes: usize,
+    sent: usize,
+    scheduled: bool,
+}
+
 #[derive(Debug, Clone)]
 struct SpanBody(pub String);
 
@@ -14,6 +38,8 @@ pub struct WASMTracingLayer {
 pub struct WASMTracingConfig {
     pub target: bool,
     pub line: bool,
+    /// WARN and ERROR events are also posted here in batches, `None` keeps them in the console.
+    pub remote: Option<String>,
 }
 
 pub fn simple_logger_init() {
@@ -23,18 +49,149 @@ pub fn simple_logger_init() {
             WASMTracingLayer::new(WASMTracingConfig {
                 line: false,
                 target: false,
+                remote: Some(String::from(CLIENT_LOG_PATH)),
             }),
         ),
     )
     .unwrap();
 }
 
+/// Logs panics like `console_error_panic_hook` and forwards them right away, the module is dead
+/// once the hook returns.
+pub fn set_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        console_error_panic_hook::hook(info);
+        let origin = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+        queue(client_event("PANIC", info.to_string(), "panic", "", origin));
+        flush();
+    }));
+}
+
 impl WASMTracingLayer {
     pub fn new(config: WASMTracingConfig) -> Self {
+        if let Some(url) = &config.remote
+            && let Ok(mut remote) = REMOTE.lock()
+        {
+            *remote = Some(RemoteSink {
+                url: url.clone(),
+                queue: Vec::new(),
+                queued_bytes: 0,
+                sent: 0,
+                scheduled: false,
+            });
+        }
         Self { config }
     }
 }
 
+fn client_event(
+    level: &str,
+    message: String,
+    target: &str,
+    spans: &str,
+    origin: String,
+) -> ClientLogEvent {
+    let window = web_sys::window();
+    ClientLogEvent {
+        level: level.to_string(),
+        message,
+        target: target.to_string(),
+        spans: spans.to_string(),
+        origin,
+        url: window
+            .as_ref()
+            .and_then(|window| window.location().href().ok())
+            .unwrap_or_default(),
+        user_agent: window
+            .as_ref()
+            .and_then(|window| window.navigator().user_agent().ok())
+            .unwrap_or_default(),
+        build: BUILD_ID.to_string(),
+    }
+}
+
+/// `try_lock` so an event logged while the queue is held, or a panic inside it, is dropped
+/// instead of deadlocking.
+fn queue(event: ClientLogEvent) {
+    let Ok(mut remote) = REMOTE.try_lock() else {
+        return;
+    };
+    let Some(remote) = remote.as_mut() else {
+        return;
+    };
+    if remote.sent + remote.queue.len() >= CLIENT_LOG_PAGE_LIMIT {
+        return;
+    }
+    let Ok(event) = serde_json::to_string(&event.clipped()) else {
+        return;
+    };
+    remote.queued_bytes += event.len() + 1;
+    remote.queue.push(event);
+    if remote.queue.len() >= CLIENT_LOG_BATCH_SIZE || remote.queued_bytes >= CLIENT_LOG_BEACON_SIZE
+    {
+        send(remote);
+    } else if !remote.scheduled {
+        remote.scheduled = true;
+        leptos::prelude::set_timeout(flush, Duration::from_millis(CLIENT_LOG_FLUSH_DELAY_MS));
+    }
+}
+
+/// Sends whatever is queued.
+pub fn flush() {
+    if let Ok(mut remote) = REMOTE.try_lock()
+        && let Some(remote) = remote.as_mut()
+    {
+        send(remote);
+    }
+}
+
+/// Beacons outlive the page, so events logged right before navigating away still arrive.
+fn send(remote: &mut RemoteSink) {
+    remote.scheduled = false;
+    if remote.queue.is_empty() {
+        return;
+    }
+    let events = std::mem::take(&mut remote.queue);
+    remote.queued_bytes = 0;
+    remote.sent += events.len();
+    if let Some(window) = web_sys::window() {
+        for body in batches(events) {
+            let _ = window
+                .navigator()
+                .send_beacon_with_opt_str(&remote.url, Some(&body));
+        }
+    }
+}
+
+/// Joins serialized events into json arrays of at most [`CLIENT_LOG_BATCH_SIZE`] events and
+/// [`CLIENT_LOG_BEACON_SIZE`] bytes, an event that is bigger on its own goes alone.
+fn batches(events: Vec<String>) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut body = String::new();
+    let mut count = 0;
+    for event in events {
+        if count > 0
+            && (count == CLIENT_LOG_BATCH_SIZE
+                || body.len() + event.len() + 2 > CLIENT_LOG_BEACON_SIZE)
+        {
+            body.push(']');
+            batches.push(std::mem::take(&mut body));
+            count = 0;
+        }
+        body.push(if count == 0 { '[' } else { ',' });
+        body.push_str(&event);
+        count += 1;
+    }
+    if count > 0 {
+        body.push(']');
+        batches.push(body);
+    }
+    batches
+}
+
 impl<S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>>
     tracing_subscriber::Layer<S> for WASMTracingLayer
 {
@@ -92,6 +249,18 @@ impl<S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'
             String::new()
         };
 
+        if self.config.remote.is_some()
+            && (level == tracing::Level::ERROR || level == tracing::Level::WARN)
+        {
+            queue(client_event(
+                level.as_str(),
+                value.clone(),
+                meta.target(),
+                spans_combined.trim(),
+                origin.trim().to_string(),
+            ));
+        }
+
         log5(
             format!("%c{level}%c{spans_combined}%c{target}{origin}%c: {value}"),
             match level {
@@ -131,3 +300,48 @@ extern "C" {
     #[wasm_bindgen(js_namespace = console, js_name = log)]
     pub fn log5(message1: String, message2: &str, message3: &str, message4: &str, message5: &str);
 }
+
+#[cfg(test)]
+mod logger_tests {
+    use crate::api::{
+        CLIENT_LOG_BATCH_SIZE, CLIENT_LOG_BEACON_SIZE, ClientLogEvent,
+        MAXIMUM_CLIENT_LOG_FIELD_LENGTH,
+    };
+
+    use super::batches;
+
+    fn event(message: String) -> String {
+        let event = ClientLogEvent {
+            level: String::from("ERROR"),
+            message,
+            target: String::from("artbounty_web_frontend::app"),
+            spans: String::new(),
+            origin: String::from("src/app.rs:10"),
+            url: String::from("http://localhost:3000/"),
+            user_agent: String::from("test"),
+            build: String::from("0.1.0"),
+        };
+        serde_json::to_string(&event.clipped()).unwrap()
+    }
+
+    #[test]
+    fn batches_stay_under_the_beacon_limit() {
+        let long = event("é".repeat(MAXIMUM_CLIENT_LOG_FIELD_LENGTH * 2));
+        assert!(long.len() < MAXIMUM_CLIENT_LOG_FIELD_LENGTH * 3);
+
+        let events = vec![long; CLIENT_LOG_BATCH_SIZE];
+        let sent = batches(events);
+        assert!(sent.len() > 1);
+        let mut count = 0;
+        for body in &sent {
+            assert!(body.len() <= CLIENT_LOG_BEACON_SIZE);
+            count += serde_json::from_str::<Vec<ClientLogEvent>>(body)
+                .unwrap()
+                .len();
+        }
+        assert_eq!(count, CLIENT_LOG_BATCH_SIZE);
+
+        let short = vec![event(String::from("oops")); CLIENT_LOG_BATCH_SIZE + 1];
+        assert_eq!(batches(short).len(), 2);
+    }
+}
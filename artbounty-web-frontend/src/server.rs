@@ -0,0 +1,140 @@
+use std::{path::PathBuf, sync::Arc};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use rand::Rng;
+use thiserror::Error;
+
+use crate::db::{Db, DbError};
+
+pub mod api_token;
+pub mod artwork;
+pub mod auth;
+pub mod bounty;
+pub mod claim;
+pub mod commission;
+pub mod discord;
+pub mod email;
+pub mod embed;
+pub mod feed;
+pub mod message;
+pub mod palette;
+pub mod rating;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod token;
+pub mod totp;
+pub mod two_factor;
+pub mod upload;
+pub mod watermark;
+pub mod webhook;
+
+pub const SITE_URL_ENV: &str = "SITE_URL";
+pub const PEPPER_ENV: &str = "PEPPER_BASE64";
+pub const TOKEN_SECRET_ENV: &str = "JWT_SECRET_BASE64";
+pub const MEDIA_DIR_ENV: &str = "MEDIA_DIR";
+pub const ID_LEN: usize = 12;
+
+const ID_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Everything server functions need, provided to them as leptos context.
+#[derive(Clone)]
+pub struct ServerState {
+    pub db: Db,
+    pub mailer: Arc<dyn email::Mailer>,
+    pub settings: Arc<Settings>,
+    pub message_hub: message::MessageHub,
+    pub claims: claim::Claims,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub site_url: String,
+    pub pepper: Vec<u8>,
+    pub token_secret: Vec<u8>,
+    pub password_cost: u32,
+    /// Uploaded files that are only served after an access check, e.g. message attachments.
+    pub media_dir: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorSettings {
+    #[error("missing env variable {0}")]
+    Missing(&'static str),
+
+    #[error("invalid base64 in {0}: {1}")]
+    Base64(&'static str, base64::DecodeError),
+}
+
+/// Error of a server module, server fns log the internal ones and hide them from the user.
+pub trait ServerError: std::error::Error {
+    fn is_internal(&self) -> bool;
+}
+
+impl ServerError for DbError {
+    fn is_internal(&self) -> bool {
+        true
+    }
+}
+
+impl ServerState {
+    pub fn new(db: Db, mailer: Arc<dyn email::Mailer>, settings: Settings) -> Self {
+        Self {
+            db,
+            mailer,
+            settings: Arc::new(settings),
+            message_hub: message::MessageHub::new(),
+            claims: claim::Claims::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_env() -> Result<Self, ErrorSettings> {
+        let site_url = std::env::var(SITE_URL_ENV)
+            .unwrap_or_else(|_| String::from("http://localhost:3000"))
+            .trim_end_matches('/')
+            .to_string();
+
+        Ok(Self {
+            site_url,
+            pepper: env_base64(PEPPER_ENV)?,
+            token_secret: env_base64(TOKEN_SECRET_ENV)?,
+            password_cost: bcrypt::DEFAULT_COST,
+            media_dir: std::env::var(MEDIA_DIR_ENV)
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("media")),
+        })
+    }
+
+    pub fn link(&self, path_and_query: &str) -> String {
+        format!("{}{}", self.site_url, path_and_query)
+    }
+}
+
+/// Random record key for artworks, bounties and other user created records.
+pub fn new_id() -> String {
+    let mut rng = rand::rngs::OsRng;
+    (0..ID_LEN)
+        .map(|_| ID_ALPHABET[rng.gen_range(0..ID_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Percent-encodes everything but the unreserved characters of a URL.
+pub fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn env_base64(name: &'static str) -> Result<Vec<u8>, ErrorSettings> {
+    let value = std::env::var(name).map_err(|_| ErrorSettings::Missing(name))?;
+    BASE64_STANDARD
+        .decode(value.trim())
+        .map_err(|err| ErrorSettings::Base64(name, err))
+}
@@ -0,0 +1,209 @@
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    api::{API_SCOPES, MAXIMUM_API_TOKEN_RATE_LIMIT, MAXIMUM_API_TOKENS},
+    db::{
+        Db, DbError,
+        acc::{self, DbAcc},
+        api_token::{self, DbApiToken},
+    },
+};
+
+use super::{ServerError, new_id};
+
+/// Prefix of every plain token, makes leaked tokens easy to grep for.
+pub const TOKEN_PREFIX: &str = "abt_";
+pub const MAXIMUM_NAME_LENGTH: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum ErrorApiToken {
+    #[error("token name must be 1-{MAXIMUM_NAME_LENGTH} characters")]
+    Name,
+
+    #[error("unknown scope {0}")]
+    Scope(String),
+
+    #[error("at least one scope is required")]
+    NoScopes,
+
+    #[error("rate limit must be 1-{MAXIMUM_API_TOKEN_RATE_LIMIT} requests per minute")]
+    RateLimit,
+
+    #[error("at most {MAXIMUM_API_TOKENS} tokens per account")]
+    TooMany,
+
+    #[error("token not found")]
+    NotFound,
+
+    #[error("invalid access token")]
+    Invalid,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorApiToken {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorApiToken::Db(_))
+    }
+}
+
+/// Parses space separated scopes, like oauth scope strings.
+pub fn parse_scopes(scopes: &str) -> Result<Vec<String>, ErrorApiToken> {
+    let mut output = Vec::<String>::new();
+    for scope in scopes.split_whitespace() {
+        if !API_SCOPES.iter().any(|(name, _)| *name == scope) {
+            return Err(ErrorApiToken::Scope(scope.to_string()));
+        }
+        if !output.iter().any(|s| s == scope) {
+            output.push(scope.to_string());
+        }
+    }
+    if output.is_empty() {
+        return Err(ErrorApiToken::NoScopes);
+    }
+    Ok(output)
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn has_scope(token: &DbApiToken, scope: &str) -> bool {
+    token.scopes.iter().any(|s| s == scope)
+}
+
+/// Returns the plain token, it's only shown to the user once.
+pub async fn create(
+    db: &Db,
+    acc: &DbAcc,
+    name: &str,
+    scopes: &str,
+    rate_limit: u32,
+    time: i64,
+) -> Result<(String, DbApiToken), ErrorApiToken> {
+    let name = name.trim();
+    if !(1..=MAXIMUM_NAME_LENGTH).contains(&name.chars().count()) {
+        return Err(ErrorApiToken::Name);
+    }
+    let scopes = parse_scopes(scopes)?;
+    if !(1..=MAXIMUM_API_TOKEN_RATE_LIMIT).contains(&rate_limit) {
+        return Err(ErrorApiToken::RateLimit);
+    }
+    if api_token::get_all_for_acc(db, &acc.username).await?.len() >= MAXIMUM_API_TOKENS {
+        return Err(ErrorApiToken::TooMany);
+    }
+
+    let mut bytes = [0_u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token = format!("{}{}", TOKEN_PREFIX, BASE64_URL_SAFE_NO_PAD.encode(bytes));
+
+    let db_token = DbApiToken {
+        token_hash: hash_token(&token),
+        token_id: new_id(),
+        acc: acc.username.clone(),
+        name: name.to_string(),
+        scopes,
+        rate_limit,
+        last_used: None,
+        modified_at: time,
+        created_at: time,
+    };
+    api_token::insert(db, db_token.clone()).await?;
+    trace!(
+        "api token {} created for {}",
+        db_token.token_id, acc.username
+    );
+
+    Ok((token, db_token))
+}
+
+pub async fn revoke(db: &Db, acc: &DbAcc, token_id: &str) -> Result<(), ErrorApiToken> {
+    if !api_token::remove(db, &acc.username, token_id).await? {
+        return Err(ErrorApiToken::NotFound);
+    }
+    trace!("api token {} revoked by {}", token_id, acc.username);
+    Ok(())
+}
+
+/// Resolves a plain token to its account and records when it was last used.
+pub async fn authenticate(
+    db: &Db,
+    token: &str,
+    time: i64,
+) -> Result<(DbApiToken, DbAcc), ErrorApiToken> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Err(ErrorApiToken::Invalid);
+    }
+    let token_hash = hash_token(token);
+    let Some(mut db_token) = api_token::get(db, &token_hash).await? else {
+        return Err(ErrorApiToken::Invalid);
+    };
+    let Some(acc) = acc::get_by_username(db, &db_token.acc).await? else {
+        return Err(ErrorApiToken::Invalid);
+    };
+    api_token::touch(db, &token_hash, time).await?;
+    db_token.last_used = Some(time);
+
+    Ok((db_token, acc))
+}
+
+#[cfg(test)]
+mod api_token_tests {
+    use crate::server::{auth::register, test_support::test_state};
+
+    use super::{ErrorApiToken, authenticate, create, has_scope, parse_scopes, revoke};
+
+    #[tokio::test]
+    async fn token_authenticates_until_revoked() {
+        let (state, _mailer) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            parse_scopes("artworks:read admin"),
+            Err(ErrorApiToken::Scope(_))
+        ));
+        assert!(matches!(
+            create(&state.db, &acc, "script", "", 60, 0).await,
+            Err(ErrorApiToken::NoScopes)
+        ));
+
+        let (token, created) = create(
+            &state.db,
+            &acc,
+            "script",
+            "artworks:read artworks:read bounties:read",
+            60,
+            0,
+        )
+        .await
+        .unwrap();
+        assert!(token.starts_with("abt_"));
+        assert_eq!(created.scopes, ["artworks:read", "bounties:read"]);
+
+        let (db_token, db_acc) = authenticate(&state.db, &token, 5).await.unwrap();
+        assert_eq!(db_acc.username, "hey");
+        assert_eq!(db_token.last_used, Some(5));
+        assert!(has_scope(&db_token, "artworks:read"));
+        assert!(!has_scope(&db_token, "artworks:write"));
+
+        revoke(&state.db, &acc, &created.token_id).await.unwrap();
+        assert!(matches!(
+            authenticate(&state.db, &token, 6).await,
+            Err(ErrorApiToken::Invalid)
+        ));
+        assert!(matches!(
+            revoke(&state.db, &acc, &created.token_id).await,
+            Err(ErrorApiToken::NotFound)
+        ));
+    }
+}
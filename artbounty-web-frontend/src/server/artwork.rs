@@ -0,0 +1,654 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    api::{
+        ARTWORK_MEDIA_PATH, ARTWORK_PAGE_SIZE, ARTWORK_VARIANT_WIDTHS, ArtworkInfo,
+        ArtworkPageInfo, ArtworkVariant, CONTENT_BLUR, CONTENT_HIDE, MAXIMUM_DESCRIPTION_LENGTH,
+        MAXIMUM_TAG_LENGTH, MAXIMUM_TAGS, MAXIMUM_TITLE_LENGTH, OwnArtworkInfo, ProfileInfo,
+        RATINGS,
+    },
+    db::{
+        Db, DbError,
+        acc::{self, DbAcc, DbAccContentPrefs},
+        artwork::{self, DbArtwork, PREVIEW_PENDING, PREVIEW_READY},
+        artwork_access::{self, DbArtworkAccess},
+        favorite::{self, DbFavorite},
+    },
+};
+
+use super::{ServerError, ServerState, Settings, embed, new_id, rating, webhook};
+
+pub const ARTWORK_DIR: &str = "artworks";
+
+#[derive(Error, Debug)]
+pub enum ErrorArtwork {
+    #[error("title must be 1-{MAXIMUM_TITLE_LENGTH} characters")]
+    Title,
+
+    #[error("description must be at most {MAXIMUM_DESCRIPTION_LENGTH} characters")]
+    Description,
+
+    #[error("at most {MAXIMUM_TAGS} tags of up to {MAXIMUM_TAG_LENGTH} letters, numbers, _ or -")]
+    Tags,
+
+    #[error("unknown content rating")]
+    Rating,
+
+    #[error("artwork not found")]
+    NotFound,
+
+    #[error("not allowed")]
+    Forbidden,
+
+    #[error("user {0} not found")]
+    UnknownUser(String),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorArtwork {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorArtwork::Db(_))
+    }
+}
+
+pub fn validate_title(title: &str) -> Result<(), ErrorArtwork> {
+    let len = title.chars().count();
+    if !(1..=MAXIMUM_TITLE_LENGTH).contains(&len) {
+        return Err(ErrorArtwork::Title);
+    }
+    Ok(())
+}
+
+pub fn validate_description(description: &str) -> Result<(), ErrorArtwork> {
+    if description.chars().count() > MAXIMUM_DESCRIPTION_LENGTH {
+        return Err(ErrorArtwork::Description);
+    }
+    Ok(())
+}
+
+pub fn validate_rating(rating: &str) -> Result<(), ErrorArtwork> {
+    if !RATINGS.iter().any(|(name, _)| *name == rating) {
+        return Err(ErrorArtwork::Rating);
+    }
+    Ok(())
+}
+
+/// Lowercases, trims and deduplicates tags, keeping their order.
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, ErrorArtwork> {
+    let mut output = Vec::<String>::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            continue;
+        }
+        let valid = tag.chars().count() <= MAXIMUM_TAG_LENGTH
+            && tag
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(ErrorArtwork::Tags);
+        }
+        if !output.contains(&tag) {
+            output.push(tag);
+        }
+    }
+    if output.len() > MAXIMUM_TAGS {
+        return Err(ErrorArtwork::Tags);
+    }
+    Ok(output)
+}
+
+/// Publishes an artwork whose `file` is already stored.
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    db: &Db,
+    username: &str,
+    title: &str,
+    description: &str,
+    tags: &[String],
+    rating: &str,
+    file: &str,
+    width: u32,
+    height: u32,
+    time: i64,
+) -> Result<DbArtwork, ErrorArtwork> {
+    let title = title.trim();
+    let description = description.trim();
+    validate_title(title)?;
+    validate_description(description)?;
+    validate_rating(rating)?;
+    let tags = normalize_tags(tags)?;
+
+    let artwork = DbArtwork {
+        artwork_id: new_id(),
+        acc: username.to_string(),
+        title: title.to_string(),
+        description: description.to_string(),
+        tags,
+        file: file.to_string(),
+        width,
+        height,
+        protected: false,
+        preview_status: PREVIEW_PENDING.to_string(),
+        rating: rating.to_string(),
+        palette: Vec::new(),
+        blurhash: String::new(),
+        sha256: String::new(),
+        modified_at: time,
+        created_at: time,
+    };
+    artwork::insert(db, artwork.clone()).await?;
+    trace!("artwork {} created by {}", artwork.artwork_id, username);
+    webhook::emit(
+        db,
+        username,
+        "artwork.created",
+        webhook::artwork_data(&artwork),
+        time,
+    )
+    .await;
+
+    Ok(artwork)
+}
+
+/// Edits the text of an artwork, only its author may do so.
+pub async fn set_info(
+    db: &Db,
+    username: &str,
+    artwork_id: &str,
+    title: &str,
+    description: &str,
+    tags: &[String],
+    time: i64,
+) -> Result<DbArtwork, ErrorArtwork> {
+    let title = title.trim();
+    let description = description.trim();
+    validate_title(title)?;
+    validate_description(description)?;
+    let tags = normalize_tags(tags)?;
+
+    let Some(artwork) = artwork::get(db, artwork_id).await? else {
+        return Err(ErrorArtwork::NotFound);
+    };
+    if artwork.acc != username {
+        return Err(ErrorArtwork::Forbidden);
+    }
+
+    trace!("artwork {} edited by {}", artwork_id, username);
+    let artwork = artwork::set_info(db, artwork_id, title, description, tags, time)
+        .await?
+        .ok_or(ErrorArtwork::NotFound)?;
+    webhook::emit(
+        db,
+        username,
+        "artwork.updated",
+        webhook::artwork_data(&artwork),
+        time,
+    )
+    .await;
+
+    Ok(artwork)
+}
+
+pub fn preview_path(settings: &Settings, artwork_id: &str) -> PathBuf {
+    settings
+        .media_dir
+        .join(ARTWORK_DIR)
+        .join(format!("{}.webp", artwork_id))
+}
+
+/// Tiny variant blurred tiles show until the viewer reveals them.
+/// Ladder of [`ARTWORK_VARIANT_WIDTHS`], steps wider than the original are the original size so
+/// only the first of them is kept.
+pub fn variants(artwork_id: &str, width: u32) -> Vec<ArtworkVariant> {
+    let mut variants = Vec::<ArtworkVariant>::new();
+    for step in ARTWORK_VARIANT_WIDTHS {
+        if variants.last().is_some_and(|last| last.width >= width) {
+            break;
+        }
+        variants.push(ArtworkVariant {
+            width: step.min(width),
+            url: format!("{}/{}/w{}", ARTWORK_MEDIA_PATH, artwork_id, step),
+        });
+    }
+    variants
+}
+
+pub fn variant_path(settings: &Settings, artwork_id: &str, width: u32) -> PathBuf {
+    settings
+        .media_dir
+        .join(ARTWORK_DIR)
+        .join(format!("{}_w{}.webp", artwork_id, width))
+}
+
+pub fn low_path(settings: &Settings, artwork_id: &str) -> PathBuf {
+    settings
+        .media_dir
+        .join(ARTWORK_DIR)
+        .join(format!("{}_low.webp", artwork_id))
+}
+
+/// Newest artworks for `viewer`, hidden ratings are left out by the query itself.
+pub async fn gallery(
+    db: &Db,
+    viewer: Option<&DbAcc>,
+    page: u32,
+) -> Result<Vec<ArtworkInfo>, ErrorArtwork> {
+    let prefs = rating::prefs_for(viewer);
+    let artworks = artwork::get_page(
+        db,
+        &rating::visible_ratings(&prefs),
+        ARTWORK_PAGE_SIZE,
+        page * ARTWORK_PAGE_SIZE,
+    )
+    .await?;
+    Ok(artworks
+        .into_iter()
+        .map(|artwork| artwork_info(artwork, &prefs))
+        .collect())
+}
+
+/// Gallery entry as seen by a viewer with `prefs`.
+pub fn artwork_info(artwork: DbArtwork, prefs: &DbAccContentPrefs) -> ArtworkInfo {
+    let ready = artwork.preview_status == PREVIEW_READY;
+    let url = |variant: &str| {
+        ready.then(|| format!("{}/{}/{}", ARTWORK_MEDIA_PATH, artwork.artwork_id, variant))
+    };
+    ArtworkInfo {
+        variants: if ready {
+            variants(&artwork.artwork_id, artwork.width)
+        } else {
+            Vec::new()
+        },
+        preview_url: url("preview"),
+        low_url: url("low"),
+        blurred: rating::display_for(prefs, &artwork.rating) == CONTENT_BLUR,
+        artwork_id: artwork.artwork_id,
+        author: artwork.acc,
+        title: artwork.title,
+        width: artwork.width,
+        height: artwork.height,
+        rating: artwork.rating,
+        palette: artwork.palette,
+        blurhash: (!artwork.blurhash.is_empty()).then_some(artwork.blurhash),
+        favorited: false,
+    }
+}
+
+/// Artwork page as seen by `viewer`, ratings they hide aren't found.
+pub async fn page(
+    db: &Db,
+    settings: &Settings,
+    viewer: Option<&DbAcc>,
+    artwork_id: &str,
+) -> Result<Option<ArtworkPageInfo>, ErrorArtwork> {
+    let prefs = rating::prefs_for(viewer);
+    let Some(artwork) = artwork::get(db, artwork_id)
+        .await?
+        .filter(|artwork| rating::display_for(&prefs, &artwork.rating) != CONTENT_HIDE)
+    else {
+        return Ok(None);
+    };
+    let meta = embed::artwork_meta(settings, &artwork);
+    let description = artwork.description.clone();
+    let tags = artwork.tags.clone();
+    let created_at = artwork.created_at;
+    let mut artworks = [artwork_info(artwork, &prefs)];
+    mark_favorites(db, viewer, &mut artworks).await?;
+    let [artwork] = artworks;
+    Ok(Some(ArtworkPageInfo {
+        artwork,
+        description,
+        tags,
+        created_at,
+        meta,
+    }))
+}
+
+/// Profile page of `username` with their newest artworks `viewer` may see.
+pub async fn profile(
+    db: &Db,
+    settings: &Settings,
+    viewer: Option<&DbAcc>,
+    username: &str,
+) -> Result<Option<ProfileInfo>, ErrorArtwork> {
+    let Some(acc) = acc::get_by_username(db, username).await? else {
+        return Ok(None);
+    };
+    let prefs = rating::prefs_for(viewer);
+    let artworks = artwork::get_page_by_acc(
+        db,
+        &acc.username,
+        &rating::visible_ratings(&prefs),
+        ARTWORK_PAGE_SIZE,
+        0,
+    )
+    .await?;
+    let meta = embed::profile_meta(settings, &acc.username, &artworks);
+    let mut artworks = artworks
+        .into_iter()
+        .map(|artwork| artwork_info(artwork, &prefs))
+        .collect::<Vec<_>>();
+    mark_favorites(db, viewer, &mut artworks).await?;
+    Ok(Some(ProfileInfo {
+        username: acc.username,
+        created_at: acc.created_at,
+        artworks,
+        meta,
+    }))
+}
+
+/// Flags the artworks `viewer` favorited.
+pub async fn mark_favorites(
+    db: &Db,
+    viewer: Option<&DbAcc>,
+    artworks: &mut [ArtworkInfo],
+) -> Result<(), ErrorArtwork> {
+    let Some(viewer) = viewer else {
+        return Ok(());
+    };
+    if artworks.is_empty() {
+        return Ok(());
+    }
+    let ids = artworks
+        .iter()
+        .map(|artwork| artwork.artwork_id.clone())
+        .collect::<Vec<_>>();
+    let favorites = favorite::get_among(db, &viewer.username, &ids).await?;
+    for artwork in artworks {
+        artwork.favorited = favorites.contains(&artwork.artwork_id);
+    }
+    Ok(())
+}
+
+/// Favorites `artwork_id` for `username` or takes it back, returns whether it's now a favorite.
+pub async fn toggle_favorite(
+    db: &Db,
+    username: &str,
+    artwork_id: &str,
+    time: i64,
+) -> Result<bool, ErrorArtwork> {
+    if artwork::get(db, artwork_id).await?.is_none() {
+        return Err(ErrorArtwork::NotFound);
+    }
+    if favorite::remove(db, username, artwork_id).await? {
+        return Ok(false);
+    }
+    favorite::upsert(
+        db,
+        DbFavorite {
+            acc: username.to_string(),
+            artwork_id: artwork_id.to_string(),
+            modified_at: time,
+            created_at: time,
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+async fn get_own(db: &Db, username: &str, artwork_id: &str) -> Result<DbArtwork, ErrorArtwork> {
+    let Some(artwork) = artwork::get(db, artwork_id).await? else {
+        return Err(ErrorArtwork::NotFound);
+    };
+    if artwork.acc != username {
+        return Err(ErrorArtwork::Forbidden);
+    }
+    Ok(artwork)
+}
+
+/// Artworks of `username` with their buyers, for managing protection.
+pub async fn own_artworks(
+    db: &Db,
+    username: &str,
+    page: u32,
+) -> Result<Vec<OwnArtworkInfo>, ErrorArtwork> {
+    let artworks = artwork::get_page_by_acc(
+        db,
+        username,
+        &rating::all_ratings(),
+        ARTWORK_PAGE_SIZE,
+        page * ARTWORK_PAGE_SIZE,
+    )
+    .await?;
+    let mut output = Vec::with_capacity(artworks.len());
+    for artwork in artworks {
+        let buyers = artwork_access::get_all_for_artwork(db, &artwork.artwork_id)
+            .await?
+            .into_iter()
+            .map(|access| access.acc)
+            .collect();
+        output.push(OwnArtworkInfo {
+            preview_url: (artwork.preview_status == PREVIEW_READY)
+                .then(|| format!("{}/{}/preview", ARTWORK_MEDIA_PATH, artwork.artwork_id)),
+            artwork_id: artwork.artwork_id,
+            title: artwork.title,
+            protected: artwork.protected,
+            rating: artwork.rating,
+            buyers,
+            created_at: artwork.created_at,
+        });
+    }
+    Ok(output)
+}
+
+/// Protecting watermarks the public preview and keeps the original to the author and buyers.
+pub async fn set_protected(
+    db: &Db,
+    username: &str,
+    artwork_id: &str,
+    protected: bool,
+    time: i64,
+) -> Result<DbArtwork, ErrorArtwork> {
+    get_own(db, username, artwork_id).await?;
+    trace!("artwork {} protected: {}", artwork_id, protected);
+    artwork::set_protected(db, artwork_id, protected, time)
+        .await?
+        .ok_or(ErrorArtwork::NotFound)
+}
+
+/// Lets `buyer` download the clean original of a protected artwork.
+pub async fn grant_access(
+    db: &Db,
+    username: &str,
+    artwork_id: &str,
+    buyer: &str,
+    time: i64,
+) -> Result<(), ErrorArtwork> {
+    get_own(db, username, artwork_id).await?;
+    let buyer = buyer.trim().trim_start_matches('@');
+    if acc::get_by_username(db, buyer).await?.is_none() {
+        return Err(ErrorArtwork::UnknownUser(buyer.to_string()));
+    }
+    artwork_access::upsert(
+        db,
+        DbArtworkAccess {
+            artwork_id: artwork_id.to_string(),
+            acc: buyer.to_string(),
+            modified_at: time,
+            created_at: time,
+        },
+    )
+    .await?;
+    trace!("{} granted access to artwork {}", buyer, artwork_id);
+    Ok(())
+}
+
+pub async fn revoke_access(
+    db: &Db,
+    username: &str,
+    artwork_id: &str,
+    buyer: &str,
+) -> Result<(), ErrorArtwork> {
+    get_own(db, username, artwork_id).await?;
+    artwork_access::remove(db, artwork_id, buyer.trim()).await?;
+    Ok(())
+}
+
+/// Unprotected originals are public, protected ones only go to the author and buyers.
+pub async fn can_see_original(
+    db: &Db,
+    artwork: &DbArtwork,
+    username: Option<&str>,
+) -> Result<bool, ErrorArtwork> {
+    if !artwork.protected {
+        return Ok(true);
+    }
+    let Some(username) = username else {
+        return Ok(false);
+    };
+    if artwork.acc == username {
+        return Ok(true);
+    }
+    Ok(artwork_access::exists(db, &artwork.artwork_id, username).await?)
+}
+
+/// Path of the public preview or, for those allowed, the original.
+pub async fn open_media(
+    state: &ServerState,
+    username: Option<&str>,
+    artwork_id: &str,
+    original: bool,
+) -> Result<PathBuf, ErrorArtwork> {
+    let artwork = artwork::get(&state.db, artwork_id)
+        .await?
+        .ok_or(ErrorArtwork::NotFound)?;
+    if original {
+        if !can_see_original(&state.db, &artwork, username).await? {
+            return Err(ErrorArtwork::Forbidden);
+        }
+        return Ok(state.settings.media_dir.join(&artwork.file));
+    }
+    if artwork.preview_status != PREVIEW_READY {
+        return Err(ErrorArtwork::NotFound);
+    }
+    Ok(preview_path(&state.settings, artwork_id))
+}
+
+#[cfg(test)]
+mod artwork_tests {
+    use crate::{
+        db::{acc, artwork::PREVIEW_PENDING},
+        server::{auth::register, rating, test_support::test_state},
+    };
+
+    use super::{
+        ErrorArtwork, artwork_info, can_see_original, create, grant_access, mark_favorites,
+        normalize_tags, revoke_access, set_protected, toggle_favorite, variants,
+    };
+
+    #[test]
+    fn tags_are_normalized() {
+        let tags = ["  Fox ", "fox", "", "pixel_art"].map(String::from);
+        assert_eq!(normalize_tags(&tags).unwrap(), ["fox", "pixel_art"]);
+
+        let tags = ["two words"].map(String::from);
+        assert!(matches!(normalize_tags(&tags), Err(ErrorArtwork::Tags)));
+    }
+
+    #[test]
+    fn variant_ladder_stops_at_original() {
+        let widths = |width| {
+            variants("cat", width)
+                .into_iter()
+                .map(|variant| variant.width)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(widths(4000), [320, 640, 960, 1440]);
+        assert_eq!(widths(700), [320, 640, 700]);
+        assert_eq!(widths(200), [200]);
+        assert_eq!(variants("cat", 700)[2].url, "/media/artworks/cat/w960");
+    }
+
+    #[tokio::test]
+    async fn protected_originals_are_for_buyers() {
+        let (state, _mailer) = test_state().await;
+        for username in ["hey", "fox"] {
+            let email = format!("{}@example.com", username);
+            register(&state, username, &email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let db = &state.db;
+        let artwork = create(db, "hey", "Cat", "", &[], "sfw", "artworks/cat", 10, 10, 0)
+            .await
+            .unwrap();
+        assert!(can_see_original(db, &artwork, None).await.unwrap());
+
+        assert!(matches!(
+            set_protected(db, "fox", &artwork.artwork_id, true, 1).await,
+            Err(ErrorArtwork::Forbidden)
+        ));
+        let artwork = set_protected(db, "hey", &artwork.artwork_id, true, 1)
+            .await
+            .unwrap();
+        assert_eq!(artwork.preview_status, PREVIEW_PENDING);
+        assert!(!can_see_original(db, &artwork, None).await.unwrap());
+        assert!(!can_see_original(db, &artwork, Some("fox")).await.unwrap());
+        assert!(can_see_original(db, &artwork, Some("hey")).await.unwrap());
+
+        assert!(matches!(
+            grant_access(db, "hey", &artwork.artwork_id, "owl", 2).await,
+            Err(ErrorArtwork::UnknownUser(_))
+        ));
+        grant_access(db, "hey", &artwork.artwork_id, "@fox", 2)
+            .await
+            .unwrap();
+        assert!(can_see_original(db, &artwork, Some("fox")).await.unwrap());
+        revoke_access(db, "hey", &artwork.artwork_id, "fox")
+            .await
+            .unwrap();
+        assert!(!can_see_original(db, &artwork, Some("fox")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn favorites_toggle_and_are_marked_for_their_owner() {
+        let (state, _mailer) = test_state().await;
+        for username in ["hey", "fox"] {
+            let email = format!("{}@example.com", username);
+            register(&state, username, &email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let db = &state.db;
+        let artwork = create(db, "hey", "Cat", "", &[], "sfw", "artworks/cat", 10, 10, 0)
+            .await
+            .unwrap();
+        assert!(matches!(
+            toggle_favorite(db, "fox", "missing", 1).await,
+            Err(ErrorArtwork::NotFound)
+        ));
+        assert!(
+            toggle_favorite(db, "fox", &artwork.artwork_id, 1)
+                .await
+                .unwrap()
+        );
+
+        let fox = acc::get_by_username(db, "fox").await.unwrap().unwrap();
+        let hey = acc::get_by_username(db, "hey").await.unwrap().unwrap();
+        let info = |viewer| artwork_info(artwork.clone(), &rating::prefs_for(viewer));
+        let mut seen = [info(Some(&fox)), info(Some(&hey)), info(None)];
+        mark_favorites(db, Some(&fox), &mut seen[..1])
+            .await
+            .unwrap();
+        mark_favorites(db, Some(&hey), &mut seen[1..2])
+            .await
+            .unwrap();
+        mark_favorites(db, None, &mut seen[2..]).await.unwrap();
+        assert_eq!(seen.map(|info| info.favorited), [true, false, false]);
+
+        assert!(
+            !toggle_favorite(db, "fox", &artwork.artwork_id, 2)
+                .await
+                .unwrap()
+        );
+        let mut seen = [info(Some(&fox))];
+        mark_favorites(db, Some(&fox), &mut seen).await.unwrap();
+        assert!(!seen[0].favorited);
+    }
+}
@@ -0,0 +1,627 @@
+use std::sync::OnceLock;
+
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{error, trace};
+
+use crate::{
+    api::{MAXIMUM_USERNAME_LENGTH, MINIMUM_PASSWORD_LENGTH, MINIMUM_USERNAME_LENGTH},
+    db::{
+        self, Conflict, DbError,
+        acc::{self, DbAcc},
+        login_challenge::{self, DbLoginChallenge},
+        session::{self, DbSession},
+    },
+};
+
+use super::{
+    ServerError, ServerState,
+    email::{ErrorMailer, template},
+    new_id,
+    token::{ErrorToken, Token, TokenKind, fingerprint},
+    two_factor,
+};
+
+pub const SESSION_COOKIE: &str = "session";
+pub const SESSION_DURATION_MS: i64 = 1000 * 60 * 60 * 24 * 30;
+pub const VERIFY_EMAIL_DURATION_MS: i64 = 1000 * 60 * 60 * 24;
+pub const RESET_PASSWORD_DURATION_MS: i64 = 1000 * 60 * 60;
+pub const LOGIN_TOTP_DURATION_MS: i64 = 1000 * 60 * 5;
+pub const DEFAULT_ROLE: &str = "member";
+
+#[derive(Error, Debug)]
+pub enum ErrorAuth {
+    #[error(
+        "username must be {MINIMUM_USERNAME_LENGTH}-{MAXIMUM_USERNAME_LENGTH} characters of letters, numbers, _ or -"
+    )]
+    Username,
+
+    #[error("invalid email address")]
+    Email,
+
+    #[error("password must be at least {MINIMUM_PASSWORD_LENGTH} characters long")]
+    Password,
+
+    #[error("username is taken")]
+    UsernameTaken,
+
+    #[error("email is already registered")]
+    EmailTaken,
+
+    #[error("invalid email or password")]
+    Credentials,
+
+    #[error("invalid authentication code")]
+    SecondFactor,
+
+    #[error("two-factor authentication is not set up")]
+    TotpNotEnrolled,
+
+    #[error("two-factor authentication is already enabled")]
+    TotpEnabled,
+
+    #[error("login required")]
+    Unauthorized,
+
+    #[error("not allowed")]
+    Forbidden,
+
+    #[error("invalid or expired link")]
+    Token(#[from] ErrorToken),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+
+    #[error("password hashing error: {0}")]
+    Hash(#[from] bcrypt::BcryptError),
+
+    #[error("mailer error: {0}")]
+    Mailer(#[from] ErrorMailer),
+}
+
+impl ServerError for ErrorAuth {
+    fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ErrorAuth::Db(_) | ErrorAuth::Hash(_) | ErrorAuth::Mailer(_)
+        )
+    }
+}
+
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub fn validate_username(username: &str) -> Result<(), ErrorAuth> {
+    let len = username.chars().count();
+    let valid = (MINIMUM_USERNAME_LENGTH..=MAXIMUM_USERNAME_LENGTH).contains(&len)
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(ErrorAuth::Username)
+    }
+}
+
+pub fn validate_email(email: &str) -> Result<(), ErrorAuth> {
+    email
+        .parse::<lettre::Address>()
+        .map(|_| ())
+        .map_err(|_| ErrorAuth::Email)
+}
+
+pub fn validate_password(password: &str) -> Result<(), ErrorAuth> {
+    if password.chars().count() < MINIMUM_PASSWORD_LENGTH {
+        return Err(ErrorAuth::Password);
+    }
+    Ok(())
+}
+
+pub fn hash_password(state: &ServerState, password: &str) -> Result<String, ErrorAuth> {
+    let hash = bcrypt::hash(peppered(state, password), state.settings.password_cost)?;
+    Ok(hash)
+}
+
+pub fn check_password(state: &ServerState, password: &str, hash: &str) -> Result<bool, ErrorAuth> {
+    let valid = bcrypt::verify(peppered(state, password), hash)?;
+    Ok(valid)
+}
+
+/// Hash of no account's password, at the cost real ones are hashed with.
+fn dummy_hash(state: &ServerState) -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        bcrypt::hash(new_id(), state.settings.password_cost).expect("password cost is valid")
+    })
+}
+
+fn peppered(state: &ServerState, password: &str) -> Vec<u8> {
+    let mut output = password.as_bytes().to_vec();
+    output.extend_from_slice(&state.settings.pepper);
+    output
+}
+
+/// Sha256 hex of a session token, the only form sessions are stored in.
+pub fn hash_session_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn new_session_token() -> String {
+    let mut bytes = [0_u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn session_cookie(state: &ServerState, token: &str) -> String {
+    let secure = if state.settings.site_url.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        SESSION_COOKIE,
+        token,
+        SESSION_DURATION_MS / 1000,
+        secure
+    )
+}
+
+pub fn clear_session_cookie() -> String {
+    format!(
+        "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0",
+        SESSION_COOKIE
+    )
+}
+
+/// Finds the session token in a `Cookie` header value.
+pub fn get_session_token(cookie_header: &str) -> Option<&str> {
+    cookie_header.split(';').find_map(|cookie| {
+        let (name, value) = cookie.trim().split_once('=')?;
+        (name == SESSION_COOKIE && !value.is_empty()).then_some(value)
+    })
+}
+
+pub async fn register(
+    state: &ServerState,
+    username: &str,
+    email: &str,
+    password: &str,
+    time: i64,
+) -> Result<DbAcc, ErrorAuth> {
+    let username = username.trim();
+    let email = normalize_email(email);
+    validate_username(username)?;
+    validate_email(&email)?;
+    validate_password(password)?;
+
+    // concurrent registrations of one name or email take turns, the insert catches the rest
+    let _username_claim = state
+        .claims
+        .claim(format!("username:{}", username))
+        .ok_or(ErrorAuth::UsernameTaken)?;
+    let _email_claim = state
+        .claims
+        .claim(format!("email:{}", email))
+        .ok_or(ErrorAuth::EmailTaken)?;
+
+    let acc = DbAcc {
+        username: username.to_string(),
+        email,
+        password: hash_password(state, password)?,
+        verified_email: false,
+        role: DEFAULT_ROLE.to_string(),
+        totp: None,
+        discord: None,
+        content_prefs: None,
+        theme: None,
+        modified_at: time,
+        created_at: time,
+    };
+    if let Err(err) = acc::insert(&state.db, acc.clone()).await {
+        return Err(match db::conflict(&err) {
+            Some(Conflict::Record) => ErrorAuth::UsernameTaken,
+            Some(Conflict::Index(index)) if index == db::ACC_EMAIL_INDEX => ErrorAuth::EmailTaken,
+            _ => err.into(),
+        });
+    }
+    trace!("registered {}", acc.username);
+
+    // the account is there either way, a lost email can be sent again with resend_verification
+    if let Err(err) = send_verification(state, &acc, time).await {
+        error!(
+            "failed to send verification email to {}: {}",
+            acc.username, err
+        );
+    }
+
+    Ok(acc)
+}
+
+pub async fn send_verification(
+    state: &ServerState,
+    acc: &DbAcc,
+    time: i64,
+) -> Result<(), ErrorAuth> {
+    let token = Token::new(
+        TokenKind::VerifyEmail,
+        &acc.username,
+        time + VERIFY_EMAIL_DURATION_MS,
+        &acc.email,
+    )
+    .sign(&state.settings.token_secret);
+    let link = state
+        .settings
+        .link(&format!("/verify_email?token={}", token));
+
+    state
+        .mailer
+        .send(template::verify_email(&acc.email, &acc.username, &link))
+        .await?;
+    Ok(())
+}
+
+/// Sends a new verification link if `email` belongs to an unverified account, silently does nothing otherwise.
+pub async fn resend_verification(
+    state: &ServerState,
+    email: &str,
+    time: i64,
+) -> Result<(), ErrorAuth> {
+    let email = normalize_email(email);
+    let Some(acc) = acc::get_by_email(&state.db, &email).await? else {
+        trace!("verification requested for unknown email");
+        return Ok(());
+    };
+    if acc.verified_email {
+        return Ok(());
+    }
+    send_verification(state, &acc, time).await
+}
+
+pub async fn verify_email(state: &ServerState, token: &str, time: i64) -> Result<DbAcc, ErrorAuth> {
+    let token = Token::verify(
+        &state.settings.token_secret,
+        TokenKind::VerifyEmail,
+        token,
+        time,
+    )?;
+    let Some(mut acc) = acc::get_by_username(&state.db, &token.subject).await? else {
+        return Err(ErrorToken::Malformed.into());
+    };
+    if acc.email != token.extra {
+        return Err(ErrorToken::Malformed.into());
+    }
+    if !acc.verified_email {
+        acc::set_verified_email(&state.db, &acc.username, time).await?;
+        acc.verified_email = true;
+        acc.modified_at = time;
+    }
+    Ok(acc)
+}
+
+/// Emails a reset link if `email` is registered. Never reveals whether it is.
+pub async fn request_password_reset(
+    state: &ServerState,
+    email: &str,
+    time: i64,
+) -> Result<(), ErrorAuth> {
+    let email = normalize_email(email);
+    let Some(acc) = acc::get_by_email(&state.db, &email).await? else {
+        trace!("password reset requested for unknown email");
+        return Ok(());
+    };
+
+    let token = Token::new(
+        TokenKind::ResetPassword,
+        &acc.username,
+        time + RESET_PASSWORD_DURATION_MS,
+        fingerprint(&acc.password),
+    )
+    .sign(&state.settings.token_secret);
+    let link = state
+        .settings
+        .link(&format!("/reset_password?token={}", token));
+
+    state
+        .mailer
+        .send(template::reset_password(&acc.email, &acc.username, &link))
+        .await?;
+    Ok(())
+}
+
+/// Sets a new password and signs out every session of the account.
+///
+/// The token is bound to the old password hash, so it can only be used once.
+pub async fn reset_password(
+    state: &ServerState,
+    token: &str,
+    password: &str,
+    time: i64,
+) -> Result<(), ErrorAuth> {
+    let token = Token::verify(
+        &state.settings.token_secret,
+        TokenKind::ResetPassword,
+        token,
+        time,
+    )?;
+    validate_password(password)?;
+    let Some(acc) = acc::get_by_username(&state.db, &token.subject).await? else {
+        return Err(ErrorToken::Malformed.into());
+    };
+    if fingerprint(&acc.password) != token.extra {
+        return Err(ErrorToken::Malformed.into());
+    }
+
+    let hash = hash_password(state, password)?;
+    acc::set_password(&state.db, &acc.username, &hash, time).await?;
+    session::remove_all_for_acc(&state.db, &acc.username).await?;
+    trace!("password reset for {}", acc.username);
+
+    Ok(())
+}
+
+pub enum LoginStep {
+    /// Logged in, with the new session token.
+    Session(String, Box<DbAcc>),
+    /// Password was correct but the account has 2FA, finish with [`login_totp`] and this challenge.
+    Totp(String),
+}
+
+pub async fn login(
+    state: &ServerState,
+    email: &str,
+    password: &str,
+    ip: &str,
+    agent: &str,
+    time: i64,
+) -> Result<LoginStep, ErrorAuth> {
+    let email = normalize_email(email);
+    let Some(acc) = acc::get_by_email(&state.db, &email).await? else {
+        // hashing anyway keeps unknown emails from answering faster than wrong passwords
+        check_password(state, password, dummy_hash(state))?;
+        return Err(ErrorAuth::Credentials);
+    };
+    if !check_password(state, password, &acc.password)? {
+        return Err(ErrorAuth::Credentials);
+    }
+
+    if acc.totp.as_ref().is_some_and(|totp| totp.enabled) {
+        let challenge = DbLoginChallenge {
+            nonce: new_id(),
+            acc: acc.username.clone(),
+            fingerprint: fingerprint(&acc.password),
+            expires_at: time + LOGIN_TOTP_DURATION_MS,
+            created_at: time,
+        };
+        login_challenge::insert(&state.db, challenge.clone()).await?;
+        let challenge = Token::new(
+            TokenKind::LoginTotp,
+            &acc.username,
+            challenge.expires_at,
+            challenge.nonce,
+        )
+        .sign(&state.settings.token_secret);
+        return Ok(LoginStep::Totp(challenge));
+    }
+
+    let token = create_session(state, &acc, ip, agent, time).await?;
+    Ok(LoginStep::Session(token, Box::new(acc)))
+}
+
+/// Second login step, accepts either a totp code or an unused recovery code. The challenge is
+/// used up by the first accepted code.
+pub async fn login_totp(
+    state: &ServerState,
+    challenge: &str,
+    code: &str,
+    ip: &str,
+    agent: &str,
+    time: i64,
+) -> Result<(String, DbAcc), ErrorAuth> {
+    let challenge = Token::verify(
+        &state.settings.token_secret,
+        TokenKind::LoginTotp,
+        challenge,
+        time,
+    )?;
+    let nonce = challenge.extra;
+    let Some(_claim) = state.claims.claim(format!("login_challenge:{}", nonce)) else {
+        return Err(ErrorToken::Expired.into());
+    };
+    let Some(stored) = login_challenge::get(&state.db, &nonce).await? else {
+        return Err(ErrorToken::Expired.into());
+    };
+    let Some(acc) = acc::get_by_username(&state.db, &stored.acc).await? else {
+        return Err(ErrorToken::Malformed.into());
+    };
+    if stored.acc != challenge.subject || fingerprint(&acc.password) != stored.fingerprint {
+        return Err(ErrorToken::Malformed.into());
+    }
+
+    let acc = two_factor::check(state, acc, code, time).await?;
+    if login_challenge::take(&state.db, &nonce).await?.is_none() {
+        return Err(ErrorToken::Expired.into());
+    }
+    let token = create_session(state, &acc, ip, agent, time).await?;
+    Ok((token, acc))
+}
+
+/// Returns the new session token.
+async fn create_session(
+    state: &ServerState,
+    acc: &DbAcc,
+    ip: &str,
+    agent: &str,
+    time: i64,
+) -> Result<String, ErrorAuth> {
+    let token = new_session_token();
+    session::insert(
+        &state.db,
+        DbSession {
+            token_hash: hash_session_token(&token),
+            acc: acc.username.clone(),
+            ip: ip.to_string(),
+            agent: agent.to_string(),
+            expires_at: time + SESSION_DURATION_MS,
+            last_used: time,
+            modified_at: time,
+            created_at: time,
+        },
+    )
+    .await?;
+    Ok(token)
+}
+
+pub async fn logout(state: &ServerState, token: &str) -> Result<(), ErrorAuth> {
+    session::remove(&state.db, &hash_session_token(token)).await?;
+    Ok(())
+}
+
+pub async fn get_session_acc(
+    state: &ServerState,
+    token: &str,
+    time: i64,
+) -> Result<Option<DbAcc>, ErrorAuth> {
+    let token_hash = hash_session_token(token);
+    let Some(session) = session::get(&state.db, &token_hash).await? else {
+        return Ok(None);
+    };
+    if session.expires_at <= time {
+        session::remove(&state.db, &token_hash).await?;
+        return Ok(None);
+    }
+    session::touch(&state.db, &token_hash, time).await?;
+
+    let acc = acc::get_by_username(&state.db, &session.acc).await?;
+    Ok(acc)
+}
+
+#[cfg(test)]
+pub mod auth_tests {
+    use std::sync::Arc;
+
+    use crate::{
+        db::acc,
+        server::{
+            email::{Email, ErrorMailer, Mailer, MailerFuture},
+            test_support::test_state,
+        },
+    };
+
+    use super::{
+        ErrorAuth, LoginStep, get_session_acc, login, register, request_password_reset,
+        resend_verification, reset_password, verify_email,
+    };
+
+    struct DownMailer;
+
+    impl Mailer for DownMailer {
+        fn send(&self, _email: Email) -> MailerFuture<'_> {
+            Box::pin(async { Err(ErrorMailer::Config("smtp", String::from("down"))) })
+        }
+    }
+
+    pub fn get_token(text: &str) -> String {
+        let (_, token) = text.split_once("?token=").unwrap();
+        token.split_whitespace().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn register_verify_and_reset_password() {
+        let (state, mailer) = test_state().await;
+
+        let acc = register(&state, "hey", "Hey@Example.com", "password123", 0)
+            .await
+            .unwrap();
+        assert_eq!(acc.email, "hey@example.com");
+        assert!(!acc.verified_email);
+        assert!(matches!(
+            register(&state, "hey2", "hey@example.com", "password123", 0).await,
+            Err(ErrorAuth::EmailTaken)
+        ));
+        assert!(matches!(
+            register(&state, "hey", "other@example.com", "password123", 0).await,
+            Err(ErrorAuth::UsernameTaken)
+        ));
+        assert!(matches!(
+            register(&state, "h", "h@example.com", "password123", 0).await,
+            Err(ErrorAuth::Username)
+        ));
+
+        let sent = mailer.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "hey@example.com");
+        let verify_token = get_token(&sent[0].text);
+        assert!(
+            verify_email(&state, &verify_token, 1)
+                .await
+                .unwrap()
+                .verified_email
+        );
+
+        let Ok(LoginStep::Session(session_token, _)) =
+            login(&state, "hey@example.com", "password123", "::1", "", 2).await
+        else {
+            panic!("expected a session");
+        };
+        assert!(matches!(
+            login(&state, "hey@example.com", "wrong", "::1", "", 2).await,
+            Err(ErrorAuth::Credentials)
+        ));
+
+        request_password_reset(&state, "nobody@example.com", 3)
+            .await
+            .unwrap();
+        assert!(mailer.take().is_empty());
+
+        request_password_reset(&state, "hey@example.com", 3)
+            .await
+            .unwrap();
+        let reset_token = get_token(&mailer.take()[0].text);
+        reset_password(&state, &reset_token, "newpassword123", 4)
+            .await
+            .unwrap();
+        assert!(matches!(
+            reset_password(&state, &reset_token, "otherpassword123", 5).await,
+            Err(ErrorAuth::Token(_))
+        ));
+
+        assert!(
+            get_session_acc(&state, &session_token, 5)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            login(&state, "hey@example.com", "newpassword123", "::1", "", 6)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn registration_survives_a_mail_outage() {
+        let (mut state, _mailer) = test_state().await;
+        state.mailer = Arc::new(DownMailer);
+        register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        assert!(
+            acc::get_by_username(&state.db, "hey")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(matches!(
+            resend_verification(&state, "hey@example.com", 1).await,
+            Err(ErrorAuth::Mailer(_))
+        ));
+    }
+}
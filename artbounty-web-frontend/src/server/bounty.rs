@@ -0,0 +1,146 @@
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    api::{BountyPageInfo, CONTENT_BLUR, CONTENT_HIDE},
+    db::{
+        Db, DbError,
+        acc::DbAcc,
+        bounty::{self, DbBounty, STATUS_CLOSED, STATUS_OPEN},
+    },
+};
+
+use super::{
+    ServerError, Settings,
+    artwork::{
+        ErrorArtwork, normalize_tags, validate_description, validate_rating, validate_title,
+    },
+    embed, new_id, rating, webhook,
+};
+
+#[derive(Error, Debug)]
+pub enum ErrorBounty {
+    #[error(transparent)]
+    Info(#[from] ErrorArtwork),
+
+    #[error("bounty not found")]
+    NotFound,
+
+    #[error("not allowed")]
+    Forbidden,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorBounty {
+    fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ErrorBounty::Db(_) | ErrorBounty::Info(ErrorArtwork::Db(_))
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    db: &Db,
+    username: &str,
+    title: &str,
+    description: &str,
+    tags: &[String],
+    reward: u64,
+    rating: &str,
+    time: i64,
+) -> Result<DbBounty, ErrorBounty> {
+    let title = title.trim();
+    let description = description.trim();
+    validate_title(title)?;
+    validate_description(description)?;
+    validate_rating(rating)?;
+    let tags = normalize_tags(tags)?;
+
+    let bounty = DbBounty {
+        bounty_id: new_id(),
+        acc: username.to_string(),
+        title: title.to_string(),
+        description: description.to_string(),
+        tags,
+        reward,
+        status: STATUS_OPEN.to_string(),
+        rating: rating.to_string(),
+        modified_at: time,
+        created_at: time,
+    };
+    bounty::insert(db, bounty.clone()).await?;
+    trace!("bounty {} created by {}", bounty.bounty_id, username);
+    webhook::emit(
+        db,
+        username,
+        "bounty.created",
+        webhook::bounty_data(&bounty),
+        time,
+    )
+    .await;
+
+    Ok(bounty)
+}
+
+/// Closes a bounty, only its author may do so.
+pub async fn close(
+    db: &Db,
+    username: &str,
+    bounty_id: &str,
+    time: i64,
+) -> Result<DbBounty, ErrorBounty> {
+    let Some(bounty) = bounty::get(db, bounty_id).await? else {
+        return Err(ErrorBounty::NotFound);
+    };
+    if bounty.acc != username {
+        return Err(ErrorBounty::Forbidden);
+    }
+
+    let bounty = bounty::set_status(db, bounty_id, STATUS_CLOSED, time)
+        .await?
+        .ok_or(ErrorBounty::NotFound)?;
+    webhook::emit(
+        db,
+        username,
+        "bounty.closed",
+        webhook::bounty_data(&bounty),
+        time,
+    )
+    .await;
+
+    Ok(bounty)
+}
+
+/// Bounty page as seen by `viewer`, ratings they hide aren't found.
+pub async fn page(
+    db: &Db,
+    settings: &Settings,
+    viewer: Option<&DbAcc>,
+    bounty_id: &str,
+) -> Result<Option<BountyPageInfo>, ErrorBounty> {
+    let prefs = rating::prefs_for(viewer);
+    let Some(bounty) = bounty::get(db, bounty_id).await? else {
+        return Ok(None);
+    };
+    let display = rating::display_for(&prefs, &bounty.rating);
+    if display == CONTENT_HIDE {
+        return Ok(None);
+    }
+    Ok(Some(BountyPageInfo {
+        meta: embed::bounty_meta(settings, &bounty),
+        blurred: display == CONTENT_BLUR,
+        bounty_id: bounty.bounty_id,
+        author: bounty.acc,
+        title: bounty.title,
+        description: bounty.description,
+        tags: bounty.tags,
+        reward: bounty.reward,
+        status: bounty.status,
+        rating: bounty.rating,
+        created_at: bounty.created_at,
+    }))
+}
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+/// Keys held by in-flight requests of this process. The embedded db engines don't catch two
+/// transactions updating the same record at once, so read-check-write sequences that must not
+/// interleave, like appending to one upload, take a claim first.
+#[derive(Clone, Default)]
+pub struct Claims {
+    held: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Claimed key, given back on drop.
+pub struct Claim {
+    held: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl Claims {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `None` while another request holds `key`.
+    pub fn claim(&self, key: impl Into<String>) -> Option<Claim> {
+        let key = key.into();
+        let mut held = self.held.lock().unwrap_or_else(|err| err.into_inner());
+        if !held.insert(key.clone()) {
+            return None;
+        }
+        Some(Claim {
+            held: self.held.clone(),
+            key,
+        })
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.held
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod claim_tests {
+    use super::Claims;
+
+    #[test]
+    fn keys_are_held_until_dropped() {
+        let claims = Claims::new();
+        let first = claims.claim("upload:a").unwrap();
+        assert!(claims.claim("upload:a").is_none());
+        assert!(claims.claim("upload:b").is_some());
+        drop(first);
+        assert!(claims.claim("upload:a").is_some());
+    }
+}
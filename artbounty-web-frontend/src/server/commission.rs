@@ -0,0 +1,657 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    api::{
+        CommissionDetail, FINAL_STAGE, MAXIMUM_COMMENT_LENGTH, MAXIMUM_REVISION_SIZE,
+        REVISION_MEDIA_PATH, REVISION_STAGES, RevisionCommentInfo, RevisionInfo,
+    },
+    db::{
+        Db, DbError, acc,
+        artwork::DbArtwork,
+        bounty,
+        commission::{self, DbCommission, STATUS_CANCELLED, STATUS_COMPLETED, STATUS_OPEN},
+        revision::{
+            self, DbRevision, PREVIEW_PENDING, PREVIEW_READY, STATUS_APPROVED,
+            STATUS_CHANGES_REQUESTED,
+        },
+        revision_comment::{self, DbRevisionComment},
+    },
+};
+
+use super::{
+    ServerError, ServerState, Settings,
+    artwork::{self, ErrorArtwork},
+    message::sniff_image,
+    new_id, upload,
+};
+
+pub const REVISION_DIR: &str = "revisions";
+pub const PREVIEW_MIME: &str = "image/webp";
+
+#[derive(Error, Debug)]
+pub enum ErrorCommission {
+    #[error("user {0} not found")]
+    UnknownUser(String),
+
+    #[error("you can't commission yourself")]
+    SelfCommission,
+
+    #[error("bounty not found")]
+    BountyNotFound,
+
+    #[error("commission not found")]
+    NotFound,
+
+    #[error("revision not found")]
+    RevisionNotFound,
+
+    #[error("not allowed")]
+    Forbidden,
+
+    #[error("commission is no longer open")]
+    Closed,
+
+    #[error("unknown stage")]
+    Stage,
+
+    #[error("text must be at most {MAXIMUM_COMMENT_LENGTH} characters")]
+    Note,
+
+    #[error("comment must be 1-{MAXIMUM_COMMENT_LENGTH} characters")]
+    Comment,
+
+    #[error("pin must be inside the image")]
+    Pin,
+
+    #[error("deliverables must be png, jpeg, gif or webp images of at most {} MiB", MAXIMUM_REVISION_SIZE / 1024 / 1024)]
+    Image,
+
+    #[error("revision was already reviewed")]
+    Reviewed,
+
+    #[error("only approved final revisions can be published")]
+    NotApproved,
+
+    #[error("revision is still being processed")]
+    Processing,
+
+    #[error("revision was already published")]
+    Published,
+
+    #[error("another revision is being uploaded, try again")]
+    Busy,
+
+    #[error(transparent)]
+    Artwork(#[from] ErrorArtwork),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorCommission {
+    fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ErrorCommission::Io(_)
+                | ErrorCommission::Db(_)
+                | ErrorCommission::Artwork(ErrorArtwork::Db(_))
+        )
+    }
+}
+
+pub fn revision_path(settings: &Settings, revision_id: &str) -> PathBuf {
+    settings.media_dir.join(REVISION_DIR).join(revision_id)
+}
+
+pub fn preview_path(settings: &Settings, revision_id: &str) -> PathBuf {
+    settings
+        .media_dir
+        .join(REVISION_DIR)
+        .join(format!("{}_preview.webp", revision_id))
+}
+
+/// The artist always may, the client only once a watermarked revision is approved.
+pub fn can_see_original(commission: &DbCommission, revision: &DbRevision, acc: &str) -> bool {
+    acc == commission.artist
+        || (acc == commission.client && (!revision.watermark || revision.status == STATUS_APPROVED))
+}
+
+/// Commission where `acc` is the client or the artist, everyone else gets [`ErrorCommission::NotFound`].
+pub async fn get_for_party(
+    db: &Db,
+    acc: &str,
+    commission_id: &str,
+) -> Result<DbCommission, ErrorCommission> {
+    commission::get(db, commission_id)
+        .await?
+        .filter(|commission| commission.client == acc || commission.artist == acc)
+        .ok_or(ErrorCommission::NotFound)
+}
+
+async fn get_revision_for_party(
+    db: &Db,
+    acc: &str,
+    revision_id: &str,
+) -> Result<(DbCommission, DbRevision), ErrorCommission> {
+    let revision = revision::get(db, revision_id)
+        .await?
+        .ok_or(ErrorCommission::RevisionNotFound)?;
+    let commission = get_for_party(db, acc, &revision.commission_id)
+        .await
+        .map_err(|err| match err {
+            ErrorCommission::NotFound => ErrorCommission::RevisionNotFound,
+            err => err,
+        })?;
+    Ok((commission, revision))
+}
+
+pub async fn create(
+    db: &Db,
+    client: &str,
+    artist: &str,
+    title: &str,
+    bounty_id: Option<&str>,
+    time: i64,
+) -> Result<DbCommission, ErrorCommission> {
+    let artist = artist.trim().trim_start_matches('@');
+    let title = title.trim();
+    artwork::validate_title(title)?;
+    if artist == client {
+        return Err(ErrorCommission::SelfCommission);
+    }
+    if acc::get_by_username(db, artist).await?.is_none() {
+        return Err(ErrorCommission::UnknownUser(artist.to_string()));
+    }
+    if let Some(bounty_id) = bounty_id {
+        bounty::get(db, bounty_id)
+            .await?
+            .filter(|bounty| bounty.acc == client)
+            .ok_or(ErrorCommission::BountyNotFound)?;
+    }
+
+    let commission = DbCommission {
+        commission_id: new_id(),
+        bounty_id: bounty_id.map(String::from),
+        client: client.to_string(),
+        artist: artist.to_string(),
+        title: title.to_string(),
+        status: STATUS_OPEN.to_string(),
+        revision_count: 0,
+        modified_at: time,
+        created_at: time,
+    };
+    commission::insert(db, commission.clone()).await?;
+    trace!(
+        "commission {} created by {} for {}",
+        commission.commission_id, client, artist
+    );
+    Ok(commission)
+}
+
+/// Either party may cancel while the commission is open.
+pub async fn cancel(
+    db: &Db,
+    acc: &str,
+    commission_id: &str,
+    time: i64,
+) -> Result<(), ErrorCommission> {
+    let commission = get_for_party(db, acc, commission_id).await?;
+    if commission.status != STATUS_OPEN {
+        return Err(ErrorCommission::Closed);
+    }
+    commission::set_status(db, commission_id, STATUS_CANCELLED, time).await?;
+    trace!("commission {} cancelled by {}", commission_id, acc);
+    Ok(())
+}
+
+/// Commission with its full revision history as seen by `acc`.
+pub async fn detail(
+    db: &Db,
+    acc: &str,
+    commission_id: &str,
+) -> Result<CommissionDetail, ErrorCommission> {
+    let commission = get_for_party(db, acc, commission_id).await?;
+    let mut revisions = Vec::new();
+    for revision in revision::get_all_for_commission(db, commission_id).await? {
+        let comments = revision_comment::get_all_for_revision(db, &revision.revision_id)
+            .await?
+            .into_iter()
+            .map(RevisionCommentInfo::from)
+            .collect();
+        let original = can_see_original(&commission, &revision, acc);
+        let preview = revision.preview_status == PREVIEW_READY;
+        let original_url =
+            original.then(|| format!("{}/{}/original", REVISION_MEDIA_PATH, revision.revision_id));
+        // the preview of a watermarked revision is pointless to someone who may see the original
+        let image_url = if original && (revision.watermark || !preview) {
+            original_url.clone()
+        } else if preview {
+            Some(format!(
+                "{}/{}/preview",
+                REVISION_MEDIA_PATH, revision.revision_id
+            ))
+        } else {
+            None
+        };
+        revisions.push(RevisionInfo {
+            revision_id: revision.revision_id,
+            version: revision.version,
+            stage: revision.stage,
+            note: revision.note,
+            watermark: revision.watermark,
+            image_url,
+            original_url,
+            width: revision.width,
+            height: revision.height,
+            status: revision.status,
+            artwork_id: revision.artwork_id,
+            comments,
+            created_at: revision.created_at,
+        });
+    }
+    Ok(CommissionDetail {
+        is_artist: commission.artist == acc,
+        commission: commission.into(),
+        revisions,
+    })
+}
+
+/// Stores the next version of the deliverable, its preview is rendered by the backend media worker.
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_revision(
+    state: &ServerState,
+    acc: &str,
+    commission_id: &str,
+    stage: &str,
+    note: &str,
+    watermark: bool,
+    data: &[u8],
+    time: i64,
+) -> Result<DbRevision, ErrorCommission> {
+    let commission = get_for_party(&state.db, acc, commission_id).await?;
+    if commission.artist != acc {
+        return Err(ErrorCommission::Forbidden);
+    }
+    if commission.status != STATUS_OPEN {
+        return Err(ErrorCommission::Closed);
+    }
+    if !REVISION_STAGES.iter().any(|(name, _)| *name == stage) {
+        return Err(ErrorCommission::Stage);
+    }
+    let note = note.trim();
+    if note.chars().count() > MAXIMUM_COMMENT_LENGTH {
+        return Err(ErrorCommission::Note);
+    }
+    if data.len() > MAXIMUM_REVISION_SIZE {
+        return Err(ErrorCommission::Image);
+    }
+    let mime = sniff_image(data).ok_or(ErrorCommission::Image)?;
+    let version = {
+        // the counter update alone isn't atomic on the embedded engines
+        let _claim = state
+            .claims
+            .claim(format!("commission:{}", commission_id))
+            .ok_or(ErrorCommission::Busy)?;
+        commission::next_revision_version(&state.db, commission_id)
+            .await?
+            .ok_or(ErrorCommission::NotFound)?
+    };
+
+    let revision = DbRevision {
+        revision_id: new_id(),
+        commission_id: commission_id.to_string(),
+        version,
+        stage: stage.to_string(),
+        note: note.to_string(),
+        mime: mime.to_string(),
+        width: 0,
+        height: 0,
+        watermark,
+        preview_status: PREVIEW_PENDING.to_string(),
+        status: revision::STATUS_PENDING.to_string(),
+        artwork_id: None,
+        modified_at: time,
+        created_at: time,
+    };
+    let path = revision_path(&state.settings, &revision.revision_id);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&path, data).await?;
+    upload::record_bytes("revision", data.len());
+    revision::insert(&state.db, revision.clone()).await?;
+    trace!(
+        "revision {} v{} of commission {} uploaded",
+        revision.revision_id, version, commission_id
+    );
+    Ok(revision)
+}
+
+/// Client approves or requests changes, approving a final revision completes the commission.
+pub async fn review(
+    db: &Db,
+    acc: &str,
+    revision_id: &str,
+    approve: bool,
+    comment_body: &str,
+    time: i64,
+) -> Result<DbRevision, ErrorCommission> {
+    let (commission, _) = get_revision_for_party(db, acc, revision_id).await?;
+    if commission.client != acc {
+        return Err(ErrorCommission::Forbidden);
+    }
+    if commission.status != STATUS_OPEN {
+        return Err(ErrorCommission::Closed);
+    }
+    if !comment_body.trim().is_empty() {
+        comment(db, acc, revision_id, comment_body, None, None, time).await?;
+    }
+    let status = if approve {
+        STATUS_APPROVED
+    } else {
+        STATUS_CHANGES_REQUESTED
+    };
+    let revision = revision::decide(db, revision_id, status, time)
+        .await?
+        .ok_or(ErrorCommission::Reviewed)?;
+    if approve && revision.stage == FINAL_STAGE {
+        commission::set_status(db, &commission.commission_id, STATUS_COMPLETED, time).await?;
+    }
+    trace!("revision {} {} by {}", revision_id, status, acc);
+    Ok(revision)
+}
+
+/// Either party may comment, `x` and `y` pin it to a point relative to the image size.
+pub async fn comment(
+    db: &Db,
+    acc: &str,
+    revision_id: &str,
+    body: &str,
+    x: Option<f32>,
+    y: Option<f32>,
+    time: i64,
+) -> Result<DbRevisionComment, ErrorCommission> {
+    get_revision_for_party(db, acc, revision_id).await?;
+    let body = body.trim();
+    if !(1..=MAXIMUM_COMMENT_LENGTH).contains(&body.chars().count()) {
+        return Err(ErrorCommission::Comment);
+    }
+    let inside = |v: f32| (0.0..=1.0).contains(&v);
+    match (x, y) {
+        (Some(x), Some(y)) if inside(x) && inside(y) => {}
+        (None, None) => {}
+        _ => return Err(ErrorCommission::Pin),
+    }
+
+    let comment = DbRevisionComment {
+        comment_id: new_id(),
+        revision_id: revision_id.to_string(),
+        acc: acc.to_string(),
+        body: body.to_string(),
+        x,
+        y,
+        modified_at: time,
+        created_at: time,
+    };
+    revision_comment::insert(db, comment.clone()).await?;
+    Ok(comment)
+}
+
+/// Turns an approved final revision into an artwork in the artist's gallery.
+#[allow(clippy::too_many_arguments)]
+pub async fn publish(
+    db: &Db,
+    acc: &str,
+    revision_id: &str,
+    title: &str,
+    description: &str,
+    tags: &[String],
+    rating: &str,
+    time: i64,
+) -> Result<DbArtwork, ErrorCommission> {
+    let (commission, revision) = get_revision_for_party(db, acc, revision_id).await?;
+    if commission.artist != acc {
+        return Err(ErrorCommission::Forbidden);
+    }
+    if revision.stage != FINAL_STAGE || revision.status != STATUS_APPROVED {
+        return Err(ErrorCommission::NotApproved);
+    }
+    if revision.artwork_id.is_some() {
+        return Err(ErrorCommission::Published);
+    }
+    if revision.preview_status != PREVIEW_READY {
+        return Err(ErrorCommission::Processing);
+    }
+
+    let file = format!("{}/{}", REVISION_DIR, revision_id);
+    let artwork = artwork::create(
+        db,
+        acc,
+        title,
+        description,
+        tags,
+        rating,
+        &file,
+        revision.width,
+        revision.height,
+        time,
+    )
+    .await?;
+    revision::set_artwork(db, revision_id, &artwork.artwork_id, time)
+        .await?
+        .ok_or(ErrorCommission::Published)?;
+    // a watermarked deliverable stays protected in the gallery, the client bought the original
+    let artwork = if revision.watermark {
+        artwork::set_protected(db, acc, &artwork.artwork_id, true, time).await?
+    } else {
+        artwork
+    };
+    artwork::grant_access(db, acc, &artwork.artwork_id, &commission.client, time).await?;
+    trace!(
+        "revision {} published as artwork {}",
+        revision_id, artwork.artwork_id
+    );
+    Ok(artwork)
+}
+
+/// Path and type of a revision image, only for the parties of its commission.
+pub async fn open_revision_media(
+    state: &ServerState,
+    acc: &str,
+    revision_id: &str,
+    original: bool,
+) -> Result<(String, PathBuf), ErrorCommission> {
+    let (commission, revision) = get_revision_for_party(&state.db, acc, revision_id).await?;
+    if original {
+        if !can_see_original(&commission, &revision, acc) {
+            return Err(ErrorCommission::Forbidden);
+        }
+        return Ok((revision.mime, revision_path(&state.settings, revision_id)));
+    }
+    if revision.preview_status != PREVIEW_READY {
+        return Err(ErrorCommission::Processing);
+    }
+    Ok((
+        PREVIEW_MIME.to_string(),
+        preview_path(&state.settings, revision_id),
+    ))
+}
+
+#[cfg(test)]
+mod commission_tests {
+    use crate::{
+        db::{
+            artwork, artwork_access,
+            commission::STATUS_COMPLETED,
+            revision::{self, PREVIEW_READY, STATUS_APPROVED, STATUS_CHANGES_REQUESTED},
+        },
+        server::{auth::register, test_support::test_state},
+    };
+
+    use super::{
+        ErrorCommission, comment, create, detail, open_revision_media, publish, review,
+        upload_revision,
+    };
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+
+    #[tokio::test]
+    async fn revisions_are_reviewed_and_published() {
+        let (state, _mailer) = test_state().await;
+        for username in ["hey", "fox", "owl"] {
+            let email = format!("{}@example.com", username);
+            register(&state, username, &email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let db = &state.db;
+        assert!(matches!(
+            create(db, "hey", "hey", "Cat", None, 0).await,
+            Err(ErrorCommission::SelfCommission)
+        ));
+        let commission = create(db, "hey", "@fox", "Cat", None, 0).await.unwrap();
+        let id = &commission.commission_id;
+
+        assert!(matches!(
+            upload_revision(&state, "hey", id, "sketch", "", true, PNG, 1).await,
+            Err(ErrorCommission::Forbidden)
+        ));
+        let sketch = upload_revision(&state, "fox", id, "sketch", "first pass", true, PNG, 1)
+            .await
+            .unwrap();
+        assert_eq!(sketch.version, 1);
+
+        // client only sees the watermarked preview, once it's rendered
+        let seen = detail(db, "hey", id).await.unwrap();
+        assert_eq!(seen.revisions[0].image_url, None);
+        assert_eq!(seen.revisions[0].original_url, None);
+        assert!(matches!(
+            open_revision_media(&state, "hey", &sketch.revision_id, true).await,
+            Err(ErrorCommission::Forbidden)
+        ));
+        assert!(matches!(
+            detail(db, "owl", id).await,
+            Err(ErrorCommission::NotFound)
+        ));
+
+        assert!(matches!(
+            comment(
+                db,
+                "hey",
+                &sketch.revision_id,
+                "ears",
+                Some(1.5),
+                Some(0.2),
+                2
+            )
+            .await,
+            Err(ErrorCommission::Pin)
+        ));
+        comment(
+            db,
+            "hey",
+            &sketch.revision_id,
+            "ears",
+            Some(0.5),
+            Some(0.2),
+            2,
+        )
+        .await
+        .unwrap();
+        let decided = review(db, "hey", &sketch.revision_id, false, "bigger", 3)
+            .await
+            .unwrap();
+        assert_eq!(decided.status, STATUS_CHANGES_REQUESTED);
+        assert!(matches!(
+            review(db, "hey", &sketch.revision_id, true, "", 3).await,
+            Err(ErrorCommission::Reviewed)
+        ));
+
+        let last = upload_revision(&state, "fox", id, "final", "", true, PNG, 4)
+            .await
+            .unwrap();
+        assert_eq!(last.version, 2);
+        review(db, "hey", &last.revision_id, true, "", 5)
+            .await
+            .unwrap();
+        revision::set_preview(db, &last.revision_id, PREVIEW_READY, 64, 32, 6)
+            .await
+            .unwrap();
+
+        let seen = detail(db, "hey", id).await.unwrap();
+        assert_eq!(seen.commission.status, STATUS_COMPLETED);
+        assert_eq!(seen.revisions[0].comments.len(), 2);
+        assert_eq!(seen.revisions[0].comments[0].x, Some(0.5));
+        assert_eq!(seen.revisions[1].status, STATUS_APPROVED);
+        assert!(seen.revisions[1].original_url.is_some());
+        assert!(
+            open_revision_media(&state, "hey", &last.revision_id, true)
+                .await
+                .is_ok()
+        );
+
+        assert!(matches!(
+            publish(db, "fox", &sketch.revision_id, "Cat", "", &[], "sfw", 7).await,
+            Err(ErrorCommission::NotApproved)
+        ));
+        let published = publish(db, "fox", &last.revision_id, "Cat", "", &[], "sfw", 7)
+            .await
+            .unwrap();
+        let stored = artwork::get(db, &published.artwork_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((stored.acc.as_str(), stored.width), ("fox", 64));
+        assert!(stored.protected);
+        assert!(
+            artwork_access::exists(db, &stored.artwork_id, "hey")
+                .await
+                .unwrap()
+        );
+        assert!(matches!(
+            publish(db, "fox", &last.revision_id, "Cat", "", &[], "sfw", 8).await,
+            Err(ErrorCommission::Published)
+        ));
+    }
+
+    #[tokio::test]
+    async fn racing_revisions_get_distinct_versions() {
+        let (state, _mailer) = test_state().await;
+        for username in ["hey", "fox"] {
+            let email = format!("{}@example.com", username);
+            register(&state, username, &email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let commission = create(&state.db, "hey", "fox", "Cat", None, 0)
+            .await
+            .unwrap();
+        let id = &commission.commission_id;
+
+        let (first, second) = tokio::join!(
+            upload_revision(&state, "fox", id, "sketch", "", true, PNG, 1),
+            upload_revision(&state, "fox", id, "sketch", "", true, PNG, 1),
+        );
+        let mut versions = [first, second]
+            .into_iter()
+            .filter_map(|uploaded| match uploaded {
+                Ok(revision) => Some(revision.version),
+                Err(ErrorCommission::Busy) => None,
+                Err(err) => panic!("unexpected error {}", err),
+            })
+            .collect::<Vec<_>>();
+        versions.sort();
+        let expected = (1..=versions.len() as u32).collect::<Vec<_>>();
+        assert_eq!(versions, expected);
+        let next = upload_revision(&state, "fox", id, "sketch", "", true, PNG, 2)
+            .await
+            .unwrap();
+        assert_eq!(next.version, versions.len() as u32 + 1);
+    }
+}
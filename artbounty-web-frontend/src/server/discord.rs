@@ -0,0 +1,237 @@
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    db::{
+        self, Conflict, Db, DbError,
+        acc::{self, DbAcc, DbAccDiscord},
+        discord_link_code::{self, DbDiscordLinkCode},
+    },
+    server::{ServerError, ServerState},
+};
+
+pub const LINK_CODE_LEN: usize = 8;
+pub const LINK_CODE_DURATION_MS: i64 = 1000 * 60 * 10;
+
+const LINK_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+#[derive(Error, Debug)]
+pub enum ErrorDiscord {
+    #[error("invalid or expired link code")]
+    Code,
+
+    #[error("this discord account is already linked to another account")]
+    Taken,
+
+    #[error("account not found")]
+    AccNotFound,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorDiscord {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorDiscord::Db(_))
+    }
+}
+
+/// Short code the user passes to the bot's `/link` command. Only its hash is stored.
+pub async fn new_link_code(db: &Db, acc: &DbAcc, time: i64) -> Result<String, ErrorDiscord> {
+    let mut rng = rand::rngs::OsRng;
+    let code = (0..LINK_CODE_LEN)
+        .map(|_| LINK_CODE_ALPHABET[rng.gen_range(0..LINK_CODE_ALPHABET.len())] as char)
+        .collect::<String>();
+
+    discord_link_code::insert(
+        db,
+        DbDiscordLinkCode {
+            code_hash: hash_link_code(&code),
+            acc: acc.username.clone(),
+            expires_at: time + LINK_CODE_DURATION_MS,
+            created_at: time,
+        },
+    )
+    .await?;
+
+    Ok(code)
+}
+
+pub fn hash_link_code(code: &str) -> String {
+    Sha256::digest(code.trim().to_uppercase().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Bot side of linking: the bot already knows the discord user, so it writes the link directly.
+///
+/// Returns the username of the linked account.
+pub async fn redeem_link_code(
+    state: &ServerState,
+    code: &str,
+    discord_user_id: &str,
+    time: i64,
+) -> Result<String, ErrorDiscord> {
+    let Some(link_code) = discord_link_code::take(&state.db, &hash_link_code(code)).await? else {
+        return Err(ErrorDiscord::Code);
+    };
+    if link_code.expires_at <= time {
+        return Err(ErrorDiscord::Code);
+    }
+
+    link(
+        state,
+        &link_code.acc,
+        DbAccDiscord {
+            user_id: discord_user_id.to_string(),
+            token: String::new(),
+        },
+        time,
+    )
+    .await?;
+
+    Ok(link_code.acc)
+}
+
+/// Links `discord` to the account, refusing discord accounts already linked elsewhere.
+pub async fn link(
+    state: &ServerState,
+    username: &str,
+    discord: DbAccDiscord,
+    time: i64,
+) -> Result<(), ErrorDiscord> {
+    // a concurrent link of the same discord account would pass the check below too
+    let Some(_claim) = state.claims.claim(format!("discord:{}", discord.user_id)) else {
+        return Err(ErrorDiscord::Taken);
+    };
+    if let Some(linked) = acc::get_by_discord_id(&state.db, &discord.user_id).await?
+        && linked.username != username
+    {
+        return Err(ErrorDiscord::Taken);
+    }
+    if acc::get_by_username(&state.db, username).await?.is_none() {
+        return Err(ErrorDiscord::AccNotFound);
+    }
+
+    trace!("linking discord {} to {}", discord.user_id, username);
+    if let Err(err) = acc::set_discord(&state.db, username, Some(discord), time).await {
+        return Err(match db::conflict(&err) {
+            Some(Conflict::Index(index)) if index == db::ACC_DISCORD_INDEX => ErrorDiscord::Taken,
+            _ => err.into(),
+        });
+    }
+    Ok(())
+}
+
+pub async fn unlink(db: &Db, username: &str, time: i64) -> Result<(), ErrorDiscord> {
+    trace!("unlinking discord from {}", username);
+    acc::set_discord(db, username, None, time).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod discord_tests {
+    use crate::{
+        db::acc::{self, DbAccDiscord},
+        server::{auth::register, test_support::test_state},
+    };
+
+    use super::{ErrorDiscord, link, new_link_code, redeem_link_code, unlink};
+
+    #[tokio::test]
+    async fn link_code_links_once() {
+        let (state, _mailer) = test_state().await;
+        let hey = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let other = register(&state, "other", "other@example.com", "password123", 0)
+            .await
+            .unwrap();
+
+        let code = new_link_code(&state.db, &hey, 0).await.unwrap();
+        assert_eq!(
+            redeem_link_code(&state, &code.to_lowercase(), "42", 1)
+                .await
+                .unwrap(),
+            "hey"
+        );
+        assert!(matches!(
+            redeem_link_code(&state, &code, "42", 1).await,
+            Err(ErrorDiscord::Code)
+        ));
+        let linked = acc::get_by_discord_id(&state.db, "42")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(linked.username, "hey");
+
+        let code = new_link_code(&state.db, &other, 0).await.unwrap();
+        assert!(matches!(
+            redeem_link_code(&state, &code, "42", 1).await,
+            Err(ErrorDiscord::Taken)
+        ));
+
+        let code = new_link_code(&state.db, &other, 0).await.unwrap();
+        assert!(matches!(
+            redeem_link_code(&state, &code, "43", 1000 * 60 * 10).await,
+            Err(ErrorDiscord::Code)
+        ));
+
+        unlink(&state.db, "hey", 2).await.unwrap();
+        assert!(
+            acc::get_by_discord_id(&state.db, "42")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn discord_account_links_to_one_account_at_once() {
+        let (state, _mailer) = test_state().await;
+        for (username, email) in [("hey", "hey@example.com"), ("other", "other@example.com")] {
+            register(&state, username, email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let discord = || DbAccDiscord {
+            user_id: String::from("42"),
+            token: String::new(),
+        };
+
+        let (hey, other) = tokio::join!(
+            link(&state, "hey", discord(), 1),
+            link(&state, "other", discord(), 1)
+        );
+        assert_eq!(
+            [hey.is_ok(), other.is_ok()]
+                .iter()
+                .filter(|ok| **ok)
+                .count(),
+            1
+        );
+        assert!(matches!(
+            hey.err().or(other.err()),
+            Some(ErrorDiscord::Taken)
+        ));
+
+        // the index backs the check up for writes that skip it
+        let linked = acc::get_by_discord_id(&state.db, "42")
+            .await
+            .unwrap()
+            .unwrap();
+        let unlinked = if linked.username == "hey" {
+            "other"
+        } else {
+            "hey"
+        };
+        assert!(
+            acc::set_discord(&state.db, unlinked, Some(discord()), 2)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use lettre::{
+    AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+};
+use thiserror::Error;
+use tracing::trace;
+
+pub const EMAIL_SINK_ENV: &str = "EMAIL_SINK";
+pub const EMAIL_FROM_ENV: &str = "EMAIL_FROM";
+pub const EMAIL_DIR_ENV: &str = "EMAIL_DIR";
+pub const SMTP_HOST_ENV: &str = "SMTP_HOST";
+pub const SMTP_PORT_ENV: &str = "SMTP_PORT";
+pub const SMTP_USER_ENV: &str = "SMTP_USER";
+pub const SMTP_PASS_ENV: &str = "SMTP_PASS";
+pub const SMTP_TLS_ENV: &str = "SMTP_TLS";
+
+pub type MailerFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ErrorMailer>> + Send + 'a>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: Email) -> MailerFuture<'_>;
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorMailer {
+    #[error("invalid address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+
+    #[error("failed to build email: {0}")]
+    Build(#[from] lettre::error::Error),
+
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
+    #[error("file sink error: {0}")]
+    File(#[from] lettre::transport::file::Error),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid {0}: {1}")]
+    Config(&'static str, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpTls {
+    /// Plain connection, for local sinks like mailpit on port 1025.
+    None,
+    StartTls,
+    Tls,
+}
+
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+/// Writes every email as an `.eml` file into a directory.
+pub struct FileMailer {
+    from: Mailbox,
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+/// Keeps sent emails in memory, for tests.
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        from: &str,
+        host: &str,
+        port: u16,
+        tls: SmtpTls,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, ErrorMailer> {
+        let builder = match tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        };
+        let builder = builder.port(port);
+        let builder = match credentials {
+            Some((user, pass)) => builder.credentials(Credentials::new(user, pass)),
+            None => builder,
+        };
+
+        Ok(Self {
+            from: from.parse()?,
+            transport: builder.build(),
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: Email) -> MailerFuture<'_> {
+        Box::pin(async move {
+            let message = build_message(&self.from, email)?;
+            self.transport.send(message).await?;
+            Ok(())
+        })
+    }
+}
+
+impl FileMailer {
+    pub fn new(from: &str, dir: impl Into<PathBuf>) -> Result<Self, ErrorMailer> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            from: from.parse()?,
+            transport: AsyncFileTransport::<Tokio1Executor>::new(dir),
+        })
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: Email) -> MailerFuture<'_> {
+        Box::pin(async move {
+            let message = build_message(&self.from, email)?;
+            let id = self.transport.send(message).await?;
+            trace!("email written to {}.eml", id);
+            Ok(())
+        })
+    }
+}
+
+impl MemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn take(&self) -> Vec<Email> {
+        std::mem::take(&mut *self.sent.lock().unwrap())
+    }
+}
+
+impl Mailer for MemoryMailer {
+    fn send(&self, email: Email) -> MailerFuture<'_> {
+        Box::pin(async move {
+            self.sent.lock().unwrap().push(email);
+            Ok(())
+        })
+    }
+}
+
+/// Picks a mailer from `EMAIL_SINK` (`smtp`, `file` or `memory`), defaulting to `.eml` files in `target/email`.
+pub fn from_env() -> Result<Arc<dyn Mailer>, ErrorMailer> {
+    let sink = std::env::var(EMAIL_SINK_ENV).unwrap_or_else(|_| String::from("file"));
+    let from = std::env::var(EMAIL_FROM_ENV)
+        .unwrap_or_else(|_| String::from("ArtBounty <noreply@localhost>"));
+
+    let mailer: Arc<dyn Mailer> = match sink.as_str() {
+        "smtp" => {
+            let host = std::env::var(SMTP_HOST_ENV).unwrap_or_else(|_| String::from("localhost"));
+            let port = std::env::var(SMTP_PORT_ENV)
+                .unwrap_or_else(|_| String::from("1025"))
+                .parse::<u16>()
+                .map_err(|err| ErrorMailer::Config(SMTP_PORT_ENV, err.to_string()))?;
+            let tls = match std::env::var(SMTP_TLS_ENV).as_deref() {
+                Ok("starttls") => SmtpTls::StartTls,
+                Ok("tls") => SmtpTls::Tls,
+                Ok("none") | Err(_) => SmtpTls::None,
+                Ok(tls) => return Err(ErrorMailer::Config(SMTP_TLS_ENV, tls.to_string())),
+            };
+            let credentials = std::env::var(SMTP_USER_ENV)
+                .ok()
+                .filter(|user| !user.is_empty())
+                .zip(std::env::var(SMTP_PASS_ENV).ok());
+            Arc::new(SmtpMailer::new(&from, &host, port, tls, credentials)?)
+        }
+        "file" => {
+            let dir = std::env::var(EMAIL_DIR_ENV).unwrap_or_else(|_| String::from("target/email"));
+            Arc::new(FileMailer::new(&from, dir)?)
+        }
+        "memory" => Arc::new(MemoryMailer::new()),
+        sink => return Err(ErrorMailer::Config(EMAIL_SINK_ENV, sink.to_string())),
+    };
+    trace!("using {} email sink", sink);
+
+    Ok(mailer)
+}
+
+fn build_message(from: &Mailbox, email: Email) -> Result<Message, ErrorMailer> {
+    let message = Message::builder()
+        .from(from.clone())
+        .to(email.to.parse()?)
+        .subject(email.subject)
+        .multipart(MultiPart::alternative_plain_html(email.text, email.html))?;
+    Ok(message)
+}
+
+pub mod template {
+    use super::Email;
+
+    const LAYOUT_HTML: &str = include_str!("email/layout.html");
+    const VERIFY_EMAIL_HTML: &str = include_str!("email/verify_email.html");
+    const VERIFY_EMAIL_TEXT: &str = include_str!("email/verify_email.txt");
+    const RESET_PASSWORD_HTML: &str = include_str!("email/reset_password.html");
+    const RESET_PASSWORD_TEXT: &str = include_str!("email/reset_password.txt");
+
+    pub fn verify_email(to: &str, username: &str, link: &str) -> Email {
+        let vars = [("username", username), ("link", link)];
+        build(
+            to,
+            "Verify your ArtBounty email",
+            VERIFY_EMAIL_HTML,
+            VERIFY_EMAIL_TEXT,
+            &vars,
+        )
+    }
+
+    pub fn reset_password(to: &str, username: &str, link: &str) -> Email {
+        let vars = [("username", username), ("link", link)];
+        build(
+            to,
+            "Reset your ArtBounty password",
+            RESET_PASSWORD_HTML,
+            RESET_PASSWORD_TEXT,
+            &vars,
+        )
+    }
+
+    fn build(to: &str, subject: &str, html: &str, text: &str, vars: &[(&str, &str)]) -> Email {
+        let body = render(html, vars, true);
+        let html = render(LAYOUT_HTML, &[("subject", &escape_html(subject))], false)
+            .replace("{{body}}", &body);
+
+        Email {
+            to: to.to_string(),
+            subject: subject.to_string(),
+            html,
+            text: render(text, vars, false),
+        }
+    }
+
+    /// Replaces every `{{name}}` with its value, html escaped when `escape` is set.
+    pub fn render(template: &str, vars: &[(&str, &str)], escape: bool) -> String {
+        let mut output = template.to_string();
+        for (name, value) in vars {
+            let value = if escape {
+                escape_html(value)
+            } else {
+                value.to_string()
+            };
+            output = output.replace(&format!("{{{{{}}}}}", name), &value);
+        }
+        output
+    }
+
+    pub fn escape_html(value: &str) -> String {
+        let mut output = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '&' => output.push_str("&amp;"),
+                '<' => output.push_str("&lt;"),
+                '>' => output.push_str("&gt;"),
+                '"' => output.push_str("&quot;"),
+                '\'' => output.push_str("&#39;"),
+                c => output.push(c),
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod email_tests {
+    use super::template;
+
+    #[test]
+    fn templates_escape_html_but_not_text() {
+        let email = template::verify_email(
+            "hey@example.com",
+            "<b>hey</b>",
+            "http://localhost:3000/verify_email?token=a&b",
+        );
+
+        assert!(email.html.contains("&lt;b&gt;hey&lt;/b&gt;"));
+        assert!(email.html.contains("token=a&amp;b"));
+        assert!(
+            email
+                .html
+                .contains("<title>Verify your ArtBounty email</title>")
+        );
+        assert!(!email.html.contains("{{"));
+        assert!(email.text.contains("<b>hey</b>"));
+        assert!(email.text.contains("token=a&b"));
+        assert!(!email.text.contains("{{"));
+    }
+}
@@ -0,0 +1,388 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    api::{
+        ARTWORK_PAGE_PATH, BOUNTY_PAGE_PATH, CONTENT_HIDE, MAXIMUM_META_DESCRIPTION_LENGTH,
+        MetaImage, OEMBED_PATH, PROFILE_PAGE_PATH, PageMeta, RATING_SFW, RATINGS, SITE_NAME,
+    },
+    db::{
+        Db, DbError, acc,
+        artwork::{self, DbArtwork, PREVIEW_READY},
+        bounty::{self, DbBounty},
+    },
+    i18n,
+};
+
+use super::{ServerError, Settings, artwork::variants, encode_query_value, rating};
+
+pub const OEMBED_VERSION: &str = "1.0";
+
+#[derive(Error, Debug)]
+pub enum ErrorEmbed {
+    #[error("not a link to an artwork, bounty or profile")]
+    Url,
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorEmbed {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorEmbed::Db(_))
+    }
+}
+
+/// Page an oEmbed `url` points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmbedTarget {
+    Artwork(String),
+    Bounty(String),
+    Profile(String),
+}
+
+/// oEmbed response, a `photo` for artworks link previews may show and a `link` otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OEmbed {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: &'static str,
+    pub provider_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+pub fn artwork_url(settings: &Settings, artwork_id: &str) -> String {
+    settings.link(&format!("{}/{}", ARTWORK_PAGE_PATH, artwork_id))
+}
+
+pub fn bounty_url(settings: &Settings, bounty_id: &str) -> String {
+    settings.link(&format!("{}/{}", BOUNTY_PAGE_PATH, bounty_id))
+}
+
+pub fn profile_url(settings: &Settings, username: &str) -> String {
+    settings.link(&format!("{}/{}", PROFILE_PAGE_PATH, username))
+}
+
+pub fn oembed_url(settings: &Settings, page_url: &str) -> String {
+    settings.link(&format!(
+        "{}?url={}&format=json",
+        OEMBED_PATH,
+        encode_query_value(page_url)
+    ))
+}
+
+/// Only links to pages of this site are embedded.
+pub fn parse_url(settings: &Settings, url: &str) -> Option<EmbedTarget> {
+    let path = url.strip_prefix(settings.site_url.trim_end_matches('/'))?;
+    let path = path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/');
+    let (page, key) = path.rsplit_once('/')?;
+    if key.is_empty() {
+        return None;
+    }
+    match page {
+        ARTWORK_PAGE_PATH => Some(EmbedTarget::Artwork(key.to_string())),
+        BOUNTY_PAGE_PATH => Some(EmbedTarget::Bounty(key.to_string())),
+        PROFILE_PAGE_PATH => Some(EmbedTarget::Profile(key.to_string())),
+        _ => None,
+    }
+}
+
+/// Widest variant fitting the bounds. Link previews are seen by anyone, so only sfw artworks
+/// get one.
+pub fn share_image(
+    settings: &Settings,
+    artwork: &DbArtwork,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Option<MetaImage> {
+    if artwork.rating != RATING_SFW || artwork.preview_status != PREVIEW_READY || artwork.width == 0
+    {
+        return None;
+    }
+    variants(&artwork.artwork_id, artwork.width)
+        .into_iter()
+        .map(|variant| MetaImage {
+            height: (variant.width as u64 * artwork.height as u64 / artwork.width as u64) as u32,
+            width: variant.width,
+            url: settings.link(&variant.url),
+        })
+        .rfind(|image| {
+            max_width.is_none_or(|max| image.width <= max)
+                && max_height.is_none_or(|max| image.height <= max)
+        })
+}
+
+/// Whitespace collapsed and cut down to the meta description limit.
+pub fn clip_description(description: &str) -> String {
+    let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    match description
+        .char_indices()
+        .nth(MAXIMUM_META_DESCRIPTION_LENGTH - 1)
+    {
+        Some((i, _)) => format!("{}…", description[..i].trim_end()),
+        None => description,
+    }
+}
+
+fn rating_label(rating: &str) -> &'static str {
+    RATINGS
+        .iter()
+        .find(|(code, _)| *code == rating)
+        .map(|(_, label)| *label)
+        .unwrap_or_default()
+}
+
+/// The description of mature and explicit artworks is left out along with the image.
+pub fn artwork_meta(settings: &Settings, artwork: &DbArtwork) -> PageMeta {
+    let url = artwork_url(settings, &artwork.artwork_id);
+    let description = if artwork.rating != RATING_SFW {
+        format!(
+            "{} artwork by {} on {}",
+            rating_label(&artwork.rating),
+            artwork.acc,
+            SITE_NAME
+        )
+    } else if artwork.description.trim().is_empty() {
+        format!("Artwork by {} on {}", artwork.acc, SITE_NAME)
+    } else {
+        clip_description(&artwork.description)
+    };
+    PageMeta {
+        title: artwork.title.clone(),
+        description,
+        oembed_url: oembed_url(settings, &url),
+        url,
+        kind: String::from("article"),
+        author: Some(artwork.acc.clone()),
+        image: share_image(settings, artwork, None, None),
+    }
+}
+
+/// Link previews of mature and explicit bounties leave the description out.
+pub fn bounty_meta(settings: &Settings, bounty: &DbBounty) -> PageMeta {
+    let url = bounty_url(settings, &bounty.bounty_id);
+    let reward = i18n::format_price(i18n::DEFAULT_LOCALE, bounty.reward);
+    let description = if bounty.rating != RATING_SFW {
+        format!(
+            "{} bounty of {} by {} on {}",
+            rating_label(&bounty.rating),
+            reward,
+            bounty.acc,
+            SITE_NAME
+        )
+    } else {
+        clip_description(&format!(
+            "{} bounty by {}. {}",
+            reward, bounty.acc, bounty.description
+        ))
+    };
+    PageMeta {
+        title: bounty.title.clone(),
+        description,
+        oembed_url: oembed_url(settings, &url),
+        url,
+        kind: String::from("article"),
+        author: Some(bounty.acc.clone()),
+        image: None,
+    }
+}
+
+/// Pictured by the newest of `artworks` that may be shown.
+pub fn profile_meta(settings: &Settings, username: &str, artworks: &[DbArtwork]) -> PageMeta {
+    let url = profile_url(settings, username);
+    PageMeta {
+        title: username.to_string(),
+        description: format!("Artworks by {} on {}", username, SITE_NAME),
+        oembed_url: oembed_url(settings, &url),
+        url,
+        kind: String::from("profile"),
+        author: Some(username.to_string()),
+        image: artworks
+            .iter()
+            .find_map(|artwork| share_image(settings, artwork, None, None)),
+    }
+}
+
+/// Embeds are fetched without a session, so ratings hidden from logged out visitors aren't found.
+pub async fn oembed(
+    db: &Db,
+    settings: &Settings,
+    url: &str,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Result<OEmbed, ErrorEmbed> {
+    let prefs = rating::default_prefs();
+    let provider_url = settings.link("/");
+    match parse_url(settings, url).ok_or(ErrorEmbed::Url)? {
+        EmbedTarget::Artwork(artwork_id) => {
+            let artwork = artwork::get(db, &artwork_id)
+                .await?
+                .filter(|artwork| rating::display_for(&prefs, &artwork.rating) != CONTENT_HIDE)
+                .ok_or(ErrorEmbed::NotFound)?;
+            let image = share_image(settings, &artwork, max_width, max_height);
+            Ok(OEmbed {
+                version: OEMBED_VERSION,
+                kind: if image.is_some() { "photo" } else { "link" },
+                title: artwork.title,
+                author_url: profile_url(settings, &artwork.acc),
+                author_name: artwork.acc,
+                provider_name: SITE_NAME,
+                provider_url,
+                width: image.as_ref().map(|image| image.width),
+                height: image.as_ref().map(|image| image.height),
+                url: image.map(|image| image.url),
+            })
+        }
+        EmbedTarget::Bounty(bounty_id) => {
+            let bounty = bounty::get(db, &bounty_id)
+                .await?
+                .filter(|bounty| rating::display_for(&prefs, &bounty.rating) != CONTENT_HIDE)
+                .ok_or(ErrorEmbed::NotFound)?;
+            Ok(OEmbed {
+                version: OEMBED_VERSION,
+                kind: "link",
+                title: bounty.title,
+                author_url: profile_url(settings, &bounty.acc),
+                author_name: bounty.acc,
+                provider_name: SITE_NAME,
+                provider_url,
+                url: None,
+                width: None,
+                height: None,
+            })
+        }
+        EmbedTarget::Profile(username) => {
+            let acc = acc::get_by_username(db, &username)
+                .await?
+                .ok_or(ErrorEmbed::NotFound)?;
+            Ok(OEmbed {
+                version: OEMBED_VERSION,
+                kind: "link",
+                title: format!("{} on {}", acc.username, SITE_NAME),
+                author_url: profile_url(settings, &acc.username),
+                author_name: acc.username,
+                provider_name: SITE_NAME,
+                provider_url,
+                url: None,
+                width: None,
+                height: None,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod embed_tests {
+    use crate::{
+        db::artwork::{PREVIEW_READY, set_preview_status},
+        server::{artwork::create, auth::register, test_support::test_state},
+    };
+
+    use super::{
+        EmbedTarget, ErrorEmbed, artwork_meta, clip_description, oembed, parse_url, share_image,
+    };
+
+    #[tokio::test]
+    async fn urls_of_this_site_are_embedded() {
+        let (state, _mailer) = test_state().await;
+        let settings = &state.settings;
+        assert_eq!(
+            parse_url(settings, "http://localhost:3000/artwork/cat?ref=discord"),
+            Some(EmbedTarget::Artwork(String::from("cat")))
+        );
+        assert_eq!(
+            parse_url(settings, "http://localhost:3000/u/hey/"),
+            Some(EmbedTarget::Profile(String::from("hey")))
+        );
+        assert_eq!(
+            parse_url(settings, "http://localhost:3000/bounty/dragon"),
+            Some(EmbedTarget::Bounty(String::from("dragon")))
+        );
+        assert_eq!(parse_url(settings, "http://localhost:3000/u/"), None);
+        assert_eq!(parse_url(settings, "http://localhost:3000/settings"), None);
+        assert_eq!(parse_url(settings, "https://example.com/artwork/cat"), None);
+    }
+
+    #[test]
+    fn descriptions_are_clipped() {
+        assert_eq!(clip_description(" a\n  cat "), "a cat");
+        let clipped = clip_description(&"a".repeat(300));
+        assert_eq!(clipped.chars().count(), 200);
+        assert!(clipped.ends_with('…'));
+    }
+
+    #[tokio::test]
+    async fn only_sfw_artworks_are_pictured() {
+        let (state, _mailer) = test_state().await;
+        let db = &state.db;
+        register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let mut ids = Vec::new();
+        for rating in ["sfw", "mature", "explicit"] {
+            let artwork = create(db, "hey", "Cat", "A cat", &[], rating, "cat", 2000, 1000, 0)
+                .await
+                .unwrap();
+            set_preview_status(db, &artwork.artwork_id, PREVIEW_READY, 1)
+                .await
+                .unwrap();
+            ids.push(artwork.artwork_id);
+        }
+        let url = |id: &str| format!("http://localhost:3000/artwork/{}", id);
+
+        let embed = oembed(db, &state.settings, &url(&ids[0]), Some(1000), None)
+            .await
+            .unwrap();
+        assert_eq!(embed.kind, "photo");
+        assert_eq!(
+            embed.url.as_deref(),
+            Some(format!("http://localhost:3000/media/artworks/{}/w960", ids[0]).as_str())
+        );
+        assert_eq!((embed.width, embed.height), (Some(960), Some(480)));
+        assert_eq!(embed.author_url, "http://localhost:3000/u/hey");
+
+        let embed = oembed(db, &state.settings, &url(&ids[1]), None, None)
+            .await
+            .unwrap();
+        assert_eq!(embed.kind, "link");
+        assert_eq!(embed.url, None);
+
+        assert!(matches!(
+            oembed(db, &state.settings, &url(&ids[2]), None, None).await,
+            Err(ErrorEmbed::NotFound)
+        ));
+        assert!(matches!(
+            oembed(
+                db,
+                &state.settings,
+                "http://localhost:3000/u/fox",
+                None,
+                None
+            )
+            .await,
+            Err(ErrorEmbed::NotFound)
+        ));
+
+        let artwork = crate::db::artwork::get(db, &ids[1]).await.unwrap().unwrap();
+        let meta = artwork_meta(&state.settings, &artwork);
+        assert_eq!(meta.image, None);
+        assert_eq!(meta.description, "Mature artwork by hey on ArtBounty");
+        assert!(share_image(&state.settings, &artwork, None, None).is_none());
+    }
+}
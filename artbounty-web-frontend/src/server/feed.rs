@@ -0,0 +1,421 @@
+use chrono::{DateTime, SecondsFormat};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    api::{
+        ARTWORK_PAGE_PATH, BOUNTY_FEED_PATH, BOUNTY_PAGE_PATH, FEED_FILE, FEED_SIZE, MetaImage,
+        PROFILE_PAGE_PATH, SITE_NAME, SITEMAP_CHUNK_PATH, SITEMAP_CHUNK_SIZE, TAG_FEED_PATH,
+    },
+    db::{
+        Db, DbError, acc,
+        artwork::{self, DbArtwork},
+        bounty::{self, DbBounty, STATUS_OPEN},
+        sitemap,
+    },
+};
+
+use super::{ServerError, Settings, artwork::normalize_tags, embed, rating};
+
+/// Kind in the chunk url, table, page of each record and whether the table is content rated.
+pub const SITEMAP_KINDS: [(&str, &str, &str, bool); 3] = [
+    ("artworks", artwork::TABLE, ARTWORK_PAGE_PATH, true),
+    ("profiles", acc::TABLE, PROFILE_PAGE_PATH, false),
+    ("bounties", bounty::TABLE, BOUNTY_PAGE_PATH, true),
+];
+
+#[derive(Error, Debug)]
+pub enum ErrorFeed {
+    #[error("not found")]
+    NotFound,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorFeed {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorFeed::Db(_))
+    }
+}
+
+/// Rendered sitemap or feed, with what conditional requests are checked against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub body: String,
+    /// Latest change of anything listed, in milliseconds.
+    pub modified_at: i64,
+    /// Quoted, changes with the body so removed entries are noticed too.
+    pub etag: String,
+}
+
+impl Document {
+    pub fn new(body: String, modified_at: i64) -> Self {
+        let hash = Sha256::digest(body.as_bytes())
+            .iter()
+            .take(16)
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        Self {
+            body,
+            modified_at,
+            etag: format!("\"{}\"", hash),
+        }
+    }
+}
+
+/// One `<entry>` of an Atom feed.
+struct Entry {
+    url: String,
+    title: String,
+    author: String,
+    author_url: String,
+    summary: String,
+    image: Option<MetaImage>,
+    published: i64,
+    updated: i64,
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+fn rfc3339(time: i64) -> String {
+    DateTime::from_timestamp_millis(time)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Crawlers and feed readers don't log in, so they get what logged out visitors see.
+fn public_ratings() -> Vec<String> {
+    rating::visible_ratings(&rating::default_prefs())
+}
+
+/// One `<sitemap>` per chunk of every kind.
+pub async fn sitemap_index(db: &Db, settings: &Settings) -> Result<Document, ErrorFeed> {
+    let mut sitemaps = String::new();
+    let mut modified_at = 0;
+    for (kind, table, _, rated) in SITEMAP_KINDS {
+        let ratings = rated.then(public_ratings);
+        let stats = sitemap::get_stats(db, table, ratings.as_deref()).await?;
+        let lastmod = stats.modified_at.unwrap_or_default();
+        modified_at = modified_at.max(lastmod);
+        for chunk in 0..stats.count.div_ceil(SITEMAP_CHUNK_SIZE as u64) {
+            let loc = settings.link(&format!("{}/{}/{}", SITEMAP_CHUNK_PATH, kind, chunk));
+            sitemaps.push_str(&format!(
+                "<sitemap><loc>{}</loc><lastmod>{}</lastmod></sitemap>\n",
+                escape_xml(&loc),
+                rfc3339(lastmod)
+            ));
+        }
+    }
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n{}</sitemapindex>\n",
+        sitemaps
+    );
+    Ok(Document::new(body, modified_at))
+}
+
+pub async fn sitemap_chunk(
+    db: &Db,
+    settings: &Settings,
+    kind: &str,
+    chunk: u32,
+) -> Result<Document, ErrorFeed> {
+    let (_, table, page, rated) = SITEMAP_KINDS
+        .into_iter()
+        .find(|(name, _, _, _)| *name == kind)
+        .ok_or(ErrorFeed::NotFound)?;
+    let ratings = rated.then(public_ratings);
+    let offset = chunk
+        .checked_mul(SITEMAP_CHUNK_SIZE)
+        .ok_or(ErrorFeed::NotFound)?;
+    let entries =
+        sitemap::get_chunk(db, table, ratings.as_deref(), SITEMAP_CHUNK_SIZE, offset).await?;
+    if entries.is_empty() && chunk > 0 {
+        return Err(ErrorFeed::NotFound);
+    }
+    let mut urls = String::new();
+    for entry in &entries {
+        let loc = settings.link(&format!("{}/{}", page, entry.key));
+        urls.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            escape_xml(&loc),
+            rfc3339(entry.modified_at)
+        ));
+    }
+    let modified_at = entries
+        .iter()
+        .map(|entry| entry.modified_at)
+        .max()
+        .unwrap_or_default();
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n{}</urlset>\n",
+        urls
+    );
+    Ok(Document::new(body, modified_at))
+}
+
+fn artwork_entry(settings: &Settings, artwork: &DbArtwork) -> Entry {
+    let meta = embed::artwork_meta(settings, artwork);
+    Entry {
+        url: meta.url,
+        title: meta.title,
+        author: artwork.acc.clone(),
+        author_url: embed::profile_url(settings, &artwork.acc),
+        summary: meta.description,
+        image: meta.image,
+        published: artwork.created_at,
+        updated: artwork.modified_at,
+    }
+}
+
+fn bounty_entry(settings: &Settings, bounty: &DbBounty) -> Entry {
+    let meta = embed::bounty_meta(settings, bounty);
+    Entry {
+        url: meta.url,
+        title: meta.title,
+        author: bounty.acc.clone(),
+        author_url: embed::profile_url(settings, &bounty.acc),
+        summary: meta.description,
+        image: None,
+        published: bounty.created_at,
+        updated: bounty.modified_at,
+    }
+}
+
+/// The feed is as old as its newest entry, or `created_at` while it has none.
+fn atom(
+    self_url: &str,
+    page_url: &str,
+    title: &str,
+    created_at: i64,
+    entries: Vec<Entry>,
+) -> Document {
+    let modified_at = entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or(created_at);
+    let mut body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n<id>{}</id>\n<title>{}</title>\n<updated>{}</updated>\n<link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n<generator>{}</generator>\n",
+        escape_xml(self_url),
+        escape_xml(title),
+        rfc3339(modified_at),
+        escape_xml(self_url),
+        escape_xml(page_url),
+        SITE_NAME
+    );
+    for entry in entries {
+        // only images link previews may show end up in the feed
+        let content = match &entry.image {
+            Some(image) => format!(
+                "<p>{}</p><img src=\"{}\" width=\"{}\" height=\"{}\" alt=\"{}\"/>",
+                escape_xml(&entry.summary),
+                escape_xml(&image.url),
+                image.width,
+                image.height,
+                escape_xml(&entry.title)
+            ),
+            None => format!("<p>{}</p>", escape_xml(&entry.summary)),
+        };
+        body.push_str(&format!(
+            "<entry>\n<id>{}</id>\n<title>{}</title>\n<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n<author><name>{}</name><uri>{}</uri></author>\n<published>{}</published>\n<updated>{}</updated>\n<summary>{}</summary>\n<content type=\"html\">{}</content>\n</entry>\n",
+            escape_xml(&entry.url),
+            escape_xml(&entry.title),
+            escape_xml(&entry.url),
+            escape_xml(&entry.author),
+            escape_xml(&entry.author_url),
+            rfc3339(entry.published),
+            rfc3339(entry.updated),
+            escape_xml(&entry.summary),
+            escape_xml(&content)
+        ));
+    }
+    body.push_str("</feed>\n");
+    Document::new(body, modified_at)
+}
+
+/// Newest artworks of `username`.
+pub async fn artist_feed(
+    db: &Db,
+    settings: &Settings,
+    username: &str,
+) -> Result<Document, ErrorFeed> {
+    let acc = acc::get_by_username(db, username)
+        .await?
+        .ok_or(ErrorFeed::NotFound)?;
+    let artworks =
+        artwork::get_page_by_acc(db, &acc.username, &public_ratings(), FEED_SIZE, 0).await?;
+    let self_url = settings.link(&format!(
+        "{}/{}/{}",
+        PROFILE_PAGE_PATH, acc.username, FEED_FILE
+    ));
+    Ok(atom(
+        &self_url,
+        &embed::profile_url(settings, &acc.username),
+        &format!("{} on {}", acc.username, SITE_NAME),
+        acc.created_at,
+        artworks
+            .iter()
+            .map(|artwork| artwork_entry(settings, artwork))
+            .collect(),
+    ))
+}
+
+/// Newest artworks tagged `tag`, tags that could never be valid aren't found.
+pub async fn tag_feed(db: &Db, settings: &Settings, tag: &str) -> Result<Document, ErrorFeed> {
+    let tag = normalize_tags(&[tag.to_string()])
+        .ok()
+        .and_then(|tags| tags.into_iter().next())
+        .ok_or(ErrorFeed::NotFound)?;
+    let artworks = artwork::get_page_by_tag(db, &tag, &public_ratings(), FEED_SIZE, 0).await?;
+    let self_url = settings.link(&format!("{}/{}/{}", TAG_FEED_PATH, tag, FEED_FILE));
+    Ok(atom(
+        &self_url,
+        &settings.link("/"),
+        &format!("#{} on {}", tag, SITE_NAME),
+        0,
+        artworks
+            .iter()
+            .map(|artwork| artwork_entry(settings, artwork))
+            .collect(),
+    ))
+}
+
+pub async fn bounty_feed(db: &Db, settings: &Settings) -> Result<Document, ErrorFeed> {
+    let bounties = bounty::get_page(db, Some(STATUS_OPEN), &public_ratings(), FEED_SIZE, 0).await?;
+    Ok(atom(
+        &settings.link(BOUNTY_FEED_PATH),
+        &settings.link("/"),
+        &format!("Open bounties on {}", SITE_NAME),
+        0,
+        bounties
+            .iter()
+            .map(|bounty| bounty_entry(settings, bounty))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod feed_tests {
+    use crate::{
+        api::SITEMAP_CHUNK_SIZE,
+        db::artwork::{PREVIEW_READY, set_preview_status},
+        server::{artwork::create, auth::register, bounty, test_support::test_state},
+    };
+
+    use super::{
+        Document, ErrorFeed, artist_feed, bounty_feed, escape_xml, sitemap_chunk, sitemap_index,
+        tag_feed,
+    };
+
+    #[test]
+    fn xml_is_escaped_and_etags_follow_the_body() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        let document = Document::new(String::from("a"), 0);
+        assert_eq!(document.etag, Document::new(String::from("a"), 5).etag);
+        assert_ne!(document.etag, Document::new(String::from("b"), 0).etag);
+        assert!(document.etag.starts_with('"') && document.etag.ends_with('"'));
+    }
+
+    #[tokio::test]
+    async fn sitemaps_and_feeds_leave_hidden_ratings_out() {
+        let (state, _mailer) = test_state().await;
+        let db = &state.db;
+        let settings = &state.settings;
+        register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        let tags = [String::from("Fox")];
+        let mut ids = Vec::new();
+        for (rating, time) in [("sfw", 10), ("mature", 20), ("explicit", 30)] {
+            let artwork = create(
+                db, "hey", "Cat", "A cat", &tags, rating, "cat", 800, 400, time,
+            )
+            .await
+            .unwrap();
+            set_preview_status(db, &artwork.artwork_id, PREVIEW_READY, time)
+                .await
+                .unwrap();
+            ids.push(artwork.artwork_id);
+        }
+        let open = bounty::create(db, "hey", "Dragon", "Big", &[], 12345, "sfw", 40)
+            .await
+            .unwrap();
+        let closed = bounty::create(db, "hey", "Wolf", "", &[], 100, "sfw", 50)
+            .await
+            .unwrap();
+        bounty::close(db, "hey", &closed.bounty_id, 60)
+            .await
+            .unwrap();
+
+        let index = sitemap_index(db, settings).await.unwrap();
+        for kind in ["artworks", "profiles", "bounties"] {
+            assert!(
+                index
+                    .body
+                    .contains(&format!("http://localhost:3000/sitemap/{}/0", kind))
+            );
+        }
+        assert!(!index.body.contains("/sitemap/artworks/1"));
+        assert_eq!(index.modified_at, 60);
+
+        let artworks = sitemap_chunk(db, settings, "artworks", 0).await.unwrap();
+        assert!(artworks.body.contains(&format!("/artwork/{}<", ids[0])));
+        assert!(artworks.body.contains(&format!("/artwork/{}<", ids[1])));
+        assert!(!artworks.body.contains(&ids[2]));
+        let profiles = sitemap_chunk(db, settings, "profiles", 0).await.unwrap();
+        assert!(profiles.body.contains("http://localhost:3000/u/hey<"));
+        assert!(matches!(
+            sitemap_chunk(db, settings, "artworks", 1).await,
+            Err(ErrorFeed::NotFound)
+        ));
+        assert!(matches!(
+            sitemap_chunk(db, settings, "sessions", 0).await,
+            Err(ErrorFeed::NotFound)
+        ));
+        assert!(matches!(
+            sitemap_chunk(db, settings, "artworks", u32::MAX / SITEMAP_CHUNK_SIZE + 1).await,
+            Err(ErrorFeed::NotFound)
+        ));
+
+        let feed = artist_feed(db, settings, "hey").await.unwrap();
+        assert_eq!(feed.body.matches("<entry>").count(), 2);
+        assert!(!feed.body.contains(&ids[2]));
+        // only the sfw artwork is pictured
+        assert_eq!(feed.body.matches("&lt;img").count(), 1);
+        assert!(feed.body.contains("Mature artwork by hey"));
+        assert_eq!(feed.modified_at, 20);
+        assert!(matches!(
+            artist_feed(db, settings, "fox").await,
+            Err(ErrorFeed::NotFound)
+        ));
+
+        let feed = tag_feed(db, settings, "FOX").await.unwrap();
+        assert_eq!(feed.body.matches("<entry>").count(), 2);
+        assert!(matches!(
+            tag_feed(db, settings, "two words").await,
+            Err(ErrorFeed::NotFound)
+        ));
+
+        let feed = bounty_feed(db, settings).await.unwrap();
+        assert!(feed.body.contains(&open.bounty_id));
+        assert!(!feed.body.contains(&closed.bounty_id));
+        assert!(feed.body.contains("$123.45 bounty by hey"));
+    }
+}
@@ -0,0 +1,630 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{trace, warn};
+
+use crate::{
+    api::{
+        CONVERSATION_PAGE_SIZE, ConversationInfo, MAXIMUM_ATTACHMENT_SIZE, MAXIMUM_ATTACHMENTS,
+        MAXIMUM_MESSAGE_LENGTH, MAXIMUM_PARTICIPANTS, MESSAGE_PAGE_SIZE, MessageEvent, MessageInfo,
+        ParticipantInfo,
+    },
+    db::{
+        Db, DbError, acc,
+        attachment::{self, DbAttachment},
+        block::{self, DbBlock},
+        conversation::{self, DbConversation},
+        conversation_member::{self, DbConversationMember},
+        message::{self, DbMessage},
+    },
+};
+
+use super::{ServerError, ServerState, Settings, new_id, upload};
+
+/// Events buffered per account, a stream that falls further behind gets a resync.
+pub const HUB_CAPACITY: usize = 64;
+pub const ATTACHMENT_DIR: &str = "attachments";
+
+#[derive(Error, Debug)]
+pub enum ErrorMessage {
+    #[error("a conversation needs 2-{MAXIMUM_PARTICIPANTS} participants")]
+    Participants,
+
+    #[error("user {0} not found")]
+    UnknownUser(String),
+
+    #[error("you can't message users who blocked you or whom you blocked")]
+    Blocked,
+
+    #[error("you can't block yourself")]
+    BlockSelf,
+
+    #[error("conversation not found")]
+    NotFound,
+
+    #[error("message must be at most {MAXIMUM_MESSAGE_LENGTH} characters and not empty")]
+    Body,
+
+    #[error("at most {MAXIMUM_ATTACHMENTS} attachments per message")]
+    TooManyAttachments,
+
+    #[error("attachments must be png, jpeg, gif or webp images of at most {} MiB", MAXIMUM_ATTACHMENT_SIZE / 1024 / 1024)]
+    AttachmentType,
+
+    #[error("attachment not found")]
+    AttachmentNotFound,
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorMessage {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorMessage::Db(_) | ErrorMessage::Io(_))
+    }
+}
+
+/// Fans [`MessageEvent`]s out to the open event streams of their recipients, one channel per
+/// account with a stream open so nobody wakes up for someone else's events.
+#[derive(Clone, Default)]
+pub struct MessageHub {
+    senders: Arc<Mutex<HashMap<String, broadcast::Sender<MessageEvent>>>>,
+}
+
+pub struct MessageSubscription {
+    acc: String,
+    rx: broadcast::Receiver<MessageEvent>,
+    senders: Arc<Mutex<HashMap<String, broadcast::Sender<MessageEvent>>>>,
+}
+
+impl MessageHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn publish(&self, recipient: &str, event: MessageEvent) {
+        let senders = self.senders.lock().unwrap_or_else(|err| err.into_inner());
+        // nobody listening isn't an error, the recipient will fetch it later
+        if let Some(tx) = senders.get(recipient) {
+            let _ = tx.send(event);
+        }
+    }
+
+    pub fn subscribe(&self, acc: &str) -> MessageSubscription {
+        let mut senders = self.senders.lock().unwrap_or_else(|err| err.into_inner());
+        let rx = senders
+            .entry(acc.to_string())
+            .or_insert_with(|| broadcast::channel(HUB_CAPACITY).0)
+            .subscribe();
+        MessageSubscription {
+            acc: acc.to_string(),
+            rx,
+            senders: self.senders.clone(),
+        }
+    }
+}
+
+impl MessageSubscription {
+    /// Next event for the subscribed account, [`MessageEvent::Resync`] if some were missed.
+    pub async fn recv(&mut self) -> Option<MessageEvent> {
+        match self.rx.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("{} missed {} message events", self.acc, skipped);
+                Some(MessageEvent::Resync)
+            }
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
+
+impl Drop for MessageSubscription {
+    /// Drops the account's channel along with its last stream.
+    fn drop(&mut self) {
+        let mut senders = self.senders.lock().unwrap_or_else(|err| err.into_inner());
+        // this subscription's receiver is still alive here, new ones need the lock
+        if senders
+            .get(&self.acc)
+            .is_some_and(|tx| tx.receiver_count() <= 1)
+        {
+            senders.remove(&self.acc);
+        }
+    }
+}
+
+/// Detects the image type from its first bytes, the client provided type isn't trusted.
+pub fn sniff_image(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+pub fn attachment_path(settings: &Settings, attachment_id: &str) -> PathBuf {
+    settings.media_dir.join(ATTACHMENT_DIR).join(attachment_id)
+}
+
+/// Usernames separated by spaces or commas, plus `acc` itself, sorted and deduplicated.
+pub fn parse_participants(acc: &str, participants: &str) -> Result<Vec<String>, ErrorMessage> {
+    let mut output = participants
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|username| username.trim().trim_start_matches('@'))
+        .filter(|username| !username.is_empty())
+        .map(String::from)
+        .chain([acc.to_string()])
+        .collect::<Vec<String>>();
+    output.sort();
+    output.dedup();
+    if !(2..=MAXIMUM_PARTICIPANTS).contains(&output.len()) {
+        return Err(ErrorMessage::Participants);
+    }
+    Ok(output)
+}
+
+fn others(conversation: &DbConversation, acc: &str) -> Vec<String> {
+    conversation
+        .participants
+        .iter()
+        .filter(|participant| *participant != acc)
+        .cloned()
+        .collect()
+}
+
+/// Conversation and the state of `acc` in it, as long as `acc` takes part.
+async fn get_member(
+    db: &Db,
+    conversation_id: &str,
+    acc: &str,
+) -> Result<(DbConversation, DbConversationMember), ErrorMessage> {
+    let conversation = conversation::get(db, conversation_id)
+        .await?
+        .ok_or(ErrorMessage::NotFound)?;
+    let member = conversation_member::get(db, conversation_id, acc)
+        .await?
+        .ok_or(ErrorMessage::NotFound)?;
+    Ok((conversation, member))
+}
+
+/// Finds or creates the conversation between `acc` and `participants`, then sends the first message.
+pub async fn start(
+    state: &ServerState,
+    acc: &str,
+    participants: &str,
+    body: &str,
+    time: i64,
+) -> Result<DbMessage, ErrorMessage> {
+    let participants = parse_participants(acc, participants)?;
+    let others = participants
+        .iter()
+        .filter(|participant| *participant != acc)
+        .cloned()
+        .collect::<Vec<String>>();
+    for username in &others {
+        if acc::get_by_username(&state.db, username).await?.is_none() {
+            return Err(ErrorMessage::UnknownUser(username.clone()));
+        }
+    }
+    if block::exists_between(&state.db, acc, &others).await? {
+        return Err(ErrorMessage::Blocked);
+    }
+
+    let conversation = match conversation::get_by_participants(&state.db, &participants).await? {
+        Some(conversation) => conversation,
+        None => {
+            let conversation = DbConversation {
+                conversation_id: new_id(),
+                participants: participants.clone(),
+                last_message_at: time,
+                modified_at: time,
+                created_at: time,
+            };
+            conversation::insert(&state.db, conversation.clone()).await?;
+            for participant in participants {
+                conversation_member::insert(
+                    &state.db,
+                    DbConversationMember {
+                        conversation_id: conversation.conversation_id.clone(),
+                        acc: participant,
+                        last_read_at: 0,
+                        muted: false,
+                        modified_at: time,
+                        created_at: time,
+                    },
+                )
+                .await?;
+            }
+            trace!(
+                "conversation {} started by {}",
+                conversation.conversation_id, acc
+            );
+            conversation
+        }
+    };
+
+    send(state, acc, &conversation.conversation_id, body, &[], time).await
+}
+
+pub async fn send(
+    state: &ServerState,
+    acc: &str,
+    conversation_id: &str,
+    body: &str,
+    attachments: &[String],
+    time: i64,
+) -> Result<DbMessage, ErrorMessage> {
+    let body = body.trim();
+    if body.chars().count() > MAXIMUM_MESSAGE_LENGTH || (body.is_empty() && attachments.is_empty())
+    {
+        return Err(ErrorMessage::Body);
+    }
+    if attachments.len() > MAXIMUM_ATTACHMENTS {
+        return Err(ErrorMessage::TooManyAttachments);
+    }
+
+    let (conversation, _) = get_member(&state.db, conversation_id, acc).await?;
+    if block::exists_between(&state.db, acc, &others(&conversation, acc)).await? {
+        return Err(ErrorMessage::Blocked);
+    }
+    for attachment_id in attachments {
+        let owned = attachment::get(&state.db, attachment_id)
+            .await?
+            .is_some_and(|attachment| {
+                attachment.acc == acc && attachment.conversation_id == conversation_id
+            });
+        if !owned {
+            return Err(ErrorMessage::AttachmentNotFound);
+        }
+    }
+
+    let message = DbMessage {
+        message_id: new_id(),
+        conversation_id: conversation_id.to_string(),
+        acc: acc.to_string(),
+        body: body.to_string(),
+        attachments: attachments.to_vec(),
+        modified_at: time,
+        created_at: time,
+    };
+    message::insert(&state.db, message.clone()).await?;
+    conversation::set_last_message_at(&state.db, conversation_id, time).await?;
+    conversation_member::set_last_read_at(&state.db, conversation_id, acc, time).await?;
+    trace!("message {} sent by {}", message.message_id, acc);
+
+    for member in conversation_member::get_all_for_conversation(&state.db, conversation_id).await? {
+        state.message_hub.publish(
+            &member.acc,
+            MessageEvent::Message {
+                message: MessageInfo::from(message.clone()),
+                muted: member.muted,
+            },
+        );
+    }
+
+    Ok(message)
+}
+
+/// Marks everything up to `time` as read and tells the other participants.
+pub async fn read(
+    state: &ServerState,
+    acc: &str,
+    conversation_id: &str,
+    time: i64,
+) -> Result<(), ErrorMessage> {
+    let (conversation, member) = get_member(&state.db, conversation_id, acc).await?;
+    if member.last_read_at >= conversation.last_message_at {
+        return Ok(());
+    }
+    conversation_member::set_last_read_at(&state.db, conversation_id, acc, time).await?;
+    for participant in &conversation.participants {
+        state.message_hub.publish(
+            participant,
+            MessageEvent::Read {
+                conversation_id: conversation_id.to_string(),
+                username: acc.to_string(),
+                read_at: time,
+            },
+        );
+    }
+    Ok(())
+}
+
+pub async fn mute(
+    db: &Db,
+    acc: &str,
+    conversation_id: &str,
+    muted: bool,
+    time: i64,
+) -> Result<(), ErrorMessage> {
+    get_member(db, conversation_id, acc).await?;
+    conversation_member::set_muted(db, conversation_id, acc, muted, time).await?;
+    Ok(())
+}
+
+/// Messages oldest first, up to [`MESSAGE_PAGE_SIZE`] of them created before `before`.
+pub async fn messages(
+    db: &Db,
+    acc: &str,
+    conversation_id: &str,
+    before: Option<i64>,
+) -> Result<Vec<DbMessage>, ErrorMessage> {
+    get_member(db, conversation_id, acc).await?;
+    let mut messages = message::get_page(db, conversation_id, before, MESSAGE_PAGE_SIZE).await?;
+    messages.reverse();
+    Ok(messages)
+}
+
+pub async fn conversations(db: &Db, acc: &str) -> Result<Vec<ConversationInfo>, ErrorMessage> {
+    let conversations = conversation::get_page_for_acc(db, acc, CONVERSATION_PAGE_SIZE).await?;
+    let mut output = Vec::with_capacity(conversations.len());
+    for conversation in conversations {
+        let members =
+            conversation_member::get_all_for_conversation(db, &conversation.conversation_id)
+                .await?;
+        let Some(member) = members.iter().find(|member| member.acc == acc) else {
+            continue;
+        };
+        let unread =
+            message::count_unread(db, &conversation.conversation_id, acc, member.last_read_at)
+                .await?;
+        let last_message = message::get_last(db, &conversation.conversation_id).await?;
+        output.push(ConversationInfo {
+            conversation_id: conversation.conversation_id,
+            muted: member.muted,
+            unread,
+            last_message: last_message.map(MessageInfo::from),
+            last_message_at: conversation.last_message_at,
+            participants: members
+                .into_iter()
+                .map(|member| ParticipantInfo {
+                    username: member.acc,
+                    last_read_at: member.last_read_at,
+                })
+                .collect(),
+        });
+    }
+    Ok(output)
+}
+
+/// Unread messages across conversations that aren't muted.
+pub async fn unread_total(db: &Db, acc: &str) -> Result<u32, ErrorMessage> {
+    let mut total = 0;
+    for member in conversation_member::get_all_for_acc(db, acc).await? {
+        if member.muted {
+            continue;
+        }
+        total +=
+            message::count_unread(db, &member.conversation_id, acc, member.last_read_at).await?;
+    }
+    Ok(total)
+}
+
+pub async fn block(db: &Db, acc: &str, username: &str, time: i64) -> Result<(), ErrorMessage> {
+    let username = username.trim();
+    if username == acc {
+        return Err(ErrorMessage::BlockSelf);
+    }
+    if acc::get_by_username(db, username).await?.is_none() {
+        return Err(ErrorMessage::UnknownUser(username.to_string()));
+    }
+    block::upsert(
+        db,
+        DbBlock {
+            acc: acc.to_string(),
+            blocked: username.to_string(),
+            modified_at: time,
+            created_at: time,
+        },
+    )
+    .await?;
+    trace!("{} blocked {}", acc, username);
+    Ok(())
+}
+
+pub async fn unblock(db: &Db, acc: &str, username: &str) -> Result<(), ErrorMessage> {
+    block::remove(db, acc, username.trim()).await?;
+    Ok(())
+}
+
+/// Stores an image for a message that `acc` is about to send to the conversation.
+pub async fn save_attachment(
+    state: &ServerState,
+    acc: &str,
+    conversation_id: &str,
+    data: &[u8],
+    time: i64,
+) -> Result<DbAttachment, ErrorMessage> {
+    get_member(&state.db, conversation_id, acc).await?;
+    if data.len() > MAXIMUM_ATTACHMENT_SIZE {
+        return Err(ErrorMessage::AttachmentType);
+    }
+    let mime = sniff_image(data).ok_or(ErrorMessage::AttachmentType)?;
+
+    let attachment = DbAttachment {
+        attachment_id: new_id(),
+        conversation_id: conversation_id.to_string(),
+        acc: acc.to_string(),
+        mime: mime.to_string(),
+        size: data.len() as u64,
+        modified_at: time,
+        created_at: time,
+    };
+    let path = attachment_path(&state.settings, &attachment.attachment_id);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&path, data).await?;
+    upload::record_bytes("attachment", data.len());
+    attachment::insert(&state.db, attachment.clone()).await?;
+    trace!(
+        "attachment {} uploaded by {}",
+        attachment.attachment_id, acc
+    );
+    Ok(attachment)
+}
+
+/// Attachment and its file, only for participants of its conversation.
+pub async fn open_attachment(
+    state: &ServerState,
+    acc: &str,
+    attachment_id: &str,
+) -> Result<(DbAttachment, PathBuf), ErrorMessage> {
+    let attachment = attachment::get(&state.db, attachment_id)
+        .await?
+        .ok_or(ErrorMessage::AttachmentNotFound)?;
+    get_member(&state.db, &attachment.conversation_id, acc)
+        .await
+        .map_err(|err| match err {
+            ErrorMessage::NotFound => ErrorMessage::AttachmentNotFound,
+            err => err,
+        })?;
+    let path = attachment_path(&state.settings, attachment_id);
+    Ok((attachment, path))
+}
+
+#[cfg(test)]
+mod message_tests {
+    use crate::{
+        api::MessageEvent,
+        server::{auth::register, test_support::test_state},
+    };
+
+    use super::{
+        ErrorMessage, MessageHub, block, conversations, open_attachment, read, save_attachment,
+        send, start, unread_total,
+    };
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+
+    #[tokio::test]
+    async fn conversation_unread_and_receipts() {
+        let (state, _mailer) = test_state().await;
+        for (username, email) in [("hey", "hey@example.com"), ("fox", "fox@example.com")] {
+            register(&state, username, email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let mut fox_events = state.message_hub.subscribe("fox");
+
+        let first = start(&state, "hey", "@fox", "hello", 10).await.unwrap();
+        let second = start(&state, "hey", "fox hey", "again", 20).await.unwrap();
+        assert_eq!(first.conversation_id, second.conversation_id);
+        assert!(matches!(
+            start(&state, "hey", "nobody", "hi", 30).await,
+            Err(ErrorMessage::UnknownUser(_))
+        ));
+        let Some(MessageEvent::Message { message, muted }) = fox_events.recv().await else {
+            panic!("expected a message event");
+        };
+        assert_eq!((message.body.as_str(), muted), ("hello", false));
+
+        assert_eq!(unread_total(&state.db, "fox").await.unwrap(), 2);
+        assert_eq!(unread_total(&state.db, "hey").await.unwrap(), 0);
+        read(&state, "fox", &first.conversation_id, 40)
+            .await
+            .unwrap();
+        assert_eq!(unread_total(&state.db, "fox").await.unwrap(), 0);
+        let listed = conversations(&state.db, "hey").await.unwrap();
+        let fox = listed[0]
+            .participants
+            .iter()
+            .find(|participant| participant.username == "fox")
+            .unwrap();
+        assert_eq!(fox.last_read_at, 40);
+        assert_eq!(listed[0].last_message.as_ref().unwrap().body, "again");
+
+        block(&state.db, "fox", "hey", 50).await.unwrap();
+        assert!(matches!(
+            send(&state, "hey", &first.conversation_id, "hi?", &[], 60).await,
+            Err(ErrorMessage::Blocked)
+        ));
+    }
+
+    #[tokio::test]
+    async fn attachments_are_images_for_participants() {
+        let (state, _mailer) = test_state().await;
+        for (username, email) in [
+            ("hey", "hey@example.com"),
+            ("fox", "fox@example.com"),
+            ("owl", "owl@example.com"),
+        ] {
+            register(&state, username, email, "password123", 0)
+                .await
+                .unwrap();
+        }
+        let first = start(&state, "hey", "fox", "hello", 10).await.unwrap();
+        let conversation_id = first.conversation_id;
+
+        assert!(matches!(
+            save_attachment(&state, "hey", &conversation_id, b"not an image", 20).await,
+            Err(ErrorMessage::AttachmentType)
+        ));
+        let attachment = save_attachment(&state, "hey", &conversation_id, PNG, 20)
+            .await
+            .unwrap();
+        assert_eq!(attachment.mime, "image/png");
+        assert!(matches!(
+            send(
+                &state,
+                "fox",
+                &conversation_id,
+                "",
+                std::slice::from_ref(&attachment.attachment_id),
+                30
+            )
+            .await,
+            Err(ErrorMessage::AttachmentNotFound)
+        ));
+        send(
+            &state,
+            "hey",
+            &conversation_id,
+            "",
+            std::slice::from_ref(&attachment.attachment_id),
+            30,
+        )
+        .await
+        .unwrap();
+
+        let (_, path) = open_attachment(&state, "fox", &attachment.attachment_id)
+            .await
+            .unwrap();
+        assert_eq!(tokio::fs::read(path).await.unwrap(), PNG);
+        assert!(matches!(
+            open_attachment(&state, "owl", &attachment.attachment_id).await,
+            Err(ErrorMessage::AttachmentNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn hub_channels_are_per_recipient_and_pruned() {
+        let hub = MessageHub::new();
+        let mut hey = hub.subscribe("hey");
+        let second_hey = hub.subscribe("hey");
+        let fox = hub.subscribe("fox");
+        hub.publish("hey", MessageEvent::Resync);
+        hub.publish("nobody", MessageEvent::Resync);
+        assert!(matches!(hey.recv().await, Some(MessageEvent::Resync)));
+        assert!(fox.rx.is_empty());
+
+        drop(second_hey);
+        assert_eq!(hub.senders.lock().unwrap().len(), 2);
+        drop(hey);
+        drop(fox);
+        assert!(hub.senders.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+use thiserror::Error;
+
+use crate::{
+    api::{ARTWORK_PAGE_SIZE, ArtworkInfo, COLOR_MATCH_DISTANCE, COLOR_SEARCH_SCAN},
+    db::{Db, DbError, acc::DbAcc, artwork},
+};
+
+use super::{ServerError, artwork::artwork_info, rating};
+
+pub const MINIMUM_PALETTE_SIZE: usize = 5;
+pub const MAXIMUM_PALETTE_SIZE: usize = 8;
+/// Clusters smaller than this share of the pixels are dropped once the minimum size is met.
+const MINIMUM_SHARE: f32 = 0.02;
+const ITERATIONS: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum ErrorPalette {
+    #[error("color must look like #a1b2c3")]
+    Color,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorPalette {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorPalette::Db(_))
+    }
+}
+
+/// CIELAB color under the D65 white point.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Lab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl Lab {
+    pub fn from_rgb(rgb: [u8; 3]) -> Self {
+        let linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let [r, g, b] = rgb.map(linear);
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let f = |t: f32| {
+            if t > 0.008856 {
+                t.cbrt()
+            } else {
+                7.787 * t + 16.0 / 116.0
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        Self {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    pub fn to_rgb(self) -> [u8; 3] {
+        let fy = (self.l + 16.0) / 116.0;
+        let fx = fy + self.a / 500.0;
+        let fz = fy - self.b / 200.0;
+        let f = |t: f32| {
+            if t.powi(3) > 0.008856 {
+                t.powi(3)
+            } else {
+                (t - 16.0 / 116.0) / 7.787
+            }
+        };
+        let (x, y, z) = (f(fx) * 0.95047, f(fy), f(fz) * 1.08883);
+        let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+        let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+        let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+        let gamma = |c: f32| {
+            let c = if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        [gamma(r), gamma(g), gamma(b)]
+    }
+
+    /// CIE76 delta E, around 2.3 is the smallest difference people notice.
+    pub fn distance(&self, other: &Lab) -> f32 {
+        ((self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2))
+            .sqrt()
+    }
+}
+
+pub fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+pub fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+/// K-means in Lab space, returns up to [`MAXIMUM_PALETTE_SIZE`] hex colors, most common first.
+/// Centers start at luminance quantiles so the same pixels always give the same palette.
+pub fn extract(pixels: &[[u8; 3]]) -> Vec<String> {
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+    let mut points = pixels
+        .iter()
+        .map(|rgb| Lab::from_rgb(*rgb))
+        .collect::<Vec<_>>();
+    points.sort_by(|a, b| a.l.total_cmp(&b.l));
+    let k = MAXIMUM_PALETTE_SIZE.min(points.len());
+    let mut centers = (0..k)
+        .map(|i| points[(i * 2 + 1) * points.len() / (k * 2)])
+        .collect::<Vec<_>>();
+    let mut counts = vec![0_usize; k];
+
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![(0.0_f32, 0.0_f32, 0.0_f32); k];
+        counts.iter_mut().for_each(|count| *count = 0);
+        for point in &points {
+            let nearest = nearest(&centers, point);
+            let sum = &mut sums[nearest];
+            sum.0 += point.l;
+            sum.1 += point.a;
+            sum.2 += point.b;
+            counts[nearest] += 1;
+        }
+        let mut moved = false;
+        for ((center, sum), count) in centers.iter_mut().zip(&sums).zip(&counts) {
+            if *count == 0 {
+                continue;
+            }
+            let n = *count as f32;
+            let next = Lab {
+                l: sum.0 / n,
+                a: sum.1 / n,
+                b: sum.2 / n,
+            };
+            moved |= next.distance(center) > 0.5;
+            *center = next;
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    let mut clusters = centers.into_iter().zip(counts).collect::<Vec<_>>();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.1));
+    let total = points.len() as f32;
+    let mut palette = Vec::<String>::new();
+    for (i, (center, count)) in clusters.into_iter().enumerate() {
+        if count == 0 || (i >= MINIMUM_PALETTE_SIZE && (count as f32 / total) < MINIMUM_SHARE) {
+            continue;
+        }
+        let hex = to_hex(center.to_rgb());
+        if !palette.contains(&hex) {
+            palette.push(hex);
+        }
+    }
+    palette
+}
+
+fn nearest(centers: &[Lab], point: &Lab) -> usize {
+    centers
+        .iter()
+        .enumerate()
+        .map(|(i, center)| (i, center.distance(point)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Closest palette color of an artwork to `target`.
+pub fn palette_distance(palette: &[String], target: &Lab) -> Option<f32> {
+    palette
+        .iter()
+        .filter_map(|hex| parse_hex(hex))
+        .map(|rgb| Lab::from_rgb(rgb).distance(target))
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+/// Artworks with a palette color within [`COLOR_MATCH_DISTANCE`] of `color`, closest first.
+/// Only the newest [`COLOR_SEARCH_SCAN`] visible artworks are considered.
+pub async fn search(
+    db: &Db,
+    viewer: Option<&DbAcc>,
+    color: &str,
+    page: u32,
+) -> Result<Vec<ArtworkInfo>, ErrorPalette> {
+    let target = Lab::from_rgb(parse_hex(color).ok_or(ErrorPalette::Color)?);
+    let prefs = rating::prefs_for(viewer);
+    let artworks =
+        artwork::get_with_palette(db, &rating::visible_ratings(&prefs), COLOR_SEARCH_SCAN).await?;
+    let mut matches = artworks
+        .into_iter()
+        .filter_map(|artwork| {
+            let distance = palette_distance(&artwork.palette, &target)?;
+            (distance <= COLOR_MATCH_DISTANCE).then_some((distance, artwork))
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(matches
+        .into_iter()
+        .skip((page * ARTWORK_PAGE_SIZE) as usize)
+        .take(ARTWORK_PAGE_SIZE as usize)
+        .map(|(_, artwork)| artwork_info(artwork, &prefs))
+        .collect())
+}
+
+#[cfg(test)]
+mod palette_tests {
+    use crate::{
+        db::artwork,
+        server::{artwork::create, test_support::test_state},
+    };
+
+    use super::{Lab, extract, parse_hex, search, to_hex};
+
+    #[test]
+    fn lab_roundtrip_and_palette() {
+        for rgb in [[0, 0, 0], [255, 255, 255], [200, 30, 40], [12, 140, 250]] {
+            assert_eq!(Lab::from_rgb(rgb).to_rgb(), rgb);
+        }
+        assert_eq!(parse_hex("#C81E28"), Some([200, 30, 40]));
+        assert_eq!(parse_hex("c81e28"), None);
+        assert_eq!(to_hex([200, 30, 40]), "#c81e28");
+
+        let mut pixels = vec![[200, 30, 40]; 600];
+        pixels.extend(vec![[12, 140, 250]; 300]);
+        pixels.extend(vec![[250, 250, 250]; 100]);
+        let palette = extract(&pixels);
+        assert!(palette.len() <= 8);
+        assert_eq!(palette[0], "#c81e28");
+        assert!(palette.contains(&String::from("#0c8cfa")));
+        assert!(palette.contains(&String::from("#fafafa")));
+        assert!(extract(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn artworks_are_found_by_nearby_color() {
+        let (state, _mailer) = test_state().await;
+        let db = &state.db;
+        for (title, palette) in [
+            ("Red", vec!["#c81e28", "#ffffff"]),
+            ("Crimson", vec!["#b4142d"]),
+            ("Sky", vec!["#0c8cfa"]),
+        ] {
+            let created = create(db, "hey", title, "", &[], "sfw", "file", 10, 10, 0)
+                .await
+                .unwrap();
+            let palette = palette.into_iter().map(String::from).collect();
+            artwork::set_palette(db, &created.artwork_id, palette, 0)
+                .await
+                .unwrap();
+        }
+        create(db, "hey", "Unrendered", "", &[], "sfw", "file", 10, 10, 0)
+            .await
+            .unwrap();
+
+        let found = search(db, None, "#c81e28", 0).await.unwrap();
+        let titles = found
+            .iter()
+            .map(|artwork| artwork.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["Red", "Crimson"]);
+        assert_eq!(found[0].palette[0], "#c81e28");
+        assert!(search(db, None, "red", 0).await.is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    api::{
+        ADMIN_ROLE, CONTENT_BLUR, CONTENT_HIDE, CONTENT_PREFS, CONTENT_SHOW, DEFAULT_EXPLICIT_PREF,
+        DEFAULT_MATURE_PREF, MAXIMUM_RATING_REASON_LENGTH, RATING_AUDIT_PAGE_SIZE, RATING_EXPLICIT,
+        RATING_MATURE, RATINGS,
+    },
+    db::{
+        Db, DbError,
+        acc::{self, DbAcc, DbAccContentPrefs},
+        artwork::{self, DbArtwork},
+        bounty::{self, DbBounty},
+        rating_audit::{self, DbRatingAudit, KIND_ARTWORK, KIND_BOUNTY},
+    },
+};
+
+use super::{
+    ServerError,
+    artwork::{ErrorArtwork, validate_rating},
+    new_id,
+};
+
+#[derive(Error, Debug)]
+pub enum ErrorRating {
+    #[error(transparent)]
+    Info(#[from] ErrorArtwork),
+
+    #[error("unknown content preference")]
+    Pref,
+
+    #[error("moderators must give a reason of 1-{MAXIMUM_RATING_REASON_LENGTH} characters")]
+    Reason,
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("not allowed")]
+    Forbidden,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorRating {
+    fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ErrorRating::Db(_) | ErrorRating::Info(ErrorArtwork::Db(_))
+        )
+    }
+}
+
+pub fn default_prefs() -> DbAccContentPrefs {
+    DbAccContentPrefs {
+        mature: DEFAULT_MATURE_PREF.to_string(),
+        explicit: DEFAULT_EXPLICIT_PREF.to_string(),
+    }
+}
+
+/// Preferences of the viewer, logged out visitors get the defaults.
+pub fn prefs_for(acc: Option<&DbAcc>) -> DbAccContentPrefs {
+    acc.and_then(|acc| acc.content_prefs.clone())
+        .unwrap_or_else(default_prefs)
+}
+
+/// Whether content of `rating` is hidden, blurred or shown, sfw is always shown.
+pub fn display_for(prefs: &DbAccContentPrefs, rating: &str) -> &'static str {
+    let pref = match rating {
+        RATING_MATURE => prefs.mature.as_str(),
+        RATING_EXPLICIT => prefs.explicit.as_str(),
+        _ => CONTENT_SHOW,
+    };
+    match pref {
+        CONTENT_SHOW => CONTENT_SHOW,
+        CONTENT_BLUR => CONTENT_BLUR,
+        _ => CONTENT_HIDE,
+    }
+}
+
+/// Ratings listing queries should return for the viewer, every one that isn't hidden.
+pub fn visible_ratings(prefs: &DbAccContentPrefs) -> Vec<String> {
+    RATINGS
+        .iter()
+        .map(|(rating, _)| *rating)
+        .filter(|rating| display_for(prefs, rating) != CONTENT_HIDE)
+        .map(String::from)
+        .collect()
+}
+
+/// Every rating, for listings of the viewer's own work.
+pub fn all_ratings() -> Vec<String> {
+    RATINGS
+        .iter()
+        .map(|(rating, _)| rating.to_string())
+        .collect()
+}
+
+pub async fn set_prefs(
+    db: &Db,
+    username: &str,
+    mature: &str,
+    explicit: &str,
+    time: i64,
+) -> Result<DbAccContentPrefs, ErrorRating> {
+    let valid = |pref: &str| CONTENT_PREFS.iter().any(|(name, _)| *name == pref);
+    if !valid(mature) || !valid(explicit) {
+        return Err(ErrorRating::Pref);
+    }
+    let prefs = DbAccContentPrefs {
+        mature: mature.to_string(),
+        explicit: explicit.to_string(),
+    };
+    acc::set_content_prefs(db, username, prefs.clone(), time).await?;
+    trace!("content prefs of {} changed", username);
+    Ok(prefs)
+}
+
+/// Authors rate their own work freely, changes by admins on someone else's work are audited.
+#[allow(clippy::too_many_arguments)]
+async fn check_moderation(
+    db: &Db,
+    acc: &DbAcc,
+    author: &str,
+    kind: &str,
+    target_id: &str,
+    from: &str,
+    to: &str,
+    reason: &str,
+    time: i64,
+) -> Result<(), ErrorRating> {
+    if acc.username == author {
+        return Ok(());
+    }
+    if acc.role != ADMIN_ROLE {
+        return Err(ErrorRating::Forbidden);
+    }
+    let reason = reason.trim();
+    if !(1..=MAXIMUM_RATING_REASON_LENGTH).contains(&reason.chars().count()) {
+        return Err(ErrorRating::Reason);
+    }
+    rating_audit::insert(
+        db,
+        DbRatingAudit {
+            audit_id: new_id(),
+            kind: kind.to_string(),
+            target_id: target_id.to_string(),
+            moderator: acc.username.clone(),
+            from: from.to_string(),
+            to: to.to_string(),
+            reason: reason.to_string(),
+            created_at: time,
+        },
+    )
+    .await?;
+    trace!("{} rated {} {} as {}", acc.username, kind, target_id, to);
+    Ok(())
+}
+
+pub async fn set_artwork_rating(
+    db: &Db,
+    acc: &DbAcc,
+    artwork_id: &str,
+    rating: &str,
+    reason: &str,
+    time: i64,
+) -> Result<DbArtwork, ErrorRating> {
+    validate_rating(rating)?;
+    let artwork = artwork::get(db, artwork_id)
+        .await?
+        .ok_or(ErrorRating::NotFound)?;
+    check_moderation(
+        db,
+        acc,
+        &artwork.acc,
+        KIND_ARTWORK,
+        artwork_id,
+        &artwork.rating,
+        rating,
+        reason,
+        time,
+    )
+    .await?;
+    artwork::set_rating(db, artwork_id, rating, time)
+        .await?
+        .ok_or(ErrorRating::NotFound)
+}
+
+pub async fn set_bounty_rating(
+    db: &Db,
+    acc: &DbAcc,
+    bounty_id: &str,
+    rating: &str,
+    reason: &str,
+    time: i64,
+) -> Result<DbBounty, ErrorRating> {
+    validate_rating(rating)?;
+    let bounty = bounty::get(db, bounty_id)
+        .await?
+        .ok_or(ErrorRating::NotFound)?;
+    check_moderation(
+        db,
+        acc,
+        &bounty.acc,
+        KIND_BOUNTY,
+        bounty_id,
+        &bounty.rating,
+        rating,
+        reason,
+        time,
+    )
+    .await?;
+    bounty::set_rating(db, bounty_id, rating, time)
+        .await?
+        .ok_or(ErrorRating::NotFound)
+}
+
+pub async fn audits(db: &Db, page: u32) -> Result<Vec<DbRatingAudit>, ErrorRating> {
+    Ok(rating_audit::get_page(db, RATING_AUDIT_PAGE_SIZE, page * RATING_AUDIT_PAGE_SIZE).await?)
+}
+
+#[cfg(test)]
+mod rating_tests {
+    use crate::{
+        api::{ADMIN_ROLE, CONTENT_BLUR, CONTENT_HIDE, CONTENT_SHOW},
+        db::{
+            acc,
+            artwork::{self, get_page},
+            bounty,
+        },
+        server::{
+            artwork::create, auth::register, bounty::create as create_bounty,
+            test_support::test_state,
+        },
+    };
+
+    use super::{
+        ErrorRating, audits, display_for, prefs_for, set_artwork_rating, set_bounty_rating,
+        set_prefs, visible_ratings,
+    };
+
+    #[tokio::test]
+    async fn listings_follow_prefs_and_moderation_is_audited() {
+        let (state, _mailer) = test_state().await;
+        let db = &state.db;
+        register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+        register(&state, "mod", "mod@example.com", "password123", 0)
+            .await
+            .unwrap();
+        acc::set_role(db, "mod", ADMIN_ROLE, 0).await.unwrap();
+        for (title, rating) in [("Cat", "sfw"), ("Fox", "mature"), ("Owl", "explicit")] {
+            create(db, "hey", title, "", &[], rating, "file", 10, 10, 0)
+                .await
+                .unwrap();
+        }
+        assert!(
+            create(db, "hey", "Bat", "", &[], "spicy", "file", 10, 10, 0)
+                .await
+                .is_err()
+        );
+
+        let logged_out = prefs_for(None);
+        assert_eq!(display_for(&logged_out, "sfw"), CONTENT_SHOW);
+        assert_eq!(display_for(&logged_out, "mature"), CONTENT_BLUR);
+        assert_eq!(display_for(&logged_out, "explicit"), CONTENT_HIDE);
+        let titles = |artworks: Vec<artwork::DbArtwork>| {
+            let mut titles = artworks
+                .into_iter()
+                .map(|artwork| artwork.title)
+                .collect::<Vec<_>>();
+            titles.sort();
+            titles
+        };
+        let listed = get_page(db, &visible_ratings(&logged_out), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(titles(listed), ["Cat", "Fox"]);
+
+        assert!(matches!(
+            set_prefs(db, "hey", "show", "peek", 1).await,
+            Err(ErrorRating::Pref)
+        ));
+        set_prefs(db, "hey", "hide", "show", 1).await.unwrap();
+        let hey = acc::get_by_username(db, "hey").await.unwrap().unwrap();
+        let listed = get_page(db, &visible_ratings(&prefs_for(Some(&hey))), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(titles(listed), ["Cat", "Owl"]);
+
+        let cat = artwork::search(db, "cat", &["sfw".to_string()], 10, 0)
+            .await
+            .unwrap()
+            .remove(0);
+        set_artwork_rating(db, &hey, &cat.artwork_id, "mature", "", 2)
+            .await
+            .unwrap();
+        assert!(audits(db, 0).await.unwrap().is_empty());
+
+        let moderator = acc::get_by_username(db, "mod").await.unwrap().unwrap();
+        assert!(matches!(
+            set_artwork_rating(db, &moderator, &cat.artwork_id, "explicit", " ", 3).await,
+            Err(ErrorRating::Reason)
+        ));
+        let rated = set_artwork_rating(db, &moderator, &cat.artwork_id, "explicit", "nsfw", 3)
+            .await
+            .unwrap();
+        assert_eq!(rated.rating, "explicit");
+
+        let bounty = create_bounty(db, "mod", "Draw a cat", "", &[], 100, "sfw", 4)
+            .await
+            .unwrap();
+        assert!(matches!(
+            set_bounty_rating(db, &hey, &bounty.bounty_id, "mature", "", 4).await,
+            Err(ErrorRating::Forbidden)
+        ));
+        let listed = bounty::get_page(db, None, &visible_ratings(&prefs_for(Some(&hey))), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+
+        let audits = audits(db, 0).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].moderator, "mod");
+        assert_eq!(
+            (audits[0].from.as_str(), audits[0].to.as_str()),
+            ("mature", "explicit")
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use crate::{
+    db,
+    server::{ServerState, Settings, email::MemoryMailer, new_id},
+};
+
+/// State on an in-memory db with a cheap password cost, shared by the tests of both crates.
+pub async fn test_state() -> (ServerState, Arc<MemoryMailer>) {
+    let db = db::connect("mem://", None).await.unwrap();
+    let mailer = Arc::new(MemoryMailer::new());
+    let settings = Settings {
+        site_url: String::from("http://localhost:3000"),
+        pepper: b"pepper".to_vec(),
+        token_secret: b"secret".to_vec(),
+        password_cost: 4,
+        media_dir: std::env::temp_dir().join(new_id()),
+    };
+    (ServerState::new(db, mailer.clone(), settings), mailer)
+}
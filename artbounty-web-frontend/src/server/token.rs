@@ -0,0 +1,192 @@
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    VerifyEmail,
+    ResetPassword,
+    LoginTotp,
+    DiscordLink,
+}
+
+/// Payload of a signed token.
+///
+/// `extra` binds the token to some state of the account, so it stops working once that state changes:
+/// the email address for verification and a fingerprint of the password hash for resets and login challenges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub subject: String,
+    pub expires_at: i64,
+    pub extra: String,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ErrorToken {
+    #[error("malformed token")]
+    Malformed,
+
+    #[error("invalid token signature")]
+    Signature,
+
+    #[error("token is meant for something else")]
+    Kind,
+
+    #[error("token expired")]
+    Expired,
+}
+
+impl TokenKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenKind::VerifyEmail => "verify_email",
+            TokenKind::ResetPassword => "reset_password",
+            TokenKind::LoginTotp => "login_totp",
+            TokenKind::DiscordLink => "discord_link",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "verify_email" => Some(TokenKind::VerifyEmail),
+            "reset_password" => Some(TokenKind::ResetPassword),
+            "login_totp" => Some(TokenKind::LoginTotp),
+            "discord_link" => Some(TokenKind::DiscordLink),
+            _ => None,
+        }
+    }
+}
+
+impl Token {
+    pub fn new(
+        kind: TokenKind,
+        subject: impl Into<String>,
+        expires_at: i64,
+        extra: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            subject: subject.into(),
+            expires_at,
+            extra: extra.into(),
+        }
+    }
+
+    /// Encodes as `base64url(payload).base64url(hmac_sha256(payload))`.
+    pub fn sign(&self, secret: &[u8]) -> String {
+        let payload = format!(
+            "{}|{}|{}|{}",
+            self.kind.as_str(),
+            self.subject,
+            self.expires_at,
+            self.extra
+        );
+        let signature = mac(secret, payload.as_bytes()).finalize().into_bytes();
+
+        format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(payload),
+            BASE64_URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    pub fn verify(
+        secret: &[u8],
+        kind: TokenKind,
+        token: &str,
+        time: i64,
+    ) -> Result<Self, ErrorToken> {
+        let (payload, signature) = token.split_once('.').ok_or(ErrorToken::Malformed)?;
+        let payload = BASE64_URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| ErrorToken::Malformed)?;
+        let signature = BASE64_URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| ErrorToken::Malformed)?;
+
+        mac(secret, &payload)
+            .verify_slice(&signature)
+            .map_err(|_| ErrorToken::Signature)?;
+
+        let payload = String::from_utf8(payload).map_err(|_| ErrorToken::Malformed)?;
+        let mut parts = payload.splitn(4, '|');
+        let (Some(token_kind), Some(subject), Some(expires_at), Some(extra)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ErrorToken::Malformed);
+        };
+        let token_kind = TokenKind::parse(token_kind).ok_or(ErrorToken::Malformed)?;
+        let expires_at = expires_at
+            .parse::<i64>()
+            .map_err(|_| ErrorToken::Malformed)?;
+
+        if token_kind != kind {
+            return Err(ErrorToken::Kind);
+        }
+        if expires_at <= time {
+            return Err(ErrorToken::Expired);
+        }
+
+        Ok(Self::new(token_kind, subject, expires_at, extra))
+    }
+}
+
+/// Short stable digest used as `extra`, e.g. of a password hash, without putting the hash itself into a link.
+pub fn fingerprint(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .take(12)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn mac(secret: &[u8], payload: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("hmac accepts keys of any size");
+    mac.update(payload);
+    mac
+}
+
+#[cfg(test)]
+mod token_tests {
+    use super::{ErrorToken, Token, TokenKind};
+
+    const SECRET: &[u8] = b"test";
+
+    #[test]
+    fn token_roundtrip_and_rejects() {
+        let token = Token::new(TokenKind::VerifyEmail, "hey", 1000, "hey@example.com|x");
+        let signed = token.sign(SECRET);
+
+        assert_eq!(
+            Token::verify(SECRET, TokenKind::VerifyEmail, &signed, 999),
+            Ok(token)
+        );
+        assert_eq!(
+            Token::verify(SECRET, TokenKind::VerifyEmail, &signed, 1000),
+            Err(ErrorToken::Expired)
+        );
+        assert_eq!(
+            Token::verify(SECRET, TokenKind::ResetPassword, &signed, 0),
+            Err(ErrorToken::Kind)
+        );
+        assert_eq!(
+            Token::verify(b"other", TokenKind::VerifyEmail, &signed, 0),
+            Err(ErrorToken::Signature)
+        );
+        assert_eq!(
+            Token::verify(SECRET, TokenKind::VerifyEmail, "nope", 0),
+            Err(ErrorToken::Malformed)
+        );
+
+        let (payload, _) = signed.split_once('.').unwrap();
+        let forged = format!("{}.{}", payload, "AAAA");
+        assert_eq!(
+            Token::verify(SECRET, TokenKind::VerifyEmail, &forged, 0),
+            Err(ErrorToken::Signature)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use hmac::{Hmac, Mac};
+use qrcode::{QrCode, render::svg};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+type HmacSha1 = Hmac<Sha1>;
+
+pub const ISSUER: &str = "ArtBounty";
+pub const DIGITS: u32 = 6;
+pub const STEP_MS: i64 = 30_000;
+/// How many steps before and after the current one are still accepted, to tolerate clock drift.
+pub const SKEW: i64 = 1;
+pub const SECRET_LEN: usize = 20;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const RECOVERY_CODE_ALPHABET: &[u8; 32] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+pub fn new_secret() -> String {
+    let mut bytes = [0_u8; SECRET_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    base32_encode(&bytes)
+}
+
+/// RFC 4648 base32 without padding, the format authenticator apps expect.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    output
+}
+
+pub fn base32_decode(value: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(value.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in value.bytes().filter(|c| *c != b'=') {
+        let index = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | index as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+pub fn step(time: i64) -> i64 {
+    time.div_euclid(STEP_MS)
+}
+
+/// RFC 6238 code with HMAC-SHA1, the default every authenticator app supports.
+pub fn code_at_step(secret: &[u8], step: i64) -> u32 {
+    let mut mac = HmacSha1::new_from_slice(secret).expect("hmac accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10_u32.pow(DIGITS)
+}
+
+/// Returns the matched step, so the caller can refuse to accept it again.
+pub fn verify(secret: &str, code: &str, time: i64, last_step: Option<i64>) -> Option<i64> {
+    let secret = base32_decode(secret)?;
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let current = step(time);
+
+    (current - SKEW..=current + SKEW)
+        .filter(|step| last_step.is_none_or(|last_step| *step > last_step))
+        .find(|step| code_at_step(&secret, *step) == code)
+}
+
+/// `otpauth://` uri that authenticator apps read from the enrollment qr code.
+pub fn provisioning_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={period}",
+        issuer = ISSUER,
+        account = encode_uri_component(account),
+        period = STEP_MS / 1000,
+    )
+}
+
+pub fn qr_svg(data: &str) -> Option<String> {
+    let code = QrCode::new(data.as_bytes()).ok()?;
+    let image = code
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .dark_color(svg::Color("#030712"))
+        .light_color(svg::Color("#e5e7eb"))
+        .build();
+    Some(image)
+}
+
+/// Plain recovery codes, shown to the user once. Only their hashes are stored.
+pub fn new_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0_u8; 10];
+            rand::rngs::OsRng.fill_bytes(&mut bytes);
+            let code = bytes
+                .iter()
+                .map(|byte| RECOVERY_CODE_ALPHABET[(byte & 0x1f) as usize] as char)
+                .collect::<String>();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    let code = code.trim().to_lowercase().replace('-', "");
+    Sha256::digest(code.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod totp_tests {
+    use super::{
+        base32_decode, base32_encode, code_at_step, hash_recovery_code, new_recovery_codes, step,
+        verify,
+    };
+
+    #[test]
+    fn rfc6238_vectors() {
+        let secret = b"12345678901234567890";
+        // 8 digit reference values from RFC 6238 appendix B, truncated to 6 digits.
+        assert_eq!(code_at_step(secret, step(59_000)), 287082);
+        assert_eq!(code_at_step(secret, step(1_111_111_109_000)), 81804);
+        assert_eq!(code_at_step(secret, step(1_234_567_890_000)), 5924);
+        assert_eq!(code_at_step(secret, step(20_000_000_000_000)), 353130);
+    }
+
+    #[test]
+    fn verify_with_skew_and_replay() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode(&secret).unwrap(), b"12345678901234567890");
+
+        let time = 59_000;
+        assert_eq!(verify(&secret, "287082", time, None), Some(1));
+        assert_eq!(verify(&secret, "287 082", time + 30_000, None), Some(1));
+        assert_eq!(verify(&secret, "287082", time + 60_000, None), None);
+        assert_eq!(verify(&secret, "287082", time, Some(1)), None);
+        assert_eq!(verify(&secret, "28708", time, None), None);
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_normalized() {
+        let codes = new_recovery_codes();
+        assert_eq!(codes.len(), 10);
+        assert_eq!(codes[0].len(), 11);
+        assert_ne!(codes[0], codes[1]);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&codes[0].to_uppercase().replace('-', ""))
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use tracing::trace;
+
+use crate::db::acc::{self, DbAcc, DbAccTotp};
+
+use super::{ServerState, auth::ErrorAuth, totp};
+
+pub struct Enrollment {
+    pub secret: String,
+    pub uri: String,
+    pub qr_svg: String,
+}
+
+/// Stores a fresh pending secret, replacing any previous unfinished enrollment.
+pub async fn begin(state: &ServerState, acc: &DbAcc, time: i64) -> Result<Enrollment, ErrorAuth> {
+    if acc.totp.as_ref().is_some_and(|totp| totp.enabled) {
+        return Err(ErrorAuth::TotpEnabled);
+    }
+
+    let secret = totp::new_secret();
+    acc::set_totp(
+        &state.db,
+        &acc.username,
+        Some(DbAccTotp {
+            secret: secret.clone(),
+            enabled: false,
+            last_step: None,
+            recovery_codes: Vec::new(),
+            modified_at: time,
+            created_at: time,
+        }),
+        time,
+    )
+    .await?;
+
+    let uri = totp::provisioning_uri(&secret, &acc.username);
+    let qr_svg = totp::qr_svg(&uri).unwrap_or_default();
+    trace!("2fa enrollment started for {}", acc.username);
+
+    Ok(Enrollment {
+        secret,
+        uri,
+        qr_svg,
+    })
+}
+
+/// Confirms the pending secret with a first code and returns the plain recovery codes.
+pub async fn enable(
+    state: &ServerState,
+    acc: &DbAcc,
+    code: &str,
+    time: i64,
+) -> Result<Vec<String>, ErrorAuth> {
+    let Some(mut totp) = acc.totp.clone() else {
+        return Err(ErrorAuth::TotpNotEnrolled);
+    };
+    if totp.enabled {
+        return Err(ErrorAuth::TotpEnabled);
+    }
+    let step = totp::verify(&totp.secret, code, time, None).ok_or(ErrorAuth::SecondFactor)?;
+
+    let recovery_codes = totp::new_recovery_codes();
+    totp.enabled = true;
+    totp.last_step = Some(step);
+    totp.recovery_codes = recovery_codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect();
+    totp.modified_at = time;
+    acc::set_totp(&state.db, &acc.username, Some(totp), time).await?;
+    trace!("2fa enabled for {}", acc.username);
+
+    Ok(recovery_codes)
+}
+
+pub async fn disable(
+    state: &ServerState,
+    acc: DbAcc,
+    code: &str,
+    time: i64,
+) -> Result<(), ErrorAuth> {
+    let acc = check(state, acc, code, time).await?;
+    acc::set_totp(&state.db, &acc.username, None, time).await?;
+    trace!("2fa disabled for {}", acc.username);
+    Ok(())
+}
+
+/// Replaces all recovery codes, the old ones stop working.
+pub async fn regenerate_recovery_codes(
+    state: &ServerState,
+    acc: DbAcc,
+    code: &str,
+    time: i64,
+) -> Result<Vec<String>, ErrorAuth> {
+    let acc = check(state, acc, code, time).await?;
+    let Some(mut totp) = acc.totp else {
+        return Err(ErrorAuth::TotpNotEnrolled);
+    };
+
+    let recovery_codes = totp::new_recovery_codes();
+    totp.recovery_codes = recovery_codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect();
+    totp.modified_at = time;
+    acc::set_totp(&state.db, &acc.username, Some(totp), time).await?;
+
+    Ok(recovery_codes)
+}
+
+/// Accepts a totp code newer than the last accepted one, or consumes a recovery code. Both are
+/// conditional updates, so a code is accepted at most once even by racing requests.
+pub async fn check(
+    state: &ServerState,
+    acc: DbAcc,
+    code: &str,
+    time: i64,
+) -> Result<DbAcc, ErrorAuth> {
+    let Some(totp) = acc.totp.as_ref().filter(|totp| totp.enabled) else {
+        return Err(ErrorAuth::TotpNotEnrolled);
+    };
+    let Some(_claim) = state.claims.claim(format!("totp:{}", acc.username)) else {
+        return Err(ErrorAuth::SecondFactor);
+    };
+
+    let checked = if let Some(step) = totp::verify(&totp.secret, code, time, totp.last_step) {
+        acc::use_totp_step(&state.db, &acc.username, step, time).await?
+    } else {
+        let hash = totp::hash_recovery_code(code);
+        let checked = acc::use_recovery_code(&state.db, &acc.username, &hash, time).await?;
+        if let Some(totp) = checked.as_ref().and_then(|acc| acc.totp.as_ref()) {
+            trace!(
+                "recovery code used by {}, {} left",
+                acc.username,
+                totp.recovery_codes.len()
+            );
+        }
+        checked
+    };
+    checked.ok_or(ErrorAuth::SecondFactor)
+}
+
+#[cfg(test)]
+mod two_factor_tests {
+    use crate::{
+        db::acc,
+        server::{
+            ServerState,
+            auth::{ErrorAuth, LoginStep, get_session_acc, login, login_totp, register},
+            test_support::test_state,
+            token::ErrorToken,
+            totp::{self, base32_decode, code_at_step, step},
+        },
+    };
+
+    use super::{begin, enable};
+
+    fn code(secret: &str, time: i64) -> String {
+        format!(
+            "{:06}",
+            code_at_step(&base32_decode(secret).unwrap(), step(time))
+        )
+    }
+
+    async fn totp_challenge(state: &ServerState, time: i64) -> String {
+        let Ok(LoginStep::Totp(challenge)) =
+            login(state, "hey@example.com", "password123", "::1", "", time).await
+        else {
+            panic!("expected a totp challenge");
+        };
+        challenge
+    }
+
+    #[tokio::test]
+    async fn login_requires_second_factor() {
+        let (state, _mailer) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+
+        let enrollment = begin(&state, &acc, 0).await.unwrap();
+        assert!(
+            enrollment
+                .uri
+                .starts_with("otpauth://totp/ArtBounty:hey?secret=")
+        );
+        assert!(enrollment.qr_svg.contains("<svg"));
+
+        let acc = acc::get_by_username(&state.db, "hey")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            enable(&state, &acc, "000000", 0).await,
+            Err(ErrorAuth::SecondFactor)
+        ));
+        let recovery_codes = enable(&state, &acc, &code(&enrollment.secret, 0), 0)
+            .await
+            .unwrap();
+        assert_eq!(recovery_codes.len(), totp::RECOVERY_CODE_COUNT);
+
+        let challenge = totp_challenge(&state, 1_000).await;
+
+        // code used during enrollment can't be replayed
+        assert!(matches!(
+            login_totp(
+                &state,
+                &challenge,
+                &code(&enrollment.secret, 0),
+                "::1",
+                "",
+                1_000
+            )
+            .await,
+            Err(ErrorAuth::SecondFactor)
+        ));
+
+        let time = 60_000;
+        let (token, _) = login_totp(
+            &state,
+            &challenge,
+            &code(&enrollment.secret, time),
+            "::1",
+            "",
+            time,
+        )
+        .await
+        .unwrap();
+        assert!(
+            get_session_acc(&state, &token, time)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // a challenge logs in once
+        assert!(matches!(
+            login_totp(&state, &challenge, &recovery_codes[0], "::1", "", time).await,
+            Err(ErrorAuth::Token(ErrorToken::Expired))
+        ));
+
+        // recovery codes work once
+        let challenge = totp_challenge(&state, time).await;
+        login_totp(&state, &challenge, &recovery_codes[0], "::1", "", time)
+            .await
+            .unwrap();
+        let challenge = totp_challenge(&state, time).await;
+        assert!(matches!(
+            login_totp(&state, &challenge, &recovery_codes[0], "::1", "", time).await,
+            Err(ErrorAuth::SecondFactor)
+        ));
+
+        // racing requests with one challenge get one session and use up one code
+        let challenge = totp_challenge(&state, time).await;
+        let (first, second) = tokio::join!(
+            login_totp(&state, &challenge, &recovery_codes[1], "::1", "", time),
+            login_totp(&state, &challenge, &recovery_codes[2], "::1", "", time),
+        );
+        assert_eq!(
+            [first.is_ok(), second.is_ok()]
+                .iter()
+                .filter(|ok| **ok)
+                .count(),
+            1
+        );
+
+        let acc = acc::get_by_username(&state.db, "hey")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            acc.totp.unwrap().recovery_codes.len(),
+            totp::RECOVERY_CODE_COUNT - 2
+        );
+    }
+}
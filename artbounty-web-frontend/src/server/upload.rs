@@ -0,0 +1,396 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::trace;
+
+use crate::{
+    api::{MAXIMUM_UPLOAD_CHUNK_SIZE, MAXIMUM_UPLOAD_SIZE, UPLOAD_EXPIRY},
+    db::{
+        Db, DbError,
+        artwork::{self as db_artwork, DbArtwork},
+        upload::{self, DbUpload},
+    },
+};
+
+use super::{
+    ServerError, ServerState, Settings,
+    artwork::{self, ErrorArtwork},
+    message::sniff_image,
+    new_id,
+};
+
+pub const UPLOAD_DIR: &str = "uploads";
+pub const ORIGINAL_DIR: &str = "originals";
+pub const SWEEP_BATCH_SIZE: u32 = 100;
+pub const UPLOAD_BYTES_TOTAL: &str = "upload_bytes_total";
+
+#[derive(Error, Debug)]
+pub enum ErrorUpload {
+    #[error("uploads must be 1 byte to {} MiB", MAXIMUM_UPLOAD_SIZE / 1024 / 1024)]
+    Length,
+
+    #[error("chunks must be at most {} MiB and end within the upload", MAXIMUM_UPLOAD_CHUNK_SIZE / 1024 / 1024)]
+    Chunk,
+
+    #[error("upload is at offset {0}")]
+    Offset(u64),
+
+    #[error("upload is not complete")]
+    Incomplete,
+
+    #[error("uploads must be png, jpeg, gif or webp images")]
+    Image,
+
+    #[error("upload not found")]
+    NotFound,
+
+    #[error("sha256 must be 64 hex characters")]
+    Sha256,
+
+    #[error(transparent)]
+    Artwork(#[from] ErrorArtwork),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorUpload {
+    fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ErrorUpload::Io(_) | ErrorUpload::Db(_) | ErrorUpload::Artwork(ErrorArtwork::Db(_))
+        )
+    }
+}
+
+pub fn upload_path(settings: &Settings, upload_id: &str) -> PathBuf {
+    settings
+        .media_dir
+        .join(UPLOAD_DIR)
+        .join(format!("{}.part", upload_id))
+}
+
+/// Upload of `acc`, expired ones are as good as gone even before the sweep removes them.
+pub async fn get(
+    state: &ServerState,
+    acc: &str,
+    upload_id: &str,
+    time: i64,
+) -> Result<DbUpload, ErrorUpload> {
+    upload::get(&state.db, upload_id)
+        .await?
+        .filter(|upload| upload.acc == acc && upload.expires_at > time)
+        .ok_or(ErrorUpload::NotFound)
+}
+
+pub async fn create(
+    state: &ServerState,
+    acc: &str,
+    length: u64,
+    time: i64,
+) -> Result<DbUpload, ErrorUpload> {
+    if !(1..=MAXIMUM_UPLOAD_SIZE).contains(&length) {
+        return Err(ErrorUpload::Length);
+    }
+    let upload = DbUpload {
+        upload_id: new_id(),
+        acc: acc.to_string(),
+        length,
+        offset: 0,
+        expires_at: time + UPLOAD_EXPIRY,
+        modified_at: time,
+        created_at: time,
+    };
+    let path = upload_path(&state.settings, &upload.upload_id);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::File::create(&path).await?;
+    upload::insert(&state.db, upload.clone()).await?;
+    trace!("upload {} of {} bytes created", upload.upload_id, length);
+    Ok(upload)
+}
+
+/// Counts bytes received by an upload path under `upload_bytes_total{kind}`.
+pub fn record_bytes(kind: &'static str, bytes: usize) {
+    metrics::counter!(UPLOAD_BYTES_TOTAL, "kind" => kind).increment(bytes as u64);
+}
+
+fn claim_key(upload_id: &str) -> String {
+    format!("upload:{}", upload_id)
+}
+
+/// Appends `chunk` if `offset` is where the upload is at, returns the new offset.
+pub async fn append(
+    state: &ServerState,
+    acc: &str,
+    upload_id: &str,
+    offset: u64,
+    chunk: &[u8],
+    time: i64,
+) -> Result<u64, ErrorUpload> {
+    // a concurrent append of the same upload wins and this one learns the offset instead
+    let Some(_claim) = state.claims.claim(claim_key(upload_id)) else {
+        return Err(ErrorUpload::Offset(
+            get(state, acc, upload_id, time).await?.offset,
+        ));
+    };
+    let upload = get(state, acc, upload_id, time).await?;
+    if offset != upload.offset {
+        return Err(ErrorUpload::Offset(upload.offset));
+    }
+    let end = offset + chunk.len() as u64;
+    if chunk.len() > MAXIMUM_UPLOAD_CHUNK_SIZE || end > upload.length {
+        return Err(ErrorUpload::Chunk);
+    }
+    write_chunk(&state.settings, upload_id, offset, chunk).await?;
+    record_bytes("artwork", chunk.len());
+    match upload::set_offset(
+        &state.db,
+        upload_id,
+        offset,
+        end,
+        time + UPLOAD_EXPIRY,
+        time,
+    )
+    .await?
+    {
+        Some(_) => Ok(end),
+        None => Err(ErrorUpload::Offset(
+            get(state, acc, upload_id, time).await?.offset,
+        )),
+    }
+}
+
+async fn write_chunk(
+    settings: &Settings,
+    upload_id: &str,
+    offset: u64,
+    chunk: &[u8],
+) -> Result<(), std::io::Error> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(upload_path(settings, upload_id))
+        .await?;
+    // a chunk written before a crash but never recorded is dropped and sent again
+    file.set_len(offset).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    file.write_all(chunk).await?;
+    file.sync_data().await
+}
+
+fn validate_sha256(sha256: &str) -> Result<(), ErrorUpload> {
+    if sha256.len() != 64 || !sha256.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) {
+        return Err(ErrorUpload::Sha256);
+    }
+    Ok(())
+}
+
+/// Id of an artwork of `acc` uploaded from the same file. The hash is the uploader's word, it
+/// only spares them a second upload.
+pub async fn duplicate(db: &Db, acc: &str, sha256: &str) -> Result<Option<String>, ErrorUpload> {
+    validate_sha256(sha256)?;
+    Ok(db_artwork::get_by_sha256(db, acc, sha256)
+        .await?
+        .map(|artwork| artwork.artwork_id))
+}
+
+/// Turns a complete upload into an artwork, the media worker fills in its size and previews.
+/// `sha256` is of the picked file, empty if the uploader didn't hash it.
+#[allow(clippy::too_many_arguments)]
+pub async fn finalize(
+    state: &ServerState,
+    acc: &str,
+    upload_id: &str,
+    title: &str,
+    description: &str,
+    tags: &[String],
+    rating: &str,
+    sha256: &str,
+    time: i64,
+) -> Result<DbArtwork, ErrorUpload> {
+    if !sha256.is_empty() {
+        validate_sha256(sha256)?;
+    }
+    let _claim = state
+        .claims
+        .claim(claim_key(upload_id))
+        .ok_or(ErrorUpload::NotFound)?;
+    let upload = get(state, acc, upload_id, time).await?;
+    if upload.offset != upload.length {
+        return Err(ErrorUpload::Incomplete);
+    }
+    let path = upload_path(&state.settings, upload_id);
+    let mut head = [0_u8; 16];
+    let read = tokio::fs::File::open(&path).await?.read(&mut head).await?;
+    sniff_image(&head[..read]).ok_or(ErrorUpload::Image)?;
+
+    artwork::validate_title(title)?;
+    artwork::validate_description(description)?;
+    artwork::validate_rating(rating)?;
+    artwork::normalize_tags(tags)?;
+    upload::take(&state.db, acc, upload_id, time)
+        .await?
+        .ok_or(ErrorUpload::NotFound)?;
+
+    let file = format!("{}/{}", ORIGINAL_DIR, upload_id);
+    let original = state.settings.media_dir.join(&file);
+    if let Some(dir) = original.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::rename(&path, &original).await?;
+    let artwork = artwork::create(
+        &state.db,
+        acc,
+        title,
+        description,
+        tags,
+        rating,
+        &file,
+        0,
+        0,
+        time,
+    )
+    .await?;
+    let artwork = if sha256.is_empty() {
+        artwork
+    } else {
+        db_artwork::set_sha256(&state.db, &artwork.artwork_id, sha256, time).await?;
+        DbArtwork {
+            sha256: sha256.to_string(),
+            ..artwork
+        }
+    };
+    trace!(
+        "upload {} finalized as artwork {}",
+        upload_id, artwork.artwork_id
+    );
+    Ok(artwork)
+}
+
+/// Removes a batch of expired uploads with their temp files, returns how many.
+pub async fn sweep(state: &ServerState, time: i64) -> Result<usize, ErrorUpload> {
+    let expired = upload::get_expired(&state.db, time, SWEEP_BATCH_SIZE).await?;
+    for upload in &expired {
+        match tokio::fs::remove_file(upload_path(&state.settings, &upload.upload_id)).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        upload::remove(&state.db, &upload.upload_id).await?;
+        trace!("upload {} expired", upload.upload_id);
+    }
+    Ok(expired.len())
+}
+
+#[cfg(test)]
+mod upload_tests {
+    use crate::{
+        api::{MAXIMUM_UPLOAD_SIZE, UPLOAD_EXPIRY},
+        db::artwork::{self, PREVIEW_PENDING},
+        server::test_support::test_state,
+    };
+
+    use super::{ErrorUpload, append, create, duplicate, finalize, get, sweep, upload_path};
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\nnot really a png";
+    const SHA256: &str = "d3f6c1e0b6a3a1e5c0e3f0c8a1b2c3d4e5f60718293a4b5c6d7e8f9012345678";
+
+    #[tokio::test]
+    async fn chunks_resume_at_offset_and_finalize_into_artwork() {
+        let (state, _mailer) = test_state().await;
+        assert!(matches!(
+            create(&state, "hey", MAXIMUM_UPLOAD_SIZE + 1, 0).await,
+            Err(ErrorUpload::Length)
+        ));
+        let upload = create(&state, "hey", PNG.len() as u64, 0).await.unwrap();
+        let id = upload.upload_id.as_str();
+        assert!(matches!(
+            get(&state, "fox", id, 0).await,
+            Err(ErrorUpload::NotFound)
+        ));
+
+        assert_eq!(
+            append(&state, "hey", id, 0, &PNG[..10], 1).await.unwrap(),
+            10
+        );
+        assert!(matches!(
+            append(&state, "hey", id, 0, &PNG[..10], 1).await,
+            Err(ErrorUpload::Offset(10))
+        ));
+        assert!(matches!(
+            finalize(&state, "hey", id, "Cat", "", &[], "sfw", "", 2).await,
+            Err(ErrorUpload::Incomplete)
+        ));
+        // a resumed client asks for the offset and continues from there
+        let offset = get(&state, "hey", id, 2).await.unwrap().offset as usize;
+        append(&state, "hey", id, offset as u64, &PNG[offset..], 2)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            finalize(&state, "hey", id, "Cat", "", &[], "sfw", "not a hash", 3).await,
+            Err(ErrorUpload::Sha256)
+        ));
+        assert_eq!(duplicate(&state.db, "hey", SHA256).await.unwrap(), None);
+        let created = finalize(&state, "hey", id, "Cat", "", &[], "sfw", SHA256, 3)
+            .await
+            .unwrap();
+        assert_eq!(
+            duplicate(&state.db, "hey", SHA256).await.unwrap(),
+            Some(created.artwork_id.clone())
+        );
+        assert_eq!(duplicate(&state.db, "fox", SHA256).await.unwrap(), None);
+        assert_eq!(created.preview_status, PREVIEW_PENDING);
+        let stored = std::fs::read(state.settings.media_dir.join(&created.file)).unwrap();
+        assert_eq!(stored, PNG);
+        assert!(!upload_path(&state.settings, id).exists());
+        assert!(
+            artwork::get(&state.db, &created.artwork_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let stale = create(&state, "hey", 10, 0).await.unwrap();
+        assert_eq!(sweep(&state, UPLOAD_EXPIRY - 1).await.unwrap(), 0);
+        assert_eq!(sweep(&state, UPLOAD_EXPIRY).await.unwrap(), 1);
+        assert!(!upload_path(&state.settings, &stale.upload_id).exists());
+    }
+
+    #[tokio::test]
+    async fn racing_appends_and_finalizes_apply_once() {
+        let (state, _mailer) = test_state().await;
+        let upload = create(&state, "hey", PNG.len() as u64, 0).await.unwrap();
+        let id = upload.upload_id.as_str();
+
+        let (first, second) = tokio::join!(
+            append(&state, "hey", id, 0, &PNG[..10], 1),
+            append(&state, "hey", id, 0, &PNG[..4], 1),
+        );
+        let end = match (first, second) {
+            (Ok(end), Err(ErrorUpload::Offset(_))) | (Err(ErrorUpload::Offset(_)), Ok(end)) => end,
+            other => panic!("expected exactly one append, got {:?}", other),
+        };
+        assert_eq!(get(&state, "hey", id, 1).await.unwrap().offset, end);
+        append(&state, "hey", id, end, &PNG[end as usize..], 2)
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            finalize(&state, "hey", id, "Cat", "", &[], "sfw", "", 3),
+            finalize(&state, "hey", id, "Cat", "", &[], "sfw", "", 3),
+        );
+        let created = match (first, second) {
+            (Ok(created), Err(ErrorUpload::NotFound))
+            | (Err(ErrorUpload::NotFound), Ok(created)) => created,
+            other => panic!("expected exactly one artwork, got {:?}", other),
+        };
+        let stored = std::fs::read(state.settings.media_dir.join(&created.file)).unwrap();
+        assert_eq!(stored, PNG);
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+use tracing::trace;
+
+use crate::{
+    api::{
+        DEFAULT_WATERMARK_OPACITY, MAXIMUM_WATERMARK_IMAGE_SIZE, MAXIMUM_WATERMARK_TEXT_LENGTH,
+        WATERMARK_KINDS, WATERMARK_PLACEMENTS, WATERMARK_TEXT_SYMBOLS,
+    },
+    db::{
+        Db, DbError, artwork,
+        watermark::{self, DbWatermark, KIND_IMAGE, KIND_TEXT, PLACEMENT_TILED},
+    },
+};
+
+use super::{ServerError, ServerState, Settings, message::sniff_image, upload};
+
+pub const WATERMARK_DIR: &str = "watermarks";
+
+#[derive(Error, Debug)]
+pub enum ErrorWatermark {
+    #[error("unknown watermark kind")]
+    Kind,
+
+    #[error("watermark text must be 1-{MAXIMUM_WATERMARK_TEXT_LENGTH} characters")]
+    Text,
+
+    #[error("watermark text can only have letters, digits and {WATERMARK_TEXT_SYMBOLS:?}")]
+    TextCharset,
+
+    #[error("unknown watermark placement")]
+    Placement,
+
+    #[error("opacity must be 1-100")]
+    Opacity,
+
+    #[error("watermark image must be a png of at most {} KiB", MAXIMUM_WATERMARK_IMAGE_SIZE / 1024)]
+    Image,
+
+    #[error("upload a watermark image first")]
+    NoImage,
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorWatermark {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorWatermark::Db(_) | ErrorWatermark::Io(_))
+    }
+}
+
+pub fn image_path(settings: &Settings, acc: &str) -> PathBuf {
+    settings
+        .media_dir
+        .join(WATERMARK_DIR)
+        .join(format!("{}.png", acc))
+}
+
+/// Whether the watermark font can draw `c`, lowercase is drawn as uppercase.
+pub fn is_watermark_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || WATERMARK_TEXT_SYMBOLS.contains(c)
+}
+
+/// Used until the artist configures their own, their handle tiled across the preview.
+pub fn default_for(acc: &str) -> DbWatermark {
+    DbWatermark {
+        acc: acc.to_string(),
+        kind: KIND_TEXT.to_string(),
+        text: format!("@{}", acc),
+        placement: PLACEMENT_TILED.to_string(),
+        opacity: DEFAULT_WATERMARK_OPACITY,
+        modified_at: 0,
+        created_at: 0,
+    }
+}
+
+pub async fn get(db: &Db, acc: &str) -> Result<DbWatermark, ErrorWatermark> {
+    Ok(watermark::get(db, acc)
+        .await?
+        .unwrap_or_else(|| default_for(acc)))
+}
+
+/// Saves the watermark settings and queues the protected previews to be rendered again.
+pub async fn set(
+    state: &ServerState,
+    acc: &str,
+    kind: &str,
+    text: &str,
+    placement: &str,
+    opacity: u8,
+    time: i64,
+) -> Result<DbWatermark, ErrorWatermark> {
+    if !WATERMARK_KINDS.iter().any(|(name, _)| *name == kind) {
+        return Err(ErrorWatermark::Kind);
+    }
+    if !WATERMARK_PLACEMENTS
+        .iter()
+        .any(|(name, _)| *name == placement)
+    {
+        return Err(ErrorWatermark::Placement);
+    }
+    if !(1..=100).contains(&opacity) {
+        return Err(ErrorWatermark::Opacity);
+    }
+    let text = text.trim();
+    if kind == KIND_TEXT && !(1..=MAXIMUM_WATERMARK_TEXT_LENGTH).contains(&text.chars().count()) {
+        return Err(ErrorWatermark::Text);
+    }
+    if kind == KIND_TEXT && !text.chars().all(is_watermark_char) {
+        return Err(ErrorWatermark::TextCharset);
+    }
+    if kind == KIND_IMAGE && !tokio::fs::try_exists(image_path(&state.settings, acc)).await? {
+        return Err(ErrorWatermark::NoImage);
+    }
+
+    let created_at = watermark::get(&state.db, acc)
+        .await?
+        .map(|watermark| watermark.created_at)
+        .unwrap_or(time);
+    let watermark = DbWatermark {
+        acc: acc.to_string(),
+        kind: kind.to_string(),
+        text: text.to_string(),
+        placement: placement.to_string(),
+        opacity,
+        modified_at: time,
+        created_at,
+    };
+    watermark::upsert(&state.db, watermark.clone()).await?;
+    artwork::rerender_protected_for_acc(&state.db, acc, time).await?;
+    trace!("watermark of {} changed", acc);
+    Ok(watermark)
+}
+
+/// Stores the png used by the image kind, replacing the previous one.
+pub async fn save_image(
+    state: &ServerState,
+    acc: &str,
+    data: &[u8],
+    time: i64,
+) -> Result<(), ErrorWatermark> {
+    if data.len() > MAXIMUM_WATERMARK_IMAGE_SIZE || sniff_image(data) != Some("image/png") {
+        return Err(ErrorWatermark::Image);
+    }
+    let path = image_path(&state.settings, acc);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&path, data).await?;
+    upload::record_bytes("watermark", data.len());
+    if get(&state.db, acc).await?.kind == KIND_IMAGE {
+        artwork::rerender_protected_for_acc(&state.db, acc, time).await?;
+    }
+    trace!("watermark image of {} uploaded", acc);
+    Ok(())
+}
+
+#[cfg(test)]
+mod watermark_tests {
+    use crate::{
+        db::{
+            artwork::{self, PREVIEW_PENDING, PREVIEW_READY},
+            watermark::{KIND_IMAGE, KIND_TEXT},
+        },
+        server::{
+            artwork::{create, set_protected},
+            test_support::test_state,
+        },
+    };
+
+    use super::{ErrorWatermark, get, save_image, set};
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+
+    #[tokio::test]
+    async fn settings_are_validated_and_rerender_previews() {
+        let (state, _mailer) = test_state().await;
+        let db = &state.db;
+        assert_eq!(get(db, "hey").await.unwrap().text, "@hey");
+
+        let artwork = create(db, "hey", "Cat", "", &[], "sfw", "artworks/cat", 10, 10, 0)
+            .await
+            .unwrap();
+        set_protected(db, "hey", &artwork.artwork_id, true, 0)
+            .await
+            .unwrap();
+        artwork::set_preview_status(db, &artwork.artwork_id, PREVIEW_READY, 0)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            set(&state, "hey", KIND_TEXT, "", "tiled", 50, 1).await,
+            Err(ErrorWatermark::Text)
+        ));
+        assert!(matches!(
+            set(&state, "hey", KIND_TEXT, "hey ♥", "tiled", 50, 1).await,
+            Err(ErrorWatermark::TextCharset)
+        ));
+        assert!(matches!(
+            set(&state, "hey", KIND_TEXT, "hey", "tiled", 0, 1).await,
+            Err(ErrorWatermark::Opacity)
+        ));
+        assert!(matches!(
+            set(&state, "hey", KIND_IMAGE, "", "corner", 50, 1).await,
+            Err(ErrorWatermark::NoImage)
+        ));
+        assert!(matches!(
+            save_image(&state, "hey", b"GIF89a....", 1).await,
+            Err(ErrorWatermark::Image)
+        ));
+        save_image(&state, "hey", PNG, 1).await.unwrap();
+        set(&state, "hey", KIND_IMAGE, "", "corner", 50, 1)
+            .await
+            .unwrap();
+        assert_eq!(get(db, "hey").await.unwrap().placement, "corner");
+
+        let artwork = artwork::get(db, &artwork.artwork_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(artwork.preview_status, PREVIEW_PENDING);
+    }
+}
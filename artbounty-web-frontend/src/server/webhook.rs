@@ -0,0 +1,582 @@
+use std::net::IpAddr;
+
+use axum::http::Uri;
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use thiserror::Error;
+use tracing::{error, trace, warn};
+
+use crate::{
+    api::{MAXIMUM_WEBHOOKS, WEBHOOK_EVENTS, WEBHOOK_TEST_EVENT},
+    db::{
+        Db, DbError,
+        acc::DbAcc,
+        artwork::DbArtwork,
+        bounty::DbBounty,
+        webhook::{self, DbWebhook},
+        webhook_delivery::{
+            self, DbWebhookAttempt, DbWebhookDelivery, STATUS_DELIVERED, STATUS_FAILED,
+            STATUS_PENDING,
+        },
+    },
+};
+
+use super::{ServerError, new_id};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SECRET_PREFIX: &str = "whsec_";
+pub const SIGNATURE_HEADER: &str = "x-artbounty-signature";
+pub const EVENT_HEADER: &str = "x-artbounty-event";
+pub const DELIVERY_HEADER: &str = "x-artbounty-delivery";
+pub const MAXIMUM_URL_LENGTH: usize = 2048;
+pub const MAXIMUM_ATTEMPTS: usize = 8;
+pub const RETRY_BASE_MS: i64 = 1000 * 30;
+pub const RETRY_MAXIMUM_MS: i64 = 1000 * 60 * 60 * 6;
+/// Failed attempts in a row after which a webhook gets disabled.
+pub const DISABLE_AFTER_FAILURES: u32 = 10;
+pub const RECENT_DELIVERIES: u32 = 20;
+
+#[derive(Error, Debug)]
+pub enum ErrorWebhook {
+    #[error("url must be an http or https url of at most {MAXIMUM_URL_LENGTH} characters")]
+    Url,
+
+    #[error("unknown event {0}")]
+    Event(String),
+
+    #[error("at least one event is required")]
+    NoEvents,
+
+    #[error("at most {MAXIMUM_WEBHOOKS} webhooks per account")]
+    TooMany,
+
+    #[error("webhook not found")]
+    NotFound,
+
+    #[error("webhook is disabled")]
+    Disabled,
+
+    #[error("db error: {0}")]
+    Db(#[from] DbError),
+}
+
+impl ServerError for ErrorWebhook {
+    fn is_internal(&self) -> bool {
+        matches!(self, ErrorWebhook::Db(_))
+    }
+}
+
+pub fn validate_url(url: &str) -> Result<(), ErrorWebhook> {
+    if url.len() > MAXIMUM_URL_LENGTH {
+        return Err(ErrorWebhook::Url);
+    }
+    let uri = url.parse::<Uri>().map_err(|_| ErrorWebhook::Url)?;
+    let valid_scheme = matches!(uri.scheme_str(), Some("http") | Some("https"));
+    if !valid_scheme || uri.host().is_none_or(str::is_empty) {
+        return Err(ErrorWebhook::Url);
+    }
+    Ok(())
+}
+
+/// Whether deliveries may go to `ip`, the server's own host and networks are refused.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            let this_network = a == 0;
+            let shared = a == 100 && (b & 0xc0) == 64;
+            let protocol_assignments = a == 192 && b == 0 && c == 0;
+            let benchmarking = a == 198 && (b & 0xfe) == 18;
+            // also covers the broadcast address
+            let reserved = a >= 240;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || this_network
+                || shared
+                || protocol_assignments
+                || benchmarking
+                || reserved)
+        }
+        // mapped and the deprecated compatible addresses both reach the embedded ipv4 one
+        IpAddr::V6(ip) => match ip.to_ipv4() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let [first, second, ..] = ip.segments();
+                let nat64 = first == 0x64 && second == 0xff9b && ip.segments()[2..6] == [0; 4];
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+                let site_local = (first & 0xffc0) == 0xfec0;
+                !(ip.is_multicast() || nat64 || unique_local || link_local || site_local)
+            }
+        },
+    }
+}
+
+/// Parses space separated event names, see [`WEBHOOK_EVENTS`].
+pub fn parse_events(events: &str) -> Result<Vec<String>, ErrorWebhook> {
+    let mut output = Vec::<String>::new();
+    for event in events.split_whitespace() {
+        if !WEBHOOK_EVENTS.iter().any(|(name, _)| *name == event) {
+            return Err(ErrorWebhook::Event(event.to_string()));
+        }
+        if !output.iter().any(|e| e == event) {
+            output.push(event.to_string());
+        }
+    }
+    if output.is_empty() {
+        return Err(ErrorWebhook::NoEvents);
+    }
+    Ok(output)
+}
+
+/// Signature header value, `t=<unix seconds>,v1=<hex hmac_sha256(secret, "<t>.<body>")>`.
+///
+/// The timestamp is signed too, so receivers can reject replays of old deliveries.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts any key size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    let signature = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("t={},v1={}", timestamp, signature)
+}
+
+/// Receiver side check of [`sign`], `tolerance` is in seconds.
+pub fn verify_signature(secret: &str, header: &str, body: &str, now: i64, tolerance: i64) -> bool {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in header.split(',') {
+        match part.split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signature = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+        return false;
+    };
+    if (now - timestamp).abs() > tolerance {
+        return false;
+    }
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts any key size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Delay before retrying after the `attempt`th failed attempt, doubling from 30 seconds up to 6 hours.
+pub fn retry_delay(attempt: usize) -> i64 {
+    let exponent = attempt.saturating_sub(1).min(20) as u32;
+    (RETRY_BASE_MS * 2_i64.pow(exponent)).min(RETRY_MAXIMUM_MS)
+}
+
+pub fn artwork_data(artwork: &DbArtwork) -> Value {
+    json!({
+        "id": artwork.artwork_id,
+        "author": artwork.acc,
+        "title": artwork.title,
+        "description": artwork.description,
+        "tags": artwork.tags,
+        "width": artwork.width,
+        "height": artwork.height,
+        "modified_at": artwork.modified_at,
+        "created_at": artwork.created_at,
+    })
+}
+
+pub fn bounty_data(bounty: &DbBounty) -> Value {
+    json!({
+        "id": bounty.bounty_id,
+        "author": bounty.acc,
+        "title": bounty.title,
+        "description": bounty.description,
+        "tags": bounty.tags,
+        "reward": bounty.reward,
+        "status": bounty.status,
+        "modified_at": bounty.modified_at,
+        "created_at": bounty.created_at,
+    })
+}
+
+pub async fn create(
+    db: &Db,
+    acc: &DbAcc,
+    url: &str,
+    events: &str,
+    time: i64,
+) -> Result<DbWebhook, ErrorWebhook> {
+    let url = url.trim();
+    validate_url(url)?;
+    let events = parse_events(events)?;
+    if webhook::get_all_for_acc(db, &acc.username).await?.len() >= MAXIMUM_WEBHOOKS {
+        return Err(ErrorWebhook::TooMany);
+    }
+
+    let mut bytes = [0_u8; 24];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let webhook = DbWebhook {
+        webhook_id: new_id(),
+        acc: acc.username.clone(),
+        url: url.to_string(),
+        events,
+        secret: format!("{}{}", SECRET_PREFIX, BASE64_URL_SAFE_NO_PAD.encode(bytes)),
+        enabled: true,
+        failures: 0,
+        modified_at: time,
+        created_at: time,
+    };
+    webhook::insert(db, webhook.clone()).await?;
+    trace!(
+        "webhook {} created for {}",
+        webhook.webhook_id, acc.username
+    );
+
+    Ok(webhook)
+}
+
+async fn get_owned(db: &Db, acc: &DbAcc, webhook_id: &str) -> Result<DbWebhook, ErrorWebhook> {
+    webhook::get(db, webhook_id)
+        .await?
+        .filter(|webhook| webhook.acc == acc.username)
+        .ok_or(ErrorWebhook::NotFound)
+}
+
+pub async fn remove(db: &Db, acc: &DbAcc, webhook_id: &str) -> Result<(), ErrorWebhook> {
+    if !webhook::remove(db, &acc.username, webhook_id).await? {
+        return Err(ErrorWebhook::NotFound);
+    }
+    webhook_delivery::remove_all_for_webhook(db, webhook_id).await?;
+    trace!("webhook {} removed by {}", webhook_id, acc.username);
+    Ok(())
+}
+
+pub async fn enable(db: &Db, acc: &DbAcc, webhook_id: &str, time: i64) -> Result<(), ErrorWebhook> {
+    get_owned(db, acc, webhook_id).await?;
+    webhook::set_state(db, webhook_id, true, 0, time).await?;
+    Ok(())
+}
+
+pub async fn recent_deliveries(
+    db: &Db,
+    acc: &DbAcc,
+    webhook_id: &str,
+) -> Result<Vec<DbWebhookDelivery>, ErrorWebhook> {
+    get_owned(db, acc, webhook_id).await?;
+    let deliveries =
+        webhook_delivery::get_page_for_webhook(db, webhook_id, RECENT_DELIVERIES).await?;
+    Ok(deliveries)
+}
+
+/// Queues a [`WEBHOOK_TEST_EVENT`] delivery, regardless of the subscribed events.
+pub async fn send_test(
+    db: &Db,
+    acc: &DbAcc,
+    webhook_id: &str,
+    time: i64,
+) -> Result<(), ErrorWebhook> {
+    let webhook = get_owned(db, acc, webhook_id).await?;
+    if !webhook.enabled {
+        return Err(ErrorWebhook::Disabled);
+    }
+    enqueue(
+        db,
+        &webhook,
+        WEBHOOK_TEST_EVENT,
+        json!({ "webhook_id": webhook.webhook_id }),
+        time,
+    )
+    .await?;
+    Ok(())
+}
+
+async fn enqueue(
+    db: &Db,
+    webhook: &DbWebhook,
+    event: &str,
+    data: Value,
+    time: i64,
+) -> Result<DbWebhookDelivery, ErrorWebhook> {
+    let delivery_id = new_id();
+    let payload = json!({
+        "id": delivery_id,
+        "event": event,
+        "created_at": time,
+        "data": data,
+    });
+    let delivery = DbWebhookDelivery {
+        delivery_id,
+        webhook_id: webhook.webhook_id.clone(),
+        event: event.to_string(),
+        payload: payload.to_string(),
+        status: STATUS_PENDING.to_string(),
+        attempts: Vec::new(),
+        next_attempt_at: time,
+        modified_at: time,
+        created_at: time,
+    };
+    webhook_delivery::insert(db, delivery.clone()).await?;
+    trace!(
+        "queued {} delivery {} for webhook {}",
+        event, delivery.delivery_id, webhook.webhook_id
+    );
+    Ok(delivery)
+}
+
+/// Queues `event` for every enabled webhook of `acc` subscribed to it.
+///
+/// Failures are logged instead of returned, a broken webhook table shouldn't fail the action that caused the event.
+pub async fn emit(db: &Db, acc: &str, event: &str, data: Value, time: i64) {
+    let webhooks = match webhook::get_enabled_for_event(db, acc, event).await {
+        Ok(webhooks) => webhooks,
+        Err(err) => {
+            error!("failed to get webhooks for {} {}: {}", acc, event, err);
+            return;
+        }
+    };
+    for webhook in webhooks {
+        if let Err(err) = enqueue(db, &webhook, event, data.clone(), time).await {
+            error!(
+                "failed to queue {} for webhook {}: {}",
+                event, webhook.webhook_id, err
+            );
+        }
+    }
+}
+
+/// Records the outcome of sending `delivery` and schedules a retry or gives up.
+///
+/// A webhook whose attempts keep failing is disabled along with its pending deliveries.
+pub async fn record_attempt(
+    db: &Db,
+    mut delivery: DbWebhookDelivery,
+    attempt: DbWebhookAttempt,
+    time: i64,
+) -> Result<DbWebhookDelivery, ErrorWebhook> {
+    let success = attempt
+        .response_code
+        .is_some_and(|code| (200..300).contains(&code));
+    delivery.attempts.push(attempt);
+    delivery.modified_at = time;
+
+    let Some(webhook) = webhook::get(db, &delivery.webhook_id).await? else {
+        delivery.status = STATUS_FAILED.to_string();
+        webhook_delivery::update(db, delivery.clone()).await?;
+        return Ok(delivery);
+    };
+
+    if success {
+        delivery.status = STATUS_DELIVERED.to_string();
+        if webhook.failures > 0 {
+            webhook::set_state(db, &webhook.webhook_id, webhook.enabled, 0, time).await?;
+        }
+        webhook_delivery::update(db, delivery.clone()).await?;
+        return Ok(delivery);
+    }
+
+    let failures = webhook.failures + 1;
+    let disable = failures >= DISABLE_AFTER_FAILURES;
+    webhook::set_state(
+        db,
+        &webhook.webhook_id,
+        webhook.enabled && !disable,
+        failures,
+        time,
+    )
+    .await?;
+
+    if disable || delivery.attempts.len() >= MAXIMUM_ATTEMPTS {
+        delivery.status = STATUS_FAILED.to_string();
+    } else {
+        delivery.next_attempt_at = time + retry_delay(delivery.attempts.len());
+    }
+    webhook_delivery::update(db, delivery.clone()).await?;
+
+    if disable {
+        warn!(
+            "webhook {} disabled after {} failed attempts",
+            webhook.webhook_id, failures
+        );
+        webhook_delivery::fail_pending_for_webhook(db, &webhook.webhook_id, time).await?;
+    }
+
+    Ok(delivery)
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use serde_json::json;
+
+    use crate::{
+        db::{
+            webhook,
+            webhook_delivery::{self, DbWebhookAttempt, STATUS_FAILED, STATUS_PENDING},
+        },
+        server::{auth::register, test_support::test_state},
+    };
+
+    use super::{
+        DISABLE_AFTER_FAILURES, ErrorWebhook, create, emit, is_public_ip, record_attempt,
+        retry_delay, sign, verify_signature,
+    };
+
+    #[test]
+    fn signature_roundtrip() {
+        let header = sign("whsec_test", 1_000, "{}");
+        assert!(verify_signature("whsec_test", &header, "{}", 1_010, 300));
+        assert!(!verify_signature("whsec_other", &header, "{}", 1_010, 300));
+        assert!(!verify_signature("whsec_test", &header, "{ }", 1_010, 300));
+        assert!(!verify_signature("whsec_test", &header, "{}", 2_000, 300));
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for (ip, public) in [
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("0.0.0.0", false),
+            ("0.1.2.3", false),
+            ("192.0.0.8", false),
+            ("192.0.1.1", true),
+            ("198.18.0.1", false),
+            ("198.19.255.255", false),
+            ("198.20.0.1", true),
+            ("224.0.0.1", false),
+            ("239.255.255.250", false),
+            ("240.0.0.1", false),
+            ("255.255.255.255", false),
+            ("93.184.216.34", true),
+            ("1.1.1.1", true),
+            ("::1", false),
+            ("::", false),
+            ("fe80::1", false),
+            ("fd00::1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:1.1.1.1", true),
+            ("::127.0.0.1", false),
+            ("::10.0.0.1", false),
+            ("64:ff9b::7f00:1", false),
+            ("64:ff9b::a00:1", false),
+            ("64:ff9b:1::1", true),
+            ("ff02::1", false),
+            ("ff0e::1", false),
+            ("fec0::1", false),
+            ("feff::1", false),
+            ("2606:4700::1111", true),
+        ] {
+            assert_eq!(is_public_ip(ip.parse().unwrap()), public, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn retries_back_off() {
+        assert_eq!(retry_delay(1), 30_000);
+        assert_eq!(retry_delay(2), 60_000);
+        assert_eq!(retry_delay(3), 120_000);
+        assert_eq!(retry_delay(50), 1000 * 60 * 60 * 6);
+    }
+
+    #[tokio::test]
+    async fn failing_webhook_gets_disabled() {
+        let (state, _mailer) = test_state().await;
+        let acc = register(&state, "hey", "hey@example.com", "password123", 0)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            create(&state.db, &acc, "ftp://example.com", "bounty.created", 0).await,
+            Err(ErrorWebhook::Url)
+        ));
+        let hook = create(
+            &state.db,
+            &acc,
+            "http://127.0.0.1:9/hook",
+            "bounty.created",
+            0,
+        )
+        .await
+        .unwrap();
+
+        emit(&state.db, "hey", "bounty.closed", json!({}), 0).await;
+        for _ in 0..DISABLE_AFTER_FAILURES {
+            emit(&state.db, "hey", "bounty.created", json!({}), 0).await;
+        }
+        let due = webhook_delivery::get_due(&state.db, 0, 100).await.unwrap();
+        assert_eq!(due.len() as u32, DISABLE_AFTER_FAILURES);
+
+        let mut due = due.into_iter();
+        let first = record_attempt(
+            &state.db,
+            due.next().unwrap(),
+            DbWebhookAttempt {
+                response_code: Some(500),
+                error: None,
+                duration_ms: 1,
+                created_at: 0,
+            },
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.status, STATUS_PENDING);
+        assert_eq!(first.next_attempt_at, 30_000);
+
+        for delivery in due {
+            record_attempt(
+                &state.db,
+                delivery,
+                DbWebhookAttempt {
+                    response_code: None,
+                    error: Some(String::from("connection refused")),
+                    duration_ms: 1,
+                    created_at: 0,
+                },
+                0,
+            )
+            .await
+            .unwrap();
+        }
+
+        let hook = webhook::get(&state.db, &hook.webhook_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!hook.enabled);
+        assert_eq!(hook.failures, DISABLE_AFTER_FAILURES);
+        assert!(
+            webhook_delivery::get_due(&state.db, i64::MAX, 100)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let deliveries = webhook_delivery::get_page_for_webhook(&state.db, &hook.webhook_id, 100)
+            .await
+            .unwrap();
+        assert!(deliveries.iter().all(|d| d.status == STATUS_FAILED));
+    }
+}
@@ -0,0 +1,134 @@
+use leptos::prelude::*;
+
+pub const THEME_COOKIE: &str = "theme";
+pub const DEFAULT_THEME: &str = "system";
+/// Every theme, in the order the nav toggle cycles through them.
+pub const THEMES: [&str; 3] = ["system", "light", "dark"];
+
+/// The supported theme matching `theme`, or the default one.
+pub fn supported(theme: &str) -> &'static str {
+    THEMES
+        .iter()
+        .find(|code| code.eq_ignore_ascii_case(theme))
+        .copied()
+        .unwrap_or(DEFAULT_THEME)
+}
+
+/// Theme after `theme` in the toggle.
+pub fn next(theme: &str) -> &'static str {
+    let i = THEMES
+        .iter()
+        .position(|code| *code == supported(theme))
+        .unwrap_or_default();
+    THEMES[(i + 1) % THEMES.len()]
+}
+
+/// Finds the theme cookie in a `Cookie` header value, only if it's a supported one.
+pub fn get_theme_cookie(cookie_header: &str) -> Option<&'static str> {
+    cookie_header.split(';').find_map(|cookie| {
+        let (name, value) = cookie.trim().split_once('=')?;
+        (name == THEME_COOKIE)
+            .then(|| THEMES.iter().find(|code| **code == value))
+            .flatten()
+            .copied()
+    })
+}
+
+pub fn theme_cookie(theme: &str) -> String {
+    format!(
+        "{}={}; Path=/; SameSite=Lax; Max-Age={}",
+        THEME_COOKIE,
+        supported(theme),
+        60 * 60 * 24 * 365
+    )
+}
+
+#[cfg(feature = "ssr")]
+pub fn theme_from_headers(headers: &axum::http::HeaderMap) -> &'static str {
+    headers
+        .get_all(axum::http::header::COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .find_map(get_theme_cookie)
+        .unwrap_or(DEFAULT_THEME)
+}
+
+/// Theme of the page being rendered. The server reads it from the cookie and writes it as the
+/// `<html>` class so the first paint already has the right colors, hydration reads it back.
+pub fn request_theme() -> &'static str {
+    #[cfg(feature = "ssr")]
+    {
+        use_context::<axum::http::request::Parts>()
+            .map(|parts| theme_from_headers(&parts.headers))
+            .unwrap_or(DEFAULT_THEME)
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        document()
+            .document_element()
+            .map(|html| supported(&html.class_name()))
+            .unwrap_or(DEFAULT_THEME)
+    }
+}
+
+/// Theme of the page, provided by the app.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub current: RwSignal<&'static str>,
+}
+
+impl Theme {
+    pub fn new(theme: &str) -> Self {
+        Self {
+            current: RwSignal::new(supported(theme)),
+        }
+    }
+
+    /// Switches the `<html>` class, the cookie is left to the caller.
+    pub fn set(&self, theme: &str) {
+        self.current.set(supported(theme));
+    }
+
+    /// Keeps the `<html>` class in sync with the current theme.
+    pub fn apply(&self) {
+        let current = self.current;
+        Effect::new(move || {
+            let theme = current.get();
+            if let Some(html) = document().document_element() {
+                html.set_class_name(theme);
+            }
+        });
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(DEFAULT_THEME)
+    }
+}
+
+pub fn use_theme() -> Theme {
+    use_context::<Theme>().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::{get_theme_cookie, next, supported, theme_cookie};
+
+    #[test]
+    fn theme_comes_from_cookie() {
+        assert_eq!(get_theme_cookie("session=abc; theme=dark"), Some("dark"));
+        assert_eq!(get_theme_cookie("theme=purple"), None);
+        assert_eq!(get_theme_cookie("locale=de"), None);
+        assert_eq!(supported("LIGHT"), "light");
+        assert_eq!(supported("purple"), "system");
+        assert!(theme_cookie("purple").starts_with("theme=system;"));
+    }
+
+    #[test]
+    fn toggle_cycles_every_theme() {
+        assert_eq!(next("system"), "light");
+        assert_eq!(next("light"), "dark");
+        assert_eq!(next("dark"), "system");
+    }
+}
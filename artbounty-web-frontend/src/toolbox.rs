@@ -3,6 +3,8 @@ pub mod prelude {
     pub use super::event_listener::{self, AddEventListener};
     pub use super::file::{self, GetFileStream, GetFiles, GetStreamChunk, PushChunkToVec};
     pub use super::intersection_observer::{self, AddIntersectionObserver};
+    pub use super::mutation_observer::{self, AddMutationObserver};
+    pub use super::observer;
     pub use super::random::{random_u8, random_u32, random_u32_ranged, random_u64};
     pub use super::resize_observer::{self, AddResizeObserver, GetContentBoxSize};
 }
@@ -128,40 +130,427 @@ pub mod uuid {
     }
 }
 
+pub mod observer {
+    use std::{
+        cell::RefCell,
+        collections::HashMap,
+        hash::{DefaultHasher, Hash, Hasher},
+        marker::PhantomData,
+        rc::Rc,
+    };
+
+    use leptos::{html::ElementType, prelude::*};
+    use tracing::{error, trace};
+    use uuid::Uuid;
+    use wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+    use web_sys::{Element, HtmlElement, Node, js_sys::Array};
+
+    use super::uuid::{get_id, set_id};
+
+    /// A kind of JS observer, one of each is created per distinct option set.
+    pub trait ObserverKind: 'static {
+        type Entry: JsCast + Clone;
+        type Observer: JsCast + Clone;
+        type Init: 'static;
+
+        /// Attribute tagging observed elements, shared by every option set of the kind.
+        const ID_FIELD_NAME: &'static str;
+
+        fn create(callback: &JsValue, init: &Self::Init) -> Result<Self::Observer, JsValue>;
+        fn observe(
+            observer: &Self::Observer,
+            target: &Element,
+            init: &Self::Init,
+        ) -> Result<(), JsValue>;
+        /// Returns false if the kind can't drop a single target, the observer is then
+        /// reconnected to the remaining ones.
+        fn unobserve(observer: &Self::Observer, target: &Element) -> bool;
+        fn disconnect(observer: &Self::Observer);
+        /// Node the entry is about, the observed element or one of its descendants.
+        fn target(entry: &Self::Entry) -> Option<Node>;
+    }
+
+    type Callback<K> =
+        Rc<RefCell<dyn FnMut(<K as ObserverKind>::Entry, <K as ObserverKind>::Observer)>>;
+
+    struct Registration<K: ObserverKind> {
+        element_id: Uuid,
+        target: Element,
+        callback: Callback<K>,
+    }
+
+    struct Group<K: ObserverKind> {
+        observer: K::Observer,
+        init: K::Init,
+        /// Freed with the group, after the observer is disconnected.
+        _closure: Closure<dyn FnMut(Array, JsValue)>,
+        registrations: HashMap<Uuid, Registration<K>>,
+        by_element: HashMap<Uuid, Vec<Uuid>>,
+    }
+
+    /// Observers of kind `K` by the hash of their options, provided by [`init_global_state`].
+    pub struct ObserverRegistry<K: ObserverKind> {
+        groups: StoredValue<HashMap<u64, Group<K>>, LocalStorage>,
+        kind: PhantomData<fn() -> K>,
+    }
+
+    impl<K: ObserverKind> Clone for ObserverRegistry<K> {
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<K: ObserverKind> Copy for ObserverRegistry<K> {}
+
+    impl<K: ObserverKind> ObserverRegistry<K> {
+        pub fn new() -> Self {
+            Self {
+                groups: StoredValue::new_local(HashMap::new()),
+                kind: PhantomData,
+            }
+        }
+    }
+
+    impl<K: ObserverKind> ObserverRegistry<K> {
+        /// Live JS observers, one per option set in use.
+        pub fn observers(&self) -> usize {
+            self.groups
+                .try_with_value(|groups| groups.len())
+                .unwrap_or_default()
+        }
+
+        pub fn registrations(&self) -> usize {
+            self.groups
+                .try_with_value(|groups| {
+                    groups.values().map(|group| group.registrations.len()).sum()
+                })
+                .unwrap_or_default()
+        }
+    }
+
+    impl<K: ObserverKind> Default for ObserverRegistry<K> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    pub fn init_global_state() {
+        provide_context(ObserverRegistry::<super::resize_observer::Resize>::new());
+        provide_context(ObserverRegistry::<super::intersection_observer::Intersection>::new());
+        provide_context(ObserverRegistry::<super::mutation_observer::Mutation>::new());
+    }
+
+    pub fn hash_of(value: &impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Id of the observed element `node` is in, the innermost one if they're nested.
+    fn element_id(node: Node, field_name: &str) -> Option<Uuid> {
+        let mut current = Some(node);
+        while let Some(node) = current {
+            if let Some(element) = node.dyn_ref::<Element>()
+                && element.has_attribute(field_name)
+            {
+                return get_id(element, field_name);
+            }
+            current = node.parent_node();
+        }
+        None
+    }
+
+    fn new_group<K: ObserverKind>(
+        registry: ObserverRegistry<K>,
+        hash: u64,
+        init: K::Init,
+    ) -> Option<Group<K>> {
+        let closure =
+            Closure::<dyn FnMut(Array, JsValue)>::new(move |entries: Array, observer: JsValue| {
+                let observer = observer.unchecked_into::<K::Observer>();
+                // callbacks run outside the registry so they can add or remove observers
+                let calls = registry
+                    .groups
+                    .try_with_value(|groups| {
+                        let Some(group) = groups.get(&hash) else {
+                            return Vec::new();
+                        };
+                        let mut calls = Vec::new();
+                        for entry in entries.iter() {
+                            let entry = entry.unchecked_into::<K::Entry>();
+                            let Some(ids) = K::target(&entry)
+                                .and_then(|node| element_id(node, K::ID_FIELD_NAME))
+                                .and_then(|element_id| group.by_element.get(&element_id))
+                            else {
+                                continue;
+                            };
+                            for id in ids {
+                                if let Some(registration) = group.registrations.get(id) {
+                                    calls.push((entry.clone(), registration.callback.clone()));
+                                }
+                            }
+                        }
+                        calls
+                    })
+                    .unwrap_or_default();
+                for (entry, callback) in calls {
+                    (callback.borrow_mut())(entry, observer.clone());
+                }
+            });
+        match K::create(closure.as_ref(), &init) {
+            Ok(observer) => Some(Group {
+                observer,
+                init,
+                _closure: closure,
+                registrations: HashMap::new(),
+                by_element: HashMap::new(),
+            }),
+            Err(err) => {
+                error!("failed to create observer: {:?}", err);
+                None
+            }
+        }
+    }
+
+    /// Observes `target` with the shared observer of `hash`, returns the registration to pass to
+    /// [`unobserve`].
+    pub fn observe<K: ObserverKind>(
+        registry: ObserverRegistry<K>,
+        target: &Element,
+        hash: u64,
+        init: K::Init,
+        callback: Callback<K>,
+    ) -> Option<Uuid> {
+        let element_id = match target.has_attribute(K::ID_FIELD_NAME) {
+            true => get_id(target, K::ID_FIELD_NAME)?,
+            false => {
+                let id = Uuid::new_v4();
+                set_id(target, K::ID_FIELD_NAME, id);
+                id
+            }
+        };
+        let registration = Uuid::new_v4();
+        let missing = registry
+            .groups
+            .with_value(|groups| !groups.contains_key(&hash));
+        let group = match missing {
+            true => Some(new_group(registry, hash, init)?),
+            false => None,
+        };
+        registry
+            .groups
+            .try_update_value(|groups| {
+                let group = match group {
+                    Some(group) => groups.entry(hash).or_insert(group),
+                    None => groups.get_mut(&hash)?,
+                };
+                if let Err(err) = K::observe(&group.observer, target, &group.init) {
+                    error!("failed to observe: {:?}", err);
+                    return None;
+                }
+                group.registrations.insert(
+                    registration,
+                    Registration {
+                        element_id,
+                        target: target.clone(),
+                        callback,
+                    },
+                );
+                group
+                    .by_element
+                    .entry(element_id)
+                    .or_default()
+                    .push(registration);
+                trace!("observing {} as {}", element_id, registration);
+                Some(registration)
+            })
+            .flatten()
+    }
+
+    /// Drops the registration, the observer goes with its last one.
+    pub fn unobserve<K: ObserverKind>(
+        registry: ObserverRegistry<K>,
+        hash: u64,
+        registration: Uuid,
+    ) {
+        let emptied = registry.groups.try_update_value(|groups| {
+            let group = groups.get_mut(&hash)?;
+            let removed = group.registrations.remove(&registration)?;
+            let ids = group.by_element.entry(removed.element_id).or_default();
+            ids.retain(|id| *id != registration);
+            if ids.is_empty() {
+                group.by_element.remove(&removed.element_id);
+                if !K::unobserve(&group.observer, &removed.target) {
+                    K::disconnect(&group.observer);
+                    for ids in group.by_element.values() {
+                        let Some(remaining) =
+                            ids.first().and_then(|id| group.registrations.get(id))
+                        else {
+                            continue;
+                        };
+                        if let Err(err) =
+                            K::observe(&group.observer, &remaining.target, &group.init)
+                        {
+                            error!("failed to observe again: {:?}", err);
+                        }
+                    }
+                }
+            }
+            trace!("removed {}", registration);
+            if !group.registrations.is_empty() {
+                return None;
+            }
+            K::disconnect(&group.observer);
+            groups.remove(&hash)
+        });
+        // dropped outside the registry, in case it was the one dispatching
+        drop(emptied);
+    }
+
+    /// Observation started by [`new`], it ends when the owner is cleaned up or on [`cancel`].
+    ///
+    /// [`cancel`]: ObserverHandle::cancel
+    pub struct ObserverHandle<K: ObserverKind> {
+        registry: ObserverRegistry<K>,
+        current: StoredValue<Option<(u64, Uuid)>>,
+        cancelled: StoredValue<bool>,
+    }
+
+    impl<K: ObserverKind> Clone for ObserverHandle<K> {
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<K: ObserverKind> Copy for ObserverHandle<K> {}
+
+    impl<K: ObserverKind> ObserverHandle<K> {
+        /// Stops observing for good, a remounted target isn't observed again.
+        pub fn cancel(&self) {
+            self.cancelled.try_set_value(true);
+            if let Some(Some((hash, registration))) = self.current.try_update_value(Option::take) {
+                unobserve(self.registry, hash, registration);
+            }
+        }
+
+        pub fn is_active(&self) -> bool {
+            matches!(self.current.try_get_value(), Some(Some(_)))
+        }
+    }
+
+    /// Keeps `target` observed while the current owner lives. `options` returns the hash and
+    /// init of the option set, or `None` while something it needs isn't mounted yet.
+    pub fn new<K, E, F>(
+        target: NodeRef<E>,
+        options: impl Fn() -> Option<(u64, K::Init)> + 'static,
+        callback: F,
+    ) -> ObserverHandle<K>
+    where
+        K: ObserverKind,
+        E: ElementType,
+        E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
+        F: FnMut(K::Entry, K::Observer) + 'static,
+    {
+        let callback: Callback<K> = Rc::new(RefCell::new(callback));
+        let handle = ObserverHandle {
+            registry: expect_context::<ObserverRegistry<K>>(),
+            current: StoredValue::new(None),
+            cancelled: StoredValue::new(false),
+        };
+
+        Effect::new(move || {
+            let Some(target) = target.get() else {
+                return;
+            };
+            let Some((hash, init)) = options() else {
+                return;
+            };
+            if handle.cancelled.get_value() {
+                return;
+            }
+            // a remounted target or changed options replace the previous registration
+            if let Some((hash, registration)) = handle.current.get_value() {
+                unobserve(handle.registry, hash, registration);
+            }
+            let target: HtmlElement = target.into();
+            let registration = observe(handle.registry, &target, hash, init, callback.clone());
+            handle
+                .current
+                .set_value(registration.map(|registration| (hash, registration)));
+        });
+
+        on_cleanup(move || handle.cancel());
+
+        handle
+    }
+}
+
 pub mod intersection_observer {
-    use std::collections::HashMap;
-    use std::hash::{DefaultHasher, Hash, Hasher};
-    use std::ops::Deref;
+    use std::hash::Hash;
 
-    use leptos::html;
     use leptos::{html::ElementType, prelude::*};
     use ordered_float::OrderedFloat;
-    use send_wrapper::SendWrapper;
-    use sha2::Digest;
-    use tracing::{error, trace, trace_span, warn};
     use uuid::Uuid;
-    use wasm_bindgen::prelude::Closure;
     use wasm_bindgen::{JsCast, JsValue};
     use web_sys::{
         Element, HtmlElement, IntersectionObserver, IntersectionObserverEntry,
-        IntersectionObserverInit, js_sys::Array,
+        IntersectionObserverInit, Node,
     };
 
-    use super::uuid::{get_id, set_id};
+    use super::{
+        observer::{self, ObserverHandle, ObserverKind, hash_of},
+        uuid::set_id,
+    };
 
     const ID_FIELD_NAME: &str = "data-leptos_toolbox_intersection_observer_id";
     const ID_FIELD_ROOT_NAME: &str = "data-leptos_toolbox_intersection_observer_root_id";
 
+    pub struct Intersection;
+
+    impl ObserverKind for Intersection {
+        type Entry = IntersectionObserverEntry;
+        type Observer = IntersectionObserver;
+        type Init = IntersectionObserverInit;
+
+        const ID_FIELD_NAME: &'static str = ID_FIELD_NAME;
+
+        fn create(callback: &JsValue, init: &Self::Init) -> Result<Self::Observer, JsValue> {
+            IntersectionObserver::new_with_options(callback.unchecked_ref(), init)
+        }
+
+        fn observe(
+            observer: &Self::Observer,
+            target: &Element,
+            _init: &Self::Init,
+        ) -> Result<(), JsValue> {
+            observer.observe(target);
+            Ok(())
+        }
+
+        fn unobserve(observer: &Self::Observer, target: &Element) -> bool {
+            observer.unobserve(target);
+            true
+        }
+
+        fn disconnect(observer: &Self::Observer) {
+            observer.disconnect();
+        }
+
+        fn target(entry: &Self::Entry) -> Option<Node> {
+            Some(entry.target().into())
+        }
+    }
+
     pub trait AddIntersectionObserver {
-        fn observe_intersection_with_options<F, R>(&self, callback: F, options: Options<R>)
+        fn observe_intersection_with_options<F, R>(
+            &self,
+            callback: F,
+            options: Options<R>,
+        ) -> ObserverHandle<Intersection>
         where
             R: ElementType,
             R::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-            F: FnMut(IntersectionObserverEntry, IntersectionObserver)
-                + Send
-                + Sync
-                + Clone
-                + 'static;
+            F: FnMut(IntersectionObserverEntry, IntersectionObserver) + 'static;
     }
 
     impl<E> AddIntersectionObserver for NodeRef<E>
@@ -169,67 +558,20 @@ pub mod intersection_observer {
         E: ElementType,
         E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
     {
-        fn observe_intersection_with_options<F, R>(&self, callback: F, options: Options<R>)
+        fn observe_intersection_with_options<F, R>(
+            &self,
+            callback: F,
+            options: Options<R>,
+        ) -> ObserverHandle<Intersection>
         where
             R: ElementType,
             R::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-            F: FnMut(IntersectionObserverEntry, IntersectionObserver)
-                + Send
-                + Sync
-                + Clone
-                + 'static,
+            F: FnMut(IntersectionObserverEntry, IntersectionObserver) + 'static,
         {
-            new(self.clone(), callback, options);
+            new(*self, callback, options)
         }
     }
 
-    // impl<E, F, R> AddIntersectionObserver<F, R> for NodeRef<E>
-    // where
-    //     E: ElementType,
-    //     E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-    //     R: ElementType,
-    //     R::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-    //     F: FnMut(IntersectionObserverEntry, IntersectionObserver) + Send + Sync + Clone + 'static,
-    // {
-    //     fn add_intersection_observer(&self, callback: F, options: Options<R>) {
-    //         new(self.clone(), callback, options);
-    //     }
-    // }
-
-    #[derive(Default, Clone)]
-    pub struct GlobalState {
-        pub observer: StoredValue<HashMap<u64, SendWrapper<IntersectionObserver>>>,
-        pub callbacks: StoredValue<
-            HashMap<
-                Uuid,
-                Box<
-                    dyn FnMut(IntersectionObserverEntry, IntersectionObserver)
-                        + Send
-                        + Sync
-                        + 'static,
-                >,
-            >,
-        >,
-    }
-
-    // #[derive(Default, Clone)]
-    // pub struct Options<E>
-    // where
-    //     E: ElementType,
-    //     E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-    // {
-    //     root: Option<NodeRef<E>>,
-    //     root_margin: Option<String>,
-    //     threshold: Option<OrderedFloat<f64>>,
-    // }
-
-    // //#[derive(Default, Clone)]
-    // pub struct Options {
-    //     root: Box<dyn Into<HtmlElement>>,
-    //     root_margin: Option<String>,
-    //     threshold: Option<OrderedFloat<f64>>,
-    // }
-
     #[derive(Clone)]
     pub struct Options<E = leptos::html::Div>
     where
@@ -274,22 +616,27 @@ pub mod intersection_observer {
             self.threshold = Some(OrderedFloat(threshold));
             self
         }
-    }
 
-    // impl <E> Options<E> where
-    //     E: ElementType,
-    //     E::Output: JsCast + Clone + 'static + Into<HtmlElement>, {
-    //     pub fn into_
-    // }
-
-    // impl <E> Into<IntersectionObserverInit> for Options<E> where  E: ElementType,
-    // E::Output: JsCast + Clone + 'static + Into<HtmlElement> {
-    //     fn into(self) -> IntersectionObserverInit {
-    //         let init = IntersectionObserverInit::new();
-    //         if let Some(root) =
-    //         init
-    //     }
-    // }
+        /// Hash and init once the root is mounted, tagging the root so equal options share an
+        /// observer only if they share the root too.
+        fn init(&self) -> Option<(u64, IntersectionObserverInit)> {
+            let init = IntersectionObserverInit::new();
+            if let Some(root) = &self.root {
+                let root: HtmlElement = root.get()?.into();
+                if !root.has_attribute(ID_FIELD_ROOT_NAME) {
+                    set_id(&root, ID_FIELD_ROOT_NAME, Uuid::new_v4());
+                }
+                init.set_root(Some(&root));
+            }
+            if let Some(margin) = &self.root_margin {
+                init.set_root_margin(margin);
+            }
+            if let Some(threshold) = self.threshold {
+                init.set_threshold(&JsValue::from_f64(*threshold));
+            }
+            Some((hash_of(self), init))
+        }
+    }
 
     impl<E> Hash for Options<E>
     where
@@ -300,7 +647,7 @@ pub mod intersection_observer {
             self.root
                 .as_ref()
                 .and_then(|v| {
-                    let root: HtmlElement = v.get().unwrap().into();
+                    let root: HtmlElement = v.get_untracked()?.into();
                     root.get_attribute(ID_FIELD_ROOT_NAME)
                 })
                 .hash(state);
@@ -309,383 +656,233 @@ pub mod intersection_observer {
         }
     }
 
-    // pub fn init_global_state() {
-    //     provide_context(GlobalState::default());
-
-    //     Effect::new(move || {
-    //         let ctx = expect_context::<GlobalState>();
-
-    //         let observer = new_raw(move |entries, observer| {
-    //             ctx.callbacks.update_value(|callbacks| {
-    //                 for entry in entries {
-    //                     let target = entry.target();
-    //                     let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-    //                         continue;
-    //                     };
-
-    //                     let Some(callback) = callbacks.get_mut(&id) else {
-    //                         continue;
-    //                     };
-    //                     callback(entry, observer.clone());
-    //                 }
-    //             });
-    //         });
-
-    //         ctx.observer.set(Some(SendWrapper::new(observer)));
-    //     });
-    // }
-
-    // pub fn new<E, R, F>(target: NodeRef<E>, mut callback: F, options: Options<R>)
-    // where
-    //     E: ElementType,
-    //     E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-    //     R: ElementType,
-    //     R::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-    //     F: FnMut(IntersectionObserverEntry, IntersectionObserver) + Clone + Send + Sync + 'static,
-    // {
-
-    pub fn new<E, R, F>(target: NodeRef<E>, mut callback: F, options: Options<R>)
+    pub fn new<E, R, F>(
+        target: NodeRef<E>,
+        callback: F,
+        options: Options<R>,
+    ) -> ObserverHandle<Intersection>
     where
         E: ElementType,
         E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
         R: ElementType,
         R::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-        F: FnMut(IntersectionObserverEntry, IntersectionObserver) + Clone + Send + Sync + 'static,
+        F: FnMut(IntersectionObserverEntry, IntersectionObserver) + 'static,
     {
-        let ctx = match use_context::<GlobalState>() {
-            Some(v) => v,
-            None => {
-                provide_context(GlobalState::default());
-                let ctx = expect_context::<GlobalState>();
-
-                // Effect::new(move || {
-                //     let mut hasher = DefaultHasher::new();
-                //     options.hash(&mut hasher);
-                //     let hash = hasher.finish();
-                //     trace!("hash of options: {}", hash);
-
-                //     let root = if let Some(root) = &options.root {
-                //         if let Some(root) = root.get() {
-                //             let root: HtmlElement = root.into();
-                //             Some(root)
-                //         } else {
-                //             return;
-                //         }
-                //     } else {
-                //         None
-                //     };
-
-                //     let observer_settings = IntersectionObserverInit::new();
-
-                //     if let Some(root) = root {
-                //         observer_settings.set_root(Some(&root));
-                //     }
-
-                //     if let Some(margin) = &options.root_margin {
-                //         observer_settings.set_root_margin(margin);
-                //     }
-
-                //     if let Some(threshold) = options.threshold {
-                //         observer_settings.set_threshold(&JsValue::from_f64(*threshold));
-                //     }
-
-                //     let observer = new_with_options_raw(
-                //         move |entries, observer| {
-                //             ctx.callbacks.update_value(|callbacks| {
-                //                 for entry in entries {
-                //                     let target = entry.target();
-                //                     let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-                //                         continue;
-                //                     };
-
-                //                     let Some(callback) = callbacks.get_mut(&id) else {
-                //                         continue;
-                //                     };
-                //                     callback(entry, observer.clone());
-                //                 }
-                //             });
-                //         },
-                //         &observer_settings,
-                //     );
-
-                //     ctx.observer.set(Some(SendWrapper::new(observer)));
-                // });
-
-                ctx
-            }
-        };
-        let id = Uuid::new_v4();
-        let options_hash = StoredValue::new(None::<u64>);
-
-        Effect::new(move || {
-            let span = trace_span!("intersection observer", "{}", &id).entered();
-
-            let Some(target) = target.get() else {
-                return;
-            };
-
-            let root: Option<HtmlElement> = match &options.root {
-                Some(v) => match v.get() {
-                    Some(v) => {
-                        let root: HtmlElement = v.into();
+        observer::new::<Intersection, _, _>(target, move || options.init(), callback)
+    }
+}
 
-                        Some(root)
-                    }
-                    None => {
-                        trace!("root is not ready");
-                        return;
-                    }
-                },
-                None => None,
-            };
+pub mod mutation_observer {
+    use std::hash::Hash;
 
-            trace!("root options parsed");
+    use leptos::{html::ElementType, prelude::*};
+    use wasm_bindgen::{JsCast, JsValue};
+    use web_sys::{
+        Element, HtmlElement, MutationObserver, MutationObserverInit, MutationRecord, Node,
+        js_sys::Array,
+    };
 
-            let mut hasher = DefaultHasher::new();
-            options.hash(&mut hasher);
-            let hash = hasher.finish();
-            options_hash.set_value(Some(hash));
-            trace!("hash of options: {}", hash);
+    use super::observer::{self, ObserverHandle, ObserverKind, hash_of};
 
-            let target: HtmlElement = target.into();
+    const ID_FIELD_NAME: &str = "data-leptos_toolbox_mutation_observer_id";
 
-            set_id(&target, ID_FIELD_NAME, id);
+    pub struct Mutation;
 
-            ctx.callbacks.update_value(|v| {
-                v.insert(id, Box::new(callback.clone()));
-                trace!("created callback");
-            });
+    impl ObserverKind for Mutation {
+        type Entry = MutationRecord;
+        type Observer = MutationObserver;
+        type Init = MutationObserverInit;
 
-            ctx.observer.update_value(|observers| {
-                trace!("getting observer...");
-                match observers.get_mut(&hash) {
-                    Some(observer) => {
-                        observer.observe(&target);
-                        trace!("observer already exists");
-                    }
-                    None => {
-                        trace!("no observer found");
+        const ID_FIELD_NAME: &'static str = ID_FIELD_NAME;
 
-                        let observer_settings = IntersectionObserverInit::new();
+        fn create(callback: &JsValue, _init: &Self::Init) -> Result<Self::Observer, JsValue> {
+            MutationObserver::new(callback.unchecked_ref())
+        }
 
-                        if let Some(root) = root {
-                            trace!("root option set");
-                            observer_settings.set_root(Some(&root));
-                        }
+        fn observe(
+            observer: &Self::Observer,
+            target: &Element,
+            init: &Self::Init,
+        ) -> Result<(), JsValue> {
+            observer.observe_with_options(target, init)
+        }
 
-                        if let Some(margin) = &options.root_margin {
-                            trace!("margin option set");
-                            observer_settings.set_root_margin(margin);
-                        }
+        fn unobserve(_observer: &Self::Observer, _target: &Element) -> bool {
+            false
+        }
 
-                        if let Some(threshold) = options.threshold {
-                            trace!("threshold option set");
-                            observer_settings.set_threshold(&JsValue::from_f64(*threshold));
-                        }
+        fn disconnect(observer: &Self::Observer) {
+            observer.disconnect();
+        }
 
-                        trace!("creating raw observer");
-                        let observer = new_with_options_raw(
-                            move |entries, observer| {
-                                ctx.callbacks.update_value(|callbacks| {
-                                    for entry in entries {
-                                        let target = entry.target();
-                                        let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-                                            continue;
-                                        };
-
-                                        let Some(callback) = callbacks.get_mut(&id) else {
-                                            continue;
-                                        };
-                                        callback(entry, observer.clone());
-                                    }
-                                });
-                            },
-                            &observer_settings,
-                        );
-
-                        trace!("inserting raw observer...");
-                        // trace!("getting observer entry");
-                        let observer = observers.entry(hash).or_insert(SendWrapper::new(observer));
-                        observer.observe(&target);
-                        trace!("observer created");
-
-                        // ctx.observer.update_value(|observers| {
-
-                        // });
-                    }
-                };
-            });
+        fn target(entry: &Self::Entry) -> Option<Node> {
+            entry.target()
+        }
+    }
 
-            // ctx.observer.update_value(|observers| {
-            //     let observer = observers.entry(id).or_insert_with(|| {
-            //         let observer_settings = IntersectionObserverInit::new();
-
-            //         if let Some(root) = root {
-            //             observer_settings.set_root(Some(&root));
-            //         }
-
-            //         if let Some(margin) = &options.root_margin {
-            //             observer_settings.set_root_margin(margin);
-            //         }
-
-            //         if let Some(threshold) = options.threshold {
-            //             observer_settings.set_threshold(&JsValue::from_f64(*threshold));
-            //         }
-
-            //         let observer = new_with_options_raw(
-            //             move |entries, observer| {
-            //                 ctx.callbacks.update_value(|callbacks| {
-            //                     for entry in entries {
-            //                         let target = entry.target();
-            //                         let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-            //                             continue;
-            //                         };
-
-            //                         let Some(callback) = callbacks.get_mut(&id) else {
-            //                             continue;
-            //                         };
-            //                         callback(entry, observer.clone());
-            //                     }
-            //                 });
-            //             },
-            //             &observer_settings,
-            //         );
-            //         RwSignal::new(None)
-            //     });
-            // });
-
-            // let root = if let Some(root) = &options.root {
-            //     if let Some(root) = root.get() {
-            //         let root: HtmlElement = root.into();
-            //         Some(root)
-            //     } else {
-            //         return;
-            //     }
-            // } else {
-            //     None
-            // };
-
-            // observer.observe(&target);
+    pub trait AddMutationObserver {
+        fn observe_mutations<F>(&self, callback: F, options: Options) -> ObserverHandle<Mutation>
+        where
+            F: FnMut(MutationRecord, MutationObserver) + 'static;
+    }
 
-            span.exit();
-        });
+    impl<E> AddMutationObserver for NodeRef<E>
+    where
+        E: ElementType,
+        E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
+    {
+        fn observe_mutations<F>(&self, callback: F, options: Options) -> ObserverHandle<Mutation>
+        where
+            F: FnMut(MutationRecord, MutationObserver) + 'static,
+        {
+            new(*self, callback, options)
+        }
+    }
 
-        on_cleanup(move || {
-            let span = trace_span!("intersection observer").entered();
-            //     let mut hasher = DefaultHasher::new();
-            //     options.hash(&mut hasher);
-            //     let hash = hasher.finish();
-            //     trace!("hash of options: {}", hash);
-
-            //     let root = if let Some(root) = &options.root {
-            //         if let Some(root) = root.get() {
-            //             let root: HtmlElement = root.into();
-            //             Some(root)
-            //         } else {
-            //             return;
-            //         }
-            //     } else {
-            //         None
-            //     };
-
-            let Some(target) = target.get_untracked() else {
-                return;
-            };
+    /// At least one of child list, attributes or character data has to be set, records of a
+    /// subtree go to the innermost observed element they're in.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+    pub struct Options {
+        child_list: bool,
+        attributes: bool,
+        character_data: bool,
+        subtree: bool,
+        attribute_old_value: bool,
+        character_data_old_value: bool,
+        attribute_filter: Option<Vec<String>>,
+    }
 
-            let Some(options_hash) = options_hash.get_value() else {
-                return;
-            };
+    impl Options {
+        pub fn set_child_list(mut self, child_list: bool) -> Self {
+            self.child_list = child_list;
+            self
+        }
 
-            let target: HtmlElement = target.into();
+        pub fn set_attributes(mut self, attributes: bool) -> Self {
+            self.attributes = attributes;
+            self
+        }
 
-            let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-                return;
-            };
+        pub fn set_character_data(mut self, character_data: bool) -> Self {
+            self.character_data = character_data;
+            self
+        }
 
-            ctx.observer
-                .with_value(|observers| match observers.get(&options_hash) {
-                    Some(observer) => {
-                        observer.unobserve(&target);
-                    }
-                    None => {
-                        warn!("observer not found with hash {} for {}", options_hash, id);
-                    }
-                });
+        pub fn set_subtree(mut self, subtree: bool) -> Self {
+            self.subtree = subtree;
+            self
+        }
 
-            ctx.callbacks.update_value(|callbacks| {
-                callbacks.remove(&id);
-                trace!("removed {}", &id);
-            });
+        pub fn set_attribute_old_value(mut self, attribute_old_value: bool) -> Self {
+            self.attribute_old_value = attribute_old_value;
+            self
+        }
 
-            span.exit();
-        });
-    }
+        pub fn set_character_data_old_value(mut self, character_data_old_value: bool) -> Self {
+            self.character_data_old_value = character_data_old_value;
+            self
+        }
 
-    pub fn new_closure(
-        mut callback: impl FnMut(Vec<IntersectionObserverEntry>, IntersectionObserver) + 'static,
-    ) -> JsValue {
-        Closure::<dyn FnMut(Array, IntersectionObserver)>::new(
-            move |entries: Array, observer: IntersectionObserver| {
-                let entries: Vec<IntersectionObserverEntry> = entries
-                    .to_vec()
-                    .into_iter()
-                    .map(|v| v.unchecked_into::<IntersectionObserverEntry>())
-                    .collect();
-                callback(entries, observer);
-            },
-        )
-        .into_js_value()
-    }
+        pub fn set_attribute_filter(mut self, attribute_filter: Vec<String>) -> Self {
+            self.attribute_filter = Some(attribute_filter);
+            self
+        }
 
-    pub fn new_raw<F>(callback: F) -> IntersectionObserver
-    where
-        F: FnMut(Vec<IntersectionObserverEntry>, IntersectionObserver) + Clone + 'static,
-    {
-        IntersectionObserver::new(new_closure(callback).as_ref().unchecked_ref()).unwrap()
+        /// Only the enabled fields are set, the browser rejects some explicit `false`s.
+        fn init(&self) -> MutationObserverInit {
+            let init = MutationObserverInit::new();
+            if self.child_list {
+                init.set_child_list(true);
+            }
+            if self.attributes {
+                init.set_attributes(true);
+            }
+            if self.character_data {
+                init.set_character_data(true);
+            }
+            if self.subtree {
+                init.set_subtree(true);
+            }
+            if self.attribute_old_value {
+                init.set_attribute_old_value(true);
+            }
+            if self.character_data_old_value {
+                init.set_character_data_old_value(true);
+            }
+            if let Some(filter) = &self.attribute_filter {
+                let filter = filter
+                    .iter()
+                    .map(|name| JsValue::from_str(name))
+                    .collect::<Array>();
+                init.set_attribute_filter(&filter);
+            }
+            init
+        }
     }
 
-    pub fn new_with_options_raw<F>(
-        callback: F,
-        options: &IntersectionObserverInit,
-    ) -> IntersectionObserver
+    pub fn new<E, F>(target: NodeRef<E>, callback: F, options: Options) -> ObserverHandle<Mutation>
     where
-        F: FnMut(Vec<IntersectionObserverEntry>, IntersectionObserver) + Clone + 'static,
+        E: ElementType,
+        E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
+        F: FnMut(MutationRecord, MutationObserver) + 'static,
     {
-        IntersectionObserver::new_with_options(
-            new_closure(callback).as_ref().unchecked_ref(),
-            options,
-        )
-        .unwrap()
+        let hash = hash_of(&options);
+        observer::new::<Mutation, _, _>(target, move || Some((hash, options.init())), callback)
     }
 }
 
 pub mod resize_observer {
-    use std::{collections::HashMap, ops::DerefMut, str::FromStr};
-
-    use leptos::{
-        html::ElementType,
-        prelude::{
-            Effect, Get, GetUntracked, LocalStorage, NodeRef, RwSignal, Set, Storage, StoredValue,
-            UpdateValue, With, expect_context, on_cleanup, provide_context,
-        },
-    };
-    use send_wrapper::SendWrapper;
-    use tracing::{error, trace, trace_span};
-    use uuid::Uuid;
+    use leptos::{html::ElementType, prelude::NodeRef};
     use wasm_bindgen::prelude::*;
     use web_sys::{
-        self, Element, HtmlElement, ResizeObserver, ResizeObserverEntry, ResizeObserverSize,
+        self, Element, HtmlElement, Node, ResizeObserver, ResizeObserverEntry, ResizeObserverSize,
         js_sys::Array,
     };
 
-    use super::uuid::{get_id, set_id};
+    use super::observer::{self, ObserverHandle, ObserverKind};
 
     const ID_FIELD_NAME: &str = "data-leptos_toolbox_resize_observer_id";
 
+    pub struct Resize;
+
+    impl ObserverKind for Resize {
+        type Entry = ResizeObserverEntry;
+        type Observer = ResizeObserver;
+        type Init = ();
+
+        const ID_FIELD_NAME: &'static str = ID_FIELD_NAME;
+
+        fn create(callback: &JsValue, _init: &Self::Init) -> Result<Self::Observer, JsValue> {
+            ResizeObserver::new(callback.unchecked_ref())
+        }
+
+        fn observe(
+            observer: &Self::Observer,
+            target: &Element,
+            _init: &Self::Init,
+        ) -> Result<(), JsValue> {
+            observer.observe(target);
+            Ok(())
+        }
+
+        fn unobserve(observer: &Self::Observer, target: &Element) -> bool {
+            observer.unobserve(target);
+            true
+        }
+
+        fn disconnect(observer: &Self::Observer) {
+            observer.disconnect();
+        }
+
+        fn target(entry: &Self::Entry) -> Option<Node> {
+            Some(entry.target().into())
+        }
+    }
+
     pub trait AddResizeObserver {
-        fn add_resize_observer<F>(&self, callback: F)
+        fn add_resize_observer<F>(&self, callback: F) -> ObserverHandle<Resize>
         where
-            F: FnMut(ResizeObserverEntry, ResizeObserver) + Send + Sync + Clone + 'static;
+            F: FnMut(ResizeObserverEntry, ResizeObserver) + 'static;
     }
 
     pub trait GetContentBoxSize {
@@ -707,105 +904,21 @@ pub mod resize_observer {
         E: ElementType,
         E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
     {
-        fn add_resize_observer<F>(&self, callback: F)
+        fn add_resize_observer<F>(&self, callback: F) -> ObserverHandle<Resize>
         where
-            F: FnMut(ResizeObserverEntry, ResizeObserver) + Send + Sync + Clone + 'static,
+            F: FnMut(ResizeObserverEntry, ResizeObserver) + 'static,
         {
-            new(self.clone(), callback);
+            new(*self, callback)
         }
     }
 
-    #[derive(Default, Clone)]
-    pub struct GlobalState {
-        pub observer: RwSignal<Option<SendWrapper<ResizeObserver>>>,
-        pub callbacks: StoredValue<
-            HashMap<
-                Uuid,
-                Box<dyn FnMut(ResizeObserverEntry, ResizeObserver) + Send + Sync + 'static>,
-            >,
-        >,
-    }
-
-    pub fn init_global_state() {
-        provide_context(GlobalState::default());
-
-        Effect::new(move || {
-            let ctx = expect_context::<GlobalState>();
-
-            let observer = new_raw(move |entries, observer| {
-                ctx.callbacks.update_value(|callbacks| {
-                    for entry in entries {
-                        let target = entry.target();
-                        let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-                            continue;
-                        };
-
-                        let Some(callback) = callbacks.get_mut(&id) else {
-                            continue;
-                        };
-                        callback(entry, observer.clone());
-                    }
-                });
-            });
-
-            ctx.observer.set(Some(SendWrapper::new(observer)));
-        });
-    }
-
-    pub fn new<E, F>(target: NodeRef<E>, mut callback: F)
+    pub fn new<E, F>(target: NodeRef<E>, callback: F) -> ObserverHandle<Resize>
     where
         E: ElementType,
         E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
-        F: FnMut(ResizeObserverEntry, web_sys::ResizeObserver) + Clone + Send + Sync + 'static,
+        F: FnMut(ResizeObserverEntry, web_sys::ResizeObserver) + 'static,
     {
-        let ctx = expect_context::<GlobalState>();
-        let id = Uuid::new_v4();
-
-        Effect::new(move || {
-            let span = trace_span!("resize observer").entered();
-
-            let (Some(target), Some(observer)) = (target.get(), ctx.observer.get()) else {
-                return;
-            };
-
-            let target: HtmlElement = target.into();
-
-            set_id(&target, ID_FIELD_NAME, id);
-
-            ctx.callbacks.update_value(|v| {
-                v.insert(id, Box::new(callback.clone()));
-                trace!("created {}", &id);
-            });
-
-            observer.observe(&target);
-
-            span.exit();
-        });
-
-        on_cleanup(move || {
-            let span = trace_span!("resize observer").entered();
-
-            let (Some(target), Some(observer)) =
-                (target.get_untracked(), ctx.observer.get_untracked())
-            else {
-                return;
-            };
-
-            let target: HtmlElement = target.into();
-
-            let Some(id) = get_id(&target, ID_FIELD_NAME) else {
-                return;
-            };
-
-            observer.unobserve(&target);
-
-            ctx.callbacks.update_value(|callbacks| {
-                callbacks.remove(&id);
-                trace!("removed {}", &id);
-            });
-
-            span.exit();
-        });
+        observer::new::<Resize, _, _>(target, || Some((0, ())), callback)
     }
 
     pub fn new_raw<F>(mut callback: F) -> ResizeObserver
@@ -828,15 +941,15 @@ pub mod resize_observer {
 }
 
 pub mod event_listener {
-    use std::fmt::Debug;
+    use std::{any::Any, borrow::Cow, fmt::Debug};
 
     use leptos::{ev::EventDescriptor, html::ElementType, prelude::*};
     use tracing::{trace, trace_span};
     use wasm_bindgen::prelude::*;
-    use web_sys::HtmlElement;
+    use web_sys::{EventTarget, HtmlElement, Window, js_sys::Function};
 
     pub trait AddEventListener {
-        fn add_event_listener<T, F>(&self, event: T, callback: F)
+        fn add_event_listener<T, F>(&self, event: T, callback: F) -> EventListenerHandle
         where
             T: EventDescriptor + Debug + 'static,
             F: FnMut(<T as EventDescriptor>::EventType) + Clone + 'static;
@@ -847,38 +960,140 @@ pub mod event_listener {
         E: ElementType,
         E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
     {
-        fn add_event_listener<T, F>(&self, event: T, callback: F)
+        fn add_event_listener<T, F>(&self, event: T, callback: F) -> EventListenerHandle
+        where
+            T: EventDescriptor + Debug + 'static,
+            F: FnMut(<T as EventDescriptor>::EventType) + Clone + 'static,
+        {
+            new(*self, event, callback)
+        }
+    }
+
+    /// Only call it where `window()` exists, in an effect or an event handler.
+    impl AddEventListener for Window {
+        fn add_event_listener<T, F>(&self, event: T, callback: F) -> EventListenerHandle
         where
             T: EventDescriptor + Debug + 'static,
             F: FnMut(<T as EventDescriptor>::EventType) + Clone + 'static,
         {
-            new(self.clone(), event, callback);
+            new_on(self.clone().into(), event, callback)
+        }
+    }
+
+    struct Listener {
+        target: EventTarget,
+        event: Cow<'static, str>,
+        callback: Function,
+        /// The rust side of `callback`, freed once it's removed.
+        _closure: Box<dyn Any>,
+    }
+
+    impl Listener {
+        fn remove(self) {
+            let _ = self
+                .target
+                .remove_event_listener_with_callback(&self.event, &self.callback);
+        }
+    }
+
+    /// Listener added by [`new`], it's removed when the owner is cleaned up or on [`cancel`].
+    ///
+    /// [`cancel`]: EventListenerHandle::cancel
+    #[derive(Clone, Copy)]
+    pub struct EventListenerHandle {
+        listener: StoredValue<Option<Listener>, LocalStorage>,
+        cancelled: StoredValue<bool>,
+    }
+
+    impl EventListenerHandle {
+        /// Removes the listener for good, a remounted target doesn't get it again.
+        pub fn cancel(&self) {
+            self.cancelled.try_set_value(true);
+            if let Some(Some(listener)) = self.listener.try_update_value(Option::take) {
+                listener.remove();
+            }
+        }
+
+        pub fn is_active(&self) -> bool {
+            self.listener
+                .try_with_value(Option::is_some)
+                .unwrap_or_default()
         }
     }
 
-    pub fn new<E, T, F>(target: NodeRef<E>, event: T, f: F)
+    pub fn new<E, T, F>(target: NodeRef<E>, event: T, f: F) -> EventListenerHandle
     where
         E: ElementType,
         E::Output: JsCast + Clone + 'static + Into<HtmlElement>,
         T: EventDescriptor + Debug + 'static,
         F: FnMut(<T as EventDescriptor>::EventType) + Clone + 'static,
     {
+        let handle = EventListenerHandle {
+            listener: StoredValue::new_local(None),
+            cancelled: StoredValue::new(false),
+        };
+
         Effect::new(move || {
             let span = trace_span!("event_listener").entered();
             let Some(node) = target.get() else {
                 trace!("target not found");
                 return;
             };
+            if handle.cancelled.get_value() {
+                return;
+            }
+            // a remounted target replaces the listener on the old one
+            if let Some(Some(listener)) = handle.listener.try_update_value(Option::take) {
+                listener.remove();
+            }
 
             let node: HtmlElement = node.into();
 
-            let closure = Closure::<dyn FnMut(_)>::new(f.clone()).into_js_value();
+            let closure = Closure::<dyn FnMut(_)>::new(f.clone());
+            let callback = closure.as_ref().unchecked_ref::<Function>().clone();
 
-            node.add_event_listener_with_callback(&event.name(), closure.as_ref().unchecked_ref())
+            node.add_event_listener_with_callback(&event.name(), &callback)
                 .unwrap();
 
+            handle.listener.set_value(Some(Listener {
+                target: node.into(),
+                event: event.name(),
+                callback,
+                _closure: Box::new(closure),
+            }));
+
             span.exit();
         });
+
+        on_cleanup(move || handle.cancel());
+
+        handle
+    }
+
+    /// Listens on a target that's always there, like the window, until the owner is cleaned up.
+    pub fn new_on<T, F>(target: EventTarget, event: T, f: F) -> EventListenerHandle
+    where
+        T: EventDescriptor + Debug + 'static,
+        F: FnMut(<T as EventDescriptor>::EventType) + 'static,
+    {
+        let closure = Closure::<dyn FnMut(_)>::new(f);
+        let callback = closure.as_ref().unchecked_ref::<Function>().clone();
+        target
+            .add_event_listener_with_callback(&event.name(), &callback)
+            .unwrap();
+
+        let handle = EventListenerHandle {
+            listener: StoredValue::new_local(Some(Listener {
+                target,
+                event: event.name(),
+                callback,
+                _closure: Box::new(closure),
+            })),
+            cancelled: StoredValue::new(false),
+        };
+        on_cleanup(move || handle.cancel());
+
+        handle
     }
 }
 
@@ -887,7 +1102,7 @@ pub mod file {
     use wasm_bindgen::JsCast;
     use wasm_bindgen_futures::JsFuture;
     use web_sys::{
-        DragEvent, File, FileList, ReadableStreamDefaultReader,
+        Blob, DragEvent, File, ReadableStreamDefaultReader,
         js_sys::{Object, Reflect, Uint8Array},
     };
 
@@ -946,7 +1161,7 @@ pub mod file {
         }
     }
 
-    impl GetFileStream for File {
+    impl GetFileStream for Blob {
         fn get_file_stream(&self) -> Result<ReadableStreamDefaultReader, ErrorGetFileStream> {
             get_file_stream(self)
         }
@@ -968,7 +1183,7 @@ pub mod file {
         files
     }
 
-    pub fn get_file_stream(file: &File) -> Result<ReadableStreamDefaultReader, ErrorGetFileStream> {
+    pub fn get_file_stream(file: &Blob) -> Result<ReadableStreamDefaultReader, ErrorGetFileStream> {
         let stream = file.stream();
         let reader = stream
             .get_reader()
@@ -1290,3 +1505,156 @@ pub mod dropzone {
         });
     }
 }
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod toolbox_tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use leptos::{
+        ev, html::Div, prelude::*, tachys::html::node_ref::NodeRefContainer, task::Executor,
+    };
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    use web_sys::{HtmlElement, js_sys::Promise};
+
+    use super::{
+        event_listener::AddEventListener,
+        intersection_observer::{self, AddIntersectionObserver, Intersection},
+        mutation_observer::{self, AddMutationObserver, Mutation},
+        observer::{self, ObserverRegistry},
+        resize_observer::{AddResizeObserver, Resize},
+    };
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn mounted_div() -> (HtmlElement, NodeRef<Div>) {
+        let element = document().create_element("div").unwrap();
+        document().body().unwrap().append_child(&element).unwrap();
+        let node_ref = NodeRef::<Div>::new();
+        node_ref.load(&element);
+        (element.unchecked_into(), node_ref)
+    }
+
+    /// Lets effects and observer callbacks run.
+    async fn next_task() {
+        let timeout = Promise::new(&mut |resolve, _| {
+            window().set_timeout_with_callback(&resolve).unwrap();
+        });
+        JsFuture::from(timeout).await.unwrap();
+    }
+
+    fn registries() -> (
+        Owner,
+        ObserverRegistry<Resize>,
+        ObserverRegistry<Intersection>,
+        ObserverRegistry<Mutation>,
+    ) {
+        let _ = Executor::init_wasm_bindgen();
+        let root = Owner::new();
+        root.with(|| {
+            observer::init_global_state();
+            (
+                root.clone(),
+                expect_context(),
+                expect_context(),
+                expect_context(),
+            )
+        })
+    }
+
+    #[wasm_bindgen_test]
+    async fn observers_are_removed_with_their_owner() {
+        let (root, resize, intersection, mutation) = registries();
+        let component = root.with(Owner::new);
+        component.with(|| {
+            for _ in 0..2 {
+                let (_, node_ref) = mounted_div();
+                node_ref.add_resize_observer(|_, _| {});
+                node_ref.observe_intersection_with_options(
+                    |_, _| {},
+                    intersection_observer::Options::<Div>::default(),
+                );
+                node_ref.observe_mutations(
+                    |_, _| {},
+                    mutation_observer::Options::default().set_attributes(true),
+                );
+            }
+        });
+        next_task().await;
+
+        // equal options share one observer
+        assert_eq!((resize.observers(), resize.registrations()), (1, 2));
+        assert_eq!(
+            (intersection.observers(), intersection.registrations()),
+            (1, 2)
+        );
+        assert_eq!((mutation.observers(), mutation.registrations()), (1, 2));
+
+        component.cleanup();
+        assert_eq!((resize.observers(), resize.registrations()), (0, 0));
+        assert_eq!(
+            (intersection.observers(), intersection.registrations()),
+            (0, 0)
+        );
+        assert_eq!((mutation.observers(), mutation.registrations()), (0, 0));
+    }
+
+    #[wasm_bindgen_test]
+    async fn cancelled_observers_stop_receiving_records() {
+        let (root, _resize, _intersection, mutation) = registries();
+        let records = Rc::new(Cell::new(0));
+        let (element, handle, kept) = root.with(|| {
+            let (element, node_ref) = mounted_div();
+            let options = mutation_observer::Options::default().set_attributes(true);
+            let handle = node_ref.observe_mutations(
+                {
+                    let records = records.clone();
+                    move |_, _| records.set(records.get() + 1)
+                },
+                options.clone(),
+            );
+            let kept = node_ref.observe_mutations(|_, _| {}, options);
+            (element, handle, kept)
+        });
+        next_task().await;
+        element.set_attribute("title", "first").unwrap();
+        next_task().await;
+        assert_eq!(records.get(), 1);
+
+        handle.cancel();
+        assert!(!handle.is_active());
+        assert!(kept.is_active());
+        assert_eq!(mutation.registrations(), 1);
+        element.set_attribute("title", "second").unwrap();
+        next_task().await;
+        assert_eq!(records.get(), 1);
+
+        kept.cancel();
+        assert_eq!((mutation.observers(), mutation.registrations()), (0, 0));
+    }
+
+    #[wasm_bindgen_test]
+    async fn event_listeners_are_removed_with_their_owner() {
+        let _ = Executor::init_wasm_bindgen();
+        let clicks = Rc::new(Cell::new(0));
+        let component = Owner::new();
+        let (element, handle) = component.with(|| {
+            let (element, node_ref) = mounted_div();
+            let handle = node_ref.add_event_listener(ev::click, {
+                let clicks = clicks.clone();
+                move |_| clicks.set(clicks.get() + 1)
+            });
+            (element, handle)
+        });
+        next_task().await;
+        assert!(handle.is_active());
+        element.click();
+        assert_eq!(clicks.get(), 1);
+
+        component.cleanup();
+        assert!(!handle.is_active());
+        element.click();
+        assert_eq!(clicks.get(), 1);
+    }
+}
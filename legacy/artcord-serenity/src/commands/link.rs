@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{
+        application::command::CommandOptionType,
+        prelude::{application_command::ApplicationCommandInteraction, InteractionResponseType},
+    },
+    prelude::Context,
+};
+
+use super::{get_option_string, CommandError};
+
+pub async fn run(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), CommandError> {
+    let code = get_option_string(command.data.options.get(0))?;
+    let site_url = std::env::var("SITE_URL").unwrap_or_else(|_| String::from("http://localhost:3000"));
+    let Ok(secret) = std::env::var("DISCORD_BOT_SECRET") else {
+        return Err(CommandError::NotImplemented(String::from(
+            "link, DISCORD_BOT_SECRET is missing in .env",
+        )));
+    };
+
+    let mut body = HashMap::new();
+    body.insert("code", code.clone());
+    body.insert("user_id", command.user.id.0.to_string());
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post(format!("{}/auth/discord/redeem", site_url.trim_end_matches('/')))
+        .bearer_auth(secret)
+        .json(&body)
+        .send()
+        .await?;
+
+    let output = match res.status().as_u16() {
+        200 => format!("Linked to {}.", res.text().await?),
+        404 => String::from("Invalid or expired code."),
+        409 => String::from("This discord account is already linked to another account."),
+        _ => String::from("Failed to link 500."),
+    };
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| message.content(output).ephemeral(true))
+        })
+        .await?;
+    Ok(())
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("link")
+        .description("Link your discord account to your artbounty account")
+        .create_option(|option| {
+            option
+                .name("code")
+                .description("Code shown in artbounty account settings.")
+                .kind(CommandOptionType::String)
+                .required(true)
+                .min_length(8)
+                .max_length(8)
+        })
+}
@@ -167,6 +167,7 @@ pub async fn resolve_command(
             commands::sync::run(gallery_root_dir, &ctx, &command, &db, guild_id.0).await
         }
         "verify" => commands::verify::run(&ctx, &command, &db).await,
+        "link" => commands::link::run(&ctx, &command).await,
         name => Err(crate::commands::CommandError::NotImplemented(
             name.to_string(),
         )),
@@ -29,6 +29,7 @@ pub async fn ready(ctx: Context, ready: serenity::model::gateway::Ready) {
             commands
                 .create_application_command(|command| commands::who::register(command))
                 .create_application_command(|command| commands::verify::register(command))
+                .create_application_command(|command| commands::link::register(command))
                 .create_application_command(|command| commands::test::register(command))
                 .create_application_command(|command| commands::guilds::register(command))
                 .create_application_command(|command| commands::leave::register(command))